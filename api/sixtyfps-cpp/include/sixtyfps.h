@@ -41,10 +41,15 @@ extern const cbindgen_private::ItemVTable FocusScopeVTable;
 extern const cbindgen_private::ItemVTable ImageVTable;
 extern const cbindgen_private::ItemVTable ClippedImageVTable;
 extern const cbindgen_private::ItemVTable PathVTable;
+extern const cbindgen_private::ItemVTable LineVTable;
 extern const cbindgen_private::ItemVTable FlickableVTable;
 extern const cbindgen_private::ItemVTable WindowVTable;
 extern const cbindgen_private::ItemVTable TextInputVTable;
 extern const cbindgen_private::ItemVTable ClipVTable;
+extern const cbindgen_private::ItemVTable TransformVTable;
+extern const cbindgen_private::ItemVTable DesaturateVTable;
+extern const cbindgen_private::ItemVTable LayerVTable;
+extern const cbindgen_private::ItemVTable CustomRenderingVTable;
 extern const cbindgen_private::ItemVTable BoxShadowVTable;
 
 extern const cbindgen_private::ItemVTable NativeButtonVTable;
@@ -74,12 +79,22 @@ using cbindgen_private::PropertyAnimation;
 using cbindgen_private::Slice;
 using cbindgen_private::TextHorizontalAlignment;
 using cbindgen_private::TextVerticalAlignment;
+using cbindgen_private::TextVerticalCentering;
 using cbindgen_private::TextOverflow;
 using cbindgen_private::TextWrap;
 using cbindgen_private::TraversalOrder;
 using cbindgen_private::ImageFit;
+using cbindgen_private::ImageRendering;
+using cbindgen_private::ImageHorizontalAlignment;
+using cbindgen_private::ImageVerticalAlignment;
+using cbindgen_private::FontStyle;
+using cbindgen_private::NumeralStyle;
 using cbindgen_private::KeyEvent;
 using cbindgen_private::EventResult;
+using cbindgen_private::LineCap;
+using cbindgen_private::StrokeAlignment;
+using cbindgen_private::ClipShape;
+using cbindgen_private::InputType;
 using cbindgen_private::KeyboardModifiers;
 
 namespace private_api {
@@ -149,11 +164,16 @@ using cbindgen_private::ClippedImage;
 using cbindgen_private::Flickable;
 using cbindgen_private::FocusScope;
 using cbindgen_private::Image;
+using cbindgen_private::Line;
 using cbindgen_private::Path;
 using cbindgen_private::Rectangle;
 using cbindgen_private::Text;
 using cbindgen_private::TextInput;
 using cbindgen_private::TouchArea;
+using cbindgen_private::Transform;
+using cbindgen_private::Desaturate;
+using cbindgen_private::Layer;
+using cbindgen_private::CustomRendering;
 using cbindgen_private::Window;
 using cbindgen_private::BoxShadow;
 
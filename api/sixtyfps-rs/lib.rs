@@ -171,12 +171,30 @@ struct MyStruct {
 /// This function can be used to register a custom TrueType font with SixtyFPS,
 /// for use with the `font-family` property. The provided slice must be a valid TrueType
 /// font.
+///
+/// Fonts can be registered at any time, from any thread, also before the first window is created.
 pub fn register_application_font_from_memory(
     data: &'static [u8],
 ) -> Result<(), Box<dyn std::error::Error>> {
     sixtyfps_rendering_backend_default::backend().register_application_font_from_memory(data)
 }
 
+/// This function can be used to register a custom TrueType font with SixtyFPS,
+/// for use with the `font-family` property. The font is read from the file at the given path,
+/// and an error is returned if the file cannot be read or does not contain a supported font.
+pub fn register_application_font_from_path<P: AsRef<std::path::Path>>(
+    path: P,
+) -> Result<(), Box<dyn std::error::Error>> {
+    sixtyfps_rendering_backend_default::backend().register_application_font_from_path(path.as_ref())
+}
+
+/// Returns the names of the font families that can be used with the `font-family` property,
+/// sorted and without duplicates. The list includes the families of the fonts registered with
+/// [`register_application_font_from_memory`] and [`register_application_font_from_path`].
+pub fn available_font_families() -> Vec<String> {
+    sixtyfps_rendering_backend_default::backend().available_font_families()
+}
+
 // FIXME: this should not be in this namespace
 // but the name is `sixtyfps::StateInfo` in builtin.60
 #[doc(hidden)]
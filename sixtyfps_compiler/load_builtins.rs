@@ -111,10 +111,15 @@ pub fn load_builtins(register: &mut TypeRegister) {
         n.cpp_type = parse_annotation("cpp_type", &e.node).map(|x| x.unwrap());
         n.rust_type_constructor =
             parse_annotation("rust_type_constructor", &e.node).map(|x| x.unwrap());
+        let mut base_default_bindings = HashMap::new();
         let global = if let Some(base) = e.QualifiedName() {
             let base = QualifiedTypeName::from_node(base).to_string();
             if base != "_" {
-                n.parent = Some(natives.get(&base).unwrap().native_class.clone())
+                let base = natives.get(&base).unwrap();
+                n.parent = Some(base.native_class.clone());
+                // Non-exported bases are never instantiated themselves, so their default bindings
+                // are applied through the elements inheriting from them.
+                base_default_bindings = base.default_bindings.clone();
             };
             false
         } else {
@@ -122,6 +127,7 @@ pub fn load_builtins(register: &mut TypeRegister) {
         };
         let mut builtin = BuiltinElement::new(Rc::new(n));
         builtin.is_global = global;
+        builtin.default_bindings = base_default_bindings;
         builtin.default_bindings.extend(e.PropertyDeclaration().filter_map(|p| {
             Some((
                 identifier_text(&p.DeclaredIdentifier())?,
@@ -165,7 +171,6 @@ pub fn load_builtins(register: &mut TypeRegister) {
                 register.insert_type(Type::Component(glob));
             }
         } else {
-            assert!(builtin.default_bindings.is_empty()); // because they are not taken from if we inherit from it
             assert!(builtin.additional_accepted_child_types.is_empty());
             natives.insert(id, Rc::new(builtin));
         }
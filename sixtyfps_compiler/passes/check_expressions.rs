@@ -10,14 +10,29 @@
 
 use crate::diagnostics::BuildDiagnostics;
 use crate::expression_tree::Expression;
-use crate::object_tree::visit_all_expressions;
+use crate::langtype::Type;
+use crate::object_tree::{recurse_elem_including_sub_components, visit_all_expressions};
 
 /// Check the validity of expressions
 ///
 /// - Make sure that there is no uncalled member function or macro
+/// - Make sure that the conic gradient stops set with a string literal can be parsed
 pub fn check_expressions(doc: &crate::object_tree::Document, diag: &mut BuildDiagnostics) {
     for component in &doc.inner_components {
         visit_all_expressions(component, |e, _| check_expression(e, diag));
+        recurse_elem_including_sub_components(component, &(), &mut |elem, _| {
+            let elem = elem.borrow();
+            if !matches!(elem.base_type, Type::Builtin(_)) {
+                return;
+            }
+            if let Some(binding) = elem.bindings.get("conic_gradient_stops") {
+                if let Expression::StringLiteral(stops) = &binding.expression {
+                    if let Err(err) = check_gradient_stops(stops) {
+                        diag.push_error(err, binding);
+                    }
+                }
+            }
+        });
     }
 }
 
@@ -32,3 +47,30 @@ fn check_expression(e: &Expression, diag: &mut BuildDiagnostics) {
         _ => e.visit(|e| check_expression(e, diag)),
     }
 }
+
+/// Checks the syntax of the `conic-gradient-stops` property, which the renderers parse: a comma separated list of
+/// at least two colors in hexadecimal notation, each optionally followed by its position in percent.
+fn check_gradient_stops(stops: &str) -> Result<(), String> {
+    let mut count = 0;
+    for stop in stops.split(',') {
+        let mut parts = stop.split_whitespace();
+        let color = parts.next().ok_or_else(|| format!("Empty gradient stop in {:?}", stops))?;
+        if super::resolving::parse_color_literal(color).is_none() {
+            return Err(format!("Invalid gradient color {:?}", color));
+        }
+        if let Some(position) = parts.next() {
+            if position.strip_suffix('%').and_then(|percent| percent.parse::<f32>().ok()).is_none()
+            {
+                return Err(format!("Invalid gradient stop position {:?}", position));
+            }
+        }
+        if let Some(extra) = parts.next() {
+            return Err(format!("Unexpected {:?} in gradient stop {:?}", extra, stop.trim()));
+        }
+        count += 1;
+    }
+    if count < 2 {
+        return Err(format!("A gradient needs at least two color stops, got {:?}", stops));
+    }
+    Ok(())
+}
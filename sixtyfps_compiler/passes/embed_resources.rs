@@ -28,6 +28,9 @@ fn embed_resources_from_expression(
 ) {
     if let Expression::ResourceReference(ref mut resource_ref) = e {
         match resource_ref {
+            // Remote images are loaded at run-time
+            ResourceReference::AbsolutePath(path)
+                if path.starts_with("http://") || path.starts_with("https://") => {}
             ResourceReference::AbsolutePath(path) => {
                 let mut resources = global_embedded_resources.borrow_mut();
                 let maybe_id = resources.len();
@@ -9,7 +9,8 @@
 LICENSE END */
 //! Pass that lowers synthetic `drop-shadow-*` properties to proper shadow elements
 // At the moment only shadows on `Rectangle` elements are supported, i.e. the drop shadow
-// of a rectangle is a box shadow.
+// of a rectangle is a box shadow. Elements that declare the `drop-shadow-*` properties
+// themselves, such as `Text`, draw their shadow and are left alone.
 
 use crate::diagnostics::BuildDiagnostics;
 use crate::expression_tree::ExpressionSpanned;
@@ -34,7 +35,7 @@ fn create_box_shadow_element(
         for (shadow_prop_name, shadow_prop_binding) in shadow_property_bindings {
             diag.push_error(
                 format!(
-                    "The {} property is only supported on Rectangle and Clip elements right now",
+                    "The {} property is only supported on Rectangle, Clip and Text elements right now",
                     shadow_prop_name
                 ),
                 &shadow_prop_binding,
@@ -147,8 +148,14 @@ fn inject_shadow_element_in_repeated_element(
 }
 
 fn take_shadow_property_bindings(element: &ElementRc) -> HashMap<String, ExpressionSpanned> {
+    let native_class = element.borrow().native_class();
     crate::typeregister::RESERVED_DROP_SHADOW_PROPERTIES
         .iter()
+        .filter(|(shadow_property_name, _)| {
+            native_class.as_ref().map_or(true, |native_class| {
+                native_class.lookup_property(shadow_property_name).is_none()
+            })
+        })
         .flat_map(|(shadow_property_name, _)| {
             let shadow_property_name = shadow_property_name.to_string();
             let mut element = element.borrow_mut();
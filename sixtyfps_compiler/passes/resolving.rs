@@ -1183,7 +1183,7 @@ fn error_or_try_minus(
     base
 }
 
-fn parse_color_literal(str: &str) -> Option<u32> {
+pub(crate) fn parse_color_literal(str: &str) -> Option<u32> {
     if !str.starts_with('#') {
         return None;
     }
@@ -133,16 +133,26 @@ pub fn builtin() -> Rc<RefCell<Self>> {
             );
         };
 
-        declare_enum("TextHorizontalAlignment", &["left", "center", "right"]);
+        declare_enum("TextHorizontalAlignment", &["left", "center", "right", "justify"]);
         declare_enum("TextVerticalAlignment", &["top", "center", "bottom"]);
+        declare_enum("TextVerticalCentering", &["line_box", "ascent_descent", "cap_height"]);
         declare_enum("TextWrap", &["no_wrap", "word_wrap"]);
         declare_enum("TextOverflow", &["clip", "elide"]);
         declare_enum(
             "LayoutAlignment",
             &["stretch", "center", "start", "end", "space_between", "space_around"],
         );
-        declare_enum("ImageFit", &["fill", "contain"]);
+        declare_enum("ImageFit", &["fill", "contain", "none"]);
+        declare_enum("ImageRendering", &["smooth", "pixelated"]);
+        declare_enum("ImageHorizontalAlignment", &["center", "left", "right"]);
+        declare_enum("ImageVerticalAlignment", &["center", "top", "bottom"]);
+        declare_enum("FontStyle", &["normal", "italic", "oblique"]);
+        declare_enum("NumeralStyle", &["normal", "lining", "oldstyle", "tabular", "proportional"]);
         declare_enum("EventResult", &["reject", "accept"]);
+        declare_enum("LineCap", &["butt", "round", "square"]);
+        declare_enum("StrokeAlignment", &["inside", "center", "outside"]);
+        declare_enum("ClipShape", &["rectangle", "ellipse"]);
+        declare_enum("InputType", &["text", "password"]);
 
         register.supported_property_animation_types.insert(Type::Float32.to_string());
         register.supported_property_animation_types.insert(Type::Int32.to_string());
@@ -11,6 +11,7 @@
 The backend is the abstraction for crates that need to do the actual drawing and event loop
 */
 
+use crate::graphics::{Resource, TextAntialiasing};
 use crate::window::ComponentWindow;
 
 /// Interface implemented by backends
@@ -30,8 +31,70 @@ fn register_application_font_from_memory(
         data: &'static [u8],
     ) -> Result<(), Box<dyn std::error::Error>>;
 
+    /// This function can be used to register a custom TrueType font with SixtyFPS,
+    /// for use with the `font-family` property. The font is read from the file at the given path.
+    /// An error is returned if the file cannot be read or does not contain a supported font, or if the
+    /// backend doesn't support loading fonts from files.
+    fn register_application_font_from_path(
+        &'static self,
+        path: &std::path::Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Err(format!("cannot load {}: this backend does not load fonts from files", path.display())
+            .into())
+    }
+
+    /// Returns the names of the font families that can be used with the `font-family` property,
+    /// including the ones of the fonts registered by the application, sorted and without duplicates.
+    /// Backends that cannot list the fonts return an empty list.
+    fn available_font_families(&'static self) -> Vec<String> {
+        Vec::new()
+    }
+
     fn set_clipboard_text(&'static self, text: String);
     fn clipboard_text(&'static self) -> Option<String>;
+
+    /// Sets the text of the primary selection, which is pasted with the middle mouse button on X11.
+    /// Does nothing on platforms without a primary selection.
+    fn set_primary_selection_text(&'static self, _text: String) {}
+    /// Returns the text of the primary selection, or None on platforms without a primary selection.
+    fn primary_selection_text(&'static self) -> Option<String> {
+        None
+    }
+
+    /// Copies an image to the clipboard. The pixels are in the RGBA format with 8 bits per channel and
+    /// straight alpha, row by row without padding. Does nothing if the backend doesn't support images in
+    /// the clipboard.
+    fn set_clipboard_image(&'static self, _rgba: &[u8], _width: u32, _height: u32) {}
+    /// Returns the image in the clipboard, if any, as RGBA pixels along with the width and the height.
+    /// Use [`Resource::from_rgba8`] to show it in an `Image` element.
+    fn clipboard_image(&'static self) -> Option<(Vec<u8>, u32, u32)> {
+        None
+    }
+
+    /// Marks the image the resource refers to as retained. Images are normally released once no
+    /// element displays them anymore. A pinned image stays decoded (and uploaded to the GPU, where
+    /// applicable) until it is unpinned, so that switching back and forth between screens that share
+    /// an image does not load it again.
+    /// Backends without a shared image cache ignore it.
+    fn pin_image_resource(&'static self, _resource: Resource) {}
+    /// Releases an image previously retained with [`Self::pin_image_resource`].
+    fn unpin_image_resource(&'static self, _resource: Resource) {}
+
+    /// Limits the GPU memory used by images, in bytes. When the images drawn exceed the budget, the textures
+    /// of the least recently drawn images are released, and uploaded again when they are shown next. This
+    /// requires keeping the pixels of the images in main memory as well. `None`, the default, means no limit.
+    /// Backends that don't manage the memory of the images themselves ignore it.
+    fn set_texture_memory_budget(&'static self, _budget: Option<usize>) {}
+
+    /// Enables or disables keeping images in memory while they are shown, which is the default. When disabled,
+    /// images are decoded each time they are drawn and released after the frame, which saves memory at the
+    /// expense of CPU time. Images that are animated or still loading are kept regardless.
+    /// Backends that don't manage the memory of the images themselves ignore it.
+    fn set_image_cache_enabled(&'static self, _enabled: bool) {}
+
+    /// Selects how the edges of text are antialiased. The default is [`TextAntialiasing::Grayscale`].
+    /// Backends that follow the settings of the platform ignore it.
+    fn set_text_antialiasing(&'static self, _mode: TextAntialiasing) {}
 }
 
 static PRIVATE_BACKEND_INSTANCE: once_cell::sync::OnceCell<Box<dyn Backend + 'static>> =
@@ -0,0 +1,220 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+#![warn(missing_docs)]
+/*!
+Statistics about the rendering of a frame, for profiling.
+
+A [`FrameStatsRecorder`] is an [`ItemRenderer`] that forwards the draw calls to the renderer of a backend,
+counting them by the type of the item. The backend adds the time it took to render and flush the frame.
+*/
+
+use crate::graphics::Point;
+use crate::item_rendering::{CachedRenderingData, ItemRenderer};
+use crate::items::*;
+use core::pin::Pin;
+
+/// The number of draw calls of a frame, by type of item, and the time it took to render it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    /// The number of `Rectangle` items drawn, with or without border
+    pub rectangles: usize,
+    /// The number of `Text` and `TextInput` items drawn
+    pub texts: usize,
+    /// The number of `Image` and `ClippedImage` items drawn, and of pixmaps drawn by custom items
+    pub images: usize,
+    /// The number of `Path`, `Line` and `CustomRendering` items drawn
+    pub paths: usize,
+    /// The number of box shadows drawn
+    pub shadows: usize,
+    /// The number of `Clip` items applied
+    pub clips: usize,
+    /// The time from starting the frame until all its draw calls were issued
+    pub render_duration: core::time::Duration,
+    /// The time it took to flush the draw calls to the graphics system and present the frame
+    pub flush_duration: core::time::Duration,
+}
+
+/// Counts the draw calls of a frame while passing them on to another renderer. Calls to
+/// [`ItemRenderer::as_any`] return the other renderer, so that custom items can still reach the
+/// renderer of the backend.
+pub struct FrameStatsRecorder<'a> {
+    renderer: &'a mut dyn ItemRenderer,
+    stats: FrameStats,
+}
+
+impl FrameStats {
+    fn add_draw_calls(&mut self, other: &FrameStats) {
+        self.rectangles += other.rectangles;
+        self.texts += other.texts;
+        self.images += other.images;
+        self.paths += other.paths;
+        self.shadows += other.shadows;
+        self.clips += other.clips;
+    }
+}
+
+impl<'a> FrameStatsRecorder<'a> {
+    /// Creates a recorder that passes the draw calls on to the given renderer.
+    pub fn new(renderer: &'a mut dyn ItemRenderer) -> Self {
+        Self { renderer, stats: FrameStats::default() }
+    }
+
+    /// Returns the number of draw calls counted so far. The durations are left for the backend to fill in.
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+}
+
+impl<'a> ItemRenderer for FrameStatsRecorder<'a> {
+    fn draw_rectangle(&mut self, pos: Point, rect: Pin<&Rectangle>) {
+        self.stats.rectangles += 1;
+        self.renderer.draw_rectangle(pos, rect)
+    }
+
+    fn draw_border_rectangle(&mut self, pos: Point, rect: Pin<&BorderRectangle>) {
+        self.stats.rectangles += 1;
+        self.renderer.draw_border_rectangle(pos, rect)
+    }
+
+    fn draw_image(&mut self, pos: Point, image: Pin<&Image>) {
+        self.stats.images += 1;
+        self.renderer.draw_image(pos, image)
+    }
+
+    fn draw_clipped_image(&mut self, pos: Point, image: Pin<&ClippedImage>) {
+        self.stats.images += 1;
+        self.renderer.draw_clipped_image(pos, image)
+    }
+
+    fn draw_text(&mut self, pos: Point, text: Pin<&Text>) {
+        self.stats.texts += 1;
+        self.renderer.draw_text(pos, text)
+    }
+
+    fn draw_text_input(&mut self, pos: Point, text_input: Pin<&TextInput>) {
+        self.stats.texts += 1;
+        self.renderer.draw_text_input(pos, text_input)
+    }
+
+    fn draw_path(&mut self, pos: Point, path: Pin<&Path>) {
+        self.stats.paths += 1;
+        self.renderer.draw_path(pos, path)
+    }
+
+    fn draw_line(&mut self, pos: Point, line: Pin<&Line>) {
+        self.stats.paths += 1;
+        self.renderer.draw_line(pos, line)
+    }
+
+    fn draw_box_shadow(&mut self, pos: Point, box_shadow: Pin<&BoxShadow>) {
+        self.stats.shadows += 1;
+        self.renderer.draw_box_shadow(pos, box_shadow)
+    }
+
+    fn draw_custom_rendering(&mut self, pos: Point, custom_rendering: Pin<&CustomRendering>) {
+        self.stats.paths += 1;
+        self.renderer.draw_custom_rendering(pos, custom_rendering)
+    }
+
+    fn combine_clip(&mut self, pos: Point, clip: Pin<&Clip>) {
+        self.stats.clips += 1;
+        self.renderer.combine_clip(pos, clip)
+    }
+
+    fn combine_transform(&mut self, pos: Point, transform: Pin<&Transform>) {
+        self.renderer.combine_transform(pos, transform)
+    }
+
+    fn combine_desaturate(&mut self, pos: Point, desaturate: Pin<&Desaturate>) {
+        self.renderer.combine_desaturate(pos, desaturate)
+    }
+
+    fn draw_layer(
+        &mut self,
+        pos: Point,
+        layer: Pin<&Layer>,
+        render_children: &mut dyn FnMut(&mut dyn ItemRenderer),
+    ) {
+        // Only the children that are rendered again are counted, not the cached rendering of the layer.
+        let stats = &mut self.stats;
+        self.renderer.draw_layer(pos, layer, &mut |renderer| {
+            let mut recorder = FrameStatsRecorder::new(renderer);
+            render_children(&mut recorder);
+            stats.add_draw_calls(&recorder.stats);
+        })
+    }
+
+    fn save_state(&mut self) {
+        self.renderer.save_state()
+    }
+
+    fn restore_state(&mut self) {
+        self.renderer.restore_state()
+    }
+
+    fn scale_factor(&self) -> f32 {
+        self.renderer.scale_factor()
+    }
+
+    fn draw_cached_pixmap(
+        &mut self,
+        item_cache: &CachedRenderingData,
+        pos: Point,
+        update_fn: &dyn Fn(&mut dyn FnMut(u32, u32, &[u8])),
+    ) {
+        self.stats.images += 1;
+        self.renderer.draw_cached_pixmap(item_cache, pos, update_fn)
+    }
+
+    fn as_any(&mut self) -> &mut dyn core::any::Any {
+        self.renderer.as_any()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_commands::RenderCommandList;
+
+    #[test]
+    fn draw_calls_are_counted_by_item_type() {
+        let rectangle = Box::pin(Rectangle::default());
+        let border_rectangle = Box::pin(BorderRectangle::default());
+        let text = Box::pin(Text::default());
+        let text_input = Box::pin(TextInput::default());
+        let image = Box::pin(Image::default());
+        let line = Box::pin(Line::default());
+
+        let mut commands = RenderCommandList::new(1.);
+        let mut recorder = FrameStatsRecorder::new(&mut commands);
+        recorder.draw_rectangle(Point::default(), rectangle.as_ref());
+        recorder.draw_rectangle(Point::default(), rectangle.as_ref());
+        recorder.draw_border_rectangle(Point::default(), border_rectangle.as_ref());
+        recorder.save_state();
+        recorder.draw_text(Point::default(), text.as_ref());
+        recorder.draw_text_input(Point::default(), text_input.as_ref());
+        recorder.restore_state();
+        recorder.draw_image(Point::default(), image.as_ref());
+        recorder.draw_cached_pixmap(
+            &CachedRenderingData::default(),
+            Point::default(),
+            &|callback| callback(1, 1, &[0, 0, 0, 0]),
+        );
+        recorder.draw_line(Point::default(), line.as_ref());
+        assert!(recorder.as_any().is::<RenderCommandList>());
+
+        assert_eq!(
+            recorder.stats(),
+            FrameStats { rectangles: 3, texts: 2, images: 2, paths: 1, ..Default::default() }
+        );
+        // The draw calls reach the other renderer.
+        assert_eq!(commands.commands().len(), 10);
+    }
+}
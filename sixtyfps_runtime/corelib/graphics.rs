@@ -220,6 +220,125 @@ fn from(col: Color) -> Self {
     }
 }
 
+/// A color of a gradient, at a position along the gradient from 0 to 1.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct GradientStop {
+    /// Position along the gradient, from 0 to 1
+    pub position: f32,
+    /// Color at that position
+    pub color: Color,
+}
+
+/// Parses the color stops of a gradient, as set with the `conic-gradient-stops` property: a comma separated list
+/// of colors in hexadecimal notation, each optionally followed by its position in percent, such as
+/// `"#f00, #00f 25%, #f00"`. Like in CSS, the first and last stop default to 0% and 100%, the stops without a
+/// position are spread evenly between their neighbors, and a stop positioned before the previous one is moved to it.
+pub fn parse_gradient_stops(stops: &str) -> Result<Vec<GradientStop>, String> {
+    let mut parsed = stops
+        .split(',')
+        .map(|stop| {
+            let mut parts = stop.split_whitespace();
+            let color =
+                parts.next().ok_or_else(|| format!("Empty gradient stop in {:?}", stops))?;
+            let color = parse_hex_color(color)
+                .ok_or_else(|| format!("Invalid gradient color {:?}", color))?;
+            let position = match parts.next() {
+                Some(position) => Some(
+                    position
+                        .strip_suffix('%')
+                        .and_then(|percent| percent.parse::<f32>().ok())
+                        .ok_or_else(|| format!("Invalid gradient stop position {:?}", position))?
+                        / 100.,
+                ),
+                None => None,
+            };
+            if let Some(extra) = parts.next() {
+                return Err(format!("Unexpected {:?} in gradient stop {:?}", extra, stop.trim()));
+            }
+            Ok((position, color))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if parsed.len() < 2 {
+        return Err(format!("A gradient needs at least two color stops, got {:?}", stops));
+    }
+
+    let last = parsed.len() - 1;
+    parsed[0].0.get_or_insert(0.);
+    parsed[last].0.get_or_insert(1.);
+    let mut previous = 0;
+    for index in 1..parsed.len() {
+        if let Some(position) = parsed[index].0 {
+            let start = parsed[previous].0.unwrap();
+            let position = position.max(start);
+            parsed[index].0 = Some(position);
+            let count = (index - previous) as f32;
+            for (step, unpositioned) in (previous + 1..index).enumerate() {
+                parsed[unpositioned].0 =
+                    Some(start + (position - start) * (step + 1) as f32 / count);
+            }
+            previous = index;
+        }
+    }
+
+    Ok(parsed
+        .into_iter()
+        .map(|(position, color)| GradientStop { position: position.unwrap(), color })
+        .collect())
+}
+
+/// Parses a color in the `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa` notation.
+fn parse_hex_color(color: &str) -> Option<Color> {
+    let digits = color.strip_prefix('#')?;
+    if !digits.is_ascii() {
+        return None;
+    }
+    let channel = |index: usize, width: usize| {
+        u8::from_str_radix(&digits[index * width..(index + 1) * width], 16).ok().map(|value| {
+            if width == 1 {
+                value * 0x11
+            } else {
+                value
+            }
+        })
+    };
+    let width = match digits.len() {
+        3 | 4 => 1,
+        6 | 8 => 2,
+        _ => return None,
+    };
+    let alpha = if digits.len() / width == 4 { channel(3, width)? } else { 0xff };
+    Some(Color::from_argb_u8(alpha, channel(0, width)?, channel(1, width)?, channel(2, width)?))
+}
+
+/// The convention used for the alpha channel of pixel data, such as the pixels read back from a window.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AlphaMode {
+    /// The color channels are independent of the alpha channel. This is what image file formats
+    /// such as PNG expect.
+    Straight,
+    /// The color channels are already multiplied with the alpha channel, as expected by many
+    /// compositors.
+    Premultiplied,
+}
+
+/// How the edges of the glyphs of text are antialiased.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextAntialiasing {
+    /// The glyphs are blended with the background by their coverage of each pixel. This looks right on any
+    /// display, with any transform and over any background.
+    Grayscale,
+    /// The glyphs are rendered for the red, green and blue subpixels of LCD displays, which makes text
+    /// look sharper on low-DPI displays. Text that is rotated, skewed or mirrored, and text in transparent
+    /// windows, is antialiased in grayscale regardless, as it would get colored fringes otherwise.
+    Subpixel,
+}
+
+impl Default for TextAntialiasing {
+    fn default() -> Self {
+        Self::Grayscale
+    }
+}
+
 /// A resource is a reference to binary data, for example images. They can be accessible on the file
 /// system or embedded in the resulting binary. Or they might be URLs to a web server and a downloaded
 /// is necessary before they can be used.
@@ -244,6 +363,22 @@ fn default() -> Self {
     }
 }
 
+impl Resource {
+    /// Creates an image resource from pixels in the RGBA format with 8 bits per channel, row by row
+    /// without padding, such as the ones returned by [`crate::backend::Backend::clipboard_image`].
+    /// Returns None if the amount of data does not match the size of the image.
+    pub fn from_rgba8(width: u32, height: u32, rgba: &[u8]) -> Option<Self> {
+        if rgba.len() != width as usize * height as usize * 4 {
+            return None;
+        }
+        let data = rgba
+            .chunks_exact(4)
+            .map(|pixel| u32::from_be_bytes([pixel[3], pixel[0], pixel[1], pixel[2]]))
+            .collect();
+        Some(Resource::EmbeddedRgbaImage { width, height, data })
+    }
+}
+
 /// CachedGraphicsData allows the graphics backend to store an arbitrary piece of data associated with
 /// an item, which is typically computed by accessing properties. The dependency_tracker is used to allow
 /// for a lazy computation. Typically backends store either compute intensive data or handles that refer to
@@ -284,6 +419,16 @@ pub struct FontRequest {
     pub weight: Option<i32>,
     /// If the pixel size is None, the system default font size should be used.
     pub pixel_size: Option<f32>,
+    /// Whether an italic or oblique face of the font should be used.
+    pub style: crate::items::FontStyle,
+    /// The style of the digits, which may need support from the font.
+    pub numeral_style: crate::items::NumeralStyle,
+    /// The language of the text, as a BCP 47 tag such as "sr", for the glyph forms that the font has
+    /// for the language. Empty if unknown.
+    pub language: SharedString,
+    /// The distance between the tab stops that tab characters advance the text to, in pixels. None
+    /// for the default of eight spaces.
+    pub tab_width: Option<f32>,
 }
 
 /// The FontMetrics trait is constructed from a FontRequest by the graphics backend and supplied to text related
@@ -291,15 +436,38 @@ pub struct FontRequest {
 pub trait FontMetrics {
     /// Returns the size of the given string in physical pixels.
     fn text_size(&self, text: &str) -> Size;
+    /// Returns the size of the given string in physical pixels when it is wrapped at word boundaries to lines of
+    /// the given width, like a `Text` element with `wrap: word-wrap` draws it. The height is the one of all lines,
+    /// so that a layout can reserve the space the text takes at that width.
+    fn text_size_for_width(&self, text: &str, max_width: f32) -> Size;
+    /// Returns the bounding box of the outlines of the glyphs of the given line of text, in physical pixels and
+    /// relative to the top left corner of the box measured by [`Self::text_size`]. That box is based on the advances
+    /// of the glyphs and the height of the font, while this is the area that is actually painted, which allows
+    /// centering a single large glyph, such as an icon, optically. The rectangle is empty if no glyph has an outline.
+    fn text_ink_bounds(&self, text: &str) -> Rect;
     /// Returns the (UTF-8) byte offset in the given text that refers to the character that contributed to
     /// the glyph cluster that's visually nearest to the given x coordinate. This is used for hit-testing,
     /// for example when receiving a mouse click into a text field. Then this function returns the "cursor"
     /// position.
     fn text_offset_for_x_position<'a>(&self, text: &'a str, x: f32) -> usize;
+    /// Same as [`Self::text_offset_for_x_position`], but for text that is laid out in multiple lines, like in a
+    /// `Text` element: the line is determined by the y coordinate, and the x coordinate is relative to the start of
+    /// that line. If `wrap_width` is set, the text is wrapped at word boundaries to lines of that width, otherwise
+    /// lines only break at newline characters.
+    fn text_offset_for_position(&self, text: &str, pos: Point, wrap_width: Option<f32>) -> usize;
     /// Returns the height of the font. This is typically the sum of the ascent and the descent, resulting
     /// in the height that can fit the talltest glyphs of the font. Note that it is possible though that
     /// the font may include glyphs that exceed this.
     fn height(&self) -> f32;
+    /// Returns the distance from the baseline to the top of the tallest glyphs of the font, in the same
+    /// unit as [`Self::height`].
+    fn ascent(&self) -> f32;
+    /// Returns the distance from the baseline to the bottom of the lowest glyphs of the font, as a positive
+    /// value in the same unit as [`Self::height`].
+    fn descent(&self) -> f32;
+    /// Returns the space the font recommends between the descent of a line and the ascent of the next one.
+    /// [`Self::height`] is at least the sum of the ascent, the descent and the line gap.
+    fn line_gap(&self) -> f32;
 }
 
 #[repr(C)]
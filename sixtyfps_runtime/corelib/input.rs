@@ -327,6 +327,8 @@ pub fn process_mouse_input(
 pub(crate) struct TextCursorBlinker {
     cursor_visible: Property<bool>,
     cursor_blink_timer: crate::timers::Timer,
+    /// The time between toggling the cursor. Zero means that the cursor doesn't blink.
+    blink_interval: core::cell::Cell<std::time::Duration>,
 }
 
 impl TextCursorBlinker {
@@ -336,13 +338,23 @@ pub fn new() -> Pin<Rc<Self>> {
         Rc::pin(Self {
             cursor_visible: Property::new(true),
             cursor_blink_timer: Default::default(),
+            blink_interval: core::cell::Cell::new(std::time::Duration::from_millis(500)),
         })
     }
 
     /// Sets a binding on the provided property that will ensure that the property value
-    /// is true when the cursor should be shown and false if not.
-    pub fn set_binding(instance: Pin<Rc<TextCursorBlinker>>, prop: &Property<bool>) {
+    /// is true when the cursor should be shown and false if not. The cursor is toggled every
+    /// `blink_interval`, or stays visible if it is zero.
+    pub fn set_binding(
+        instance: Pin<Rc<TextCursorBlinker>>,
+        prop: &Property<bool>,
+        blink_interval: std::time::Duration,
+    ) {
         instance.as_ref().cursor_visible.set(true);
+        if instance.blink_interval.replace(blink_interval) != blink_interval {
+            // The timer is started again with the new interval.
+            instance.cursor_blink_timer.stop();
+        }
         // Re-start timer, in case.
         Self::start(&instance);
         prop.set_binding(move || {
@@ -353,7 +365,10 @@ pub fn set_binding(instance: Pin<Rc<TextCursorBlinker>>, prop: &Property<bool>)
     /// Starts the blinking cursor timer that will toggle the cursor and update all bindings that
     /// were installed on properties with set_binding call.
     pub fn start(self: &Pin<Rc<Self>>) {
-        if self.cursor_blink_timer.running() {
+        let blink_interval = self.blink_interval.get();
+        if blink_interval.is_zero() {
+            self.cursor_blink_timer.stop();
+        } else if self.cursor_blink_timer.running() {
             self.cursor_blink_timer.restart();
         } else {
             let toggle_cursor = {
@@ -370,7 +385,7 @@ pub fn start(self: &Pin<Rc<Self>>) {
             };
             self.cursor_blink_timer.start(
                 crate::timers::TimerMode::Repeated,
-                std::time::Duration::from_millis(500),
+                blink_interval,
                 toggle_cursor,
             );
         }
@@ -382,3 +397,25 @@ pub fn stop(&self) {
         self.cursor_blink_timer.stop()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_with_zero_blink_interval_does_not_blink() {
+        let cursor_visible = Box::pin(Property::new(false));
+        let blinker = TextCursorBlinker::new();
+        TextCursorBlinker::set_binding(
+            blinker.clone(),
+            &cursor_visible,
+            std::time::Duration::from_millis(300),
+        );
+        assert!(cursor_visible.as_ref().get());
+        assert!(blinker.cursor_blink_timer.running());
+
+        TextCursorBlinker::set_binding(blinker.clone(), &cursor_visible, Default::default());
+        assert!(cursor_visible.as_ref().get());
+        assert!(!blinker.cursor_blink_timer.running());
+    }
+}
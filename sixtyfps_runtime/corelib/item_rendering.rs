@@ -14,9 +14,11 @@
 use super::items::*;
 use crate::component::ComponentRc;
 use crate::graphics::Point;
-use crate::item_tree::ItemVisitorResult;
+use crate::item_tree::{ItemVisitor, ItemVisitorVTable, TraversalOrder, VisitChildrenResult};
+use crate::properties::PropertyTracker;
 use core::pin::Pin;
 use std::cell::{Cell, RefCell};
+use vtable::VRc;
 
 /// This structure must be present in items that are Rendered and contains information.
 /// Used by the backend.
@@ -27,40 +29,60 @@ pub struct CachedRenderingData {
     pub(crate) cache_index: Cell<usize>,
     /// Set to false initially and when changes happen that require updating the cache
     pub(crate) cache_ok: Cell<bool>,
+    /// Set by [`Self::invalidate`] to update the cache regardless of the properties it depends on
+    pub(crate) cache_invalidated: Cell<bool>,
 }
 
 impl CachedRenderingData {
     /// This function allows retrieving the backend specific per-item data cache, updating
     /// it if depending properties have changed. The supplied update_fn will be called when
-    /// properties have changed or the cache is initialized the first time.
+    /// properties have changed, the cache is initialized the first time, or after [`Self::invalidate`].
+    /// When called while rendering the children of a [`Layer`], changes of these properties also
+    /// invalidate the rendering of the layer.
     pub fn ensure_up_to_date<T: Clone>(
         &self,
         cache: &mut RenderingCache<T>,
         update_fn: impl FnOnce() -> T,
     ) -> T {
+        let invalidated = self.cache_invalidated.replace(false);
         if self.cache_ok.get() {
             let index = self.cache_index.get();
             let existing_entry = cache.get_mut(index).unwrap();
-            if existing_entry.dependency_tracker.is_dirty() {
+            existing_entry.dependency_tracker.as_ref().register_as_dependency_to_current_binding();
+            if invalidated || existing_entry.dependency_tracker.is_dirty() {
                 existing_entry.data = existing_entry.dependency_tracker.as_ref().evaluate(update_fn)
             }
             existing_entry.data.clone()
         } else {
             self.cache_index.set(cache.insert(crate::graphics::CachedGraphicsData::new(update_fn)));
             self.cache_ok.set(true);
-            cache.get(self.cache_index.get()).unwrap().data.clone()
+            let entry = cache.get(self.cache_index.get()).unwrap();
+            entry.dependency_tracker.as_ref().register_as_dependency_to_current_binding();
+            entry.data.clone()
         }
     }
 
     /// This function can be used to remove an entry from the rendering cache for a given item, if it
     /// exists, i.e. if any data was ever cached. This is typically called by the graphics backend's
-    /// implementation of the release_item_graphics_cache function.
+    /// implementation of the release_item_graphics_cache function. A subsequent call to ensure_up_to_date
+    /// creates a new entry.
     pub fn release<T>(&self, cache: &mut RenderingCache<T>) {
+        self.cache_invalidated.set(false);
         if self.cache_ok.get() {
             let index = self.cache_index.get();
             cache.remove(index);
+            self.cache_ok.set(false);
         }
     }
+
+    /// Marks the cached data as outdated, so that the backend calls the update function again the next
+    /// time the item is drawn. This is for items that draw data that is not held in properties, such as
+    /// custom items drawn with [`ItemRenderer::draw_cached_pixmap`], when that data changes. Call
+    /// [`ComponentWindow::request_redraw`](crate::window::ComponentWindow::request_redraw) as well, for
+    /// the window to draw the item again.
+    pub fn invalidate(&self) {
+        self.cache_invalidated.set(true);
+    }
 }
 
 /// Renders the tree of items that component holds, using the specified renderer. Rendering is done
@@ -70,26 +92,43 @@ pub fn render_component_items(
     renderer: &mut dyn ItemRenderer,
     origin: crate::graphics::Point,
 ) {
-    let renderer = RefCell::new(renderer);
-
-    crate::item_tree::visit_items_with_post_visit(
-        component,
-        crate::item_tree::TraversalOrder::BackToFront,
-        |_, item, _, translation| {
-            renderer.borrow_mut().save_state();
-
-            item.as_ref()
-                .render(*translation, &mut (*renderer.borrow_mut() as &mut dyn ItemRenderer));
-
-            let origin = item.as_ref().geometry().origin;
-            let translation = *translation + euclid::Vector2D::new(origin.x, origin.y);
-
-            (ItemVisitorResult::Continue(translation), ())
-        },
-        |_, _, _| {
-            renderer.borrow_mut().restore_state();
-        },
-        origin,
+    render_item_children(component, -1, renderer, origin);
+}
+
+/// Renders the children of the item at the given index of the component, or its root item for an index of -1.
+/// The children of a [`Layer`] with `cache_rendering_hint` are drawn with [`ItemRenderer::draw_layer`].
+fn render_item_children(
+    component: &ComponentRc,
+    index: isize,
+    renderer: &mut dyn ItemRenderer,
+    origin: Point,
+) {
+    let mut actual_visitor =
+        |component: &ComponentRc, index: usize, item: Pin<ItemRef>| -> VisitChildrenResult {
+            renderer.save_state();
+
+            let item_origin = item.as_ref().geometry().origin;
+            let translation = origin + euclid::Vector2D::new(item_origin.x, item_origin.y);
+            match ItemRef::downcast_pin::<Layer>(item) {
+                Some(layer) if layer.cache_rendering_hint() => {
+                    renderer.draw_layer(origin, layer, &mut |renderer| {
+                        render_item_children(component, index as isize, renderer, translation)
+                    })
+                }
+                _ => {
+                    item.as_ref().render(origin, &mut (&mut *renderer as &mut dyn ItemRenderer));
+                    render_item_children(component, index as isize, renderer, translation);
+                }
+            }
+
+            renderer.restore_state();
+            VisitChildrenResult::CONTINUE
+        };
+    vtable::new_vref!(let mut actual_visitor : VRefMut<ItemVisitorVTable> for ItemVisitor = &mut actual_visitor);
+    VRc::borrow_pin(component).as_ref().visit_children_item(
+        index,
+        TraversalOrder::BackToFront,
+        actual_visitor,
     );
 }
 
@@ -106,8 +145,25 @@ pub trait ItemRenderer {
     fn draw_text(&mut self, pos: Point, text: Pin<&Text>);
     fn draw_text_input(&mut self, pos: Point, text_input: Pin<&TextInput>);
     fn draw_path(&mut self, pos: Point, path: Pin<&Path>);
+    fn draw_line(&mut self, pos: Point, line: Pin<&Line>);
     fn draw_box_shadow(&mut self, pos: Point, box_shadow: Pin<&BoxShadow>);
     fn combine_clip(&mut self, pos: Point, clip: Pin<&Clip>);
+    /// Applies the transform of the element to everything drawn until the state is restored.
+    fn combine_transform(&mut self, pos: Point, transform: Pin<&Transform>);
+    /// Draws everything until the state is restored with its saturation reduced to the one of the element.
+    fn combine_desaturate(&mut self, pos: Point, desaturate: Pin<&Desaturate>);
+    /// Draws the children of a `Layer` element with `cache_rendering_hint`, by calling `render_children`
+    /// with the renderer to draw them with. A renderer can draw them into an image once, with a
+    /// [`LayerCache`], and draw that image instead of calling `render_children` again.
+    fn draw_layer(
+        &mut self,
+        pos: Point,
+        layer: Pin<&Layer>,
+        render_children: &mut dyn FnMut(&mut dyn ItemRenderer),
+    );
+    /// Calls the draw handler that the application registered with the backend under the `renderer` name of
+    /// the element, to draw within the geometry of the element. Backends without such handlers draw nothing.
+    fn draw_custom_rendering(&mut self, pos: Point, custom_rendering: Pin<&CustomRendering>);
     fn save_state(&mut self);
     fn restore_state(&mut self);
 
@@ -128,3 +184,357 @@ fn draw_cached_pixmap(
     /// Return the internal renderer
     fn as_any(&mut self) -> &mut dyn core::any::Any;
 }
+
+/// The rendering of the children of a [`Layer`] element with `cache_rendering_hint`, for example an image they
+/// were drawn into, to be drawn again instead of the children as long as none of the properties the children
+/// were drawn with change. Used by the backends, in the rendering cache of the layer.
+pub struct LayerCache<T> {
+    dependency_tracker: Pin<Box<PropertyTracker>>,
+    /// The rendering, and the size in pixels that it was rendered at.
+    rendering: RefCell<Option<(T, (u32, u32))>>,
+}
+
+impl<T> Default for LayerCache<T> {
+    fn default() -> Self {
+        Self {
+            dependency_tracker: Box::pin(PropertyTracker::default()),
+            rendering: RefCell::new(None),
+        }
+    }
+}
+
+impl<T: Clone> LayerCache<T> {
+    /// Returns the rendering of the children at the given size in pixels. The render function is called to
+    /// render them again if the size has changed, for example with the scale factor, or if any of the properties
+    /// that the children were rendered with has changed. That includes the properties that the cached rendering
+    /// data of the children depends on.
+    pub fn get_or_render(
+        &self,
+        pixel_size: (u32, u32),
+        render: impl FnOnce() -> Option<T>,
+    ) -> Option<T> {
+        // Layers within layers are rendered again when their rendering changes.
+        self.dependency_tracker.as_ref().register_as_dependency_to_current_binding();
+        if !self.dependency_tracker.is_dirty() {
+            if let Some((rendering, size)) = &*self.rendering.borrow() {
+                if *size == pixel_size {
+                    return Some(rendering.clone());
+                }
+            }
+        }
+        let rendering = self.dependency_tracker.as_ref().evaluate(render);
+        *self.rendering.borrow_mut() = rendering.clone().map(|rendering| (rendering, pixel_size));
+        rendering
+    }
+}
+
+/// How long after the last change of its size an image with `image-rendering: pixelated` is still drawn smooth.
+const IMAGE_SCALE_SETTLE_TIME: core::time::Duration = core::time::Duration::from_millis(150);
+
+struct ImageScale {
+    pixel_size: (u32, u32),
+    /// The animation tick at which the pixel size last changed.
+    changed: Option<crate::animations::Instant>,
+    drawn_in_current_frame: bool,
+}
+
+/// Keeps track of the size in pixels at which the images of items with `image-rendering: pixelated` are drawn.
+/// While that size changes, for example when the size of the item is animated, the image should be drawn smooth,
+/// as switching between smooth and nearest pixel sampling from one frame to the next makes the image flicker.
+/// Used by the backends.
+#[derive(Default)]
+pub struct ImageScaleTracker {
+    /// By the address of the item's CachedRenderingData.
+    items: std::collections::HashMap<usize, ImageScale>,
+    settle_timer: crate::timers::Timer,
+}
+
+impl ImageScaleTracker {
+    /// Records the size in pixels at which the image of the item is drawn in the current frame. Returns true if
+    /// the size has changed recently, in which case the image should be drawn smooth. A redraw is then scheduled
+    /// for when the size has settled.
+    pub fn is_scaling(&mut self, item: &CachedRenderingData, pixel_size: (u32, u32)) -> bool {
+        let scaling = self.update(
+            item as *const CachedRenderingData as usize,
+            pixel_size,
+            crate::animations::current_tick(),
+        );
+        if scaling {
+            // The timer firing is enough for the event loop to redraw all windows.
+            self.settle_timer.start(
+                crate::timers::TimerMode::SingleShot,
+                IMAGE_SCALE_SETTLE_TIME,
+                || {},
+            );
+        }
+        scaling
+    }
+
+    fn update(
+        &mut self,
+        item: usize,
+        pixel_size: (u32, u32),
+        now: crate::animations::Instant,
+    ) -> bool {
+        let scale = self.items.entry(item).or_insert(ImageScale {
+            pixel_size,
+            changed: None,
+            drawn_in_current_frame: true,
+        });
+        scale.drawn_in_current_frame = true;
+        if scale.pixel_size != pixel_size {
+            scale.pixel_size = pixel_size;
+            scale.changed = Some(now);
+        }
+        scale.changed.map_or(false, |changed| now - changed < IMAGE_SCALE_SETTLE_TIME)
+    }
+
+    /// Forgets the items that were not drawn in the current frame. This must be called after each frame.
+    pub fn finish_frame(&mut self) {
+        self.items.retain(|_, scale| core::mem::take(&mut scale.drawn_in_current_frame));
+    }
+}
+
+#[test]
+fn image_scale_tracker() {
+    use crate::animations::Instant;
+    let mut tracker = ImageScaleTracker::default();
+    assert!(!tracker.update(1, (10, 10), Instant(1000)));
+    tracker.finish_frame();
+    assert!(!tracker.update(1, (10, 10), Instant(1016)));
+    tracker.finish_frame();
+    // Stays smooth while the size changes, even if it stays the same for a frame or two
+    assert!(tracker.update(1, (12, 12), Instant(1032)));
+    tracker.finish_frame();
+    assert!(tracker.update(1, (12, 12), Instant(1048)));
+    tracker.finish_frame();
+    assert!(tracker.update(1, (14, 14), Instant(1064)));
+    tracker.finish_frame();
+    assert!(tracker.update(1, (14, 14), Instant(1213)));
+    tracker.finish_frame();
+    assert!(!tracker.update(1, (14, 14), Instant(1214)));
+    tracker.finish_frame();
+    // Items that were not drawn in a frame are forgotten
+    tracker.finish_frame();
+    assert!(!tracker.update(1, (20, 20), Instant(1300)));
+}
+
+#[test]
+fn invalidated_rendering_data_is_updated() {
+    let mut cache = RenderingCache::default();
+    let data = CachedRenderingData::default();
+    let updates = Cell::new(0);
+    let update = || {
+        updates.set(updates.get() + 1);
+        updates.get()
+    };
+    assert_eq!(data.ensure_up_to_date(&mut cache, update), 1);
+    assert_eq!(data.ensure_up_to_date(&mut cache, update), 1);
+    data.invalidate();
+    assert_eq!(data.ensure_up_to_date(&mut cache, update), 2);
+    assert_eq!(data.ensure_up_to_date(&mut cache, update), 2);
+    assert_eq!(cache.len(), 1);
+}
+
+#[test]
+fn layer_is_rendered_once_while_unchanged() {
+    let layer = LayerCache::default();
+    let color = Box::pin(crate::Property::new(1));
+    let text = Box::pin(crate::Property::new(10));
+    let mut cache = RenderingCache::default();
+    let child = CachedRenderingData::default();
+    let renders = Cell::new(0);
+    let mut render = || {
+        renders.set(renders.get() + 1);
+        let text = child.ensure_up_to_date(&mut cache, || text.as_ref().get());
+        Some(color.as_ref().get() + text)
+    };
+
+    assert_eq!(layer.get_or_render((10, 10), &mut render), Some(11));
+    assert_eq!(layer.get_or_render((10, 10), &mut render), Some(11));
+    assert_eq!(renders.get(), 1);
+    color.as_ref().set(2);
+    assert_eq!(layer.get_or_render((10, 10), &mut render), Some(12));
+    assert_eq!(renders.get(), 2);
+    // Properties that only the cached rendering data of a child depends on invalidate the layer as well
+    text.as_ref().set(20);
+    assert_eq!(layer.get_or_render((10, 10), &mut render), Some(22));
+    assert_eq!(renders.get(), 3);
+    // Changing the scale factor changes the size in pixels
+    assert_eq!(layer.get_or_render((20, 20), &mut render), Some(22));
+    assert_eq!(renders.get(), 4);
+    assert_eq!(layer.get_or_render((20, 20), &mut render), Some(22));
+    assert_eq!(renders.get(), 4);
+}
+
+#[test]
+#[allow(unsafe_code)]
+fn layer_children_are_rendered_once_until_they_change() {
+    use crate::component::{Component, ComponentVTable};
+    use crate::graphics::{Color, Rect};
+    use crate::item_tree::{visit_item_tree, ItemTreeNode, ItemVisitorRefMut};
+    use crate::layout::LayoutInfo;
+    use const_field_offset::FieldOffsets;
+    use vtable::{VOffset, VWeak};
+
+    // A root rectangle with a layer that contains another rectangle.
+    #[derive(FieldOffsets, Default)]
+    #[repr(C)]
+    #[pin]
+    struct LayerComponent {
+        root: Rectangle,
+        layer: Layer,
+        child: Rectangle,
+        self_weak: once_cell::unsync::OnceCell<VWeak<ComponentVTable, LayerComponent>>,
+    }
+
+    ComponentVTable_static!(static LAYER_COMPONENT_VT for LayerComponent);
+
+    impl LayerComponent {
+        fn item_tree() -> &'static [ItemTreeNode<Self>] {
+            static ITEM_TREE: once_cell::sync::Lazy<[ItemTreeNode<LayerComponent>; 3]> =
+                once_cell::sync::Lazy::new(|| {
+                    [
+                        ItemTreeNode::Item {
+                            item: VOffset::new(LayerComponent::FIELD_OFFSETS.root),
+                            chilren_count: 1,
+                            children_index: 1,
+                            parent_index: 0,
+                        },
+                        ItemTreeNode::Item {
+                            item: VOffset::new(LayerComponent::FIELD_OFFSETS.layer),
+                            chilren_count: 1,
+                            children_index: 2,
+                            parent_index: 0,
+                        },
+                        ItemTreeNode::Item {
+                            item: VOffset::new(LayerComponent::FIELD_OFFSETS.child),
+                            chilren_count: 0,
+                            children_index: 3,
+                            parent_index: 1,
+                        },
+                    ]
+                });
+            &*ITEM_TREE
+        }
+    }
+
+    impl Component for LayerComponent {
+        fn visit_children_item(
+            self: Pin<&Self>,
+            index: isize,
+            order: TraversalOrder,
+            visitor: ItemVisitorRefMut,
+        ) -> VisitChildrenResult {
+            let self_rc = VRc::into_dyn(self.self_weak.get().unwrap().upgrade().unwrap());
+            visit_item_tree(
+                self,
+                &self_rc,
+                Self::item_tree(),
+                index,
+                order,
+                visitor,
+                |_, _, _, _| unreachable!(),
+            )
+        }
+        fn get_item_ref(self: Pin<&Self>, index: usize) -> Pin<ItemRef> {
+            match &Self::item_tree()[index] {
+                ItemTreeNode::Item { item, .. } => item.apply_pin(self),
+                ItemTreeNode::DynamicTree { .. } => unreachable!(),
+            }
+        }
+        fn parent_item(self: Pin<&Self>, _index: usize, _result: &mut ItemWeak) {}
+        fn layout_info(self: Pin<&Self>) -> LayoutInfo {
+            Default::default()
+        }
+        fn apply_layout(self: Pin<&Self>, _rect: Rect) {}
+    }
+
+    // Records the rectangles it draws, and draws the children of layers once, while they are unchanged.
+    struct RecordingRenderer<'a> {
+        layer_cache: &'a LayerCache<()>,
+        rectangles: Vec<(Point, Color)>,
+    }
+
+    impl ItemRenderer for RecordingRenderer<'_> {
+        fn draw_rectangle(&mut self, pos: Point, rect: Pin<&Rectangle>) {
+            self.rectangles.push((pos + euclid::vec2(rect.x(), rect.y()), rect.color()));
+        }
+        fn draw_border_rectangle(&mut self, _: Point, _: Pin<&BorderRectangle>) {}
+        fn draw_image(&mut self, _: Point, _: Pin<&Image>) {}
+        fn draw_clipped_image(&mut self, _: Point, _: Pin<&ClippedImage>) {}
+        fn draw_text(&mut self, _: Point, _: Pin<&Text>) {}
+        fn draw_text_input(&mut self, _: Point, _: Pin<&TextInput>) {}
+        fn draw_path(&mut self, _: Point, _: Pin<&Path>) {}
+        fn draw_line(&mut self, _: Point, _: Pin<&Line>) {}
+        fn draw_box_shadow(&mut self, _: Point, _: Pin<&BoxShadow>) {}
+        fn combine_clip(&mut self, _: Point, _: Pin<&Clip>) {}
+        fn combine_transform(&mut self, _: Point, _: Pin<&Transform>) {}
+        fn combine_desaturate(&mut self, _: Point, _: Pin<&Desaturate>) {}
+        fn draw_layer(
+            &mut self,
+            _: Point,
+            _: Pin<&Layer>,
+            render_children: &mut dyn FnMut(&mut dyn ItemRenderer),
+        ) {
+            let layer_cache = self.layer_cache;
+            layer_cache.get_or_render((1, 1), || {
+                render_children(self);
+                Some(())
+            });
+        }
+        fn draw_custom_rendering(&mut self, _: Point, _: Pin<&CustomRendering>) {}
+        fn save_state(&mut self) {}
+        fn restore_state(&mut self) {}
+        fn scale_factor(&self) -> f32 {
+            1.
+        }
+        fn draw_cached_pixmap(
+            &mut self,
+            _: &CachedRenderingData,
+            _: Point,
+            _: &dyn Fn(&mut dyn FnMut(u32, u32, &[u8])),
+        ) {
+        }
+        fn as_any(&mut self) -> &mut dyn core::any::Any {
+            unimplemented!()
+        }
+    }
+
+    let component = VRc::new(LayerComponent::default());
+    component.self_weak.set(VRc::downgrade(&component)).map_err(|_| ()).unwrap();
+    let instance = component.as_pin_ref();
+    instance.layer.x.set(10.);
+    instance.child.x.set(5.);
+    instance.child.color.set(Color::from_rgb_u8(255, 0, 0));
+    let component_rc = VRc::into_dyn(component.clone());
+
+    let layer_cache = LayerCache::default();
+    let render = || {
+        let mut renderer = RecordingRenderer { layer_cache: &layer_cache, rectangles: Vec::new() };
+        render_component_items(&component_rc, &mut renderer, Point::default());
+        renderer.rectangles
+    };
+    let root = (Point::default(), Color::default());
+    let red_child = (Point::new(15., 0.), Color::from_rgb_u8(255, 0, 0));
+
+    // Without the hint, the children of the layer are drawn like any other children.
+    assert_eq!(render(), [root, red_child]);
+    assert_eq!(render(), [root, red_child]);
+
+    instance.layer.cache_rendering_hint.set(true);
+    assert_eq!(render(), [root, red_child]);
+    // The cached rendering is used instead of drawing the child again.
+    assert_eq!(render(), [root]);
+    assert_eq!(render(), [root]);
+
+    // Changing a property that the child was drawn with renders the layer again, once.
+    instance.child.color.set(Color::from_rgb_u8(0, 0, 255));
+    let blue_child = (Point::new(15., 0.), Color::from_rgb_u8(0, 0, 255));
+    assert_eq!(render(), [root, blue_child]);
+    assert_eq!(render(), [root]);
+    // So does moving the child within the layer.
+    instance.child.x.set(6.);
+    assert_eq!(render(), [root, (Point::new(16., 0.), Color::from_rgb_u8(0, 0, 255))]);
+    assert_eq!(render(), [root]);
+}
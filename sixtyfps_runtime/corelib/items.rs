@@ -226,6 +226,12 @@ pub struct BorderRectangle {
     pub border_width: Property<f32>,
     pub border_radius: Property<f32>,
     pub border_color: Property<Color>,
+    pub conic_gradient_stops: Property<SharedString>,
+    pub conic_gradient_center_x: Property<f32>,
+    pub conic_gradient_center_y: Property<f32>,
+    pub conic_gradient_start_angle: Property<f32>,
+    pub stroke_alignment: Property<StrokeAlignment>,
+    pub hairline: Property<bool>,
     pub cached_rendering_data: CachedRenderingData,
 }
 
@@ -491,6 +497,21 @@ impl ItemConsts for FocusScope {
     pub static FocusScopeVTable for FocusScope
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+/// The shape of the area a `Clip` element shows its children in
+pub enum ClipShape {
+    rectangle,
+    ellipse,
+}
+
+impl Default for ClipShape {
+    fn default() -> Self {
+        Self::rectangle
+    }
+}
+
 #[repr(C)]
 #[derive(FieldOffsets, Default, SixtyFPSElement)]
 #[pin]
@@ -500,6 +521,9 @@ pub struct Clip {
     pub y: Property<f32>,
     pub width: Property<f32>,
     pub height: Property<f32>,
+    pub shape: Property<ClipShape>,
+    pub fade_vertical: Property<f32>,
+    pub fade_horizontal: Property<f32>,
     pub cached_rendering_data: CachedRenderingData,
 }
 
@@ -549,6 +573,279 @@ impl ItemConsts for Clip {
     pub static ClipVTable for Clip
 }
 
+#[repr(C)]
+#[derive(FieldOffsets, Default, SixtyFPSElement)]
+#[pin]
+/// The implementation of the `Transform` element
+pub struct Transform {
+    pub x: Property<f32>,
+    pub y: Property<f32>,
+    pub width: Property<f32>,
+    pub height: Property<f32>,
+    pub rotation_angle: Property<f32>,
+    pub origin_x: Property<f32>,
+    pub origin_y: Property<f32>,
+    pub scale_x: Property<f32>,
+    pub scale_y: Property<f32>,
+    pub shear_x: Property<f32>,
+    pub shear_y: Property<f32>,
+    pub cached_rendering_data: CachedRenderingData,
+}
+
+impl Item for Transform {
+    fn init(self: Pin<&Self>, _window: &ComponentWindow) {}
+
+    fn geometry(self: Pin<&Self>) -> Rect {
+        euclid::rect(self.x(), self.y(), self.width(), self.height())
+    }
+
+    fn layouting_info(self: Pin<&Self>, _window: &ComponentWindow) -> LayoutInfo {
+        LayoutInfo { horizontal_stretch: 1., vertical_stretch: 1., ..LayoutInfo::default() }
+    }
+
+    fn implicit_size(self: Pin<&Self>, _window: &ComponentWindow) -> Size {
+        Default::default()
+    }
+
+    fn input_event(
+        self: Pin<&Self>,
+        _: MouseEvent,
+        _window: &ComponentWindow,
+        _self_rc: &ItemRc,
+    ) -> InputEventResult {
+        InputEventResult::EventIgnored
+    }
+
+    fn key_event(self: Pin<&Self>, _: &KeyEvent, _window: &ComponentWindow) -> KeyEventResult {
+        KeyEventResult::EventIgnored
+    }
+
+    fn focus_event(self: Pin<&Self>, _: &FocusEvent, _window: &ComponentWindow) {}
+
+    fn render(self: Pin<&Self>, pos: Point, backend: &mut ItemRendererRef) {
+        (*backend).combine_transform(pos, self)
+    }
+}
+
+impl Transform {
+    /// Returns the transform that the children are drawn with, when the element is drawn at the given position.
+    /// It maps the coordinates the children are drawn at to the coordinates of the element's parent: they are
+    /// scaled, then sheared, then rotated clockwise, around the origin given relative to the element.
+    pub fn children_transform(self: Pin<&Self>, pos: Point) -> euclid::default::Transform2D<f32> {
+        let origin =
+            pos.to_vector() + euclid::vec2(self.x() + self.origin_x(), self.y() + self.origin_y());
+        euclid::default::Transform2D::translation(-origin.x, -origin.y)
+            .then_scale(self.scale_x(), self.scale_y())
+            .then(&euclid::default::Transform2D::new(
+                1.,
+                self.shear_y(),
+                self.shear_x(),
+                1.,
+                0.,
+                0.,
+            ))
+            .then_rotate(euclid::Angle::degrees(self.rotation_angle()))
+            .then_translate(origin)
+    }
+}
+
+impl ItemConsts for Transform {
+    const cached_rendering_data_offset: const_field_offset::FieldOffset<
+        Transform,
+        CachedRenderingData,
+    > = Transform::FIELD_OFFSETS.cached_rendering_data.as_unpinned_projection();
+}
+
+ItemVTable_static! {
+    /// The VTable for `Transform`
+    #[no_mangle]
+    pub static TransformVTable for Transform
+}
+
+#[repr(C)]
+#[derive(FieldOffsets, Default, SixtyFPSElement)]
+#[pin]
+/// The implementation of the `Desaturate` element
+pub struct Desaturate {
+    pub x: Property<f32>,
+    pub y: Property<f32>,
+    pub width: Property<f32>,
+    pub height: Property<f32>,
+    pub saturation: Property<f32>,
+    pub cached_rendering_data: CachedRenderingData,
+}
+
+impl Item for Desaturate {
+    fn init(self: Pin<&Self>, _window: &ComponentWindow) {}
+
+    fn geometry(self: Pin<&Self>) -> Rect {
+        euclid::rect(self.x(), self.y(), self.width(), self.height())
+    }
+
+    fn layouting_info(self: Pin<&Self>, _window: &ComponentWindow) -> LayoutInfo {
+        LayoutInfo { horizontal_stretch: 1., vertical_stretch: 1., ..LayoutInfo::default() }
+    }
+
+    fn implicit_size(self: Pin<&Self>, _window: &ComponentWindow) -> Size {
+        Default::default()
+    }
+
+    fn input_event(
+        self: Pin<&Self>,
+        _: MouseEvent,
+        _window: &ComponentWindow,
+        _self_rc: &ItemRc,
+    ) -> InputEventResult {
+        InputEventResult::EventIgnored
+    }
+
+    fn key_event(self: Pin<&Self>, _: &KeyEvent, _window: &ComponentWindow) -> KeyEventResult {
+        KeyEventResult::EventIgnored
+    }
+
+    fn focus_event(self: Pin<&Self>, _: &FocusEvent, _window: &ComponentWindow) {}
+
+    fn render(self: Pin<&Self>, pos: Point, backend: &mut ItemRendererRef) {
+        (*backend).combine_desaturate(pos, self)
+    }
+}
+
+impl ItemConsts for Desaturate {
+    const cached_rendering_data_offset: const_field_offset::FieldOffset<
+        Desaturate,
+        CachedRenderingData,
+    > = Desaturate::FIELD_OFFSETS.cached_rendering_data.as_unpinned_projection();
+}
+
+ItemVTable_static! {
+    /// The VTable for `Desaturate`
+    #[no_mangle]
+    pub static DesaturateVTable for Desaturate
+}
+
+#[repr(C)]
+#[derive(FieldOffsets, Default, SixtyFPSElement)]
+#[pin]
+/// The implementation of the `Layer` element
+pub struct Layer {
+    pub x: Property<f32>,
+    pub y: Property<f32>,
+    pub width: Property<f32>,
+    pub height: Property<f32>,
+    pub cache_rendering_hint: Property<bool>,
+    pub cached_rendering_data: CachedRenderingData,
+}
+
+impl Item for Layer {
+    fn init(self: Pin<&Self>, _window: &ComponentWindow) {}
+
+    fn geometry(self: Pin<&Self>) -> Rect {
+        euclid::rect(self.x(), self.y(), self.width(), self.height())
+    }
+
+    fn layouting_info(self: Pin<&Self>, _window: &ComponentWindow) -> LayoutInfo {
+        LayoutInfo { horizontal_stretch: 1., vertical_stretch: 1., ..LayoutInfo::default() }
+    }
+
+    fn implicit_size(self: Pin<&Self>, _window: &ComponentWindow) -> Size {
+        Default::default()
+    }
+
+    fn input_event(
+        self: Pin<&Self>,
+        _: MouseEvent,
+        _window: &ComponentWindow,
+        _self_rc: &ItemRc,
+    ) -> InputEventResult {
+        InputEventResult::EventIgnored
+    }
+
+    fn key_event(self: Pin<&Self>, _: &KeyEvent, _window: &ComponentWindow) -> KeyEventResult {
+        KeyEventResult::EventIgnored
+    }
+
+    fn focus_event(self: Pin<&Self>, _: &FocusEvent, _window: &ComponentWindow) {}
+
+    fn render(self: Pin<&Self>, _pos: Point, _backend: &mut ItemRendererRef) {
+        // The children are drawn with ItemRenderer::draw_layer by render_component_items when the
+        // rendering is cached, and like the children of any other element otherwise.
+    }
+}
+
+impl ItemConsts for Layer {
+    const cached_rendering_data_offset: const_field_offset::FieldOffset<
+        Layer,
+        CachedRenderingData,
+    > = Layer::FIELD_OFFSETS.cached_rendering_data.as_unpinned_projection();
+}
+
+ItemVTable_static! {
+    /// The VTable for `Layer`
+    #[no_mangle]
+    pub static LayerVTable for Layer
+}
+
+#[repr(C)]
+#[derive(FieldOffsets, Default, SixtyFPSElement)]
+#[pin]
+/// The implementation of the `CustomRendering` element
+pub struct CustomRendering {
+    pub x: Property<f32>,
+    pub y: Property<f32>,
+    pub width: Property<f32>,
+    pub height: Property<f32>,
+    pub renderer: Property<SharedString>,
+    pub cached_rendering_data: CachedRenderingData,
+}
+
+impl Item for CustomRendering {
+    fn init(self: Pin<&Self>, _window: &ComponentWindow) {}
+
+    fn geometry(self: Pin<&Self>) -> Rect {
+        euclid::rect(self.x(), self.y(), self.width(), self.height())
+    }
+
+    fn layouting_info(self: Pin<&Self>, _window: &ComponentWindow) -> LayoutInfo {
+        LayoutInfo { horizontal_stretch: 1., vertical_stretch: 1., ..LayoutInfo::default() }
+    }
+
+    fn implicit_size(self: Pin<&Self>, _window: &ComponentWindow) -> Size {
+        Default::default()
+    }
+
+    fn input_event(
+        self: Pin<&Self>,
+        _: MouseEvent,
+        _window: &ComponentWindow,
+        _self_rc: &ItemRc,
+    ) -> InputEventResult {
+        InputEventResult::EventIgnored
+    }
+
+    fn key_event(self: Pin<&Self>, _: &KeyEvent, _window: &ComponentWindow) -> KeyEventResult {
+        KeyEventResult::EventIgnored
+    }
+
+    fn focus_event(self: Pin<&Self>, _: &FocusEvent, _window: &ComponentWindow) {}
+
+    fn render(self: Pin<&Self>, pos: Point, backend: &mut ItemRendererRef) {
+        (*backend).draw_custom_rendering(pos, self)
+    }
+}
+
+impl ItemConsts for CustomRendering {
+    const cached_rendering_data_offset: const_field_offset::FieldOffset<
+        CustomRendering,
+        CachedRenderingData,
+    > = CustomRendering::FIELD_OFFSETS.cached_rendering_data.as_unpinned_projection();
+}
+
+ItemVTable_static! {
+    /// The VTable for `CustomRendering`
+    #[no_mangle]
+    pub static CustomRenderingVTable for CustomRendering
+}
+
 /// The implementation of the `Path` element
 #[repr(C)]
 #[derive(FieldOffsets, Default, SixtyFPSElement)]
@@ -562,6 +859,12 @@ pub struct Path {
     pub fill_color: Property<Color>,
     pub stroke_color: Property<Color>,
     pub stroke_width: Property<f32>,
+    pub conic_gradient_stops: Property<SharedString>,
+    pub conic_gradient_center_x: Property<f32>,
+    pub conic_gradient_center_y: Property<f32>,
+    pub conic_gradient_start_angle: Property<f32>,
+    pub stroke_alignment: Property<StrokeAlignment>,
+    pub hairline: Property<bool>,
     pub cached_rendering_data: CachedRenderingData,
 }
 
@@ -611,6 +914,155 @@ impl ItemConsts for Path {
     pub static PathVTable for Path
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+/// The shape used at the end points of a stroked `Line`
+pub enum LineCap {
+    butt,
+    round,
+    square,
+}
+
+impl Default for LineCap {
+    fn default() -> Self {
+        Self::butt
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+/// Where the stroke of a `Rectangle` border or of a `Path` lies relative to the outline of the shape
+pub enum StrokeAlignment {
+    inside,
+    center,
+    outside,
+}
+
+impl Default for StrokeAlignment {
+    fn default() -> Self {
+        Self::center
+    }
+}
+
+/// Returns how far outside of the outline of a shape the center line of its stroke lies: half the stroke width
+/// inwards for `inside`, on the outline for `center`, and half the stroke width outwards for `outside`.
+pub fn stroke_offset(alignment: StrokeAlignment, stroke_width: f32) -> f32 {
+    match alignment {
+        StrokeAlignment::inside => -stroke_width / 2.,
+        StrokeAlignment::center => 0.,
+        StrokeAlignment::outside => stroke_width / 2.,
+    }
+}
+
+/// Returns the width of a stroke in logical pixels. A `hairline` stroke is exactly one device pixel wide, whatever
+/// its width, where `scale` is the number of device pixels per logical pixel.
+pub fn effective_stroke_width(stroke_width: f32, hairline: bool, scale: f32) -> f32 {
+    if hairline {
+        1. / scale.max(f32::EPSILON)
+    } else {
+        stroke_width
+    }
+}
+
+/// The implementation of the `Line` element
+#[repr(C)]
+#[derive(FieldOffsets, Default, SixtyFPSElement)]
+#[pin]
+pub struct Line {
+    pub x: Property<f32>,
+    pub y: Property<f32>,
+    pub from_x: Property<f32>,
+    pub from_y: Property<f32>,
+    pub to_x: Property<f32>,
+    pub to_y: Property<f32>,
+    pub stroke_color: Property<Color>,
+    pub stroke_width: Property<f32>,
+    pub stroke_line_cap: Property<LineCap>,
+    pub dash_length: Property<f32>,
+    pub dash_gap: Property<f32>,
+    pub cached_rendering_data: CachedRenderingData,
+}
+
+impl Item for Line {
+    fn init(self: Pin<&Self>, _window: &ComponentWindow) {}
+
+    fn geometry(self: Pin<&Self>) -> Rect {
+        euclid::rect(self.x(), self.y(), 0., 0.)
+    }
+
+    fn layouting_info(self: Pin<&Self>, _window: &ComponentWindow) -> LayoutInfo {
+        LayoutInfo::default()
+    }
+
+    fn implicit_size(self: Pin<&Self>, _window: &ComponentWindow) -> Size {
+        Default::default()
+    }
+
+    fn input_event(
+        self: Pin<&Self>,
+        _: MouseEvent,
+        _window: &ComponentWindow,
+        _self_rc: &ItemRc,
+    ) -> InputEventResult {
+        InputEventResult::EventIgnored
+    }
+
+    fn key_event(self: Pin<&Self>, _: &KeyEvent, _window: &ComponentWindow) -> KeyEventResult {
+        KeyEventResult::EventIgnored
+    }
+
+    fn focus_event(self: Pin<&Self>, _: &FocusEvent, _window: &ComponentWindow) {}
+
+    fn render(self: Pin<&Self>, pos: Point, backend: &mut ItemRendererRef) {
+        (*backend).draw_line(pos, self)
+    }
+}
+
+/// The maximum number of dashes of a [`Line`]. Longer dash patterns are stretched to this many dashes, so that tiny
+/// dash lengths on long lines don't produce millions of segments.
+pub const MAX_LINE_DASHES: usize = 10_000;
+
+impl Line {
+    /// Returns the segments that need to be stroked, relative to the item's position.
+    /// Without a dash pattern, this is the single segment from the start to the end point.
+    /// The dash pattern is stretched if it would produce more than [`MAX_LINE_DASHES`] segments.
+    pub fn segments(self: Pin<&Self>) -> Vec<(Point, Point)> {
+        let from = Point::new(self.from_x(), self.from_y());
+        let to = Point::new(self.to_x(), self.to_y());
+        let dash_length = self.dash_length();
+        let dash_gap = self.dash_gap().max(0.);
+        let length = (to - from).length();
+        if dash_length <= 0. || length <= 0. {
+            return vec![(from, to)];
+        }
+        let direction = (to - from) / length;
+        let dash_count = length / (dash_length + dash_gap);
+        let stretch = (dash_count / MAX_LINE_DASHES as f32).max(1.);
+        let (dash_length, dash_gap) = (dash_length * stretch, dash_gap * stretch);
+        let mut segments = Vec::new();
+        let mut start = 0.;
+        while start < length {
+            let end = (start + dash_length).min(length);
+            segments.push((from + direction * start, from + direction * end));
+            start = end + dash_gap;
+        }
+        segments
+    }
+}
+
+impl ItemConsts for Line {
+    const cached_rendering_data_offset: const_field_offset::FieldOffset<Line, CachedRenderingData> =
+        Line::FIELD_OFFSETS.cached_rendering_data.as_unpinned_projection();
+}
+
+ItemVTable_static! {
+    /// The VTable for `Line`
+    #[no_mangle]
+    pub static LineVTable for Line
+}
+
 /// The implementation of the `Flickable` element
 #[repr(C)]
 #[derive(FieldOffsets, Default, SixtyFPSElement)]
@@ -740,6 +1192,7 @@ pub struct Window {
     pub height: Property<f32>,
     pub color: Property<Color>,
     pub title: Property<SharedString>,
+    pub transparent_background: Property<bool>,
     pub cached_rendering_data: CachedRenderingData,
 }
 
@@ -863,3 +1316,33 @@ impl ItemConsts for BoxShadow {
     #[no_mangle]
     pub static TextInputVTable for TextInput
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_dashes_alternate_with_gaps() {
+        let line = Box::pin(Line::default());
+        line.to_x.set(25.);
+        assert_eq!(line.as_ref().segments(), vec![(Point::new(0., 0.), Point::new(25., 0.))]);
+
+        line.dash_length.set(6.);
+        line.dash_gap.set(4.);
+        let starts_and_ends = |line: Pin<&Line>| {
+            line.segments().iter().map(|(from, to)| (from.x, to.x)).collect::<Vec<_>>()
+        };
+        // The last dash is cut off at the end of the line.
+        assert_eq!(starts_and_ends(line.as_ref()), vec![(0., 6.), (10., 16.), (20., 25.)]);
+
+        // Tiny dashes on a long line are stretched to a bounded number of segments.
+        line.to_x.set(1e6);
+        line.dash_length.set(1e-6);
+        line.dash_gap.set(1e-6);
+        let segments = line.as_ref().segments();
+        assert!(segments.len() <= MAX_LINE_DASHES + 1);
+        assert!(segments.len() >= MAX_LINE_DASHES - 1);
+        let (from, to) = segments[0];
+        assert!(((to - from).length() - 50.).abs() < 0.01);
+    }
+}
@@ -20,7 +20,7 @@
  - Don't forget to update the documentation
 */
 use super::{Item, ItemConsts, ItemRc};
-use crate::graphics::{Point, Rect, Resource, Size};
+use crate::graphics::{Color, Point, Rect, Resource, Size};
 use crate::input::{FocusEvent, InputEventResult, KeyEvent, KeyEventResult, MouseEvent};
 use crate::item_rendering::CachedRenderingData;
 use crate::item_rendering::ItemRenderer;
@@ -39,6 +39,7 @@
 pub enum ImageFit {
     fill,
     contain,
+    none,
 }
 
 impl Default for ImageFit {
@@ -47,6 +48,105 @@ fn default() -> Self {
     }
 }
 
+/// How the pixels of an image are sampled when it is drawn scaled.
+#[derive(Copy, Clone, Debug, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum ImageRendering {
+    /// Interpolate linearly between the pixels of the image.
+    smooth,
+    /// Use the nearest pixel of the image, which keeps scaled up pixel art crisp. While the
+    /// scale of the image changes, for example during an animation, the image is drawn smooth.
+    pixelated,
+}
+
+impl Default for ImageRendering {
+    fn default() -> Self {
+        ImageRendering::smooth
+    }
+}
+
+/// Where an image is placed horizontally within the `Image` element, when its width differs from the one of
+/// the element.
+#[derive(Copy, Clone, Debug, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum ImageHorizontalAlignment {
+    center,
+    left,
+    right,
+}
+
+impl Default for ImageHorizontalAlignment {
+    fn default() -> Self {
+        ImageHorizontalAlignment::center
+    }
+}
+
+/// Where an image is placed vertically within the `Image` element, when its height differs from the one of
+/// the element.
+#[derive(Copy, Clone, Debug, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum ImageVerticalAlignment {
+    center,
+    top,
+    bottom,
+}
+
+impl Default for ImageVerticalAlignment {
+    fn default() -> Self {
+        ImageVerticalAlignment::center
+    }
+}
+
+/// Returns the fractions of the horizontal and vertical space left between the image and the bounds of the
+/// element that end up at the left and at the top of the image. The space is negative when the image is
+/// larger than the element, in which case the image is moved left and up by that fraction of the excess.
+pub fn image_alignment_factors(
+    horizontal: ImageHorizontalAlignment,
+    vertical: ImageVerticalAlignment,
+) -> (f32, f32) {
+    (
+        match horizontal {
+            ImageHorizontalAlignment::left => 0.,
+            ImageHorizontalAlignment::center => 0.5,
+            ImageHorizontalAlignment::right => 1.,
+        },
+        match vertical {
+            ImageVerticalAlignment::top => 0.,
+            ImageVerticalAlignment::center => 0.5,
+            ImageVerticalAlignment::bottom => 1.,
+        },
+    )
+}
+
+/// Returns the sine and cosine of the `rotation_angle` of an image, in degrees. Multiples of 90 degrees
+/// give exact results, so that images rotated in quarter turns stay aligned to the pixel grid.
+pub fn rotation_sin_cos(rotation_angle: f32) -> (f32, f32) {
+    let quarter_turns = rotation_angle / 90.;
+    if quarter_turns.fract() == 0. {
+        match (quarter_turns as i64).rem_euclid(4) {
+            0 => (0., 1.),
+            1 => (1., 0.),
+            2 => (0., -1.),
+            _ => (-1., 0.),
+        }
+    } else {
+        rotation_angle.to_radians().sin_cos()
+    }
+}
+
+/// Returns the size of the bounding box of an image of the given size, when rotated by `rotation_angle`
+/// degrees. For quarter turns, this swaps the width and the height.
+pub fn rotated_image_size(size: Size, rotation_angle: f32) -> Size {
+    let (sin, cos) = rotation_sin_cos(rotation_angle);
+    euclid::size2(
+        (size.width * cos).abs() + (size.height * sin).abs(),
+        (size.width * sin).abs() + (size.height * cos).abs(),
+    )
+}
+
 #[repr(C)]
 #[derive(FieldOffsets, Default, SixtyFPSElement)]
 #[pin]
@@ -58,6 +158,18 @@ pub struct Image {
     pub width: Property<f32>,
     pub height: Property<f32>,
     pub image_fit: Property<ImageFit>,
+    pub rotation_angle: Property<f32>,
+    pub flip_horizontal: Property<bool>,
+    pub flip_vertical: Property<bool>,
+    pub colorize: Property<Color>,
+    pub image_rendering: Property<ImageRendering>,
+    pub horizontal_alignment: Property<ImageHorizontalAlignment>,
+    pub vertical_alignment: Property<ImageVerticalAlignment>,
+    pub nine_slice_top: Property<i32>,
+    pub nine_slice_right: Property<i32>,
+    pub nine_slice_bottom: Property<i32>,
+    pub nine_slice_left: Property<i32>,
+    pub opacity: Property<f32>,
     pub cached_rendering_data: CachedRenderingData,
 }
 
@@ -74,7 +186,13 @@ fn layouting_info(self: Pin<&Self>, _window: &ComponentWindow) -> LayoutInfo {
     }
 
     fn implicit_size(self: Pin<&Self>, window: &ComponentWindow) -> Size {
-        window.0.image_size(&self.cached_rendering_data, Self::FIELD_OFFSETS.source.apply_pin(self))
+        rotated_image_size(
+            window.0.image_size(
+                &self.cached_rendering_data,
+                Self::FIELD_OFFSETS.source.apply_pin(self),
+            ),
+            self.rotation_angle(),
+        )
     }
 
     fn input_event(
@@ -115,6 +233,18 @@ pub struct ClippedImage {
     pub width: Property<f32>,
     pub height: Property<f32>,
     pub image_fit: Property<ImageFit>,
+    pub rotation_angle: Property<f32>,
+    pub flip_horizontal: Property<bool>,
+    pub flip_vertical: Property<bool>,
+    pub colorize: Property<Color>,
+    pub image_rendering: Property<ImageRendering>,
+    pub horizontal_alignment: Property<ImageHorizontalAlignment>,
+    pub vertical_alignment: Property<ImageVerticalAlignment>,
+    pub nine_slice_top: Property<i32>,
+    pub nine_slice_right: Property<i32>,
+    pub nine_slice_bottom: Property<i32>,
+    pub nine_slice_left: Property<i32>,
+    pub opacity: Property<f32>,
     pub source_clip_x: Property<i32>,
     pub source_clip_y: Property<i32>,
     pub source_clip_width: Property<i32>,
@@ -135,7 +265,13 @@ fn layouting_info(self: Pin<&Self>, _window: &ComponentWindow) -> LayoutInfo {
     }
 
     fn implicit_size(self: Pin<&Self>, window: &ComponentWindow) -> Size {
-        window.0.image_size(&self.cached_rendering_data, Self::FIELD_OFFSETS.source.apply_pin(self))
+        rotated_image_size(
+            window.0.image_size(
+                &self.cached_rendering_data,
+                Self::FIELD_OFFSETS.source.apply_pin(self),
+            ),
+            self.rotation_angle(),
+        )
     }
 
     fn input_event(
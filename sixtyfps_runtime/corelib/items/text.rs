@@ -44,6 +44,7 @@ pub enum TextHorizontalAlignment {
     left,
     center,
     right,
+    justify,
 }
 
 impl Default for TextHorizontalAlignment {
@@ -67,6 +68,25 @@ fn default() -> Self {
     }
 }
 
+/// What part of the lines of a `Text` is centered when its vertical alignment is `center`.
+#[derive(Copy, Clone, Debug, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum TextVerticalCentering {
+    /// The whole height of the lines, including the gap that the font leaves below each line.
+    line_box,
+    /// The lines from the ascent of the first line to the descent of the last line, without the line gap.
+    ascent_descent,
+    /// The capital letters, from the cap height of the first line to the baseline of the last line.
+    cap_height,
+}
+
+impl Default for TextVerticalCentering {
+    fn default() -> Self {
+        Self::line_box
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, strum_macros::EnumString, strum_macros::Display)]
 #[repr(C)]
 #[allow(non_camel_case_types)]
@@ -95,6 +115,75 @@ fn default() -> Self {
     }
 }
 
+/// How the text of a `TextInput` is shown.
+#[derive(Copy, Clone, Debug, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum InputType {
+    /// The text is shown as it is.
+    text,
+    /// Every grapheme of the text is shown as the mask character, and the text can't be copied.
+    password,
+}
+
+impl Default for InputType {
+    fn default() -> Self {
+        Self::text
+    }
+}
+
+/// The style of a font, as in the CSS font-style property.
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Hash, strum_macros::EnumString, strum_macros::Display,
+)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum FontStyle {
+    normal,
+    italic,
+    oblique,
+}
+
+impl Default for FontStyle {
+    fn default() -> Self {
+        Self::normal
+    }
+}
+
+/// The style of the digits in text, selected with OpenType features of the font.
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Hash, strum_macros::EnumString, strum_macros::Display,
+)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum NumeralStyle {
+    normal,
+    lining,
+    oldstyle,
+    tabular,
+    proportional,
+}
+
+impl Default for NumeralStyle {
+    fn default() -> Self {
+        Self::normal
+    }
+}
+
+impl NumeralStyle {
+    /// The tag of the OpenType feature that selects this style of digits, or None for the
+    /// default digits of the font.
+    pub fn opentype_feature(self) -> Option<&'static [u8; 4]> {
+        match self {
+            Self::normal => None,
+            Self::lining => Some(b"lnum"),
+            Self::oldstyle => Some(b"onum"),
+            Self::tabular => Some(b"tnum"),
+            Self::proportional => Some(b"pnum"),
+        }
+    }
+}
+
 /// The implementation of the `Text` element
 #[repr(C)]
 #[derive(FieldOffsets, Default, SixtyFPSElement)]
@@ -104,11 +193,21 @@ pub struct Text {
     pub font_family: Property<SharedString>,
     pub font_size: Property<f32>,
     pub font_weight: Property<i32>,
+    pub font_style: Property<FontStyle>,
+    pub numeral_style: Property<NumeralStyle>,
+    pub language: Property<SharedString>,
+    pub tab_width: Property<f32>,
     pub color: Property<Color>,
     pub horizontal_alignment: Property<TextHorizontalAlignment>,
     pub vertical_alignment: Property<TextVerticalAlignment>,
+    pub vertical_centering: Property<TextVerticalCentering>,
     pub wrap: Property<TextWrap>,
     pub overflow: Property<TextOverflow>,
+    pub max_lines: Property<i32>,
+    pub drop_shadow_offset_x: Property<f32>,
+    pub drop_shadow_offset_y: Property<f32>,
+    pub drop_shadow_blur: Property<f32>,
+    pub drop_shadow_color: Property<Color>,
     pub x: Property<f32>,
     pub y: Property<f32>,
     pub width: Property<f32>,
@@ -188,6 +287,17 @@ pub fn font_request(self: Pin<&Self>) -> crate::graphics::FontRequest {
                     Some(font_size)
                 }
             },
+            style: self.font_style(),
+            numeral_style: self.numeral_style(),
+            language: self.language(),
+            tab_width: {
+                let tab_width = self.tab_width();
+                if tab_width > 0. {
+                    Some(tab_width)
+                } else {
+                    None
+                }
+            },
         }
     }
 }
@@ -201,9 +311,13 @@ pub struct TextInput {
     pub font_family: Property<SharedString>,
     pub font_size: Property<f32>,
     pub font_weight: Property<i32>,
+    pub font_style: Property<FontStyle>,
     pub color: Property<Color>,
     pub selection_foreground_color: Property<Color>,
     pub selection_background_color: Property<Color>,
+    pub invert_selection: Property<bool>,
+    pub input_type: Property<InputType>,
+    pub mask_character: Property<SharedString>,
     pub horizontal_alignment: Property<TextHorizontalAlignment>,
     pub vertical_alignment: Property<TextVerticalAlignment>,
     pub x: Property<f32>,
@@ -213,12 +327,16 @@ pub struct TextInput {
     pub cursor_position: Property<i32>, // byte offset,
     pub anchor_position: Property<i32>, // byte offset
     pub text_cursor_width: Property<f32>,
+    pub cursor_color: Property<Color>,
+    pub cursor_blink_interval: Property<i64>,
     pub cursor_visible: Property<bool>,
     pub has_focus: Property<bool>,
     pub enabled: Property<bool>,
     pub accepted: Callback<VoidArg>,
     pub edited: Callback<VoidArg>,
     pub pressed: std::cell::Cell<bool>,
+    /// The distance that the renderer scrolled the text to the left, to keep the cursor in view.
+    pub horizontal_scroll_offset: std::cell::Cell<f32>,
     pub cached_rendering_data: CachedRenderingData,
 }
 
@@ -249,7 +367,7 @@ fn implicit_size(self: Pin<&Self>, window: &ComponentWindow) -> Size {
         window
             .0
             .font_metrics(self.font_request())
-            .map(|metrics| metrics.text_size(&self.text()))
+            .map(|metrics| metrics.text_size(&self.displayed_text().text))
             .unwrap_or_default()
     }
 
@@ -263,12 +381,14 @@ fn input_event(
             return InputEventResult::EventIgnored;
         }
 
-        let text = self.text();
+        let text = self.displayed_text();
         let font_metrics = match window.0.font_metrics(self.font_request()) {
             Some(font) => font,
             None => return InputEventResult::EventIgnored,
         };
-        let clicked_offset = font_metrics.text_offset_for_x_position(&text, event.pos.x) as i32;
+        let pos = event.pos + euclid::vec2(self.horizontal_scroll_offset.get(), 0.);
+        let clicked_offset =
+            text.text_offset(font_metrics.text_offset_for_position(&text.text, pos, None)) as i32;
 
         if matches!(event.what, MouseEventType::MousePressed) {
             self.as_ref().pressed.set(true);
@@ -282,6 +402,7 @@ fn input_event(
         match event.what {
             MouseEventType::MouseReleased => {
                 self.as_ref().pressed.set(false);
+                self.update_primary_selection();
             }
             MouseEventType::MouseMoved if self.as_ref().pressed.get() => {
                 self.as_ref().cursor_position.set(clicked_offset);
@@ -424,8 +545,36 @@ fn from(modifiers: KeyboardModifiers) -> Self {
 }
 
 impl TextInput {
-    fn show_cursor(&self, window: &ComponentWindow) {
-        window.set_cursor_blink_binding(&self.cursor_visible);
+    /// Returns the color the cursor is drawn with: the `cursor_color`, or the color of the text when it is
+    /// not set.
+    pub fn effective_cursor_color(self: Pin<&Self>) -> Color {
+        let cursor_color = self.cursor_color();
+        if cursor_color.alpha() == 0 {
+            self.color()
+        } else {
+            cursor_color
+        }
+    }
+
+    /// Returns the text as it is shown: the text itself, or the mask character for every grapheme of the
+    /// text in password mode.
+    pub fn displayed_text(self: Pin<&Self>) -> DisplayedText {
+        let text = self.text();
+        match self.input_type() {
+            InputType::text => DisplayedText { text, masked: None },
+            InputType::password => {
+                let mask_character = self.mask_character();
+                let mask =
+                    if mask_character.is_empty() { "\u{2022}" } else { mask_character.as_str() };
+                DisplayedText::masked(&text, mask)
+            }
+        }
+    }
+
+    fn show_cursor(self: Pin<&Self>, window: &ComponentWindow) {
+        let blink_interval =
+            std::time::Duration::from_millis(self.cursor_blink_interval().max(0) as u64);
+        window.set_cursor_blink_binding(&self.cursor_visible, blink_interval);
     }
 
     fn hide_cursor(&self) {
@@ -471,7 +620,7 @@ fn move_cursor(
         self.as_ref().cursor_position.set(new_cursor_pos as i32);
 
         match anchor_mode {
-            AnchorMode::KeepAnchor => {}
+            AnchorMode::KeepAnchor => self.update_primary_selection(),
             AnchorMode::MoveAnchor => {
                 self.as_ref().anchor_position.set(new_cursor_pos as i32);
             }
@@ -535,10 +684,19 @@ pub fn has_selection(self: Pin<&Self>) -> bool {
         anchor_pos != cursor_pos
     }
 
-    fn selected_text(self: Pin<&Self>) -> String {
-        let (anchor, cursor) = self.selection_anchor_and_cursor();
-        let text: String = self.text().into();
-        text.split_at(anchor).1.split_at(cursor - anchor).0.to_string()
+    /// Returns the (UTF-8) byte range of the text between the anchor and the cursor position, see
+    /// [`selection_byte_range`]. Renderers highlight the glyphs of this range as selection.
+    pub fn selection_range(self: Pin<&Self>) -> core::ops::Range<usize> {
+        selection_byte_range(self.text().as_str(), self.anchor_position(), self.cursor_position())
+    }
+
+    /// Returns the text between the anchor and the cursor position. This is the same range of
+    /// characters that the renderers highlight as selection, and what is copied to the clipboard.
+    pub fn selected_text(self: Pin<&Self>) -> String {
+        let text = self.text();
+        text.as_str()
+            [selection_byte_range(text.as_str(), self.anchor_position(), self.cursor_position())]
+        .to_string()
     }
 
     fn insert(self: Pin<&Self>, text_to_insert: &str) {
@@ -554,9 +712,21 @@ fn insert(self: Pin<&Self>, text_to_insert: &str) {
     }
 
     fn copy(self: Pin<&Self>) {
+        if self.input_type() == InputType::password {
+            return;
+        }
         crate::backend::instance().map(|backend| backend.set_clipboard_text(self.selected_text()));
     }
 
+    // Makes the selected text available for pasting with the middle mouse button, on platforms with a
+    // primary selection.
+    fn update_primary_selection(self: Pin<&Self>) {
+        if self.has_selection() && self.input_type() != InputType::password {
+            crate::backend::instance()
+                .map(|backend| backend.set_primary_selection_text(self.selected_text()));
+        }
+    }
+
     fn paste(self: Pin<&Self>) {
         if let Some(text) = crate::backend::instance().and_then(|backend| backend.clipboard_text())
         {
@@ -583,6 +753,205 @@ pub fn font_request(self: Pin<&Self>) -> crate::graphics::FontRequest {
                     Some(font_size)
                 }
             },
+            style: self.font_style(),
+            numeral_style: Default::default(),
+            language: Default::default(),
+            tab_width: None,
+        }
+    }
+}
+
+/// The text of a `TextInput` as it is shown, see [`TextInput::displayed_text`]. The cursor and the anchor
+/// position are byte offsets in the text of the `TextInput`, which map to different offsets in masked text.
+pub struct DisplayedText {
+    /// The text to lay out and draw
+    pub text: SharedString,
+    // The length of the mask and the byte offsets in the text of the `TextInput` where its graphemes end,
+    // for masked text.
+    masked: Option<(usize, Vec<usize>)>,
+}
+
+impl DisplayedText {
+    fn masked(text: &str, mask: &str) -> Self {
+        use unicode_segmentation::UnicodeSegmentation;
+        let grapheme_ends: Vec<usize> =
+            text.grapheme_indices(true).map(|(offset, grapheme)| offset + grapheme.len()).collect();
+        DisplayedText {
+            text: mask.repeat(grapheme_ends.len()).into(),
+            masked: Some((mask.len(), grapheme_ends)),
+        }
+    }
+
+    /// Returns the offset in the displayed text for a byte offset in the text of the `TextInput`. An offset
+    /// within a masked grapheme maps to the start of its mask.
+    pub fn displayed_offset(&self, text_offset: usize) -> usize {
+        match &self.masked {
+            None => text_offset,
+            Some((mask_len, grapheme_ends)) => {
+                grapheme_ends.iter().take_while(|end| **end <= text_offset).count() * mask_len
+            }
+        }
+    }
+
+    /// Returns the byte offset in the text of the `TextInput` for an offset in the displayed text.
+    pub fn text_offset(&self, displayed_offset: usize) -> usize {
+        match &self.masked {
+            None => displayed_offset,
+            Some((mask_len, grapheme_ends)) => match displayed_offset / mask_len {
+                0 => 0,
+                graphemes => grapheme_ends[graphemes.min(grapheme_ends.len()) - 1],
+            },
         }
     }
 }
+
+/// Returns the (UTF-8) byte range of the text that is selected between the given anchor and cursor positions,
+/// which may be in either order. Positions outside of the text are clamped to it, and positions within a
+/// character are moved to its start, so that the range can always be sliced from the text.
+pub fn selection_byte_range(text: &str, anchor: i32, cursor: i32) -> core::ops::Range<usize> {
+    let clamp = |position: i32| {
+        let mut position = (position.max(0) as usize).min(text.len());
+        while !text.is_char_boundary(position) {
+            position -= 1;
+        }
+        position
+    };
+    let (anchor, cursor) = (clamp(anchor), clamp(cursor));
+    anchor.min(cursor)..anchor.max(cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selected_text_handles_multi_byte_text_and_reversed_selections() {
+        let text_input = Box::pin(TextInput::default());
+        // "é" is two bytes long, and the emoji four.
+        text_input.text.set("aé\u{1F600}b".into());
+        text_input.anchor_position.set(1);
+        text_input.cursor_position.set(7);
+        assert_eq!(text_input.as_ref().selection_range(), 1..7);
+        assert_eq!(text_input.as_ref().selected_text(), "é\u{1F600}");
+
+        // The cursor before the anchor selects the same text.
+        text_input.anchor_position.set(7);
+        text_input.cursor_position.set(1);
+        assert_eq!(text_input.as_ref().selection_range(), 1..7);
+        assert_eq!(text_input.as_ref().selected_text(), "é\u{1F600}");
+
+        // Positions within a character, or outside of the text, are moved to the closest boundary before them.
+        assert_eq!(selection_byte_range("aé\u{1F600}b", 2, 5), 1..3);
+        assert_eq!(selection_byte_range("aé\u{1F600}b", 100, -3), 0..8);
+        text_input.anchor_position.set(5);
+        text_input.cursor_position.set(100);
+        assert_eq!(text_input.as_ref().selected_text(), "\u{1F600}b");
+    }
+
+    #[test]
+    fn password_text_is_masked_per_grapheme() {
+        let text_input = Box::pin(TextInput::default());
+        text_input.text.set("ae\u{301}\u{1F600}".into());
+        assert_eq!(text_input.as_ref().displayed_text().text, "ae\u{301}\u{1F600}");
+
+        text_input.input_type.set(InputType::password);
+        let displayed = text_input.as_ref().displayed_text();
+        assert_eq!(displayed.text, "\u{2022}\u{2022}\u{2022}");
+        // The offsets between the graphemes map to the offsets between the masks, and back.
+        let bullet = '\u{2022}'.len_utf8();
+        for (text_offset, displayed_offset) in
+            [(0, 0), (1, bullet), (4, 2 * bullet), (8, 3 * bullet)].iter()
+        {
+            assert_eq!(displayed.displayed_offset(*text_offset), *displayed_offset);
+            assert_eq!(displayed.text_offset(*displayed_offset), *text_offset);
+        }
+        // Within the e with its combining accent.
+        assert_eq!(displayed.displayed_offset(2), bullet);
+
+        text_input.mask_character.set("*".into());
+        assert_eq!(text_input.as_ref().displayed_text().text, "***");
+    }
+
+    // The texts that were copied to the clipboard and to the primary selection, with the name of the method.
+    static COPIED_TEXTS: std::sync::Mutex<Vec<(&str, String)>> = std::sync::Mutex::new(Vec::new());
+
+    // What the recording backend returns as the text of the clipboard, to tell it apart from other backends.
+    const RECORDING_BACKEND_MARKER: &str = "recorded by RecordingBackend";
+
+    struct RecordingBackend;
+
+    impl crate::backend::Backend for RecordingBackend {
+        fn create_window(&'static self) -> ComponentWindow {
+            unimplemented!()
+        }
+        fn run_event_loop(&'static self) {}
+        fn register_application_font_from_memory(
+            &'static self,
+            _data: &'static [u8],
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            Ok(())
+        }
+        fn set_clipboard_text(&'static self, text: String) {
+            COPIED_TEXTS.lock().unwrap().push(("set_clipboard_text", text));
+        }
+        fn clipboard_text(&'static self) -> Option<String> {
+            Some(RECORDING_BACKEND_MARKER.into())
+        }
+        fn set_primary_selection_text(&'static self, text: String) {
+            COPIED_TEXTS.lock().unwrap().push(("set_primary_selection_text", text));
+        }
+    }
+
+    #[test]
+    fn password_text_never_reaches_the_clipboard() {
+        let backend = crate::backend::instance_or_init(|| Box::new(RecordingBackend));
+        assert_eq!(
+            backend.clipboard_text().as_deref(),
+            Some(RECORDING_BACKEND_MARKER),
+            "another test installed its backend first"
+        );
+        COPIED_TEXTS.lock().unwrap().clear();
+        // Only the texts copied from this text input are compared, as other tests may copy text as well.
+        const SECRET: &str = "password secret";
+        let copied_secrets = || {
+            COPIED_TEXTS
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(_, text)| text.contains(SECRET))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        let text_input = Box::pin(TextInput::default());
+        text_input.text.set(SECRET.into());
+        text_input.anchor_position.set(0);
+        text_input.cursor_position.set(SECRET.len() as i32);
+
+        text_input.input_type.set(InputType::password);
+        text_input.as_ref().copy();
+        text_input.as_ref().update_primary_selection();
+        assert_eq!(copied_secrets(), []);
+
+        // The same selection in a plain text input is copied to both.
+        text_input.input_type.set(InputType::text);
+        text_input.as_ref().copy();
+        text_input.as_ref().update_primary_selection();
+        assert_eq!(
+            copied_secrets(),
+            [
+                ("set_clipboard_text", SECRET.to_string()),
+                ("set_primary_selection_text", SECRET.to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn cursor_color_falls_back_to_the_text_color() {
+        let text_input = Box::pin(TextInput::default());
+        text_input.color.set(Color::from_rgb_u8(0, 0, 0));
+        assert_eq!(text_input.as_ref().effective_cursor_color(), Color::from_rgb_u8(0, 0, 0));
+        text_input.cursor_color.set(Color::from_rgb_u8(255, 0, 0));
+        assert_eq!(text_input.as_ref().effective_cursor_color(), Color::from_rgb_u8(255, 0, 0));
+    }
+}
@@ -21,8 +21,10 @@
 pub mod animations;
 pub mod backend;
 pub mod callbacks;
+#[cfg_attr(test, macro_use)]
 pub mod component;
 pub(crate) mod flickable;
+pub mod frame_stats;
 pub mod graphics;
 pub mod input;
 pub mod item_rendering;
@@ -31,6 +33,7 @@
 pub mod layout;
 pub mod model;
 pub mod properties;
+pub mod render_commands;
 pub mod sharedvector;
 pub mod slice;
 pub mod string;
@@ -1287,6 +1287,19 @@ fn default() -> Self {
     }
 }
 
+impl Drop for PropertyTracker {
+    fn drop(&mut self) {
+        // SAFETY: `dependencies` is the head of the list of the bindings that depend on this tracker, and
+        // DependencyListHead is a transparent wrapper around the same Cell<usize>. The list is only changed by
+        // `append` and by the nodes removing themselves, which keeps it consistent, so its first node, owned by a
+        // binding that is still alive, is valid. Dropping the head unlinks that node from the tracker, so that it
+        // doesn't point to the tracker's memory once it is freed. Nothing else can access the head during drop.
+        unsafe {
+            DependencyListHead::drop(self.holder.dependencies.as_ptr() as *mut DependencyListHead)
+        }
+    }
+}
+
 impl PropertyTracker {
     /// Any of the properties accessed during the last evaluation of the closure called
     /// from the last call to evaluate is pottentially dirty.
@@ -1298,7 +1311,7 @@ pub fn is_dirty(&self) -> bool {
     pub fn evaluate<R>(self: Pin<&Self>, f: impl FnOnce() -> R) -> R {
         // clear all the nodes so that we can start from scratch
         *self.holder.dep_nodes.borrow_mut() = Default::default();
-        // Safety: it is safe to project the holder as we don't implement drop or unpin
+        // Safety: it is safe to project the holder as we don't implement unpin, and drop doesn't move it
         let pinned_holder = unsafe { self.map_unchecked(|s| &s.holder) };
         let r = CURRENT_BINDING.set(pinned_holder, f);
         self.holder.dirty.set(false);
@@ -1316,6 +1329,33 @@ pub fn evaluate_if_dirty(self: Pin<&Self>, f: impl FnOnce()) {
     pub fn set_dirty(&self) {
         self.holder.dirty.set(true);
     }
+
+    /// Makes the binding or tracker that is currently being evaluated depend on this tracker, so that it
+    /// becomes dirty whenever this tracker does. This is for a tracker guarding a cached value, when that
+    /// value is used while evaluating another tracker or binding.
+    pub fn register_as_dependency_to_current_binding(self: Pin<&Self>) {
+        if CURRENT_BINDING.is_set() {
+            CURRENT_BINDING.with(|cur_binding| {
+                if core::ptr::eq(cur_binding.get_ref(), &self.holder) {
+                    return;
+                }
+                let node = DependencyNode::for_binding(cur_binding);
+                let mut dep_nodes = cur_binding.dep_nodes.borrow_mut();
+                let node = dep_nodes.push_front(node);
+                // SAFETY: `dependencies` is the head of a valid list, as a transparent DependencyListHead. The node
+                // is pinned and owned by the current binding, which removes it from the list when it is evaluated
+                // again or dropped, and the tracker unlinks it when the tracker is dropped first, so the list never
+                // refers to freed memory. The tracker itself is pinned, so the head doesn't move while it is linked.
+                // A tracker depending on itself was excluded above, as it would mark itself dirty.
+                unsafe {
+                    DependencyListHead::append(
+                        self.holder.dependencies.as_ptr() as *mut DependencyListHead,
+                        node.get_ref() as *const _,
+                    )
+                }
+            });
+        }
+    }
 }
 
 #[test]
@@ -1344,6 +1384,36 @@ fn test_property_listener_scope() {
     assert!(ok);
 }
 
+#[test]
+fn test_nested_property_tracker() {
+    let outer = Box::pin(PropertyTracker::default());
+    let inner = Box::pin(PropertyTracker::default());
+    let prop = Box::pin(Property::new(42));
+    let r = outer.as_ref().evaluate(|| {
+        inner.as_ref().register_as_dependency_to_current_binding();
+        inner.as_ref().evaluate(|| prop.as_ref().get())
+    });
+    assert_eq!(r, 42);
+    assert!(!outer.is_dirty());
+    assert!(!inner.is_dirty());
+    prop.as_ref().set(88);
+    assert!(inner.is_dirty());
+    assert!(outer.is_dirty()); // dirty through the inner tracker
+    outer.as_ref().evaluate(|| {
+        inner.as_ref().register_as_dependency_to_current_binding();
+    });
+    inner.as_ref().evaluate(|| prop.as_ref().get());
+    prop.as_ref().set(1);
+    assert!(outer.is_dirty());
+    // Dropping the inner tracker leaves the outer one valid
+    outer.as_ref().evaluate(|| {
+        inner.as_ref().register_as_dependency_to_current_binding();
+    });
+    drop(inner);
+    prop.as_ref().set(2);
+    assert!(!outer.is_dirty());
+}
+
 pub(crate) mod ffi {
     use super::*;
     use core::pin::Pin;
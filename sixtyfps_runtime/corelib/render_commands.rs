@@ -0,0 +1,290 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+#![warn(missing_docs)]
+/*!
+Recording of the draw calls of a frame, for inspecting and replaying them later.
+
+A [`RenderCommandList`] is an [`ItemRenderer`] that doesn't draw anything. Instead, it keeps a copy of
+the properties of every item it is asked to draw, at the time of the call. The list can be printed,
+and replayed into the renderer of a backend, independently of the components it was recorded from.
+*/
+
+use crate::graphics::{Point, RenderingCache};
+use crate::item_rendering::{CachedRenderingData, ItemRenderer};
+use crate::items::*;
+use core::pin::Pin;
+
+/// Copies the values of the given properties of an item into a new item of the same type.
+macro_rules! snapshot {
+    ($item:expr, $ty:ident { $($field:ident),* $(,)? }) => {{
+        let copy = Box::pin($ty::default());
+        $(copy.$field.set($ty::FIELD_OFFSETS.$field.apply_pin($item).get());)*
+        copy
+    }};
+}
+
+/// Formats the given properties of an item, for printing a command.
+macro_rules! debug_item {
+    ($f:expr, $item:expr, $ty:ident { $($field:ident),* $(,)? }) => {{
+        let item: Pin<&$ty> = $item;
+        $f.debug_struct(stringify!($ty))
+            $(.field(stringify!($field), &$ty::FIELD_OFFSETS.$field.apply_pin(item).get()))*
+            .finish()
+    }};
+}
+
+/// Declares the command enum for the items, along with taking and printing their snapshots.
+macro_rules! declare_render_commands {
+    ($(
+        $draw_fn:ident => $ty:ident { $($field:ident),* $(,)? } $(not_drawn { $($ignored:ident),* })?;
+    )*) => {
+        /// A single call to an [`ItemRenderer`], with a copy of the item it was called with.
+        pub enum RenderCommand {
+            $(
+                #[doc = concat!("A call to `", stringify!($draw_fn), "`, with the position and the item")]
+                $ty(Point, Pin<Box<$ty>>),
+            )*
+            /// A call to `save_state`
+            SaveState,
+            /// A call to `restore_state`
+            RestoreState,
+            /// A call to `draw_cached_pixmap`, with the position and the pixmap that was drawn: the width, the
+            /// height and the premultiplied pixel values
+            CachedPixmap(Point, u32, u32, Vec<u8>),
+        }
+
+        impl RenderCommand {
+            fn replay(&self, renderer: &mut dyn ItemRenderer) {
+                match self {
+                    $(Self::$ty(pos, item) => renderer.$draw_fn(*pos, item.as_ref()),)*
+                    Self::SaveState => renderer.save_state(),
+                    Self::RestoreState => renderer.restore_state(),
+                    Self::CachedPixmap(pos, width, height, data) => renderer.draw_cached_pixmap(
+                        &CachedRenderingData::default(),
+                        *pos,
+                        &|callback| callback(*width, *height, data),
+                    ),
+                }
+            }
+
+            fn cached_rendering_data(&self) -> Option<&CachedRenderingData> {
+                match self {
+                    $(Self::$ty(_, item) => Some(&item.cached_rendering_data),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl core::fmt::Debug for RenderCommand {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match self {
+                    $(Self::$ty(pos, item) => {
+                        write!(f, "{} at ({}, {}): ", stringify!($draw_fn), pos.x, pos.y)?;
+                        debug_item!(f, item.as_ref(), $ty { $($field),* })
+                    })*
+                    Self::SaveState => write!(f, "save_state"),
+                    Self::RestoreState => write!(f, "restore_state"),
+                    Self::CachedPixmap(pos, width, height, _) => write!(
+                        f,
+                        "draw_cached_pixmap at ({}, {}): {}x{} pixels",
+                        pos.x, pos.y, width, height
+                    ),
+                }
+            }
+        }
+
+        impl ItemRenderer for RenderCommandList {
+            $(
+                fn $draw_fn(&mut self, pos: Point, item: Pin<&$ty>) {
+                    // A property added to the item must be added to the lists of this module.
+                    let $ty { $($field: _,)* $($($ignored: _,)*)? cached_rendering_data: _ } = &*item;
+                    self.commands.push(RenderCommand::$ty(pos, snapshot!(item, $ty { $($field),* })));
+                }
+            )*
+
+            fn save_state(&mut self) {
+                self.commands.push(RenderCommand::SaveState);
+            }
+
+            fn restore_state(&mut self) {
+                self.commands.push(RenderCommand::RestoreState);
+            }
+
+            fn draw_layer(
+                &mut self,
+                _pos: Point,
+                _layer: Pin<&Layer>,
+                render_children: &mut dyn FnMut(&mut dyn ItemRenderer),
+            ) {
+                // The list can't be cached, so the commands of the children are recorded in its place.
+                render_children(self)
+            }
+
+            fn scale_factor(&self) -> f32 {
+                self.scale_factor
+            }
+
+            fn draw_cached_pixmap(
+                &mut self,
+                _item_cache: &CachedRenderingData,
+                pos: Point,
+                update_fn: &dyn Fn(&mut dyn FnMut(u32, u32, &[u8])),
+            ) {
+                let mut pixmap = None;
+                update_fn(&mut |width, height, data| pixmap = Some((width, height, data.to_vec())));
+                if let Some((width, height, data)) = pixmap {
+                    self.commands.push(RenderCommand::CachedPixmap(pos, width, height, data));
+                }
+            }
+
+            fn as_any(&mut self) -> &mut dyn core::any::Any {
+                self
+            }
+        }
+    };
+}
+
+declare_render_commands! {
+    draw_rectangle => Rectangle { color, x, y, width, height };
+    draw_border_rectangle => BorderRectangle {
+        color, x, y, width, height, border_width, border_radius, border_color,
+        conic_gradient_stops, conic_gradient_center_x, conic_gradient_center_y,
+        conic_gradient_start_angle, stroke_alignment, hairline
+    };
+    draw_image => Image {
+        source, x, y, width, height, image_fit, rotation_angle, flip_horizontal, flip_vertical,
+        colorize, image_rendering, horizontal_alignment, vertical_alignment, nine_slice_top,
+        nine_slice_right, nine_slice_bottom, nine_slice_left, opacity
+    };
+    draw_clipped_image => ClippedImage {
+        source, x, y, width, height, image_fit, rotation_angle, flip_horizontal, flip_vertical,
+        colorize, image_rendering, horizontal_alignment, vertical_alignment, nine_slice_top,
+        nine_slice_right, nine_slice_bottom, nine_slice_left, opacity, source_clip_x, source_clip_y,
+        source_clip_width, source_clip_height
+    };
+    draw_text => Text {
+        text, font_family, font_size, font_weight, font_style, numeral_style, language, tab_width,
+        color, horizontal_alignment, vertical_alignment, vertical_centering, wrap, overflow,
+        max_lines, drop_shadow_offset_x, drop_shadow_offset_y, drop_shadow_blur, drop_shadow_color, x, y,
+        width, height
+    };
+    draw_text_input => TextInput {
+        text, font_family, font_size, font_weight, font_style, color, selection_foreground_color,
+        selection_background_color, invert_selection, input_type, mask_character,
+        horizontal_alignment, vertical_alignment, x, y, width, height, cursor_position, anchor_position, text_cursor_width, cursor_color,
+        cursor_visible, has_focus, enabled
+    } not_drawn {
+        cursor_blink_interval, accepted, edited, pressed, horizontal_scroll_offset
+    };
+    draw_path => Path {
+        x, y, width, height, elements, fill_color, stroke_color, stroke_width, conic_gradient_stops,
+        conic_gradient_center_x, conic_gradient_center_y, conic_gradient_start_angle,
+        stroke_alignment, hairline
+    };
+    draw_line => Line {
+        x, y, from_x, from_y, to_x, to_y, stroke_color, stroke_width, stroke_line_cap, dash_length,
+        dash_gap
+    };
+    draw_box_shadow => BoxShadow {
+        x, y, width, height, border_radius, offset_x, offset_y, color, blur
+    };
+    combine_clip => Clip { x, y, width, height, shape, fade_vertical, fade_horizontal };
+    combine_transform => Transform {
+        x, y, width, height, rotation_angle, origin_x, origin_y, scale_x, scale_y, shear_x, shear_y
+    };
+    combine_desaturate => Desaturate { x, y, width, height, saturation };
+    draw_custom_rendering => CustomRendering { x, y, width, height, renderer };
+}
+
+/// The draw calls of a frame, recorded by using the list as the [`ItemRenderer`] that renders the items,
+/// for example with [`render_component_items`](crate::item_rendering::render_component_items).
+///
+/// Printing the list with `{:?}` shows one command per line, with the properties of the items.
+pub struct RenderCommandList {
+    commands: Vec<RenderCommand>,
+    scale_factor: f32,
+}
+
+impl RenderCommandList {
+    /// Creates an empty list, that reports the given scale factor to the items while recording.
+    pub fn new(scale_factor: f32) -> Self {
+        Self { commands: Vec::new(), scale_factor }
+    }
+
+    /// Returns the recorded commands, in the order they were issued.
+    pub fn commands(&self) -> &[RenderCommand] {
+        &self.commands
+    }
+
+    /// Issues the recorded commands again, to the given renderer.
+    pub fn replay(&self, renderer: &mut dyn ItemRenderer) {
+        for command in &self.commands {
+            command.replay(renderer);
+        }
+    }
+
+    /// Removes the entries that replaying the commands added to the rendering cache of a backend, as the
+    /// copies of the items are not part of a component that would free them.
+    pub fn release_graphics_resources<T>(&self, cache: &mut RenderingCache<T>) {
+        for cached_rendering_data in self.commands.iter().filter_map(|c| c.cached_rendering_data())
+        {
+            cached_rendering_data.release(cache);
+        }
+    }
+}
+
+impl core::fmt::Debug for RenderCommandList {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for command in &self.commands {
+            writeln!(f, "{:?}", command)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Color;
+
+    #[test]
+    fn recorded_commands_keep_the_properties_at_the_time_of_the_call() {
+        let rectangle = Box::pin(Rectangle::default());
+        rectangle.color.set(Color::from_rgb_u8(255, 0, 0));
+        rectangle.width.set(10.);
+        let text = Box::pin(Text::default());
+        text.text.set("Hello".into());
+
+        let mut recorded = RenderCommandList::new(2.);
+        recorded.save_state();
+        recorded.draw_rectangle(Point::new(1., 2.), rectangle.as_ref());
+        recorded.draw_text(Point::default(), text.as_ref());
+        recorded.restore_state();
+        rectangle.width.set(20.);
+        text.text.set("World".into());
+
+        match &recorded.commands()[1] {
+            RenderCommand::Rectangle(pos, rectangle) => {
+                assert_eq!(*pos, Point::new(1., 2.));
+                assert_eq!(rectangle.as_ref().width(), 10.);
+                assert_eq!(rectangle.as_ref().color(), Color::from_rgb_u8(255, 0, 0));
+            }
+            command => panic!("unexpected command {:?}", command),
+        }
+        let printed = format!("{:?}", recorded);
+        assert!(printed.starts_with("save_state\ndraw_rectangle at (1, 2): Rectangle {"));
+        assert!(printed.contains("text: \"Hello\""));
+        assert!(printed.ends_with("restore_state\n"));
+
+        let mut replayed = RenderCommandList::new(2.);
+        recorded.replay(&mut replayed);
+        assert_eq!(format!("{:?}", replayed), printed);
+    }
+}
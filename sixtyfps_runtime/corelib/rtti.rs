@@ -38,12 +38,22 @@ pub trait ValueType: 'static + Default + Clone $(+ TryInto<$ty> + TryFrom<$ty>)*
     crate::animations::EasingCurve,
     crate::items::TextHorizontalAlignment,
     crate::items::TextVerticalAlignment,
+    crate::items::TextVerticalCentering,
     crate::items::TextOverflow,
     crate::items::TextWrap,
+    crate::items::FontStyle,
+    crate::items::NumeralStyle,
     crate::model::StandardListViewItem,
     crate::items::ImageFit,
+    crate::items::ImageRendering,
+    crate::items::ImageHorizontalAlignment,
+    crate::items::ImageVerticalAlignment,
     crate::input::KeyEvent,
     crate::items::EventResult,
+    crate::items::LineCap,
+    crate::items::StrokeAlignment,
+    crate::items::ClipShape,
+    crate::items::InputType,
 ];
 
 /// What kind of animation is on a binding
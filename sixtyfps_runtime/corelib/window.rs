@@ -15,7 +15,7 @@
 use crate::input::{KeyEvent, MouseEventType, MouseInputState, TextCursorBlinker};
 use crate::items::{ItemRc, ItemRef, ItemWeak};
 use crate::slice::Slice;
-use crate::Resource;
+use crate::{Resource, SharedString};
 use core::cell::Cell;
 use core::pin::Pin;
 use std::cell::RefCell;
@@ -32,6 +32,11 @@ pub trait PlatformWindow {
     /// Issue a request to the windowing system to re-render the contents of the window. This is typically an asynchronous
     /// request.
     fn request_redraw(&self);
+    /// Issue a request to re-render the given region of the window, in the coordinates of the items. Backends that
+    /// cannot redraw parts of the window redraw all of it.
+    fn request_redraw_region(&self, _region: crate::graphics::Rect) {
+        self.request_redraw()
+    }
     /// Returns the scale factor set on the window, as provided by the windowing system.
     fn scale_factor(&self) -> f32;
     /// Sets an overriding scale factor for the window. This is typically only used for testing.
@@ -66,6 +71,40 @@ fn image_size(
         item_graphics_cache: &crate::item_rendering::CachedRenderingData,
         source: Pin<&crate::properties::Property<Resource>>,
     ) -> crate::graphics::Size;
+
+    /// Renders the window and returns its contents in physical pixels, with the color channels
+    /// following the given alpha convention. Returns None if the window is not visible or if the
+    /// backend cannot read back the rendered pixels.
+    fn read_pixels(&self, _alpha_mode: crate::graphics::AlphaMode) -> Option<image::RgbaImage> {
+        None
+    }
+
+    /// Renders the recorded draw calls into the window, instead of the items of its component, and
+    /// returns the rendered pixels like [`Self::read_pixels`].
+    fn replay_frame(
+        &self,
+        _commands: &crate::render_commands::RenderCommandList,
+        _alpha_mode: crate::graphics::AlphaMode,
+    ) -> Option<image::RgbaImage> {
+        None
+    }
+
+    /// Does the work needed before the first frame can be drawn, without showing the window: creating
+    /// the graphics context, loading the default font and decoding the given images. Backends that
+    /// prepare everything when the window is shown do nothing.
+    fn warm_up(self: Rc<Self>, _resources: &[Resource]) {}
+
+    /// Returns the font families that were requested for drawing or measuring text but are not
+    /// available, each with the family of the font that is used instead. Backends that don't track
+    /// the substitutions return an empty list.
+    fn font_substitutions(&self) -> Vec<(SharedString, SharedString)> {
+        Vec::new()
+    }
+
+    /// Returns the draw calls and the timing of the last frame that was rendered, if the backend collects them.
+    fn last_frame_stats(&self) -> Option<crate::frame_stats::FrameStats> {
+        None
+    }
 }
 
 /// Structure that represent a Window in the runtime
@@ -77,6 +116,7 @@ pub struct Window {
 
     focus_item: RefCell<ItemWeak>,
     cursor_blinker: RefCell<pin_weak::rc::PinWeak<crate::input::TextCursorBlinker>>,
+    scale_factor_change_notifier: ScaleFactorChangeNotifier,
 }
 
 impl Drop for Window {
@@ -96,6 +136,7 @@ pub fn new(platform_window: Rc<dyn PlatformWindow>) -> Self {
             mouse_input_state: Default::default(),
             focus_item: Default::default(),
             cursor_blinker: Default::default(),
+            scale_factor_change_notifier: Default::default(),
         }
     }
 
@@ -148,7 +189,12 @@ pub fn process_key_input(self: Rc<Self>, event: &KeyEvent) {
     }
 
     /// Installs a binding on the specified property that's toggled whenever the text cursor is supposed to be visible or not.
-    pub fn set_cursor_blink_binding(&self, prop: &crate::Property<bool>) {
+    /// The cursor blinks with the given interval, or stays visible if it is zero.
+    pub fn set_cursor_blink_binding(
+        &self,
+        prop: &crate::Property<bool>,
+        blink_interval: std::time::Duration,
+    ) {
         let existing_blinker = self.cursor_blinker.borrow().clone();
 
         let blinker = existing_blinker.upgrade().unwrap_or_else(|| {
@@ -158,7 +204,7 @@ pub fn set_cursor_blink_binding(&self, prop: &crate::Property<bool>) {
             new_blinker
         });
 
-        TextCursorBlinker::set_binding(blinker, prop);
+        TextCursorBlinker::set_binding(blinker, prop, blink_interval);
     }
 
     /// Sets the focus to the item pointed to by item_ptr. This will remove the focus from any
@@ -192,6 +238,48 @@ pub fn set_focus(self: Rc<Self>, have_focus: bool) {
             focus_item.borrow().as_ref().focus_event(&event, &window);
         }
     }
+
+    /// Sets the handler that is called with the new scale factor when the window renders a frame at a
+    /// different scale factor than the previous one. This replaces any previously set handler.
+    pub fn on_scale_factor_changed(&self, handler: impl Fn(f32) + 'static) {
+        self.scale_factor_change_notifier.set_handler(handler)
+    }
+
+    /// This function is called by the backends when they start rendering a frame at the given scale factor.
+    /// Returns true if it differs from the scale factor of the previous frame, after calling the handler set
+    /// with [`Self::on_scale_factor_changed`]. The backend should then drop the caches that depend on it.
+    pub fn update_rendered_scale_factor(&self, scale_factor: f32) -> bool {
+        self.scale_factor_change_notifier.update(scale_factor)
+    }
+}
+
+/// Notifies about changes of the scale factor that the frames of a window are rendered at, such as when the
+/// window moves to a screen with a different DPI.
+#[derive(Default)]
+struct ScaleFactorChangeNotifier {
+    rendered_scale_factor: Cell<Option<f32>>,
+    handler: RefCell<Option<Rc<dyn Fn(f32)>>>,
+}
+
+impl ScaleFactorChangeNotifier {
+    fn set_handler(&self, handler: impl Fn(f32) + 'static) {
+        *self.handler.borrow_mut() = Some(Rc::new(handler));
+    }
+
+    // Records the scale factor of a frame, and calls the handler if it changed since the previous one. The
+    // first frame is not a change.
+    fn update(&self, scale_factor: f32) -> bool {
+        let previous = self.rendered_scale_factor.replace(Some(scale_factor));
+        if previous.map_or(true, |previous| previous == scale_factor) {
+            return false;
+        }
+        // The handler is cloned out of the cell, so that it may replace itself.
+        let handler = self.handler.borrow().clone();
+        if let Some(handler) = handler {
+            handler(scale_factor);
+        }
+        true
+    }
 }
 
 impl core::ops::Deref for Window {
@@ -236,6 +324,91 @@ pub fn set_scale_factor(&self, factor: f32) {
         self.0.set_scale_factor(factor)
     }
 
+    /// Renders the window and returns its contents in physical pixels, for example to save a screenshot.
+    /// Use [`AlphaMode::Straight`](crate::graphics::AlphaMode::Straight) for pixels that are written to
+    /// image files such as PNG.
+    pub fn read_pixels(&self, alpha_mode: crate::graphics::AlphaMode) -> Option<image::RgbaImage> {
+        self.0.read_pixels(alpha_mode)
+    }
+
+    /// Renders the window and returns the pixels that it shows, at the physical size of the window: its size
+    /// multiplied by the scale factor, so that the capture matches what the user sees at their DPI. The
+    /// pixels are in RGBA order with straight alpha, and are followed by the width and the height. Returns
+    /// None in the same cases as [`Self::read_pixels`].
+    pub fn capture_current_frame(&self) -> Option<(Vec<u8>, u32, u32)> {
+        let pixels = self.read_pixels(crate::graphics::AlphaMode::Straight)?;
+        let (width, height) = pixels.dimensions();
+        Some((pixels.into_raw(), width, height))
+    }
+
+    /// Records the draw calls for the items of the window's component, as last laid out, without drawing
+    /// anything. The returned list can be printed for inspection, or drawn with [`Self::replay_frame`].
+    /// Popups are not recorded.
+    pub fn record_frame(&self) -> crate::render_commands::RenderCommandList {
+        let mut commands = crate::render_commands::RenderCommandList::new(self.scale_factor());
+        crate::item_rendering::render_component_items(
+            &self.0.component(),
+            &mut commands,
+            Point::default(),
+        );
+        commands
+    }
+
+    /// Draws the recorded draw calls in the window, instead of its items, and returns the rendered
+    /// pixels like [`Self::read_pixels`]. The window shows the replayed frame until it is redrawn.
+    pub fn replay_frame(
+        &self,
+        commands: &crate::render_commands::RenderCommandList,
+        alpha_mode: crate::graphics::AlphaMode,
+    ) -> Option<image::RgbaImage> {
+        self.0.replay_frame(commands, alpha_mode)
+    }
+
+    /// Requests the window to draw its items again, for example after invalidating the cached rendering
+    /// data of an item with [`CachedRenderingData::invalidate`](crate::item_rendering::CachedRenderingData::invalidate).
+    pub fn request_redraw(&self) {
+        self.0.platform_window.request_redraw()
+    }
+
+    /// Returns the number of draw calls by item type, and the time it took to render and flush the last frame of
+    /// the window, to find what dominates a slow frame. The GL backend collects these only when the
+    /// `SIXTYFPS_FRAME_STATS` environment variable is set, and returns None otherwise.
+    pub fn last_frame_stats(&self) -> Option<crate::frame_stats::FrameStats> {
+        self.0.last_frame_stats()
+    }
+
+    /// Requests the window to draw only the given region again, in the coordinates of the items, for a change that
+    /// does not affect anything outside of it. The regions requested before the next frame is drawn are combined.
+    /// The region must cover everything the change affects, including translucent items overlapping it and the
+    /// previous position of items that moved or scrolled. The whole window is drawn when it needs to be redrawn
+    /// for any other reason, such as a call to [`Self::request_redraw`].
+    pub fn request_redraw_region(&self, region: crate::graphics::Rect) {
+        self.0.platform_window.request_redraw_region(region)
+    }
+
+    /// Prepares the window for being shown, so that the first frame appears sooner: the graphics context
+    /// is created, the default font is loaded and the given images are decoded, while the window stays
+    /// hidden. Call this while a splash screen is visible, before [`Self::show`]. The images are kept
+    /// until the first frame is drawn.
+    pub fn warm_up(&self, resources: &[Resource]) {
+        self.0.platform_window.clone().warm_up(resources)
+    }
+
+    /// Sets the handler that is called with the new scale factor when the window is drawn at a different
+    /// scale factor than before, for example after it was moved to a screen with a different DPI. Use it to
+    /// switch to images made for the new pixel density. Only one handler can be set; setting another one
+    /// replaces it.
+    pub fn on_scale_factor_changed(&self, handler: impl Fn(f32) + 'static) {
+        self.0.on_scale_factor_changed(handler)
+    }
+
+    /// Returns the font families that the text of the window asked for but that are not installed, each
+    /// along with the family of the font that is drawn instead. A warning is printed as well, the first
+    /// time a family is found missing.
+    pub fn font_substitutions(&self) -> Vec<(SharedString, SharedString)> {
+        self.0.font_substitutions()
+    }
+
     /// This function is called by the generated code when a component and therefore its tree of items are destroyed. The
     /// implementation typically uses this to free the underlying graphics resources cached via [RenderingCache][`crate::graphics::RenderingCache`].
     pub fn free_graphics_resources<'a>(&self, items: &Slice<'a, Pin<ItemRef<'a>>>) {
@@ -243,8 +416,13 @@ pub fn free_graphics_resources<'a>(&self, items: &Slice<'a, Pin<ItemRef<'a>>>) {
     }
 
     /// Installs a binding on the specified property that's toggled whenever the text cursor is supposed to be visible or not.
-    pub(crate) fn set_cursor_blink_binding(&self, prop: &crate::properties::Property<bool>) {
-        self.0.clone().set_cursor_blink_binding(prop)
+    /// The cursor blinks with the given interval, or stays visible if it is zero.
+    pub(crate) fn set_cursor_blink_binding(
+        &self,
+        prop: &crate::properties::Property<bool>,
+        blink_interval: std::time::Duration,
+    ) {
+        self.0.clone().set_cursor_blink_binding(prop, blink_interval)
     }
 
     pub(crate) fn process_key_input(&self, event: &KeyEvent) {
@@ -395,3 +573,28 @@ pub mod ffi {
         window.close_popup();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_factor_change_is_notified_once() {
+        let notifier = ScaleFactorChangeNotifier::default();
+        let notifications = Rc::new(RefCell::new(Vec::new()));
+        notifier.set_handler({
+            let notifications = notifications.clone();
+            move |scale_factor| notifications.borrow_mut().push(scale_factor)
+        });
+
+        assert!(!notifier.update(1.));
+        assert!(!notifier.update(1.));
+        assert!(notifier.update(2.));
+        assert!(!notifier.update(2.));
+        assert!(!notifier.update(2.));
+        assert_eq!(*notifications.borrow(), vec![2.]);
+
+        assert!(notifier.update(1.5));
+        assert_eq!(*notifications.borrow(), vec![2., 1.5]);
+    }
+}
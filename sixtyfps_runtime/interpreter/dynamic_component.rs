@@ -486,10 +486,15 @@ fn generate_component<'id>(
                 rtti_for::<TouchArea>(),
                 rtti_for::<FocusScope>(),
                 rtti_for::<Path>(),
+                rtti_for::<Line>(),
                 rtti_for_flickable(),
                 rtti_for::<Window>(),
                 rtti_for::<TextInput>(),
                 rtti_for::<Clip>(),
+                rtti_for::<Transform>(),
+                rtti_for::<Desaturate>(),
+                rtti_for::<Layer>(),
+                rtti_for::<CustomRendering>(),
                 rtti_for::<BoxShadow>(),
             ]
             .iter()
@@ -639,9 +644,25 @@ fn animated_property_info<T: Clone + Default + InterpolatedPropertyValue + 'stat
                 "TextVerticalAlignment" => {
                     property_info::<sixtyfps_corelib::items::TextVerticalAlignment>()
                 }
+                "TextVerticalCentering" => {
+                    property_info::<sixtyfps_corelib::items::TextVerticalCentering>()
+                }
                 "TextWrap" => property_info::<sixtyfps_corelib::items::TextWrap>(),
                 "TextOverflow" => property_info::<sixtyfps_corelib::items::TextOverflow>(),
+                "FontStyle" => property_info::<sixtyfps_corelib::items::FontStyle>(),
+                "NumeralStyle" => property_info::<sixtyfps_corelib::items::NumeralStyle>(),
                 "ImageFit" => property_info::<sixtyfps_corelib::items::ImageFit>(),
+                "ImageRendering" => property_info::<sixtyfps_corelib::items::ImageRendering>(),
+                "ImageHorizontalAlignment" => {
+                    property_info::<sixtyfps_corelib::items::ImageHorizontalAlignment>()
+                }
+                "ImageVerticalAlignment" => {
+                    property_info::<sixtyfps_corelib::items::ImageVerticalAlignment>()
+                }
+                "LineCap" => property_info::<sixtyfps_corelib::items::LineCap>(),
+                "StrokeAlignment" => property_info::<sixtyfps_corelib::items::StrokeAlignment>(),
+                "ClipShape" => property_info::<sixtyfps_corelib::items::ClipShape>(),
+                "InputType" => property_info::<sixtyfps_corelib::items::InputType>(),
                 _ => panic!("unkown enum"),
             },
             _ => panic!("bad type"),
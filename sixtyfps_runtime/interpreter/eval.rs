@@ -244,12 +244,22 @@ fn try_into(self) -> Result<$ty, ()> {
 
 declare_value_enum_conversion!(corelib::items::TextHorizontalAlignment, TextHorizontalAlignment);
 declare_value_enum_conversion!(corelib::items::TextVerticalAlignment, TextVerticalAlignment);
+declare_value_enum_conversion!(corelib::items::TextVerticalCentering, TextVerticalCentering);
 declare_value_enum_conversion!(corelib::items::TextOverflow, TextOverflow);
 declare_value_enum_conversion!(corelib::items::TextWrap, TextWrap);
 declare_value_enum_conversion!(corelib::layout::LayoutAlignment, LayoutAlignment);
 declare_value_enum_conversion!(corelib::items::ImageFit, ImageFit);
+declare_value_enum_conversion!(corelib::items::ImageRendering, ImageRendering);
+declare_value_enum_conversion!(corelib::items::ImageHorizontalAlignment, ImageHorizontalAlignment);
+declare_value_enum_conversion!(corelib::items::ImageVerticalAlignment, ImageVerticalAlignment);
+declare_value_enum_conversion!(corelib::items::FontStyle, FontStyle);
+declare_value_enum_conversion!(corelib::items::NumeralStyle, NumeralStyle);
 declare_value_enum_conversion!(corelib::input::KeyEventType, KeyEventType);
 declare_value_enum_conversion!(corelib::items::EventResult, EventResult);
+declare_value_enum_conversion!(corelib::items::LineCap, LineCap);
+declare_value_enum_conversion!(corelib::items::StrokeAlignment, StrokeAlignment);
+declare_value_enum_conversion!(corelib::items::InputType, InputType);
+declare_value_enum_conversion!(corelib::items::ClipShape, ClipShape);
 
 impl TryFrom<corelib::animations::Instant> for Value {
     type Error = ();
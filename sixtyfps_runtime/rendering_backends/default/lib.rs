@@ -39,6 +39,10 @@ pub fn backend() -> &'static dyn sixtyfps_corelib::backend::Backend {
         if backend_config == "GL" {
             return Box::new(sixtyfps_rendering_backend_gl::Backend);
         }
+        #[cfg(all(feature = "sixtyfps-rendering-backend-gl", not(target_arch = "wasm32")))]
+        if backend_config == "GL-headless" {
+            return Box::new(sixtyfps_rendering_backend_gl::HeadlessBackend);
+        }
 
         #[cfg(any(
             feature = "sixtyfps-rendering-backend-qt",
@@ -0,0 +1,120 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+#![cfg(not(target_arch = "wasm32"))]
+/*!
+Adaptive vsync for variable refresh rate (G-Sync, FreeSync) displays.
+
+With regular vsync, a frame that misses the vertical blank waits for the next one, so an animation that
+can't keep up with the refresh rate alternates between showing frames for one and two refresh periods,
+which stutters. With adaptive vsync (a swap interval of -1, the `swap_control_tear` extensions of GLX and
+WGL), a late frame is presented right away. On a variable refresh rate display, the display then waits
+for the frame instead of tearing, so frames are shown when the animation produced them.
+
+glutin only supports turning vsync on and off, so the swap interval is set here, after the context was
+created with vsync. If the extension is not available, vsync stays as it is.
+*/
+
+use std::ffi::CStr;
+
+type Context = glutin::WindowedContext<glutin::PossiblyCurrent>;
+
+#[allow(dead_code)] // Not used on platforms without adaptive vsync
+fn has_extension(extensions: &CStr, name: &str) -> bool {
+    extensions.to_string_lossy().split(' ').any(|extension| extension == name)
+}
+
+/// Sets the swap interval of the current context to -1, if the platform supports that. Returns true on
+/// success.
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "ios")),
+    feature = "x11"
+))]
+pub(crate) fn enable(context: &Context) -> bool {
+    use glutin::platform::unix::RawHandle;
+    use glutin::platform::ContextTraitExt;
+    use std::os::raw::{c_char, c_int, c_ulong, c_void};
+    use winit::platform::unix::WindowExtUnix;
+
+    // With EGL, for example on Wayland, the compositor takes care of variable refresh rates.
+    if !matches!(unsafe { context.raw_handle() }, RawHandle::Glx(_)) {
+        return false;
+    }
+    let window = context.window();
+    let (display, screen, drawable) =
+        match (window.xlib_display(), window.xlib_screen_id(), window.xlib_window()) {
+            (Some(display), Some(screen), Some(drawable)) => (display, screen, drawable),
+            _ => return false,
+        };
+
+    let query_extensions_string = context.get_proc_address("glXQueryExtensionsString");
+    let swap_interval = context.get_proc_address("glXSwapIntervalEXT");
+    if query_extensions_string.is_null() || swap_interval.is_null() {
+        return false;
+    }
+    unsafe {
+        let query_extensions_string: unsafe extern "C" fn(*mut c_void, c_int) -> *const c_char =
+            std::mem::transmute(query_extensions_string);
+        let extensions = CStr::from_ptr(query_extensions_string(display, screen));
+        if !has_extension(extensions, "GLX_EXT_swap_control_tear") {
+            return false;
+        }
+        let swap_interval: unsafe extern "C" fn(*mut c_void, c_ulong, c_int) =
+            std::mem::transmute(swap_interval);
+        swap_interval(display, drawable, -1);
+    }
+    true
+}
+
+/// Sets the swap interval of the current context to -1, if the platform supports that. Returns true on
+/// success.
+#[cfg(target_family = "windows")]
+pub(crate) fn enable(context: &Context) -> bool {
+    use std::os::raw::{c_char, c_int};
+
+    let get_extensions_string = context.get_proc_address("wglGetExtensionsStringEXT");
+    let swap_interval = context.get_proc_address("wglSwapIntervalEXT");
+    if get_extensions_string.is_null() || swap_interval.is_null() {
+        return false;
+    }
+    unsafe {
+        let get_extensions_string: unsafe extern "system" fn() -> *const c_char =
+            std::mem::transmute(get_extensions_string);
+        let extensions = CStr::from_ptr(get_extensions_string());
+        if !has_extension(extensions, "WGL_EXT_swap_control_tear") {
+            return false;
+        }
+        let swap_interval: unsafe extern "system" fn(c_int) -> c_int =
+            std::mem::transmute(swap_interval);
+        swap_interval(-1) != 0
+    }
+}
+
+/// Sets the swap interval of the current context to -1, if the platform supports that. Returns true on
+/// success.
+#[cfg(not(any(
+    all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "ios")),
+        feature = "x11"
+    ),
+    target_family = "windows"
+)))]
+pub(crate) fn enable(_context: &Context) -> bool {
+    false
+}
+
+#[test]
+fn extension_names_match_whole_words() {
+    let extensions =
+        CStr::from_bytes_with_nul(b"GLX_EXT_swap_control GLX_ARB_multisample\0").unwrap();
+    assert!(has_extension(extensions, "GLX_EXT_swap_control"));
+    assert!(!has_extension(extensions, "GLX_EXT_swap_control_tear"));
+}
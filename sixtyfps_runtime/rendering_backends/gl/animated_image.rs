@@ -0,0 +1,213 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+/*!
+Support for animated images (GIF and APNG).
+
+All frames are decoded up-front. The frame to show is derived from the animation driver's
+current tick, so all animated images advance in sync with property animations. A single-shot
+timer fires when the current frame's delay has elapsed, which makes the event loop redraw the
+windows.
+*/
+
+use std::cell::Cell;
+use std::time::Duration;
+
+use image::AnimationDecoder;
+use sixtyfps_corelib::animations::Instant;
+use sixtyfps_corelib::timers::{Timer, TimerMode};
+
+use super::CachedImage;
+
+/// Frames with no delay are shown for this long, which matches what web browsers do.
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+struct AnimationFrame {
+    image: CachedImage,
+    delay: Duration,
+}
+
+pub(crate) struct AnimatedImage {
+    frames: Vec<AnimationFrame>,
+    /// The sum of all frame delays, i.e. the length of one loop of the animation.
+    total_duration: Duration,
+    /// The animation tick at which the first frame was shown.
+    start: Cell<Option<Instant>>,
+    next_frame_timer: Timer,
+}
+
+impl AnimatedImage {
+    /// Returns true if `data` is a GIF or an APNG, which may have more than one frame. This only looks at
+    /// the header of the data.
+    pub fn may_be_animated(data: &[u8]) -> bool {
+        match image::guess_format(data) {
+            Ok(image::ImageFormat::Gif) => true,
+            Ok(image::ImageFormat::Png) => {
+                image::codecs::png::PngDecoder::new(data).map_or(false, |decoder| decoder.is_apng())
+            }
+            _ => false,
+        }
+    }
+
+    /// Decodes all frames of the GIF or APNG in `data`. Returns None if the data is not an
+    /// animation with at least two frames, in which case it should be loaded as a still image.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let cursor = std::io::Cursor::new(data);
+        let frames = match image::guess_format(data).ok()? {
+            image::ImageFormat::Gif => {
+                image::codecs::gif::GifDecoder::new(cursor).ok()?.into_frames().collect_frames()
+            }
+            image::ImageFormat::Png => {
+                let decoder = image::codecs::png::PngDecoder::new(cursor).ok()?;
+                if !decoder.is_apng() {
+                    return None;
+                }
+                decoder.apng().into_frames().collect_frames()
+            }
+            _ => return None,
+        }
+        .ok()?;
+
+        if frames.len() < 2 {
+            return None;
+        }
+
+        let frames = frames
+            .into_iter()
+            .map(|frame| {
+                let delay = Duration::from(frame.delay());
+                AnimationFrame {
+                    delay: if delay.as_millis() == 0 { DEFAULT_FRAME_DELAY } else { delay },
+                    image: CachedImage::new_on_cpu(image::DynamicImage::ImageRgba8(
+                        frame.into_buffer(),
+                    )),
+                }
+            })
+            .collect::<Vec<_>>();
+        let total_duration = frames.iter().map(|frame| frame.delay).sum();
+
+        Some(Self {
+            frames,
+            total_duration,
+            start: Default::default(),
+            next_frame_timer: Default::default(),
+        })
+    }
+
+    /// Tints all frames with the given color, see CachedImage::colorize.
+    pub fn colorize(&self, color: sixtyfps_corelib::Color) {
+        for frame in &self.frames {
+            frame.image.colorize(color);
+        }
+    }
+
+    #[cfg(test)]
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns the index of the frame to show after the animation has been running for `elapsed`,
+    /// along with the time left until the next frame is due.
+    fn frame_at(&self, elapsed: Duration) -> (usize, Duration) {
+        let mut position =
+            Duration::from_nanos((elapsed.as_nanos() % self.total_duration.as_nanos()) as u64);
+        for (index, frame) in self.frames.iter().enumerate() {
+            if position < frame.delay {
+                return (index, frame.delay - position);
+            }
+            position -= frame.delay;
+        }
+        (0, self.frames[0].delay)
+    }
+
+    /// Returns the index of the frame to show at the animation driver's current tick. The first call
+    /// starts the animation. A redraw is scheduled for when the next frame is due.
+    pub fn current_frame(&self) -> usize {
+        let tick = sixtyfps_corelib::animations::current_tick();
+        let start = self.start.get().unwrap_or(tick);
+        self.start.set(Some(start));
+
+        let (index, remaining) = self.frame_at(tick - start);
+        if !self.next_frame_timer.running() {
+            // The timer firing is enough for the event loop to redraw all windows.
+            self.next_frame_timer.start(TimerMode::SingleShot, remaining, || {});
+        }
+        index
+    }
+
+    pub fn current_frame_image(&self) -> &CachedImage {
+        &self.frames[self.current_frame()].image
+    }
+
+    /// The size of the animation, which is the size of its first frame.
+    pub fn size(&self) -> sixtyfps_corelib::graphics::Size {
+        self.frames[0].image.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sixtyfps_corelib::animations::CURRENT_ANIMATION_DRIVER;
+
+    fn three_frame_gif() -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = image::codecs::gif::GifEncoder::new(&mut data);
+            encoder
+                .encode_frames([[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]].iter().map(
+                    |color| {
+                        image::Frame::from_parts(
+                            image::RgbaImage::from_pixel(4, 2, image::Rgba(*color)),
+                            0,
+                            0,
+                            image::Delay::from_numer_denom_ms(50, 1),
+                        )
+                    },
+                ))
+                .unwrap();
+        }
+        data
+    }
+
+    fn set_tick(ms: u64) {
+        CURRENT_ANIMATION_DRIVER.with(|driver| driver.update_animations(Instant(ms)));
+    }
+
+    #[test]
+    fn gif_frames_advance_with_time() {
+        let animation = AnimatedImage::decode(&three_frame_gif()).unwrap();
+        assert_eq!(animation.frame_count(), 3);
+        assert_eq!(animation.size(), euclid::size2(4., 2.));
+
+        set_tick(1000);
+        assert_eq!(animation.current_frame(), 0);
+        assert!(animation.next_frame_timer.running());
+        set_tick(1049);
+        assert_eq!(animation.current_frame(), 0);
+        set_tick(1050);
+        assert_eq!(animation.current_frame(), 1);
+        set_tick(1120);
+        assert_eq!(animation.current_frame(), 2);
+        // Loops back to the first frame
+        set_tick(1150);
+        assert_eq!(animation.current_frame(), 0);
+        set_tick(1210);
+        assert_eq!(animation.current_frame(), 1);
+    }
+
+    #[test]
+    fn still_images_are_not_animations() {
+        let mut data = Vec::new();
+        image::DynamicImage::new_rgba8(2, 2)
+            .write_to(&mut data, image::ImageOutputFormat::Gif)
+            .unwrap();
+        assert!(AnimatedImage::decode(&data).is_none());
+    }
+}
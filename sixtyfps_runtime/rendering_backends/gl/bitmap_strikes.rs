@@ -0,0 +1,404 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+/*!
+Support for the bitmap strikes embedded in fonts (the `EBLC` and `EBDT` tables).
+
+Some fonts come with hand-tuned bitmaps of their glyphs for small sizes, which look crisper than
+rasterized outlines. femtovg only renders outlines, so the glyphs of a strike are extracted here and
+drawn as images instead.
+
+Composite bitmaps (image formats 8 and 9) are not supported, text using them falls back to the
+outlines.
+
+The color bitmap tables (`CBLC` and `CBDT`) share the layout, with PNG images as glyph data. These
+are read with the same code, see the `color_glyphs` module.
+*/
+
+use std::convert::TryInto;
+
+/// The bitmap of a single glyph. The coverage is stored with one byte per pixel, row by row.
+#[derive(Debug, PartialEq)]
+pub(crate) struct GlyphBitmap {
+    /// Horizontal distance from the pen position to the left edge of the bitmap.
+    pub left: i32,
+    /// Vertical distance from the baseline up to the top edge of the bitmap.
+    pub top: i32,
+    pub width: u32,
+    pub height: u32,
+    pub coverage: Vec<u8>,
+}
+
+/// The bitmap location and size tables of a font, along with the bitmap data.
+pub(crate) struct BitmapStrikes {
+    eblc: Vec<u8>,
+    ebdt: Vec<u8>,
+}
+
+fn read_u8(data: &[u8], offset: usize) -> Option<u8> {
+    data.get(offset).copied()
+}
+
+fn read_i8(data: &[u8], offset: usize) -> Option<i8> {
+    read_u8(data, offset).map(|value| value as i8)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+const BITMAP_SIZE_RECORD_LENGTH: usize = 48;
+
+/// The glyph metrics shared by all glyph bitmaps, which are stored either in the `EBDT` table
+/// next to the bitmap, or in the `EBLC` table for glyphs of the same size.
+#[derive(Clone, Copy)]
+struct GlyphMetrics {
+    height: u8,
+    width: u8,
+    bearing_x: i8,
+    bearing_y: i8,
+}
+
+impl GlyphMetrics {
+    // Small and big glyph metrics both start with these fields.
+    fn read(data: &[u8], offset: usize) -> Option<Self> {
+        Some(Self {
+            height: read_u8(data, offset)?,
+            width: read_u8(data, offset + 1)?,
+            bearing_x: read_i8(data, offset + 2)?,
+            bearing_y: read_i8(data, offset + 3)?,
+        })
+    }
+}
+
+const SMALL_GLYPH_METRICS_LENGTH: usize = 5;
+const BIG_GLYPH_METRICS_LENGTH: usize = 8;
+
+impl BitmapStrikes {
+    /// Returns the bitmap strikes of the first font in the given font data, or None if it has none.
+    pub fn from_font_data(data: &[u8]) -> Option<Self> {
+        let face = ttf_parser::Face::from_slice(data, 0).ok()?;
+        Self::from_tables(
+            face.table_data(ttf_parser::Tag::from_bytes(b"EBLC"))?,
+            face.table_data(ttf_parser::Tag::from_bytes(b"EBDT"))?,
+        )
+    }
+
+    /// Returns the color bitmap strikes of the first font in the given font data, or None if it has none.
+    pub fn color_from_font_data(data: &[u8]) -> Option<Self> {
+        let face = ttf_parser::Face::from_slice(data, 0).ok()?;
+        Self::from_tables(
+            face.table_data(ttf_parser::Tag::from_bytes(b"CBLC"))?,
+            face.table_data(ttf_parser::Tag::from_bytes(b"CBDT"))?,
+        )
+    }
+
+    fn from_tables(eblc: &[u8], ebdt: &[u8]) -> Option<Self> {
+        let strikes = Self { eblc: eblc.to_vec(), ebdt: ebdt.to_vec() };
+        if strikes.strike_count() == 0 {
+            return None;
+        }
+        Some(strikes)
+    }
+
+    fn strike_count(&self) -> usize {
+        read_u32(&self.eblc, 4).unwrap_or(0) as usize
+    }
+
+    fn strike_offsets(&self) -> impl Iterator<Item = usize> {
+        (0..self.strike_count()).map(|index| 8 + index * BITMAP_SIZE_RECORD_LENGTH)
+    }
+
+    // Returns the offset of the bitmap size record in the EBLC table for the given pixels per em.
+    fn strike(&self, ppem: u16) -> Option<usize> {
+        self.strike_offsets().find(|offset| {
+            read_u8(&self.eblc, offset + 45).map(u16::from) == Some(ppem)
+                && read_u8(&self.eblc, offset + 44).map(u16::from) == Some(ppem)
+        })
+    }
+
+    /// Returns true if the font has bitmaps of its glyphs for the given size.
+    pub fn has_strike(&self, ppem: u16) -> bool {
+        self.strike(ppem).is_some()
+    }
+
+    /// Returns the pixels per em of the strikes with square pixels.
+    pub fn strike_sizes(&self) -> impl Iterator<Item = u16> + '_ {
+        self.strike_offsets().filter_map(move |offset| {
+            let ppem_x = read_u8(&self.eblc, offset + 44)?;
+            let ppem_y = read_u8(&self.eblc, offset + 45)?;
+            Some(ppem_x.into()).filter(|_| ppem_x == ppem_y)
+        })
+    }
+
+    /// Returns the bitmap of the glyph in the strike for the given size, if the strike has one.
+    pub fn glyph(&self, glyph_id: u16, ppem: u16) -> Option<GlyphBitmap> {
+        let location = self.glyph_location(glyph_id, ppem)?;
+        let data = location.data;
+        let (metrics, bitmap, bit_aligned) = match location.image_format {
+            1 | 2 => (
+                GlyphMetrics::read(data, 0)?,
+                &data[SMALL_GLYPH_METRICS_LENGTH.min(data.len())..],
+                location.image_format == 2,
+            ),
+            5 => (location.shared_metrics?, data, true),
+            6 | 7 => (
+                GlyphMetrics::read(data, 0)?,
+                &data[BIG_GLYPH_METRICS_LENGTH.min(data.len())..],
+                location.image_format == 7,
+            ),
+            _ => return None,
+        };
+
+        Some(GlyphBitmap {
+            left: metrics.bearing_x.into(),
+            top: metrics.bearing_y.into(),
+            width: metrics.width.into(),
+            height: metrics.height.into(),
+            coverage: unpack_coverage(
+                bitmap,
+                metrics.width as usize,
+                metrics.height as usize,
+                location.bit_depth,
+                bit_aligned,
+            )?,
+        })
+    }
+
+    /// Returns the placement of the glyph in the color strike for the given size, along with the PNG
+    /// image of the glyph, if the strike has one. The placement has no coverage.
+    pub fn png_glyph(&self, glyph_id: u16, ppem: u16) -> Option<(GlyphBitmap, &[u8])> {
+        let location = self.glyph_location(glyph_id, ppem)?;
+        let data = location.data;
+        let (metrics, png_offset) = match location.image_format {
+            17 => (GlyphMetrics::read(data, 0)?, SMALL_GLYPH_METRICS_LENGTH),
+            18 => (GlyphMetrics::read(data, 0)?, BIG_GLYPH_METRICS_LENGTH),
+            19 => (location.shared_metrics?, 0),
+            _ => return None,
+        };
+        let png_length = read_u32(data, png_offset)? as usize;
+        let png = data.get(png_offset + 4..png_offset + 4 + png_length)?;
+        Some((
+            GlyphBitmap {
+                left: metrics.bearing_x.into(),
+                top: metrics.bearing_y.into(),
+                width: metrics.width.into(),
+                height: metrics.height.into(),
+                coverage: Vec::new(),
+            },
+            png,
+        ))
+    }
+
+    fn glyph_location(&self, glyph_id: u16, ppem: u16) -> Option<GlyphLocation<'_>> {
+        let strike = self.strike(ppem)?;
+        let bit_depth = read_u8(&self.eblc, strike + 46)?;
+        let array_offset = read_u32(&self.eblc, strike)? as usize;
+        let subtable_count = read_u32(&self.eblc, strike + 8)? as usize;
+
+        (0..subtable_count).find_map(|index| {
+            let entry = array_offset + index * 8;
+            let first_glyph = read_u16(&self.eblc, entry)?;
+            let last_glyph = read_u16(&self.eblc, entry + 2)?;
+            if glyph_id < first_glyph || glyph_id > last_glyph {
+                return None;
+            }
+            let subtable = array_offset + read_u32(&self.eblc, entry + 4)? as usize;
+            self.glyph_location_in_subtable(subtable, glyph_id - first_glyph, glyph_id, bit_depth)
+        })
+    }
+
+    fn glyph_location_in_subtable(
+        &self,
+        subtable: usize,
+        index: u16,
+        glyph_id: u16,
+        bit_depth: u8,
+    ) -> Option<GlyphLocation<'_>> {
+        let index_format = read_u16(&self.eblc, subtable)?;
+        let image_format = read_u16(&self.eblc, subtable + 2)?;
+        let image_data_offset = read_u32(&self.eblc, subtable + 4)? as usize;
+        let index = index as usize;
+
+        // The location of the glyph in the EBDT table, and for formats with constant metrics, the metrics.
+        let (offset, length, shared_metrics) = match index_format {
+            1 => {
+                let start = read_u32(&self.eblc, subtable + 8 + index * 4)? as usize;
+                let end = read_u32(&self.eblc, subtable + 8 + (index + 1) * 4)? as usize;
+                (start, end.checked_sub(start)?, None)
+            }
+            2 => {
+                let image_size = read_u32(&self.eblc, subtable + 8)? as usize;
+                let metrics = GlyphMetrics::read(&self.eblc, subtable + 12)?;
+                (index * image_size, image_size, Some(metrics))
+            }
+            3 => {
+                let start = read_u16(&self.eblc, subtable + 8 + index * 2)? as usize;
+                let end = read_u16(&self.eblc, subtable + 8 + (index + 1) * 2)? as usize;
+                (start, end.checked_sub(start)?, None)
+            }
+            4 => {
+                let glyph_count = read_u32(&self.eblc, subtable + 8)? as usize;
+                let pairs = subtable + 12;
+                let position = (0..glyph_count)
+                    .find(|pair| read_u16(&self.eblc, pairs + pair * 4) == Some(glyph_id))?;
+                let start = read_u16(&self.eblc, pairs + position * 4 + 2)? as usize;
+                let end = read_u16(&self.eblc, pairs + (position + 1) * 4 + 2)? as usize;
+                (start, end.checked_sub(start)?, None)
+            }
+            5 => {
+                let image_size = read_u32(&self.eblc, subtable + 8)? as usize;
+                let metrics = GlyphMetrics::read(&self.eblc, subtable + 12)?;
+                let glyph_count = read_u32(&self.eblc, subtable + 12 + BIG_GLYPH_METRICS_LENGTH)?;
+                let glyph_ids = subtable + 16 + BIG_GLYPH_METRICS_LENGTH;
+                let position = (0..glyph_count as usize).find(|position| {
+                    read_u16(&self.eblc, glyph_ids + position * 2) == Some(glyph_id)
+                })?;
+                (position * image_size, image_size, Some(metrics))
+            }
+            _ => return None,
+        };
+
+        let data =
+            self.ebdt.get(image_data_offset + offset..image_data_offset + offset + length)?;
+        Some(GlyphLocation { data, image_format, shared_metrics, bit_depth })
+    }
+}
+
+// The data of a glyph in the EBDT table, with what the EBLC table says about it.
+struct GlyphLocation<'a> {
+    data: &'a [u8],
+    image_format: u16,
+    shared_metrics: Option<GlyphMetrics>,
+    bit_depth: u8,
+}
+
+// Expands the packed pixels of an EBDT bitmap to one coverage byte per pixel. With byte aligned data, each
+// row starts at a new byte; with bit aligned data, rows follow each other without padding.
+fn unpack_coverage(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    bit_aligned: bool,
+) -> Option<Vec<u8>> {
+    let bit_depth = bit_depth as usize;
+    if ![1, 2, 4, 8].contains(&bit_depth) {
+        return None;
+    }
+    let max_value = (1 << bit_depth) - 1;
+    let row_bits = if bit_aligned { width * bit_depth } else { (width * bit_depth + 7) / 8 * 8 };
+
+    let mut coverage = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let bit = y * row_bits + x * bit_depth;
+            let byte = *data.get(bit / 8)? as usize;
+            let value = (byte >> (8 - bit_depth - bit % 8)) & max_value;
+            coverage.push((value * 255 / max_value) as u8);
+        }
+    }
+    Some(coverage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds an EBLC table with a single strike, whose only index subtable (format 1) covers glyphs 3 and 4.
+    fn eblc_with_strike(
+        ppem: u8,
+        bit_depth: u8,
+        image_format: u16,
+        glyph_offsets: [u32; 3],
+    ) -> Vec<u8> {
+        let mut eblc = vec![0, 2, 0, 0, 0, 0, 0, 1];
+        let array_offset = 8 + BITMAP_SIZE_RECORD_LENGTH as u32;
+        eblc.extend_from_slice(&array_offset.to_be_bytes());
+        eblc.extend_from_slice(&[0; 4]); // indexTablesSize
+        eblc.extend_from_slice(&1u32.to_be_bytes());
+        eblc.extend_from_slice(&[0; 4 + 12 + 12]); // colorRef and line metrics
+        eblc.extend_from_slice(&[0, 3, 0, 4, ppem, ppem, bit_depth, 1]);
+        // The index subtable array, then the index subtable
+        eblc.extend_from_slice(&[0, 3, 0, 4, 0, 0, 0, 8]);
+        eblc.extend_from_slice(&1u16.to_be_bytes());
+        eblc.extend_from_slice(&image_format.to_be_bytes());
+        eblc.extend_from_slice(&4u32.to_be_bytes()); // imageDataOffset, after the EBDT version
+        for offset in glyph_offsets.iter() {
+            eblc.extend_from_slice(&offset.to_be_bytes());
+        }
+        eblc
+    }
+
+    #[test]
+    fn monochrome_byte_aligned_glyphs() {
+        // A 3x2 glyph and a 9x1 glyph, with small metrics, one bit per pixel.
+        let ebdt = [
+            &[0, 2, 0, 0][..],
+            &[2, 3, 1, 7, 4, 0b1010_0000, 0b0100_0000],
+            &[1, 9, 0, 1, 10, 0b1111_1111, 0b1000_0000],
+        ]
+        .concat();
+        let strikes =
+            BitmapStrikes::from_tables(&eblc_with_strike(9, 1, 1, [0, 7, 14]), &ebdt).unwrap();
+        assert!(strikes.has_strike(9));
+        assert!(!strikes.has_strike(10));
+
+        assert_eq!(
+            strikes.glyph(3, 9),
+            Some(GlyphBitmap {
+                left: 1,
+                top: 7,
+                width: 3,
+                height: 2,
+                coverage: vec![255, 0, 255, 0, 255, 0]
+            })
+        );
+        assert_eq!(strikes.glyph(4, 9).unwrap().coverage, vec![255; 9]);
+        assert_eq!(strikes.glyph(5, 9), None);
+        assert_eq!(strikes.glyph(3, 10), None);
+    }
+
+    #[test]
+    fn grayscale_bit_aligned_glyphs() {
+        // A 3x2 glyph with two bits per pixel, the rows are not padded.
+        let ebdt = [&[0, 2, 0, 0][..], &[2, 3, 0, 2, 3, 0b0001_1011, 0b1110_0100]].concat();
+        let strikes =
+            BitmapStrikes::from_tables(&eblc_with_strike(9, 2, 2, [0, 7, 7]), &ebdt).unwrap();
+        assert_eq!(strikes.glyph(3, 9).unwrap().coverage, vec![0, 85, 170, 255, 255, 170]);
+        // Empty glyphs, such as the space, have no bitmap data.
+        assert_eq!(strikes.glyph(4, 9), None);
+    }
+
+    #[test]
+    fn png_glyphs_of_color_strikes() {
+        // A 20x10 glyph with small metrics, followed by the length of its (here fake) PNG data.
+        let cbdt = [&[0, 3, 0, 0][..], &[10, 20, 2, 9, 22], &3u32.to_be_bytes(), b"png"].concat();
+        let strikes =
+            BitmapStrikes::from_tables(&eblc_with_strike(109, 32, 17, [0, 12, 12]), &cbdt).unwrap();
+        assert_eq!(strikes.strike_sizes().collect::<Vec<_>>(), vec![109]);
+
+        let (placement, png) = strikes.png_glyph(3, 109).unwrap();
+        assert_eq!((placement.left, placement.top), (2, 9));
+        assert_eq!((placement.width, placement.height), (20, 10));
+        assert_eq!(png, b"png");
+        assert!(strikes.png_glyph(4, 109).is_none());
+        // The glyph isn't a monochrome or grayscale bitmap.
+        assert_eq!(strikes.glyph(3, 109), None);
+    }
+
+    #[test]
+    fn fonts_without_strikes() {
+        assert!(BitmapStrikes::from_font_data(include_bytes!("fonts/DejaVuSans.ttf")).is_none());
+    }
+}
@@ -0,0 +1,228 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+/*!
+Support for color glyphs, such as emoji, that fonts embed as PNG images. Either in color bitmap strikes
+(the `CBLC` and `CBDT` tables, used by Noto Color Emoji), or in the `sbix` table (used by Apple Color
+Emoji).
+
+These glyphs have no outlines, so femtovg lays them out but draws nothing for them. They are drawn as
+images on top of the text instead, scaled from the strike closest to the font size.
+
+Color glyphs made of layers of outlines (the `COLR` table, used by Segoe UI Emoji) are drawn with
+their outlines, in the color of the text.
+*/
+
+use super::bitmap_strikes::BitmapStrikes;
+use std::convert::TryInto;
+
+/// The image of a single color glyph, with its placement in pixels of the strike it comes from.
+pub(crate) struct ColorGlyph {
+    /// Horizontal distance from the pen position to the left edge of the image.
+    pub left: i32,
+    /// Vertical distance from the baseline up to the top edge of the image.
+    pub top: i32,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: image::RgbaImage,
+}
+
+/// The color glyph images of a font.
+pub(crate) enum ColorGlyphs {
+    Cbdt(BitmapStrikes),
+    Sbix(Vec<u8>),
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn decode_png(data: &[u8]) -> Option<image::RgbaImage> {
+    Some(image::load_from_memory_with_format(data, image::ImageFormat::Png).ok()?.to_rgba8())
+}
+
+impl ColorGlyphs {
+    /// Returns the color glyphs of the first font in the given font data, or None if it has none.
+    pub fn from_font_data(data: &[u8]) -> Option<Self> {
+        if let Some(strikes) = BitmapStrikes::color_from_font_data(data) {
+            return Some(Self::Cbdt(strikes));
+        }
+        let face = ttf_parser::Face::from_slice(data, 0).ok()?;
+        Self::from_sbix_table(face.table_data(ttf_parser::Tag::from_bytes(b"sbix"))?)
+    }
+
+    fn from_sbix_table(sbix: &[u8]) -> Option<Self> {
+        if read_u32(sbix, 4)? == 0 {
+            return None;
+        }
+        Some(Self::Sbix(sbix.to_vec()))
+    }
+
+    fn strike_sizes(&self) -> Vec<u16> {
+        match self {
+            Self::Cbdt(strikes) => strikes.strike_sizes().collect(),
+            Self::Sbix(sbix) => {
+                sbix_strikes(sbix).filter_map(|strike| read_u16(sbix, strike)).collect()
+            }
+        }
+    }
+
+    /// Returns the pixels per em of the strike to draw glyphs of the given size with: the smallest
+    /// strike that is at least as large, or the largest strike when they are all smaller.
+    pub fn strike_for_size(&self, pixel_size: f32) -> Option<u16> {
+        let sizes = self.strike_sizes();
+        sizes
+            .iter()
+            .copied()
+            .filter(|ppem| *ppem as f32 >= pixel_size)
+            .min()
+            .or_else(|| sizes.iter().copied().max())
+    }
+
+    /// Returns the image of the glyph in the strike for the given size, if the strike has one.
+    pub fn glyph(&self, glyph_id: u16, ppem: u16) -> Option<ColorGlyph> {
+        match self {
+            Self::Cbdt(strikes) => {
+                let (placement, png) = strikes.png_glyph(glyph_id, ppem)?;
+                Some(ColorGlyph {
+                    left: placement.left,
+                    top: placement.top,
+                    width: placement.width,
+                    height: placement.height,
+                    pixels: decode_png(png)?,
+                })
+            }
+            Self::Sbix(sbix) => {
+                let strike =
+                    sbix_strikes(sbix).find(|strike| read_u16(sbix, *strike) == Some(ppem))?;
+                sbix_glyph(sbix, strike, glyph_id, true)
+            }
+        }
+    }
+}
+
+// Returns the offsets of the strikes in the sbix table.
+fn sbix_strikes(sbix: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    let strike_count = read_u32(sbix, 4).unwrap_or(0) as usize;
+    (0..strike_count).filter_map(move |index| Some(read_u32(sbix, 8 + index * 4)? as usize))
+}
+
+fn sbix_glyph(sbix: &[u8], strike: usize, glyph_id: u16, follow_dupe: bool) -> Option<ColorGlyph> {
+    let offsets = strike + 4 + glyph_id as usize * 4;
+    let start = strike + read_u32(sbix, offsets)? as usize;
+    let end = strike + read_u32(sbix, offsets + 4)? as usize;
+    let data = sbix.get(start..end)?;
+    // Glyphs without an image, such as the space, have no data.
+    let origin_x = read_i16(data, 0)?;
+    let origin_y = read_i16(data, 2)?;
+    let image = data.get(8..)?;
+    match data.get(4..8)? {
+        b"png " => {
+            let pixels = decode_png(image)?;
+            let (width, height) = pixels.dimensions();
+            Some(ColorGlyph {
+                // The origin is the position of the bottom left corner of the image.
+                left: origin_x.into(),
+                top: origin_y as i32 + height as i32,
+                width,
+                height,
+                pixels,
+            })
+        }
+        // The same image as another glyph, such as for emoji that have several code points.
+        b"dupe" if follow_dupe => sbix_glyph(sbix, strike, read_u16(image, 0)?, false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        image::png::PngEncoder::new(&mut data)
+            .encode(
+                &vec![255; (width * height * 4) as usize],
+                width,
+                height,
+                image::ColorType::Rgba8,
+            )
+            .unwrap();
+        data
+    }
+
+    // Builds an sbix table with a strike for each of the given sizes, each with the same glyph data for glyphs
+    // 0 to 2.
+    fn sbix_with_strikes(sizes: &[u16], glyphs: [&[u8]; 3]) -> Vec<u8> {
+        let mut sbix = vec![0, 1, 0, 1];
+        sbix.extend_from_slice(&(sizes.len() as u32).to_be_bytes());
+        let strike_length = 4 + 4 * 4 + glyphs.iter().map(|glyph| glyph.len()).sum::<usize>();
+        for index in 0..sizes.len() {
+            let offset = 8 + sizes.len() * 4 + index * strike_length;
+            sbix.extend_from_slice(&(offset as u32).to_be_bytes());
+        }
+        for ppem in sizes {
+            sbix.extend_from_slice(&ppem.to_be_bytes());
+            sbix.extend_from_slice(&72u16.to_be_bytes());
+            let mut offset = 4 + 4 * 4;
+            for glyph in glyphs.iter() {
+                sbix.extend_from_slice(&(offset as u32).to_be_bytes());
+                offset += glyph.len();
+            }
+            sbix.extend_from_slice(&(offset as u32).to_be_bytes());
+            for glyph in glyphs.iter() {
+                sbix.extend_from_slice(glyph);
+            }
+        }
+        sbix
+    }
+
+    #[test]
+    fn sbix_glyphs() {
+        let image = [&[0, 1, 255, 254][..], b"png ", &png(4, 3)].concat();
+        let dupe = [&[0, 0, 0, 0][..], b"dupe", &[0, 1]].concat();
+        let glyphs =
+            ColorGlyphs::from_sbix_table(&sbix_with_strikes(&[20, 40], [&[], &image, &dupe]))
+                .unwrap();
+
+        let glyph = glyphs.glyph(1, 40).unwrap();
+        assert_eq!((glyph.left, glyph.top), (1, 1));
+        assert_eq!((glyph.width, glyph.height), (4, 3));
+        assert_eq!(glyph.pixels.dimensions(), (4, 3));
+        assert_eq!(glyphs.glyph(2, 20).unwrap().pixels.dimensions(), (4, 3));
+        assert!(glyphs.glyph(0, 20).is_none());
+        assert!(glyphs.glyph(3, 20).is_none());
+        assert!(glyphs.glyph(1, 30).is_none());
+    }
+
+    #[test]
+    fn strikes_for_sizes() {
+        let glyphs =
+            ColorGlyphs::from_sbix_table(&sbix_with_strikes(&[40, 20, 96], [&[]; 3])).unwrap();
+        assert_eq!(glyphs.strike_for_size(12.), Some(20));
+        assert_eq!(glyphs.strike_for_size(20.), Some(20));
+        assert_eq!(glyphs.strike_for_size(20.5), Some(40));
+        assert_eq!(glyphs.strike_for_size(200.), Some(96));
+        assert!(ColorGlyphs::from_sbix_table(&sbix_with_strikes(&[], [&[]; 3])).is_none());
+    }
+
+    #[test]
+    fn fonts_without_color_glyphs() {
+        assert!(ColorGlyphs::from_font_data(include_bytes!("fonts/DejaVuSans.ttf")).is_none());
+    }
+}
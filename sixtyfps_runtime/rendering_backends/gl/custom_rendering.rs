@@ -0,0 +1,54 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+/*!
+Draw handlers registered by the application for the `CustomRendering` element, which draw directly with femtovg.
+
+They are an escape hatch for content that the elements can't draw efficiently, such as plots of many thousands of
+lines, which femtovg can draw as a few paths. The handlers are called while rendering, so they are registered on
+the thread that runs the event loop, like the windows they draw in.
+*/
+
+use sixtyfps_corelib::graphics::Size;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+type CustomRendererFn = dyn Fn(&mut femtovg::Canvas<femtovg::renderer::OpenGl>, Size);
+
+thread_local!(static CUSTOM_RENDERERS: RefCell<HashMap<String, Rc<CustomRendererFn>>> = Default::default());
+
+/// Registers the draw handler for the `CustomRendering` elements whose `renderer` property is the given name,
+/// replacing the handler previously registered with that name. Elements without a handler draw nothing.
+///
+/// The handler is called with the femtovg canvas, whose origin is at the top left corner of the element, and with
+/// the size of the element. The drawing is clipped to the element, as well as to the clips of its parents, and is
+/// scaled, transformed and mirrored like the other elements. The state of the canvas and its render target are
+/// restored afterwards, so the handler may change the transform, the clip, the alpha and the composite operation
+/// freely. It must balance its own calls to `save()` and `restore()`, and must not flush, resize or screenshot
+/// the canvas.
+///
+/// The handler is called whenever the window is drawn. After the data it draws has changed, call
+/// `request_redraw()` on the window to draw it again.
+///
+/// The canvas is that of the femtovg crate this backend was built with, so the application has to depend on the
+/// same version of femtovg, currently 0.1. The femtovg API is not covered by the stability of the SixtyFPS API:
+/// any release of SixtyFPS may update femtovg to a version with breaking changes.
+pub fn register_custom_renderer(
+    name: &str,
+    draw: impl Fn(&mut femtovg::Canvas<femtovg::renderer::OpenGl>, Size) + 'static,
+) {
+    CUSTOM_RENDERERS.with(|renderers| renderers.borrow_mut().insert(name.into(), Rc::new(draw)));
+}
+
+/// Returns the draw handler registered with the given name.
+pub(crate) fn custom_renderer(name: &str) -> Option<Rc<CustomRendererFn>> {
+    // The handler is returned without holding the borrow, so that it may register further handlers.
+    CUSTOM_RENDERERS.with(|renderers| renderers.borrow().get(name).cloned())
+}
@@ -110,6 +110,14 @@ pub enum CustomEvent {
     /// request an animation frame.
     #[cfg(target_arch = "wasm32")]
     WakeUpAndPoll,
+    /// Sent from a background thread when the data of an image loaded from a http:// or https:// URL
+    /// has arrived, or when an image was decoded ahead of drawing it.
+    #[cfg(not(target_arch = "wasm32"))]
+    ImageFetched,
+    /// Sent from the thread that watches the files of the images when one of them changed, see
+    /// `GLRenderer::reload_changed_image_files`.
+    #[cfg(not(target_arch = "wasm32"))]
+    ImageFileChanged,
 }
 
 /// Runs the event loop and renders the items in the provided `component` in its
@@ -129,6 +137,11 @@ pub fn run() {
     // last seen cursor position, (physical coordinate)
     let mut cursor_pos = Point::default();
     let mut pressed = false;
+    // When the SIXTYFPS_PAUSE_WHEN_UNFOCUSED environment variable is set, animations stop driving the
+    // rendering while none of the windows have the focus. The windows are still redrawn when needed, for
+    // example when a timer fires, and the animations catch up once a window gets the focus back.
+    let pause_when_unfocused = std::env::var("SIXTYFPS_PAUSE_WHEN_UNFOCUSED").is_ok();
+    let mut unfocused_windows = std::collections::HashSet::new();
     let mut run_fn = move |event: Event<CustomEvent>,
                            event_loop_target: &EventLoopWindowTarget<CustomEvent>,
                            control_flow: &mut ControlFlow| {
@@ -142,6 +155,28 @@ pub fn run() {
                     event: winit::event::WindowEvent::CloseRequested,
                     ..
                 } => *control_flow = winit::event_loop::ControlFlow::Exit,
+                #[cfg(not(target_arch = "wasm32"))]
+                winit::event::Event::UserEvent(CustomEvent::ImageFetched) => {
+                    crate::poll_fetching_images();
+                    ALL_WINDOWS.with(|windows| {
+                        windows.borrow().values().for_each(|window| {
+                            if let Some(window) = window.upgrade() {
+                                window.request_redraw();
+                            }
+                        })
+                    })
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                winit::event::Event::UserEvent(CustomEvent::ImageFileChanged) => {
+                    ALL_WINDOWS.with(|windows| {
+                        windows.borrow().values().for_each(|window| {
+                            if let Some(window) = window.upgrade() {
+                                window.reload_changed_image_files();
+                                window.request_redraw();
+                            }
+                        })
+                    })
+                }
                 winit::event::Event::RedrawRequested(id) => {
                     corelib::animations::update_animations();
                     ALL_WINDOWS.with(|windows| {
@@ -414,6 +449,11 @@ pub fn run() {
                     ref window_id,
                     event: winit::event::WindowEvent::Focused(have_focus),
                 } => {
+                    if have_focus {
+                        unfocused_windows.remove(window_id);
+                    } else {
+                        unfocused_windows.insert(*window_id);
+                    }
                     ALL_WINDOWS.with(|windows| {
                         if let Some(Some(window)) =
                             windows.borrow().get(&window_id).map(|weakref| weakref.upgrade())
@@ -439,6 +479,13 @@ pub fn run() {
                     if !driver.has_active_animations() {
                         return;
                     }
+                    if pause_when_unfocused
+                        && ALL_WINDOWS.with(|windows| {
+                            windows.borrow().keys().all(|id| unfocused_windows.contains(id))
+                        })
+                    {
+                        return;
+                    }
                     *control_flow = ControlFlow::Poll;
                     ALL_WINDOWS.with(|windows| {
                         windows.borrow().values().for_each(|window| {
@@ -470,6 +517,19 @@ pub fn run() {
 
     #[cfg(not(target_arch = "wasm32"))]
     winit_loop.run_return(run_fn);
+    // The Wayland primary selection uses the display connection of the event loop, which is closed when it is
+    // dropped at the end of this function.
+    #[cfg(all(
+        unix,
+        not(any(
+            target_os = "macos",
+            target_os = "android",
+            target_os = "ios",
+            target_os = "emscripten"
+        )),
+        feature = "wayland"
+    ))]
+    crate::drop_wayland_primary_selection();
     #[cfg(target_arch = "wasm32")]
     {
         // Since wasm does not have a run_return function that takes a non-static closure,
@@ -0,0 +1,386 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+/*!
+Support for OpenType features that substitute the glyphs of single characters, such as the old-style
+figures of the `onum` feature.
+
+femtovg shapes text without any OpenType features. So instead of enabling a feature when shaping,
+the characters it affects are shaped once with the feature, and the character to glyph mapping (the
+`cmap` table) of the font is rewritten to map them to the substituted glyphs. The rewritten font is
+then added to the canvas like any other font.
+
+Substitutions that depend on the surrounding characters are not applied this way, but the features
+selecting the style of digits don't use them.
+
+Rewriting the mapping also removes characters from fonts, so that femtovg falls back to the next font
+for them, such as for emoji.
+*/
+
+use sixtyfps_corelib::items::NumeralStyle;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+use std::ops::RangeInclusive;
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+const TABLE_RECORD_LENGTH: usize = 16;
+const ENCODING_RECORD_LENGTH: usize = 8;
+
+/// Returns a copy of the font data with the digits mapped to the glyphs of the numeral style, or
+/// None if the font has no such glyphs.
+pub(crate) fn apply_numeral_style(data: &[u8], numeral_style: NumeralStyle) -> Option<Vec<u8>> {
+    let feature = numeral_style.opentype_feature()?;
+    let features = [rustybuzz::Feature::new(rustybuzz::Tag::from_bytes(feature), 1, ..)];
+    let substitutions = substitutions(data, &features, None, '0'..='9')?;
+    if substitutions.is_empty() {
+        return None;
+    }
+    with_substituted_glyphs(data, &substitutions)
+}
+
+/// Returns a copy of the font data without glyphs for the characters in the given ranges, or None if
+/// the font has none of them.
+pub(crate) fn without_characters(data: &[u8], ranges: &[RangeInclusive<u32>]) -> Option<Vec<u8>> {
+    let face = ttf_parser::Face::from_slice(data, 0).ok()?;
+    let has_characters = ranges.iter().cloned().flatten().any(|codepoint| {
+        std::char::from_u32(codepoint).and_then(|character| face.glyph_index(character)).is_some()
+    });
+    if !has_characters {
+        return None;
+    }
+    with_edited_mapping(data, |mapping| {
+        mapping.retain(|codepoint, _| !ranges.iter().any(|range| range.contains(codepoint)))
+    })
+}
+
+/// Returns a copy of the font data with the characters mapped to the glyphs that the font has for
+/// them in the given language (a BCP 47 tag such as `sr`), with the `locl` feature, or None if
+/// the font has no such glyphs.
+pub(crate) fn apply_language(data: &[u8], language: &str) -> Option<Vec<u8>> {
+    let language = language.parse::<rustybuzz::Language>().ok()?;
+    let face = ttf_parser::Face::from_slice(data, 0).ok()?;
+    // Shaping every character of the font takes long, so only the characters whose glyphs may be
+    // substituted are shaped.
+    let substituted_glyphs = single_substitution_inputs(&face)?;
+    let mut characters = BTreeSet::new();
+    for subtable in face.character_mapping_subtables().filter(|subtable| subtable.is_unicode()) {
+        subtable.codepoints(|codepoint| {
+            let glyph = subtable.glyph_index(codepoint);
+            if glyph.map_or(false, |glyph| substituted_glyphs.contains(&glyph.0)) {
+                characters.extend(std::char::from_u32(codepoint));
+            }
+        });
+    }
+    let substitutions = substitutions(data, &[], Some(language), characters.into_iter())?;
+    if substitutions.is_empty() {
+        return None;
+    }
+    with_substituted_glyphs(data, &substitutions)
+}
+
+/// Returns the glyphs that shaping with the features and the language substitutes for the characters.
+/// Characters that shaping replaces without them too, such as with the isolated forms of Arabic
+/// letters, are left out.
+fn substitutions(
+    data: &[u8],
+    features: &[rustybuzz::Feature],
+    language: Option<rustybuzz::Language>,
+    characters: impl Iterator<Item = char>,
+) -> Option<Vec<(char, u16)>> {
+    let face = ttf_parser::Face::from_slice(data, 0).ok()?;
+    let shaping_face = rustybuzz::Face::from_slice(data, 0)?;
+    let shape = |character: char,
+                 features: &[rustybuzz::Feature],
+                 language: Option<&rustybuzz::Language>| {
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(&character.to_string());
+        if let Some(language) = language {
+            buffer.set_language(language.clone());
+        }
+        match rustybuzz::shape(&shaping_face, features, buffer).glyph_infos() {
+            [info] => Some(info.codepoint as u16),
+            _ => None,
+        }
+    };
+    Some(
+        characters
+            .filter_map(|character| {
+                let glyph = face.glyph_index(character)?.0;
+                if shape(character, &[], None)? != glyph {
+                    return None;
+                }
+                match shape(character, features, language.as_ref())? {
+                    substituted if substituted != glyph && substituted != 0 => {
+                        Some((character, substituted))
+                    }
+                    _ => None,
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Returns the glyphs that the single substitution lookups of the `GSUB` table of the font replace,
+/// for any script, language and feature.
+fn single_substitution_inputs(face: &ttf_parser::Face) -> Option<BTreeSet<u16>> {
+    let gsub = face.table_data(ttf_parser::Tag::from_bytes(b"GSUB"))?;
+    let lookup_list = read_u16(gsub, 8)? as usize;
+    let mut glyphs = BTreeSet::new();
+    for lookup_index in 0..read_u16(gsub, lookup_list)? as usize {
+        let lookup = lookup_list + read_u16(gsub, lookup_list + 2 + lookup_index * 2)? as usize;
+        let lookup_type = read_u16(gsub, lookup)?;
+        for subtable_index in 0..read_u16(gsub, lookup + 4)? as usize {
+            let mut subtable = lookup + read_u16(gsub, lookup + 6 + subtable_index * 2)? as usize;
+            // Extension lookups hold the actual subtable at a 32-bit offset.
+            let subtable_type = if lookup_type == 7 {
+                let extended_type = read_u16(gsub, subtable + 2)?;
+                subtable += read_u32(gsub, subtable + 4)? as usize;
+                extended_type
+            } else {
+                lookup_type
+            };
+            if subtable_type == 1 {
+                let coverage = subtable + read_u16(gsub, subtable + 2)? as usize;
+                read_coverage(gsub, coverage, &mut glyphs)?;
+            }
+        }
+    }
+    Some(glyphs)
+}
+
+fn read_coverage(data: &[u8], coverage: usize, glyphs: &mut BTreeSet<u16>) -> Option<()> {
+    let count = read_u16(data, coverage + 2)? as usize;
+    match read_u16(data, coverage)? {
+        1 => {
+            for index in 0..count {
+                glyphs.insert(read_u16(data, coverage + 4 + index * 2)?);
+            }
+        }
+        2 => {
+            for index in 0..count {
+                let range = coverage + 4 + index * 6;
+                glyphs.extend(read_u16(data, range)?..=read_u16(data, range + 2)?);
+            }
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Returns a copy of the font data, with a `cmap` table that maps the given characters to the given
+/// glyphs, and all other characters to the same glyphs as before.
+fn with_substituted_glyphs(data: &[u8], substitutions: &[(char, u16)]) -> Option<Vec<u8>> {
+    with_edited_mapping(data, |mapping| {
+        for (character, glyph) in substitutions {
+            mapping.insert(*character as u32, *glyph);
+        }
+    })
+}
+
+/// Returns a copy of the font data, with a `cmap` table holding the mapping of Unicode characters to
+/// glyphs of the font, after the given function has changed it.
+fn with_edited_mapping(data: &[u8], edit: impl FnOnce(&mut BTreeMap<u32, u16>)) -> Option<Vec<u8>> {
+    let face = ttf_parser::Face::from_slice(data, 0).ok()?;
+
+    let mut mapping = BTreeMap::new();
+    for subtable in face.character_mapping_subtables() {
+        if subtable.platform_id() == ttf_parser::PlatformId::Windows && subtable.encoding_id() == 0
+        {
+            // Symbol fonts map private use characters, leave them alone.
+            return None;
+        }
+        if !subtable.is_unicode() {
+            continue;
+        }
+        subtable.codepoints(|codepoint| {
+            if let Some(glyph) = subtable.glyph_index(codepoint) {
+                mapping.entry(codepoint).or_insert(glyph.0);
+            }
+        });
+    }
+    edit(&mut mapping);
+
+    // Collections (`ttcf`) have a header per font, which is not handled here.
+    if data.get(0..4)? == b"ttcf" {
+        return None;
+    }
+    let table_count = read_u16(data, 4)? as usize;
+    let cmap_record = (0..table_count)
+        .map(|index| 12 + index * TABLE_RECORD_LENGTH)
+        .find(|record| data.get(*record..*record + 4) == Some(b"cmap"))?;
+    let cmap_offset = read_u32(data, cmap_record + 8)? as usize;
+    let cmap_length = read_u32(data, cmap_record + 12)? as usize;
+    let cmap = data.get(cmap_offset..cmap_offset + cmap_length)?;
+
+    let cmap = rewrite_cmap(cmap, &mapping)?;
+    let mut font = data.to_vec();
+    font.resize((font.len() + 3) & !3, 0);
+    let new_cmap_offset = font.len() as u32;
+    font.extend_from_slice(&cmap);
+    font.resize((font.len() + 3) & !3, 0);
+    font[cmap_record + 4..cmap_record + 8].copy_from_slice(&checksum(&cmap).to_be_bytes());
+    font[cmap_record + 8..cmap_record + 12].copy_from_slice(&new_cmap_offset.to_be_bytes());
+    font[cmap_record + 12..cmap_record + 16].copy_from_slice(&(cmap.len() as u32).to_be_bytes());
+    Some(font)
+}
+
+/// Builds a `cmap` table with a single subtable for the Unicode characters, holding the mapping.
+/// Subtables of other platforms and the Unicode variation sequences are kept from the old table.
+fn rewrite_cmap(cmap: &[u8], mapping: &BTreeMap<u32, u16>) -> Option<Vec<u8>> {
+    let record_count = read_u16(cmap, 2)? as usize;
+    let mut records = Vec::with_capacity(record_count + 1);
+    for index in 0..record_count {
+        let record = 4 + index * ENCODING_RECORD_LENGTH;
+        let platform = read_u16(cmap, record)?;
+        let encoding = read_u16(cmap, record + 2)?;
+        let offset = read_u32(cmap, record + 4)?;
+        let format = read_u16(cmap, offset as usize)?;
+        let is_unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+        if !is_unicode || format == 14 {
+            records.push((platform, encoding, offset));
+        }
+    }
+
+    // The kept subtables stay where they are, after the longer list of encoding records.
+    let old_header_length = 4 + record_count * ENCODING_RECORD_LENGTH;
+    let new_header_length = 4 + (records.len() + 1) * ENCODING_RECORD_LENGTH;
+    let mut subtables = cmap.get(old_header_length..)?.to_vec();
+    subtables.resize((subtables.len() + 3) & !3, 0);
+    for record in &mut records {
+        record.2 = record.2.checked_sub(old_header_length as u32)? + new_header_length as u32;
+    }
+    // Windows, Unicode full repertoire, which is the preferred subtable of text shapers.
+    records.push((3, 10, (new_header_length + subtables.len()) as u32));
+    records.sort();
+    subtables.extend_from_slice(&segmented_coverage_subtable(mapping));
+
+    let mut table = Vec::with_capacity(new_header_length + subtables.len());
+    table.extend_from_slice(&0u16.to_be_bytes());
+    table.extend_from_slice(&(records.len() as u16).to_be_bytes());
+    for (platform, encoding, offset) in records {
+        table.extend_from_slice(&platform.to_be_bytes());
+        table.extend_from_slice(&encoding.to_be_bytes());
+        table.extend_from_slice(&offset.to_be_bytes());
+    }
+    table.extend_from_slice(&subtables);
+    Some(table)
+}
+
+/// Encodes the mapping as a format 12 subtable, with a group for every run of consecutive characters
+/// mapped to consecutive glyphs.
+fn segmented_coverage_subtable(mapping: &BTreeMap<u32, u16>) -> Vec<u8> {
+    let mut groups: Vec<(u32, u32, u32)> = Vec::new();
+    for (&codepoint, &glyph) in mapping {
+        match groups.last_mut() {
+            Some((start, end, start_glyph))
+                if *end + 1 == codepoint && *start_glyph + (codepoint - *start) == glyph as u32 =>
+            {
+                *end = codepoint
+            }
+            _ => groups.push((codepoint, codepoint, glyph as u32)),
+        }
+    }
+
+    let length = 16 + groups.len() * 12;
+    let mut subtable = Vec::with_capacity(length);
+    subtable.extend_from_slice(&12u16.to_be_bytes());
+    subtable.extend_from_slice(&0u16.to_be_bytes());
+    subtable.extend_from_slice(&(length as u32).to_be_bytes());
+    subtable.extend_from_slice(&0u32.to_be_bytes()); // language
+    subtable.extend_from_slice(&(groups.len() as u32).to_be_bytes());
+    for (start, end, start_glyph) in groups {
+        subtable.extend_from_slice(&start.to_be_bytes());
+        subtable.extend_from_slice(&end.to_be_bytes());
+        subtable.extend_from_slice(&start_glyph.to_be_bytes());
+    }
+    subtable
+}
+
+/// Computes the checksum of a font table, as stored in the table directory of the font.
+pub(crate) fn checksum(table: &[u8]) -> u32 {
+    table.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEJAVU_SANS: &[u8] = include_bytes!("fonts/DejaVuSans.ttf");
+
+    #[test]
+    fn substituted_glyphs_replace_the_mapping_of_their_characters() {
+        let original = ttf_parser::Face::from_slice(DEJAVU_SANS, 0).unwrap();
+        let glyph = |face: &ttf_parser::Face, character| face.glyph_index(character).unwrap().0;
+        let a = glyph(&original, 'a');
+
+        let font = with_substituted_glyphs(DEJAVU_SANS, &[('1', a)]).unwrap();
+        let face = ttf_parser::Face::from_slice(&font, 0).unwrap();
+        assert_eq!(glyph(&face, '1'), a);
+        for character in ['0', '2', 'a', 'Z', '\u{263A}', '\u{FB01}'].iter() {
+            assert_eq!(glyph(&face, *character), glyph(&original, *character));
+        }
+
+        // Text shapers pick up the new mapping as well.
+        let shaping_face = rustybuzz::Face::from_slice(&font, 0).unwrap();
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str("1a");
+        let shaped = rustybuzz::shape(&shaping_face, &[], buffer);
+        let glyphs = shaped.glyph_infos().iter().map(|info| info.codepoint).collect::<Vec<_>>();
+        assert_eq!(glyphs, vec![a as u32, a as u32]);
+    }
+
+    #[test]
+    fn removed_characters_are_no_longer_mapped() {
+        let original = ttf_parser::Face::from_slice(DEJAVU_SANS, 0).unwrap();
+        assert!(original.glyph_index('\u{1F600}').is_some());
+
+        let font = without_characters(DEJAVU_SANS, &[0x1F600..=0x1F64F]).unwrap();
+        let face = ttf_parser::Face::from_slice(&font, 0).unwrap();
+        assert!(face.glyph_index('\u{1F600}').is_none());
+        assert!(face.glyph_index('\u{1F64F}').is_none());
+        assert_eq!(face.glyph_index('a'), original.glyph_index('a'));
+        assert_eq!(face.glyph_index('\u{263A}'), original.glyph_index('\u{263A}'));
+
+        assert!(without_characters(DEJAVU_SANS, &[0xE000..=0xE0FF]).is_none());
+    }
+
+    #[test]
+    fn languages_map_characters_to_their_local_forms() {
+        let original = ttf_parser::Face::from_slice(DEJAVU_SANS, 0).unwrap();
+        // DejaVu Sans has the Serbian form of the small letter be.
+        let font = apply_language(DEJAVU_SANS, "sr").unwrap();
+        let face = ttf_parser::Face::from_slice(&font, 0).unwrap();
+        assert_ne!(face.glyph_index('\u{0431}'), original.glyph_index('\u{0431}'));
+        for character in ['a', '\u{0430}', '\u{0432}', '1'].iter() {
+            assert_eq!(face.glyph_index(*character), original.glyph_index(*character));
+        }
+
+        assert!(apply_language(DEJAVU_SANS, "").is_none());
+        assert!(apply_language(DEJAVU_SANS, "en").is_none());
+    }
+
+    #[test]
+    fn numeral_styles_the_font_does_not_provide_are_ignored() {
+        // DejaVu Sans has lining, proportional digits only, without features to select them.
+        assert!(apply_numeral_style(DEJAVU_SANS, NumeralStyle::oldstyle).is_none());
+        assert!(apply_numeral_style(DEJAVU_SANS, NumeralStyle::tabular).is_none());
+        assert!(apply_numeral_style(DEJAVU_SANS, NumeralStyle::normal).is_none());
+    }
+}
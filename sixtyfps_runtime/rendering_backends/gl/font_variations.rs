@@ -0,0 +1,303 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+/*!
+Support for the weight axis (`wght`) of variable fonts.
+
+femtovg only draws the glyphs of a variable font at the default position of its axes. For any other
+weight, a static instance of the font is made here: the outlines and advances of all glyphs are
+computed at the requested weight, and written into a new TrueType font with a `glyf` table, which is
+then added to the canvas like any other font.
+
+The instance is an approximation of the variable font in a few ways:
+ * Cubic curves (of `CFF2` fonts) are approximated by quadratic ones.
+ * Hinting instructions are dropped.
+ * Positioning from the `GPOS` table, such as kerning, stays at the default weight.
+ * Without a `HVAR` table, the advances stay at the default weight.
+
+When no instance can be made, because the font isn't variable, has no weight axis, or has too many
+glyphs to instance them all, the font is used as is. This is the static face that matched the
+requested weight best.
+*/
+
+use super::font_features::checksum;
+use std::convert::TryInto;
+
+/// Fonts with more glyphs, such as CJK fonts, take too long to instance when loading them.
+const MAX_INSTANCE_GLYPHS: u16 = 8192;
+
+/// Tables that are replaced, or don't apply to the instance.
+const DROPPED_TABLES: &[&[u8; 4]] = &[
+    b"CFF ", b"CFF2", b"VORG", b"glyf", b"loca", b"hmtx", b"gvar", b"cvar", b"fvar", b"avar",
+    b"HVAR", b"VVAR", b"MVAR", b"STAT", b"hdmx", b"LTSH", b"VDMX", b"fpgm", b"prep", b"cvt ",
+];
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+/// Returns a static instance of a variable font at the weight, or None if the font should be used
+/// as is.
+pub(crate) fn instance_for_weight(data: &[u8], weight: i32) -> Option<Vec<u8>> {
+    let wght = ttf_parser::Tag::from_bytes(b"wght");
+    let mut face = ttf_parser::Face::from_slice(data, 0).ok()?;
+    let axis = face.variation_axes().find(|axis| axis.tag == wght)?;
+    let weight = (weight as f32).max(axis.min_value).min(axis.max_value);
+    if weight == axis.def_value || face.number_of_glyphs() > MAX_INSTANCE_GLYPHS {
+        return None;
+    }
+    face.set_variation(wght, weight)?;
+
+    let mut glyf = Vec::new();
+    let mut loca = Vec::new();
+    let mut hmtx = Vec::new();
+    let (mut max_points, mut max_contours, mut max_advance) = (0, 0, 0);
+    for glyph_id in 0..face.number_of_glyphs() {
+        let glyph_id = ttf_parser::GlyphId(glyph_id);
+        let mut outline = Outline::default();
+        face.outline_glyph(glyph_id, &mut outline);
+        loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+        let left_side_bearing = outline.write_glyph(&mut glyf);
+        let advance = face.glyph_hor_advance(glyph_id).unwrap_or_default();
+        hmtx.extend_from_slice(&advance.to_be_bytes());
+        hmtx.extend_from_slice(&left_side_bearing.to_be_bytes());
+
+        max_points = max_points.max(outline.points.len() as u16);
+        max_contours = max_contours.max(outline.contour_ends.len() as u16);
+        max_advance = max_advance.max(advance);
+    }
+    loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+
+    let glyph_count = face.number_of_glyphs();
+    let mut tables = Vec::new();
+    for (tag, table) in font_tables(data)? {
+        let mut table = table.to_vec();
+        match &tag {
+            tag if DROPPED_TABLES.contains(&tag) => continue,
+            b"head" if table.len() >= 54 => {
+                // checkSumAdjustment, which is not verified
+                table[8..12].copy_from_slice(&0u32.to_be_bytes());
+                write_u16(&mut table, 50, 1); // indexToLocFormat: 32-bit offsets
+            }
+            b"hhea" if table.len() >= 36 => {
+                write_u16(&mut table, 10, max_advance);
+                write_u16(&mut table, 34, glyph_count);
+            }
+            b"OS/2" if table.len() >= 6 => write_u16(&mut table, 4, weight.round() as u16),
+            b"maxp" => {
+                // Version 1.0, as required with a `glyf` table. Everything after the maximum number of
+                // contours is about hinting and composite glyphs, which the instance has none of.
+                table = vec![0; 32];
+                table[0..4].copy_from_slice(&0x00010000u32.to_be_bytes());
+                write_u16(&mut table, 4, glyph_count);
+                write_u16(&mut table, 6, max_points);
+                write_u16(&mut table, 8, max_contours);
+                write_u16(&mut table, 14, 1); // maxZones
+            }
+            _ => {}
+        }
+        tables.push((tag, table));
+    }
+    tables.push((*b"glyf", glyf));
+    tables.push((*b"loca", loca));
+    tables.push((*b"hmtx", hmtx));
+    tables.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Some(font_data(&tables))
+}
+
+/// Returns the tags and the data of the tables of the font.
+fn font_tables(data: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    // Collections (`ttcf`) have a header per font, which is not handled here.
+    if data.get(0..4)? == b"ttcf" {
+        return None;
+    }
+    (0..read_u16(data, 4)? as usize)
+        .map(|index| {
+            let record = 12 + index * 16;
+            let tag = data.get(record..record + 4)?.try_into().ok()?;
+            let offset = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            Some((tag, data.get(offset..offset + length)?))
+        })
+        .collect()
+}
+
+/// Writes a TrueType font with the tables, which need to be sorted by their tag.
+fn font_data(tables: &[([u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let table_count = tables.len() as u16;
+    let entry_selector = 15 - table_count.leading_zeros() as u16;
+    let search_range: u16 = (1 << entry_selector) * 16;
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&0x00010000u32.to_be_bytes());
+    font.extend_from_slice(&table_count.to_be_bytes());
+    font.extend_from_slice(&search_range.to_be_bytes());
+    font.extend_from_slice(&entry_selector.to_be_bytes());
+    font.extend_from_slice(&(table_count * 16 - search_range).to_be_bytes());
+
+    let mut offset = font.len() + tables.len() * 16;
+    for (tag, table) in tables {
+        font.extend_from_slice(tag);
+        font.extend_from_slice(&checksum(table).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        offset += (table.len() + 3) & !3;
+    }
+    for (_, table) in tables {
+        font.extend_from_slice(table);
+        font.resize((font.len() + 3) & !3, 0);
+    }
+    font
+}
+
+/// The outline of a glyph as TrueType contours: on-curve points, and the off-curve control points of
+/// quadratic curves.
+#[derive(Default)]
+struct Outline {
+    points: Vec<(i16, i16, bool)>,
+    contour_ends: Vec<u16>,
+    current: (f32, f32),
+}
+
+impl Outline {
+    fn push(&mut self, x: f32, y: f32, on_curve: bool) {
+        self.points.push((x.round() as i16, y.round() as i16, on_curve));
+    }
+
+    /// Appends the glyph to the `glyf` table as a simple glyph, and returns its left side bearing.
+    fn write_glyph(&self, glyf: &mut Vec<u8>) -> i16 {
+        if self.points.is_empty() {
+            return 0;
+        }
+        let x_min = self.points.iter().map(|p| p.0).min().unwrap();
+        let y_min = self.points.iter().map(|p| p.1).min().unwrap();
+        let x_max = self.points.iter().map(|p| p.0).max().unwrap();
+        let y_max = self.points.iter().map(|p| p.1).max().unwrap();
+        for value in &[self.contour_ends.len() as i16, x_min, y_min, x_max, y_max] {
+            glyf.extend_from_slice(&value.to_be_bytes());
+        }
+        for end in &self.contour_ends {
+            glyf.extend_from_slice(&end.to_be_bytes());
+        }
+        glyf.extend_from_slice(&0u16.to_be_bytes()); // No instructions
+                                                     // Without the short vector flags, all coordinates are stored as 16-bit deltas.
+        glyf.extend(self.points.iter().map(|(_, _, on_curve)| *on_curve as u8));
+        let mut previous = 0i16;
+        for (x, _, _) in &self.points {
+            glyf.extend_from_slice(&x.wrapping_sub(previous).to_be_bytes());
+            previous = *x;
+        }
+        let mut previous = 0i16;
+        for (_, y, _) in &self.points {
+            glyf.extend_from_slice(&y.wrapping_sub(previous).to_be_bytes());
+            previous = *y;
+        }
+        glyf.resize((glyf.len() + 3) & !3, 0);
+        x_min
+    }
+}
+
+impl ttf_parser::OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.push(x, y, true);
+        self.current = (x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.push(x, y, true);
+        self.current = (x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.push(x1, y1, false);
+        self.push(x, y, true);
+        self.current = (x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        // Split the cubic curve in four, and approximate each part with a quadratic curve through
+        // its end points, with the control point where the tangents at the end points meet.
+        const PARTS: usize = 4;
+        let (x0, y0) = self.current;
+        let point = |t: f32| {
+            let s = 1. - t;
+            let at = |a: f32, b: f32, c: f32, d: f32| {
+                s * s * s * a + 3. * s * s * t * b + 3. * s * t * t * c + t * t * t * d
+            };
+            (at(x0, x1, x2, x), at(y0, y1, y2, y))
+        };
+        let derivative = |t: f32| {
+            let s = 1. - t;
+            let at = |a: f32, b: f32, c: f32, d: f32| {
+                3. * s * s * (b - a) + 6. * s * t * (c - b) + 3. * t * t * (d - c)
+            };
+            (at(x0, x1, x2, x), at(y0, y1, y2, y))
+        };
+        for part in 0..PARTS {
+            let (t0, t1) = (part as f32 / PARTS as f32, (part + 1) as f32 / PARTS as f32);
+            let (start, end) = (point(t0), point(t1));
+            let (d0, d1) = (derivative(t0), derivative(t1));
+            // The tangents of the part are the derivatives scaled to its length in t.
+            let h = (t1 - t0) / 2.;
+            let control = (
+                (start.0 + d0.0 * h + end.0 - d1.0 * h) / 2.,
+                (start.1 + d0.1 * h + end.1 - d1.1 * h) / 2.,
+            );
+            self.push(control.0, control.1, false);
+            self.push(end.0, end.1, true);
+        }
+        self.current = (x, y);
+    }
+
+    fn close(&mut self) {
+        let start = self.contour_ends.last().map_or(0, |end| *end as usize + 1);
+        // The contour closes by itself, a final point on the first one is redundant.
+        if self.points.len() > start + 1 && self.points.last() == self.points.get(start) {
+            self.points.pop();
+        }
+        if self.points.len() > start {
+            self.contour_ends.push(self.points.len() as u16 - 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CANTARELL_VF: &[u8] = include_bytes!("test-data/fonts/Cantarell-VF.otf");
+
+    #[test]
+    fn instances_of_variable_fonts() {
+        let default = ttf_parser::Face::from_slice(CANTARELL_VF, 0).unwrap();
+        let axis = default.variation_axes().next().unwrap();
+        assert_eq!(instance_for_weight(CANTARELL_VF, axis.def_value as i32), None);
+        assert_eq!(instance_for_weight(include_bytes!("fonts/DejaVuSans.ttf"), 700), None);
+
+        let bold = instance_for_weight(CANTARELL_VF, 700).unwrap();
+        let bold = ttf_parser::Face::from_slice(&bold, 0).unwrap();
+        assert!(!bold.is_variable());
+        assert_eq!(bold.number_of_glyphs(), default.number_of_glyphs());
+        assert_eq!(bold.weight().to_number(), 700);
+
+        let glyph = default.glyph_index('m').unwrap();
+        assert_eq!(bold.glyph_index('m'), Some(glyph));
+        assert!(bold.glyph_hor_advance(glyph) > default.glyph_hor_advance(glyph));
+        let bounds = bold.glyph_bounding_box(glyph).unwrap();
+        assert!(bounds.width() > default.glyph_bounding_box(glyph).unwrap().width());
+    }
+}
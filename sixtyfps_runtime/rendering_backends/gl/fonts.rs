@@ -7,15 +7,71 @@
     This file is also available under commercial licensing terms.
     Please contact info@sixtyfps.io for more information.
 LICENSE END */
-use super::CanvasRc;
+use super::bitmap_strikes::BitmapStrikes;
+use super::color_glyphs::ColorGlyphs;
+use super::font_features::{apply_language, apply_numeral_style, without_characters};
+use super::font_variations::instance_for_weight;
 use sixtyfps_corelib::graphics::FontRequest;
+use sixtyfps_corelib::items::FontStyle;
 #[cfg(target_arch = "wasm32")]
 use std::cell::Cell;
 use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
 
+/// The fonts added by the application.
+pub(crate) struct FontRegistry {
+    database: Mutex<fontdb::Database>,
+    /// Incremented each time a font is registered, to invalidate what depends on the application fonts.
+    generation: AtomicUsize,
+}
+
+impl FontRegistry {
+    fn new() -> Self {
+        Self { database: Mutex::new(fontdb::Database::new()), generation: AtomicUsize::new(0) }
+    }
+}
+
+/// Registry used to keep track of fonts added by the application. It is shared by all threads and
+/// independent of any canvas, so that fonts can be registered before the backend is started, and
+/// every renderer loads them from it when they are first used.
+static APPLICATION_FONTS: once_cell::sync::Lazy<FontRegistry> =
+    once_cell::sync::Lazy::new(FontRegistry::new);
+
+#[cfg(test)]
 thread_local! {
-    /// Database used to keep track of fonts added by the application
-    static APPLICATION_FONTS: RefCell<fontdb::Database> = RefCell::new(fontdb::Database::new())
+    /// The registry that replaces the shared one on this thread, see [`isolate_application_fonts`].
+    static THREAD_APPLICATION_FONTS: std::cell::Cell<Option<&'static FontRegistry>> = Default::default()
+}
+
+/// Makes the current thread use a new registry without any application font instead of the shared one, so that
+/// a test does not see the fonts that the tests running at the same time register. The registry is returned to
+/// pass it to [`use_application_fonts`] on the threads that the test spawns.
+#[cfg(test)]
+pub(crate) fn isolate_application_fonts() -> &'static FontRegistry {
+    let registry = Box::leak(Box::new(FontRegistry::new()));
+    use_application_fonts(registry);
+    registry
+}
+
+/// Makes the current thread use the given registry instead of the shared one.
+#[cfg(test)]
+pub(crate) fn use_application_fonts(registry: &'static FontRegistry) {
+    THREAD_APPLICATION_FONTS.with(|fonts| fonts.set(Some(registry)));
+}
+
+fn application_font_registry() -> &'static FontRegistry {
+    #[cfg(test)]
+    if let Some(registry) = THREAD_APPLICATION_FONTS.with(|fonts| fonts.get()) {
+        return registry;
+    }
+    &APPLICATION_FONTS
+}
+
+fn application_fonts() -> MutexGuard<'static, fontdb::Database> {
+    // The database stays consistent even if a thread panicked while holding the lock.
+    application_font_registry().database.lock().unwrap_or_else(|err| err.into_inner())
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -26,17 +82,133 @@
 /// This function can be used to register a custom TrueType font with SixtyFPS,
 /// for use with the `font-family` property. The provided slice must be a valid TrueType
 /// font.
+///
+/// Variable fonts with a weight axis are drawn at the weight requested with the `font-weight`
+/// property, within the range of the axis. Fonts that can't be instanced that way, such as fonts
+/// with a very large number of glyphs, are drawn at their default weight, like the static font
+/// that is closest to the requested weight.
+///
+/// Registering an emoji font, such as Noto Color Emoji, makes all text draw its emoji with it, even
+/// when the system comes with another emoji font.
+///
+/// Fonts can be registered from any thread, and before any window is created: no OpenGL context is
+/// needed for it.
 pub fn register_application_font_from_memory(
     data: &'static [u8],
 ) -> Result<(), Box<dyn std::error::Error>> {
-    APPLICATION_FONTS.with(|fontdb| fontdb.borrow_mut().load_font_data(data.into()));
+    application_fonts().load_font_data(data.into());
+    application_font_registry().generation.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Same as [`register_application_font_from_memory`], but with the font read from the file at the
+/// given path. Returns an error if the file cannot be read, or does not contain a TrueType or
+/// OpenType font.
+pub fn register_application_font_from_path(
+    path: &std::path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data = std::fs::read(path)?;
+    ttf_parser::Face::from_slice(&data, 0)?;
+    application_fonts().load_font_data(data);
+    application_font_registry().generation.fetch_add(1, Ordering::SeqCst);
     Ok(())
 }
 
+/// Returns the names of the font families that can be used with the `font-family` property: the
+/// families of the fonts registered by the application and, outside of the web, the ones installed
+/// on the system. The list is sorted and contains each family once.
+pub fn available_font_families() -> Vec<String> {
+    let mut families = std::collections::BTreeSet::new();
+    families.extend(application_fonts().faces().iter().map(|face| face.family.clone()));
+    #[cfg(not(target_arch = "wasm32"))]
+    families.extend(
+        font_kit::source::Source::all_families(&font_kit::source::SystemSource::new())
+            .unwrap_or_default(),
+    );
+    families.into_iter().collect()
+}
+
+/// Returns a number that changes each time a font is registered with the application.
+pub(crate) fn application_fonts_generation() -> usize {
+    application_font_registry().generation.load(Ordering::SeqCst)
+}
+
+/// A font that was added to the canvas.
+#[derive(Clone)]
+pub(crate) struct LoadedFont {
+    pub id: femtovg::FontId,
+    /// The glyph bitmaps embedded in the font, if it has any.
+    pub bitmap_strikes: Option<Rc<BitmapStrikes>>,
+    /// The color glyph images embedded in the font, such as the ones of emoji fonts.
+    pub color_glyphs: Option<Rc<ColorGlyphs>>,
+    /// True if the face is an italic or oblique one, false if it is upright.
+    pub italic: bool,
+    /// The family name of the face, or an empty string if it has none.
+    pub family: String,
+    /// The weight of the face, or the weight it was instanced at for variable fonts.
+    pub weight: i32,
+}
+
+fn add_font_data(
+    canvas: &RefCell<femtovg::Canvas<impl femtovg::Renderer>>,
+    data: &[u8],
+    request: &FontRequest,
+) -> LoadedFont {
+    let instance = instance_for_weight(data, request.weight.unwrap());
+    let data = instance.as_deref().unwrap_or(data);
+    let with_numeral_style = apply_numeral_style(data, request.numeral_style);
+    let data = with_numeral_style.as_deref().unwrap_or(data);
+    let with_language = apply_language(data, &request.language);
+    let data = with_language.as_deref().unwrap_or(data);
+    // femtovg only falls back to the emoji font for the characters that the font lacks.
+    let has_emoji_fallback = emoji_font_family().map_or(false, |family| request.family != family);
+    let without_emoji =
+        if has_emoji_fallback { without_characters(data, EMOJI_CHARACTERS) } else { None };
+    let data = without_emoji.as_deref().unwrap_or(data);
+    LoadedFont {
+        id: canvas.borrow_mut().add_font_mem(data).unwrap(),
+        bitmap_strikes: BitmapStrikes::from_font_data(data).map(Rc::new),
+        color_glyphs: ColorGlyphs::from_font_data(data).map(Rc::new),
+        italic: ttf_parser::Face::from_slice(data, 0)
+            .map_or(false, |face| face.is_italic() || face.is_oblique()),
+        family: ttf_parser::Face::from_slice(data, 0)
+            .ok()
+            .and_then(|face| family_name(&face))
+            .unwrap_or_default(),
+        weight: if instance.is_some() {
+            request.weight.unwrap()
+        } else {
+            ttf_parser::Face::from_slice(data, 0)
+                .map_or(super::DEFAULT_FONT_WEIGHT, |face| face.weight().to_number() as i32)
+        },
+    }
+}
+
+fn family_name(face: &ttf_parser::Face) -> Option<String> {
+    // Faces of families with more than the four basic styles have the name of the whole family as
+    // typographic family name.
+    [ttf_parser::name_id::TYPOGRAPHIC_FAMILY, ttf_parser::name_id::FAMILY].iter().find_map(|id| {
+        face.names().find(|name| name.name_id() == *id && name.is_unicode())?.to_string()
+    })
+}
+
+/// The generic family names that the font system of the platform resolves to a font of its choice.
+const GENERIC_FONT_FAMILIES: &[&str] =
+    &["serif", "sans-serif", "sans", "monospace", "cursive", "fantasy", "system-ui"];
+
+/// Returns true if the font loaded for the request is not of the requested family, because no font
+/// of the family is installed.
+pub(crate) fn is_substitute(request: &FontRequest, font: &LoadedFont) -> bool {
+    let requested = request.family.as_str();
+    !requested.is_empty()
+        && !GENERIC_FONT_FAMILIES.iter().any(|generic| generic.eq_ignore_ascii_case(requested))
+        && !font.family.eq_ignore_ascii_case(requested)
+}
+
 pub(crate) fn try_load_app_font(
-    canvas: &CanvasRc,
+    canvas: &RefCell<femtovg::Canvas<impl femtovg::Renderer>>,
     request: &FontRequest,
-) -> Option<femtovg::FontId> {
+) -> Option<LoadedFont> {
     let family = if request.family.is_empty() {
         fontdb::Family::SansSerif
     } else {
@@ -46,21 +218,30 @@ pub(crate) fn try_load_app_font(
     let query = fontdb::Query {
         families: &[family],
         weight: fontdb::Weight(request.weight.unwrap() as u16),
+        style: match request.style {
+            FontStyle::normal => fontdb::Style::Normal,
+            FontStyle::italic => fontdb::Style::Italic,
+            FontStyle::oblique => fontdb::Style::Oblique,
+        },
         ..Default::default()
     };
-    APPLICATION_FONTS.with(|font_db| {
-        let font_db = font_db.borrow();
-        font_db.query(&query).and_then(|id| {
-            font_db.with_face_data(id, |data, _index| {
-                // pass index to femtovg once femtovg/femtovg/pull/21 is merged
-                canvas.borrow_mut().add_font_mem(&data).unwrap()
-            })
-        })
-    })
+    // The source of the face is shared, so that the database is not locked while the font is added to the canvas.
+    let (source, _index) = {
+        let font_db = application_fonts();
+        font_db.face_source(font_db.query(&query)?)?
+    };
+    // pass index to femtovg once femtovg/femtovg/pull/21 is merged
+    match &*source {
+        // The registered fonts are all loaded into memory.
+        fontdb::Source::Binary(data) => Some(add_font_data(canvas, data, request)),
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-pub(crate) fn load_system_font(canvas: &CanvasRc, request: &FontRequest) -> femtovg::FontId {
+pub(crate) fn load_system_font(
+    canvas: &RefCell<femtovg::Canvas<impl femtovg::Renderer>>,
+    request: &FontRequest,
+) -> LoadedFont {
     let family_name = if request.family.len() == 0 {
         font_kit::family_name::FamilyName::SansSerif
     } else {
@@ -71,24 +252,31 @@ pub(crate) fn load_system_font(canvas: &CanvasRc, request: &FontRequest) -> femt
         .select_best_match(
             &[family_name, font_kit::family_name::FamilyName::SansSerif],
             &font_kit::properties::Properties::new()
-                .weight(font_kit::properties::Weight(request.weight.unwrap() as f32)),
+                .weight(font_kit::properties::Weight(request.weight.unwrap() as f32))
+                .style(match request.style {
+                    FontStyle::normal => font_kit::properties::Style::Normal,
+                    FontStyle::italic => font_kit::properties::Style::Italic,
+                    FontStyle::oblique => font_kit::properties::Style::Oblique,
+                }),
         )
         .unwrap();
 
     // pass index to femtovg once femtovg/femtovg/pull/21 is merged
     match handle {
         font_kit::handle::Handle::Path { path, font_index: _ } => {
-            canvas.borrow_mut().add_font(path)
+            add_font_data(canvas, &std::fs::read(path).unwrap(), request)
         }
         font_kit::handle::Handle::Memory { bytes, font_index: _ } => {
-            canvas.borrow_mut().add_font_mem(bytes.as_slice())
+            add_font_data(canvas, bytes.as_slice(), request)
         }
     }
-    .unwrap()
 }
 
 #[cfg(target_arch = "wasm32")]
-pub(crate) fn load_system_font(canvas: &CanvasRc, request: &FontRequest) -> femtovg::FontId {
+pub(crate) fn load_system_font(
+    canvas: &RefCell<femtovg::Canvas<impl femtovg::Renderer>>,
+    request: &FontRequest,
+) -> LoadedFont {
     WASM_FONT_REGISTERED.with(|registered| {
         if !registered.get() {
             registered.set(true);
@@ -100,35 +288,123 @@ pub(crate) fn load_system_font(canvas: &CanvasRc, request: &FontRequest) -> femt
     try_load_app_font(canvas, &fallback_request).unwrap()
 }
 
+/// The families of the fonts with emoji that come with the platform, or that applications may bring
+/// along, in order of preference.
 #[cfg(target_os = "macos")]
-pub(crate) fn font_fallbacks_for_request(_request: &FontRequest) -> Vec<FontRequest> {
-    core_text::font::new_from_name(&_request.family, _request.pixel_size.unwrap_or_default() as f64)
-        .ok()
-        .map(|requested_font| {
-            core_text::font::cascade_list_for_languages(
-                &requested_font,
-                &core_foundation::array::CFArray::from_CFTypes(&[]),
-            )
-            .iter()
-            .map(|fallback_descriptor| FontRequest {
-                family: fallback_descriptor.family_name().into(),
-                weight: _request.weight,
-                pixel_size: _request.pixel_size,
+const EMOJI_FONT_FAMILIES: &[&str] =
+    &["Apple Color Emoji", "Noto Color Emoji", "Twitter Color Emoji", "JoyPixels", "Noto Emoji"];
+#[cfg(target_os = "windows")]
+const EMOJI_FONT_FAMILIES: &[&str] =
+    &["Segoe UI Emoji", "Noto Color Emoji", "Twitter Color Emoji", "JoyPixels", "Noto Emoji"];
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const EMOJI_FONT_FAMILIES: &[&str] =
+    &["Noto Color Emoji", "Twitter Color Emoji", "JoyPixels", "Noto Emoji"];
+
+#[cfg(not(target_arch = "wasm32"))]
+thread_local! {
+    /// The first of the emoji font families that is installed on the system, looked up once.
+    static SYSTEM_EMOJI_FONT_FAMILY: once_cell::unsync::OnceCell<Option<&'static str>> = Default::default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn system_emoji_font_family() -> Option<&'static str> {
+    SYSTEM_EMOJI_FONT_FAMILY.with(|family| {
+        *family.get_or_init(|| {
+            let source = font_kit::source::SystemSource::new();
+            EMOJI_FONT_FAMILIES.iter().copied().find(|family| {
+                font_kit::source::Source::select_family_by_name(&source, family)
+                    .map_or(false, |handle| !handle.fonts().is_empty())
             })
-            .take(2) // Take only the top two from the fallback list until we have a more efficent on-demand font loading mechanism in femtovg
-            .collect::<Vec<_>>()
         })
-        .unwrap_or_default()
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn system_emoji_font_family() -> Option<&'static str> {
+    None
+}
+
+/// The characters drawn with the emoji font, rather than with the requested font, if it has an emoji
+/// font to fall back to: the blocks of characters shown as emoji by default.
+const EMOJI_CHARACTERS: &[std::ops::RangeInclusive<u32>] = &[
+    0x1F1E6..=0x1F1FF, // Regional indicators, for flags
+    0x1F300..=0x1F5FF, // Miscellaneous Symbols and Pictographs
+    0x1F600..=0x1F64F, // Emoticons
+    0x1F680..=0x1F6FF, // Transport and Map Symbols
+    0x1F900..=0x1F9FF, // Supplemental Symbols and Pictographs
+    0x1FA70..=0x1FAFF, // Symbols and Pictographs Extended-A
+];
+
+/// Returns the family of the font to fall back to for emoji, preferring the emoji fonts registered
+/// by the application over the ones of the system. None if there is no emoji font.
+fn emoji_font_family() -> Option<&'static str> {
+    let application_family = {
+        let font_db = application_fonts();
+        EMOJI_FONT_FAMILIES
+            .iter()
+            .copied()
+            .find(|family| font_db.faces().iter().any(|face| face.family == *family))
+    };
+    application_family.or_else(system_emoji_font_family)
+}
+
+fn emoji_fallback_request(request: &FontRequest) -> Option<FontRequest> {
+    let family = emoji_font_family()?;
+    Some(FontRequest {
+        family: family.into(),
+        weight: request.weight,
+        pixel_size: request.pixel_size,
+        style: FontStyle::normal,
+        numeral_style: Default::default(),
+        language: Default::default(),
+        tab_width: None,
+    })
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn font_fallbacks_for_request(_request: &FontRequest) -> Vec<FontRequest> {
+    let mut fallbacks = core_text::font::new_from_name(
+        &_request.family,
+        _request.pixel_size.unwrap_or_default() as f64,
+    )
+    .ok()
+    .map(|requested_font| {
+        core_text::font::cascade_list_for_languages(
+            &requested_font,
+            &core_foundation::array::CFArray::from_CFTypes(&[]),
+        )
+        .iter()
+        .map(|fallback_descriptor| FontRequest {
+            family: fallback_descriptor.family_name().into(),
+            weight: _request.weight,
+            pixel_size: _request.pixel_size,
+            style: _request.style,
+            numeral_style: _request.numeral_style,
+            language: _request.language.clone(),
+            tab_width: _request.tab_width,
+        })
+        .take(2) // Take only the top two from the fallback list until we have a more efficent on-demand font loading mechanism in femtovg
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+    fallbacks.extend(emoji_fallback_request(_request));
+    fallbacks
 }
 
 #[cfg(not(target_os = "macos"))]
 pub(crate) fn font_fallbacks_for_request(_request: &FontRequest) -> Vec<FontRequest> {
-    vec![
+    let mut fallbacks = vec![
         #[cfg(target_arch = "wasm32")]
         FontRequest {
             family: "DejaVu Sans".into(),
             weight: _request.weight,
             pixel_size: _request.pixel_size,
+            style: _request.style,
+            numeral_style: _request.numeral_style,
+            language: _request.language.clone(),
+            tab_width: _request.tab_width,
         },
-    ]
+    ];
+    fallbacks.extend(emoji_fallback_request(_request));
+    fallbacks
 }
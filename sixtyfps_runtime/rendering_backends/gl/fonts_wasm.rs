@@ -54,6 +54,15 @@ pub fn register_application_font_from_memory(
     load_font_from_memory(data)
 }
 
+pub fn register_application_font_from_path(
+    path: &std::path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data = std::fs::read(path)?;
+    maybe_init_fonts();
+    // Registered fonts are kept for the lifetime of the application.
+    load_font_from_memory(Box::leak(data.into_boxed_slice()))
+}
+
 fn maybe_init_fonts() {
     if FONTS.with(|fonts| fonts.borrow().is_empty()) {
         load_font_from_memory(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
@@ -109,6 +118,10 @@ pub(crate) fn font_fallbacks_for_request(_request: &FontRequest) -> Vec<FontRequ
             family: (*family_name).into(),
             weight: _request.weight,
             pixel_size: _request.pixel_size,
+            style: _request.style,
+            numeral_style: _request.numeral_style,
+            language: _request.language.clone(),
+            tab_width: _request.tab_width,
         })
         .collect()
 }
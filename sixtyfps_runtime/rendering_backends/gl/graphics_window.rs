@@ -15,20 +15,25 @@
 
 use const_field_offset::FieldOffsets;
 use corelib::component::ComponentRc;
+use corelib::frame_stats::{FrameStats, FrameStatsRecorder};
 use corelib::graphics::*;
 use corelib::input::{KeyboardModifiers, MouseEvent, MouseEventType};
+use corelib::item_rendering::ItemRenderer;
 use corelib::items::ItemRef;
 use corelib::properties::PropertyTracker;
+use corelib::render_commands::RenderCommandList;
 use corelib::slice::Slice;
 use corelib::window::{ComponentWindow, PlatformWindow};
-use corelib::Property;
+use corelib::{Property, SharedString};
 use sixtyfps_corelib as corelib;
 
 /// FIXME! this is some remains from a time where the GLRenderer was called the backend
 type Backend = super::GLRenderer;
 
-type WindowFactoryFn =
-    dyn Fn(&dyn crate::eventloop::EventLoopInterface, winit::window::WindowBuilder) -> Backend;
+type WindowFactoryFn = dyn Fn(
+    &dyn crate::eventloop::EventLoopInterface,
+    winit::window::WindowBuilder,
+) -> Result<Backend, super::ContextCreationError>;
 
 /// GraphicsWindow is an implementation of the [PlatformWindow][`crate::eventloop::PlatformWindow`] trait. This is
 /// typically instantiated by entry factory functions of the different graphics backends.
@@ -36,6 +41,8 @@ pub struct GraphicsWindow {
     pub(crate) self_weak: once_cell::unsync::OnceCell<Weak<corelib::window::Window>>,
     window_factory: Box<WindowFactoryFn>,
     map_state: RefCell<GraphicsWindowBackendState>,
+    /// The hidden window and its renderer, created ahead of mapping the window by warm_up().
+    warmed_up_backend: RefCell<Option<Backend>>,
     properties: Pin<Box<WindowProperties>>,
     keyboard_modifiers: std::cell::Cell<KeyboardModifiers>,
     /// Gets dirty when the layout restrictions, or some other property of the windows change
@@ -45,6 +52,15 @@ pub struct GraphicsWindow {
     /// Current popup's component and position
     /// FIXME: the popup should actually be another window, not just some overlay
     active_popup: std::cell::RefCell<Option<(ComponentRc, Point)>>,
+    /// The regions requested to be drawn again with request_redraw_region() since the last frame.
+    damaged_regions: RefCell<Vec<Rect>>,
+    /// Set when the whole window needs to be drawn again, which is assumed when the reason for redrawing is
+    /// not known.
+    full_redraw_requested: Cell<bool>,
+    /// Set when the SIXTYFPS_FRAME_STATS environment variable is set, to count the draw calls and time the
+    /// rendering of each frame.
+    collect_frame_stats: bool,
+    last_frame_stats: Cell<Option<FrameStats>>,
 }
 
 impl GraphicsWindow {
@@ -55,18 +71,26 @@ impl GraphicsWindow {
     ///   of the window changes to mapped. The event loop and window builder parameters can be used to create a
     ///   backing window.
     pub(crate) fn new(
-        graphics_backend_factory: impl Fn(&dyn crate::eventloop::EventLoopInterface, winit::window::WindowBuilder) -> Backend
+        graphics_backend_factory: impl Fn(
+                &dyn crate::eventloop::EventLoopInterface,
+                winit::window::WindowBuilder,
+            ) -> Result<Backend, super::ContextCreationError>
             + 'static,
     ) -> Rc<Self> {
         Rc::new(Self {
             self_weak: Default::default(),
             window_factory: Box::new(graphics_backend_factory),
             map_state: RefCell::new(GraphicsWindowBackendState::Unmapped),
+            warmed_up_backend: Default::default(),
             properties: Box::pin(WindowProperties::default()),
             keyboard_modifiers: Default::default(),
             meta_property_listener: Rc::pin(Default::default()),
             mouse_input_state: Default::default(),
             active_popup: Default::default(),
+            damaged_regions: Default::default(),
+            full_redraw_requested: Cell::new(true),
+            collect_frame_stats: std::env::var("SIXTYFPS_FRAME_STATS").is_ok(),
+            last_frame_stats: Default::default(),
         })
     }
 
@@ -128,18 +152,21 @@ fn map_window(self: Rc<Self>) {
         let component = ComponentRc::borrow_pin(&component);
         let root_item = component.as_ref().get_item_ref(0);
 
-        let window_title =
-            if let Some(window_item) = ItemRef::downcast_pin::<corelib::items::Window>(root_item) {
-                window_item.title().to_string()
-            } else {
-                "SixtyFPS Window".to_string()
-            };
-        let window_builder = winit::window::WindowBuilder::new().with_title(window_title);
-
         let id = {
-            let backend = crate::eventloop::with_window_target(|event_loop| {
-                self.window_factory.as_ref()(event_loop, window_builder)
-            });
+            let backend = match self.warmed_up_backend.borrow_mut().take() {
+                Some(backend) => {
+                    backend.window().set_visible(true);
+                    backend
+                }
+                None => match self.create_backend(self.window_builder()) {
+                    Ok(backend) => backend,
+                    Err(err) => {
+                        // The window stays unmapped, as there is nothing it could be drawn with.
+                        eprintln!("Error showing the window: {}", err);
+                        return;
+                    }
+                },
+            };
 
             // Ideally we should be passing the initial requested size to the window builder, but those properties
             // may be specified in logical pixels, relative to the scale factory, which we only know *after* mapping
@@ -214,6 +241,29 @@ fn map_window(self: Rc<Self>) {
 
         crate::eventloop::register_window(id, self.clone());
     }
+    fn window_builder(&self) -> winit::window::WindowBuilder {
+        let component = self.component();
+        let component = ComponentRc::borrow_pin(&component);
+        let root_item = component.as_ref().get_item_ref(0);
+
+        let (window_title, transparent) =
+            if let Some(window_item) = ItemRef::downcast_pin::<corelib::items::Window>(root_item) {
+                (window_item.title().to_string(), window_item.transparent_background())
+            } else {
+                ("SixtyFPS Window".to_string(), false)
+            };
+        winit::window::WindowBuilder::new().with_title(window_title).with_transparent(transparent)
+    }
+
+    fn create_backend(
+        &self,
+        window_builder: winit::window::WindowBuilder,
+    ) -> Result<Backend, super::ContextCreationError> {
+        crate::eventloop::with_window_target(|event_loop| {
+            self.window_factory.as_ref()(event_loop, window_builder)
+        })
+    }
+
     /// Removes the window from the screen. The window is not destroyed though, it can be show (mapped) again later
     /// by calling [`PlatformWindow::map_window`].
     fn unmap_window(self: Rc<Self>) {
@@ -243,9 +293,44 @@ fn drop(&mut self) {
 impl GraphicsWindow {
     /// Draw the items of the specified `component` in the given window.
     pub fn draw(self: Rc<Self>) {
+        self.render(None, None);
+    }
+
+    /// Drops the images whose files changed on disk, so that they are loaded again when the window is redrawn.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn reload_changed_image_files(&self) {
+        if let GraphicsWindowBackendState::Mapped(window) = &*self.map_state.borrow() {
+            window.backend.borrow().reload_changed_image_files();
+        }
+    }
+
+    // Renders the items, or the recorded commands if given instead, and presents them in the window. If
+    // read_back is set, the rendered pixels are returned in the requested alpha convention.
+    fn render(
+        &self,
+        read_back: Option<AlphaMode>,
+        replay: Option<&RenderCommandList>,
+    ) -> Option<image::RgbaImage> {
         let component_rc = self.component();
         let component = ComponentRc::borrow_pin(&component_rc);
 
+        // The handler for scale factor changes runs first, so that the properties it changes apply to this frame.
+        let scale_factor_changed = self
+            .self_weak
+            .get()
+            .unwrap()
+            .upgrade()
+            .unwrap()
+            .update_rendered_scale_factor(self.scale_factor());
+
+        // Only the damaged regions are drawn if nothing else asked for a redraw since the last frame.
+        let damaged_regions = self.damaged_regions.take();
+        let full_redraw = self.full_redraw_requested.replace(false)
+            || damaged_regions.is_empty()
+            || self.meta_property_listener.as_ref().is_dirty()
+            || read_back.is_some()
+            || replay.is_some();
+
         {
             if self.meta_property_listener.as_ref().is_dirty() {
                 self.meta_property_listener.as_ref().evaluate(|| {
@@ -290,6 +375,9 @@ pub fn draw(self: Rc<Self>) {
 
         let map_state = self.map_state.borrow();
         let window = map_state.as_mapped();
+        if scale_factor_changed {
+            window.backend.borrow().release_scale_factor_dependent_caches();
+        }
         let root_item = component.as_ref().get_item_ref(0);
         let background_color =
             if let Some(window_item) = ItemRef::downcast_pin::<corelib::items::Window>(root_item) {
@@ -298,23 +386,57 @@ pub fn draw(self: Rc<Self>) {
                 RgbaColor { red: 255 as u8, green: 255, blue: 255, alpha: 255 }.into()
             };
 
-        let mut renderer =
-            window.backend.borrow_mut().new_renderer(&background_color, self.scale_factor());
-        corelib::item_rendering::render_component_items(
-            &component_rc,
-            &mut renderer,
-            Point::default(),
+        let frame_started =
+            if self.collect_frame_stats { Some(instant::Instant::now()) } else { None };
+        let mut renderer = window.backend.borrow_mut().new_renderer(
+            &background_color,
+            self.scale_factor(),
+            if full_redraw { None } else { Some(&damaged_regions) },
         );
-        if let Some(popup) = &*self.active_popup.borrow() {
-            corelib::item_rendering::render_component_items(&popup.0, &mut renderer, popup.1);
+        let render_items = |renderer: &mut dyn ItemRenderer| {
+            if let Some(commands) = replay {
+                commands.replay(renderer);
+            } else {
+                corelib::item_rendering::render_component_items(
+                    &component_rc,
+                    renderer,
+                    Point::default(),
+                );
+                if let Some(popup) = &*self.active_popup.borrow() {
+                    corelib::item_rendering::render_component_items(&popup.0, renderer, popup.1);
+                }
+            }
+        };
+        let mut stats = FrameStats::default();
+        if frame_started.is_some() {
+            let mut recorder = FrameStatsRecorder::new(&mut renderer);
+            render_items(&mut recorder);
+            stats = recorder.stats();
+        } else {
+            render_items(&mut renderer);
         }
-        window.backend.borrow_mut().flush_renderer(renderer);
+        let flush_started = frame_started.map(|_| instant::Instant::now());
+        let pixels = window.backend.borrow_mut().flush_renderer(renderer, read_back);
+        if let (Some(frame_started), Some(flush_started)) = (frame_started, flush_started) {
+            stats.render_duration = flush_started - frame_started;
+            stats.flush_duration = flush_started.elapsed();
+            self.last_frame_stats.set(Some(stats));
+        }
+        if let Some(commands) = replay {
+            commands.release_graphics_resources(
+                &mut window.backend.borrow().shared_data.item_graphics_cache.borrow_mut(),
+            );
+        }
+        pixels
     }
 
     /// FIXME: this is the same as Window::process_mouse_input, but this handle the popup.
     /// Ideally the popup should be handled as a different window or by theevent loop, and
     /// this function can go away
     pub fn process_mouse_input(self: Rc<Self>, mut pos: Point, what: MouseEventType) {
+        if let GraphicsWindowBackendState::Mapped(window) = &*self.map_state.borrow() {
+            pos = window.backend.borrow().map_to_content(pos);
+        }
         let active_popup = (*self.active_popup.borrow()).clone();
         let component = if let Some(popup) = &active_popup {
             pos -= popup.1.to_vector();
@@ -379,6 +501,7 @@ pub fn set_geometry(&self, width: f32, height: f32) {
 
 impl PlatformWindow for GraphicsWindow {
     fn request_redraw(&self) {
+        self.full_redraw_requested.set(true);
         match &*self.map_state.borrow() {
             GraphicsWindowBackendState::Unmapped => {}
             GraphicsWindowBackendState::Mapped(window) => {
@@ -387,6 +510,18 @@ fn request_redraw(&self) {
         }
     }
 
+    fn request_redraw_region(&self, region: Rect) {
+        match &*self.map_state.borrow() {
+            GraphicsWindowBackendState::Unmapped => {}
+            GraphicsWindowBackendState::Mapped(window) => {
+                self.damaged_regions.borrow_mut().push(region);
+                let backend = window.backend.borrow();
+                backend.enable_partial_redraw();
+                backend.window().request_redraw();
+            }
+        }
+    }
+
     fn scale_factor(&self) -> f32 {
         WindowProperties::FIELD_OFFSETS.scale_factor.apply_pin(self.properties.as_ref()).get()
     }
@@ -460,6 +595,60 @@ fn image_size(
             }
         }
     }
+    fn read_pixels(&self, alpha_mode: AlphaMode) -> Option<image::RgbaImage> {
+        if matches!(&*self.map_state.borrow(), GraphicsWindowBackendState::Unmapped) {
+            return None;
+        }
+        self.render(Some(alpha_mode), None)
+    }
+
+    fn replay_frame(
+        &self,
+        commands: &RenderCommandList,
+        alpha_mode: AlphaMode,
+    ) -> Option<image::RgbaImage> {
+        if matches!(&*self.map_state.borrow(), GraphicsWindowBackendState::Unmapped) {
+            return None;
+        }
+        self.render(Some(alpha_mode), Some(commands))
+    }
+
+    fn warm_up(self: Rc<Self>, resources: &[Resource]) {
+        if let GraphicsWindowBackendState::Mapped(window) = &*self.map_state.borrow() {
+            window.backend.borrow().warm_up(resources, self.scale_factor());
+            return;
+        }
+        let mut warmed_up_backend = self.warmed_up_backend.borrow_mut();
+        if warmed_up_backend.is_none() {
+            match self.create_backend(self.window_builder().with_visible(false)) {
+                Ok(backend) => *warmed_up_backend = Some(backend),
+                Err(err) => {
+                    eprintln!("Error warming up the window: {}", err);
+                    return;
+                }
+            }
+        }
+        let backend = warmed_up_backend.as_ref().unwrap();
+        // The scale factor of the window property is only known once the window is mapped.
+        backend.warm_up(resources, backend.window().scale_factor() as f32);
+    }
+
+    fn font_substitutions(&self) -> Vec<(SharedString, SharedString)> {
+        match &*self.map_state.borrow() {
+            GraphicsWindowBackendState::Unmapped => self
+                .warmed_up_backend
+                .borrow()
+                .as_ref()
+                .map_or_else(Vec::new, |backend| backend.font_substitutions()),
+            GraphicsWindowBackendState::Mapped(window) => {
+                window.backend.borrow().font_substitutions()
+            }
+        }
+    }
+
+    fn last_frame_stats(&self) -> Option<FrameStats> {
+        self.last_frame_stats.get()
+    }
 }
 
 struct MappedWindow {
@@ -0,0 +1,535 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+/*!
+The headless backend renders components with an off-screen OpenGL context, without ever opening a window, for
+example to render previews or thumbnails on a server. It uses a context of the display when there is one, and
+OSMesa, a software implementation of OpenGL, otherwise.
+
+The items are drawn with the same GLRenderer as the windows of the GL backend. The rendered frames are obtained
+with [`ComponentWindow::read_pixels`](sixtyfps_corelib::window::ComponentWindow::read_pixels).
+*/
+
+use core::cell::{Cell, RefCell};
+use core::pin::Pin;
+use std::rc::{Rc, Weak};
+
+use corelib::component::ComponentRc;
+use corelib::graphics::{AlphaMode, Color, FontMetrics, FontRequest, Point, Rect, Resource};
+use corelib::items::ItemRef;
+use corelib::render_commands::RenderCommandList;
+use corelib::slice::Slice;
+use corelib::window::{ComponentWindow, PlatformWindow};
+use corelib::{Property, SharedString};
+use sixtyfps_corelib as corelib;
+
+use super::{create_with_fallbacks, ContextCreationError, GLContext, GLRenderer};
+
+/// The size of the frames of components whose root element is not a `Window` with a width and a height.
+const DEFAULT_SIZE: winit::dpi::PhysicalSize<u32> =
+    winit::dpi::PhysicalSize { width: 800, height: 600 };
+
+enum HeadlessContextState {
+    NotCurrent(glutin::Context<glutin::NotCurrent>),
+    Current(glutin::Context<glutin::PossiblyCurrent>),
+}
+
+/// An OpenGL context that draws into an off-screen buffer of a fixed size.
+struct HeadlessContext {
+    state: HeadlessContextState,
+    size: winit::dpi::PhysicalSize<u32>,
+}
+
+impl GLContext for HeadlessContext {
+    fn make_current(self: Box<Self>) -> Box<dyn GLContext> {
+        // Another context may have been made current since this one was, so it is made current in any case.
+        let context = unsafe {
+            match self.state {
+                HeadlessContextState::NotCurrent(context) => context.make_current().unwrap(),
+                HeadlessContextState::Current(context) => context.make_current().unwrap(),
+            }
+        };
+        Box::new(Self { state: HeadlessContextState::Current(context), size: self.size })
+    }
+
+    fn make_not_current(self: Box<Self>) -> Box<dyn GLContext> {
+        match self.state {
+            HeadlessContextState::NotCurrent(_) => self,
+            HeadlessContextState::Current(context) => Box::new(Self {
+                state: HeadlessContextState::NotCurrent(unsafe {
+                    context.make_not_current().unwrap()
+                }),
+                size: self.size,
+            }),
+        }
+    }
+
+    fn swap_buffers(&mut self) -> Result<(), glutin::ContextError> {
+        // There is no window to show the frame in.
+        Ok(())
+    }
+
+    fn get_proc_address(&self, symbol: &str) -> *const std::ffi::c_void {
+        match &self.state {
+            HeadlessContextState::NotCurrent(_) => std::ptr::null(),
+            HeadlessContextState::Current(context) => context.get_proc_address(symbol),
+        }
+    }
+
+    fn enable_framebuffer_srgb(&self) -> bool {
+        false
+    }
+
+    fn window(&self) -> Option<&winit::window::Window> {
+        None
+    }
+
+    fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.size
+    }
+}
+
+enum HeadlessContextKind {
+    /// A context of the display's OpenGL implementation, which requires a connection to the display.
+    Display,
+    /// A context of OSMesa, which renders in software and works without a display.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    OsMesa,
+}
+
+// Opening the event loop that the contexts of the display are created with panics on Linux if there is no display
+// to connect to.
+fn display_available() -> bool {
+    if cfg!(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )) {
+        std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some()
+    } else {
+        true
+    }
+}
+
+/// Creates an off-screen context of the given size and a femtovg renderer for it. The context is current when
+/// returned.
+pub(crate) fn create_context(
+    size: winit::dpi::PhysicalSize<u32>,
+) -> Result<(Box<dyn GLContext>, femtovg::renderer::OpenGl), ContextCreationError> {
+    let mut attempts = Vec::new();
+    if display_available() {
+        attempts.push(("a headless context of the display", HeadlessContextKind::Display));
+    }
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    attempts.push(("OSMesa", HeadlessContextKind::OsMesa));
+
+    create_with_fallbacks(attempts, |kind| {
+        // The alpha channel is kept, so that transparent backgrounds can be read back.
+        let context_builder = glutin::ContextBuilder::new().with_pixel_format(24, 8);
+        let context = match kind {
+            HeadlessContextKind::Display => crate::eventloop::with_window_target(|event_loop| {
+                context_builder.build_headless(event_loop.event_loop_target(), size)
+            })?,
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            HeadlessContextKind::OsMesa => {
+                use glutin::platform::unix::HeadlessContextExt;
+                context_builder.build_osmesa(size)?
+            }
+        };
+        let context = unsafe { context.make_current().map_err(|(_, err)| err)? };
+        let renderer =
+            femtovg::renderer::OpenGl::new(|symbol| context.get_proc_address(symbol) as *const _)?;
+        let context: Box<dyn GLContext> =
+            Box::new(HeadlessContext { state: HeadlessContextState::Current(context), size });
+        Ok((context, renderer))
+    })
+}
+
+impl GLRenderer {
+    /// Changes the size of the frames of a renderer created with [`GLRenderer::new_headless`]. The off-screen
+    /// buffer of a context cannot be resized, so the renderer switches to a new context of the given size, in
+    /// which the images, glyphs and fonts are loaded again when drawn the next time.
+    pub(crate) fn resize_headless(
+        &self,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Result<(), ContextCreationError> {
+        if self.size() == size {
+            return Ok(());
+        }
+        let shared_data = &self.shared_data;
+        let old_context = shared_data.gl_context.borrow_mut().take().unwrap().make_current();
+        // The images are deleted from the old context, which they were created in.
+        shared_data.release_gpu_resources();
+
+        let created = create_context(size).and_then(|(context, renderer)| {
+            let canvas = femtovg::Canvas::new(renderer)
+                .map_err(|err| ContextCreationError::new("the femtovg canvas", err))?;
+            Ok((context, canvas))
+        });
+        let (new_context, canvas) = match created {
+            Ok(created) => created,
+            Err(err) => {
+                *shared_data.gl_context.borrow_mut() = Some(old_context.make_not_current());
+                return Err(err);
+            }
+        };
+
+        let old_canvas = std::mem::replace(&mut *shared_data.canvas.borrow_mut(), canvas);
+        // The canvas deletes its buffers and shaders when dropped, which has to happen in its own context.
+        let old_context = old_context.make_current();
+        drop(old_canvas);
+        drop(old_context);
+
+        *shared_data.gl_context.borrow_mut() = Some(new_context.make_current().make_not_current());
+        Ok(())
+    }
+}
+
+thread_local! {
+    /// The windows that are shown, which run_event_loop() renders.
+    static SHOWN_WINDOWS: RefCell<Vec<Weak<HeadlessWindow>>> = Default::default();
+}
+
+/// A window that is rendered into an off-screen buffer instead of being shown on the screen. Its size is the
+/// `width` and `height` of the root `Window` element, 800x600 pixels by default.
+pub struct HeadlessWindow {
+    self_weak: once_cell::unsync::OnceCell<Weak<corelib::window::Window>>,
+    /// The renderer, created when first needed, as creating a context may fail.
+    renderer: RefCell<Option<GLRenderer>>,
+    /// The size of the frame that was rendered last, in physical pixels.
+    size: Cell<winit::dpi::PhysicalSize<u32>>,
+    scale_factor: Pin<Box<Property<f32>>>,
+    shown: Cell<bool>,
+    /// Current popup's component and position
+    active_popup: RefCell<Option<(ComponentRc, Point)>>,
+}
+
+impl HeadlessWindow {
+    fn new() -> Rc<Self> {
+        Rc::new(Self {
+            self_weak: Default::default(),
+            renderer: Default::default(),
+            size: Cell::new(DEFAULT_SIZE),
+            scale_factor: Box::pin(Property::new(1.0)),
+            shown: Cell::new(false),
+            active_popup: Default::default(),
+        })
+    }
+
+    fn component(&self) -> ComponentRc {
+        self.self_weak.get().unwrap().upgrade().unwrap().component()
+    }
+
+    // Calls the function with the renderer, creating it at the size of the last frame if there is none yet.
+    fn with_renderer<R>(&self, f: impl FnOnce(&mut GLRenderer) -> R) -> Option<R> {
+        let mut renderer = self.renderer.borrow_mut();
+        if renderer.is_none() {
+            match GLRenderer::new_headless(self.size.get()) {
+                Ok(new_renderer) => *renderer = Some(new_renderer),
+                Err(err) => {
+                    eprintln!("Error creating an off-screen context: {}", err);
+                    return None;
+                }
+            }
+        }
+        renderer.as_mut().map(f)
+    }
+
+    // Renders the items, or the recorded commands if given instead, and returns the rendered pixels.
+    fn render(
+        &self,
+        alpha_mode: AlphaMode,
+        replay: Option<&RenderCommandList>,
+    ) -> Option<image::RgbaImage> {
+        if !self.shown.get() {
+            return None;
+        }
+        let component_rc = self.component();
+        let component = ComponentRc::borrow_pin(&component_rc);
+        let root_item = component.as_ref().get_item_ref(0);
+
+        let scale_factor_changed = self
+            .self_weak
+            .get()
+            .unwrap()
+            .upgrade()
+            .unwrap()
+            .update_rendered_scale_factor(self.scale_factor());
+
+        let layout_info = component.as_ref().layout_info();
+        let (mut width, mut height) = (DEFAULT_SIZE.width as f32, DEFAULT_SIZE.height as f32);
+        let mut background_color = Color::from_rgb_u8(255, 255, 255);
+        if let Some(window_item) = ItemRef::downcast_pin::<corelib::items::Window>(root_item) {
+            width = window_item.width();
+            height = window_item.height();
+            background_color = window_item.color();
+        }
+        let size = winit::dpi::PhysicalSize::new(
+            width.max(layout_info.min_width).max(1.).ceil() as u32,
+            height.max(layout_info.min_height).max(1.).ceil() as u32,
+        );
+        component.as_ref().apply_layout(self.get_geometry_for_size(size));
+        if let Some((popup, pos)) = &*self.active_popup.borrow() {
+            let popup = ComponentRc::borrow_pin(popup);
+            let layout_info = popup.as_ref().layout_info();
+            popup.as_ref().apply_layout(Rect::new(
+                *pos,
+                euclid::size2(layout_info.min_width, layout_info.min_height),
+            ));
+        }
+
+        if let Some(renderer) = &*self.renderer.borrow() {
+            if let Err(err) = renderer.resize_headless(size) {
+                eprintln!("Error resizing the off-screen context: {}", err);
+                return None;
+            }
+        }
+        self.size.set(size);
+
+        let mut item_renderer = self.with_renderer(|renderer| {
+            if scale_factor_changed {
+                renderer.release_scale_factor_dependent_caches();
+            }
+            renderer.new_renderer(&background_color, self.scale_factor(), None)
+        })?;
+        if let Some(commands) = replay {
+            commands.replay(&mut item_renderer);
+        } else {
+            corelib::item_rendering::render_component_items(
+                &component_rc,
+                &mut item_renderer,
+                Point::default(),
+            );
+            if let Some((popup, pos)) = &*self.active_popup.borrow() {
+                corelib::item_rendering::render_component_items(popup, &mut item_renderer, *pos);
+            }
+        }
+        self.with_renderer(|renderer| renderer.flush_renderer(item_renderer, Some(alpha_mode)))?
+    }
+
+    fn get_geometry_for_size(&self, size: winit::dpi::PhysicalSize<u32>) -> Rect {
+        euclid::rect(0., 0., size.width as f32, size.height as f32)
+    }
+}
+
+impl PlatformWindow for HeadlessWindow {
+    fn show(self: Rc<Self>) {
+        if self.shown.replace(true) {
+            return;
+        }
+        // Elements that are sized relative to the window need the window to have a size.
+        let component = self.component();
+        let component = ComponentRc::borrow_pin(&component);
+        let root_item = component.as_ref().get_item_ref(0);
+        if let Some(window_item) = ItemRef::downcast_pin::<corelib::items::Window>(root_item) {
+            if window_item.width() <= 0. {
+                corelib::items::Window::FIELD_OFFSETS
+                    .width
+                    .apply_pin(window_item)
+                    .set(DEFAULT_SIZE.width as f32);
+            }
+            if window_item.height() <= 0. {
+                corelib::items::Window::FIELD_OFFSETS
+                    .height
+                    .apply_pin(window_item)
+                    .set(DEFAULT_SIZE.height as f32);
+            }
+        }
+        SHOWN_WINDOWS.with(|windows| windows.borrow_mut().push(Rc::downgrade(&self)));
+    }
+
+    fn hide(self: Rc<Self>) {
+        self.shown.set(false);
+        let this = Rc::downgrade(&self);
+        SHOWN_WINDOWS.with(|windows| windows.borrow_mut().retain(|window| !window.ptr_eq(&this)));
+    }
+
+    fn request_redraw(&self) {
+        // Frames are only rendered when requested with read_pixels() or run_event_loop().
+    }
+
+    fn request_redraw_region(&self, _region: Rect) {}
+
+    fn scale_factor(&self) -> f32 {
+        self.scale_factor.as_ref().get()
+    }
+
+    fn set_scale_factor(&self, factor: f32) {
+        self.scale_factor.as_ref().set(factor);
+    }
+
+    fn get_geometry(&self) -> Rect {
+        self.get_geometry_for_size(self.size.get())
+    }
+
+    fn free_graphics_resources<'a>(self: Rc<Self>, items: &Slice<'a, Pin<ItemRef<'a>>>) {
+        if let Some(renderer) = &*self.renderer.borrow() {
+            for item in items.iter() {
+                let cached_rendering_data = item.cached_rendering_data_offset();
+                cached_rendering_data
+                    .release(&mut renderer.shared_data.item_graphics_cache.borrow_mut())
+            }
+        }
+    }
+
+    fn show_popup(&self, popup: &ComponentRc, position: Point) {
+        *self.active_popup.borrow_mut() = Some((popup.clone(), position));
+    }
+
+    fn close_popup(&self) {
+        *self.active_popup.borrow_mut() = None;
+    }
+
+    fn font_metrics(&self, request: FontRequest) -> Option<Box<dyn FontMetrics>> {
+        self.with_renderer(|renderer| renderer.font_metrics(request, self.scale_factor()))
+    }
+
+    fn image_size(
+        &self,
+        item_graphics_cache: &corelib::item_rendering::CachedRenderingData,
+        source: Pin<&Property<Resource>>,
+    ) -> corelib::graphics::Size {
+        self.with_renderer(|renderer| renderer.image_size(item_graphics_cache, source))
+            .unwrap_or_default()
+            * self.scale_factor()
+    }
+
+    fn read_pixels(&self, alpha_mode: AlphaMode) -> Option<image::RgbaImage> {
+        self.render(alpha_mode, None)
+    }
+
+    fn replay_frame(
+        &self,
+        commands: &RenderCommandList,
+        alpha_mode: AlphaMode,
+    ) -> Option<image::RgbaImage> {
+        self.render(alpha_mode, Some(commands))
+    }
+
+    fn warm_up(self: Rc<Self>, resources: &[Resource]) {
+        self.with_renderer(|renderer| renderer.warm_up(resources, self.scale_factor()));
+    }
+
+    fn font_substitutions(&self) -> Vec<(SharedString, SharedString)> {
+        self.renderer
+            .borrow()
+            .as_ref()
+            .map_or_else(Vec::new, |renderer| renderer.font_substitutions())
+    }
+}
+
+/// The backend that renders without a window, selected with `SIXTYFPS_BACKEND=GL-headless`. Everything but
+/// the windows is the same as with the GL backend.
+pub struct HeadlessBackend;
+
+// The GL backend, which the functions unrelated to windows are forwarded to.
+const GL_BACKEND: &super::Backend = &super::Backend;
+
+impl corelib::backend::Backend for HeadlessBackend {
+    fn create_window(&'static self) -> ComponentWindow {
+        let platform_window = HeadlessWindow::new();
+        let window = Rc::new(corelib::window::Window::new(platform_window.clone()));
+        platform_window.self_weak.set(Rc::downgrade(&window)).ok().unwrap();
+        ComponentWindow(window)
+    }
+
+    /// Instead of waiting for events, runs the timers and animations that are due and renders each shown window
+    /// once, then returns.
+    fn run_event_loop(&'static self) {
+        corelib::timers::TimerList::maybe_activate_timers();
+        corelib::animations::update_animations();
+        let windows = SHOWN_WINDOWS
+            .with(|windows| windows.borrow().iter().filter_map(Weak::upgrade).collect::<Vec<_>>());
+        for window in windows {
+            window.render(AlphaMode::Straight, None);
+        }
+    }
+
+    fn register_application_font_from_memory(
+        &'static self,
+        data: &'static [u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        GL_BACKEND.register_application_font_from_memory(data)
+    }
+
+    fn register_application_font_from_path(
+        &'static self,
+        path: &std::path::Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        GL_BACKEND.register_application_font_from_path(path)
+    }
+
+    fn available_font_families(&'static self) -> Vec<String> {
+        GL_BACKEND.available_font_families()
+    }
+
+    fn set_clipboard_text(&'static self, text: String) {
+        GL_BACKEND.set_clipboard_text(text)
+    }
+
+    fn clipboard_text(&'static self) -> Option<String> {
+        GL_BACKEND.clipboard_text()
+    }
+
+    fn set_primary_selection_text(&'static self, text: String) {
+        GL_BACKEND.set_primary_selection_text(text)
+    }
+
+    fn primary_selection_text(&'static self) -> Option<String> {
+        GL_BACKEND.primary_selection_text()
+    }
+
+    fn set_clipboard_image(&'static self, rgba: &[u8], width: u32, height: u32) {
+        GL_BACKEND.set_clipboard_image(rgba, width, height)
+    }
+
+    fn clipboard_image(&'static self) -> Option<(Vec<u8>, u32, u32)> {
+        GL_BACKEND.clipboard_image()
+    }
+
+    fn pin_image_resource(&'static self, resource: Resource) {
+        GL_BACKEND.pin_image_resource(resource)
+    }
+
+    fn unpin_image_resource(&'static self, resource: Resource) {
+        GL_BACKEND.unpin_image_resource(resource)
+    }
+
+    fn set_texture_memory_budget(&'static self, budget: Option<usize>) {
+        GL_BACKEND.set_texture_memory_budget(budget)
+    }
+
+    fn set_image_cache_enabled(&'static self, enabled: bool) {
+        GL_BACKEND.set_image_cache_enabled(enabled)
+    }
+
+    fn set_text_antialiasing(&'static self, mode: corelib::graphics::TextAntialiasing) {
+        GL_BACKEND.set_text_antialiasing(mode)
+    }
+}
@@ -0,0 +1,74 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+/*!
+Image decoders registered by the application, for image formats that the `image` crate doesn't support, or for
+image files that are packed or encrypted.
+
+The decoders are consulted with the encoded data of image files, embedded images and images from URLs, before
+the built-in decoders. They may be called from the background threads that preload images, so they are shared
+between all threads.
+*/
+
+use once_cell::sync::Lazy;
+use std::sync::{Arc, RwLock};
+
+/// The image produced by a decoder registered with [`register_image_decoder`].
+pub enum DecodedImage {
+    /// An image decoded with, or converted to, the `image` crate.
+    Image(image::DynamicImage),
+    /// The pixels of the image, row by row, as red, green, blue and alpha bytes with straight alpha.
+    Rgba { width: u32, height: u32, data: Vec<u8> },
+}
+
+type ImageDecoderFn = dyn Fn(&[u8]) -> Option<DecodedImage> + Send + Sync;
+
+static IMAGE_DECODERS: Lazy<RwLock<Vec<Arc<ImageDecoderFn>>>> = Lazy::new(Default::default);
+
+/// Registers a decoder for images in a custom format. The decoder is called with the encoded data of an image, and
+/// returns None if it doesn't recognize the data, for example by its first bytes, in which case the next decoder is
+/// tried. The decoders are tried in the order they were registered, before the built-in decoders. SVG images are
+/// recognized by their `.svg` file extension, or by starting with `<svg`, and are always rendered by the built-in
+/// SVG renderer.
+///
+/// Decoded images are cached like any other images, by their file path or embedded data. Images that were loaded
+/// before the decoder was registered are decoded again with it after `GLRenderer::clear_image_cache`.
+///
+/// Decoders can be registered from any thread, and are called from any thread.
+pub fn register_image_decoder(
+    decoder: impl Fn(&[u8]) -> Option<DecodedImage> + Send + Sync + 'static,
+) {
+    image_decoders().push(Arc::new(decoder));
+}
+
+fn image_decoders() -> std::sync::RwLockWriteGuard<'static, Vec<Arc<ImageDecoderFn>>> {
+    IMAGE_DECODERS.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Decodes the encoded image data with the first registered decoder that recognizes it. Returns None if no
+/// decoder does.
+pub(crate) fn decode_with_registered_decoders(data: &[u8]) -> Option<image::DynamicImage> {
+    // The decoders are called without holding the lock, so that they may register further decoders.
+    let decoders = IMAGE_DECODERS.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+    decoders.iter().find_map(|decoder| match decoder(data)? {
+        DecodedImage::Image(image) => Some(image),
+        DecodedImage::Rgba { width, height, data } => {
+            match image::RgbaImage::from_raw(width, height, data) {
+                Some(pixels) => Some(image::DynamicImage::ImageRgba8(pixels)),
+                None => {
+                    eprintln!(
+                        "Error decoding image: the decoder provided too few pixels for an image of {}x{} pixels",
+                        width, height
+                    );
+                    None
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,136 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+#![cfg(not(target_arch = "wasm32"))]
+/*!
+Watching the files of the images in the image cache, to load them again when they change on disk.
+
+This is enabled with the `SIXTYFPS_WATCH_IMAGES` environment variable, for tools that show a design while it is
+being edited. A background thread compares the modification time and size of the watched files at a fixed interval,
+and calls a notification function when one of them changed. The main thread then picks up the changed files with
+[`FileWatcher::take_changed`]. The files that are watched are updated with [`FileWatcher::watch_only`] after every
+frame, to follow the images that are added to or removed from the image cache.
+*/
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// The interval at which the watched files are compared.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The modification time and the size of a file, None if the file doesn't exist. The size catches changes within
+/// the resolution of the modification time of the file system.
+type FileState = Option<(SystemTime, u64)>;
+
+type WatchedFiles = HashMap<String, FileState>;
+
+/// Watches files for changes in a background thread. Dropping it stops the thread.
+pub(crate) struct FileWatcher {
+    files: Arc<Mutex<WatchedFiles>>,
+    changed: mpsc::Receiver<String>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl FileWatcher {
+    /// Starts watching, with no files yet. `notify` is called from the background thread when files changed.
+    pub fn start(interval: Duration, notify: impl Fn() + Send + 'static) -> Self {
+        let files = Arc::new(Mutex::new(WatchedFiles::new()));
+        let (sender, changed) = mpsc::channel();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread_files = files.clone();
+        let thread_stopped = stopped.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            if thread_stopped.load(Ordering::Relaxed) {
+                return;
+            }
+            let mut any_changed = false;
+            for (path, state) in thread_files.lock().unwrap().iter_mut() {
+                let current = file_state(path);
+                if current != *state {
+                    *state = current;
+                    if sender.send(path.clone()).is_err() {
+                        return;
+                    }
+                    any_changed = true;
+                }
+            }
+            if any_changed {
+                notify();
+            }
+        });
+        Self { files, changed, stopped }
+    }
+
+    /// Watches the given files, and stops watching all other files. The files that were not watched before are
+    /// compared with their current state from now on.
+    pub fn watch_only<'a>(&self, paths: impl Iterator<Item = &'a str>) {
+        let mut files = self.files.lock().unwrap();
+        let watched = paths
+            .map(|path| {
+                let state = files.remove(path).unwrap_or_else(|| file_state(path));
+                (path.to_string(), state)
+            })
+            .collect::<WatchedFiles>();
+        *files = watched;
+    }
+
+    /// Returns the files that changed since the last call, each once.
+    pub fn take_changed(&self) -> Vec<String> {
+        let mut changed = self.changed.try_iter().collect::<Vec<_>>();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+fn file_state(path: &str) -> FileState {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_file_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let watched_path = dir.path().join("watched.png");
+        let other_path = dir.path().join("other.png");
+        std::fs::write(&watched_path, b"before").unwrap();
+        std::fs::write(&other_path, b"before").unwrap();
+        let watched_path = watched_path.to_string_lossy().to_string();
+        let other_path = other_path.to_string_lossy().to_string();
+
+        let (notify, notified) = mpsc::channel();
+        let notify = Mutex::new(notify);
+        let watcher = FileWatcher::start(Duration::from_millis(10), move || {
+            notify.lock().unwrap().send(()).ok();
+        });
+        watcher.watch_only([watched_path.as_str(), other_path.as_str()].iter().copied());
+        // The file that is no longer cached is no longer watched.
+        watcher.watch_only(std::iter::once(watched_path.as_str()));
+
+        std::fs::write(&watched_path, b"after the change").unwrap();
+        std::fs::write(&other_path, b"after the change").unwrap();
+
+        notified.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(watcher.take_changed(), vec![watched_path]);
+        assert!(watcher.take_changed().is_empty());
+    }
+}
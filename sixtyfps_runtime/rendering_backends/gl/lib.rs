@@ -9,25 +9,47 @@
 LICENSE END */
 
 use std::{
+    borrow::Cow,
     cell::RefCell,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     rc::{Rc, Weak},
 };
 
 use sixtyfps_corelib::graphics::{
-    Color, FontMetrics, FontRequest, Point, Rect, RenderingCache, Resource, Size,
+    AlphaMode, Color, FontMetrics, FontRequest, Point, Rect, RenderingCache, Resource, Size,
+    TextAntialiasing,
+};
+use sixtyfps_corelib::item_rendering::{
+    CachedRenderingData, ImageScaleTracker, ItemRenderer, LayerCache,
 };
-use sixtyfps_corelib::item_rendering::{CachedRenderingData, ItemRenderer};
 use sixtyfps_corelib::items::{
-    ImageFit, Item, TextHorizontalAlignment, TextOverflow, TextVerticalAlignment, TextWrap,
+    effective_stroke_width, image_alignment_factors, rotated_image_size, rotation_sin_cos,
+    stroke_offset, ClipShape, FontStyle, ImageFit, ImageRendering, Item, NumeralStyle,
+    StrokeAlignment, TextHorizontalAlignment, TextOverflow, TextVerticalAlignment,
+    TextVerticalCentering, TextWrap,
 };
 use sixtyfps_corelib::properties::Property;
 use sixtyfps_corelib::window::ComponentWindow;
 use sixtyfps_corelib::SharedString;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
 
+mod adaptive_sync;
 mod graphics_window;
 use graphics_window::*;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
+#[cfg(not(target_arch = "wasm32"))]
+pub use headless::HeadlessBackend;
+mod animated_image;
+mod custom_rendering;
+pub use custom_rendering::register_custom_renderer;
 pub(crate) mod eventloop;
+use animated_image::AnimatedImage;
+mod image_decoders;
+pub use image_decoders::{register_image_decoder, DecodedImage};
+mod image_file_watcher;
+mod remote_image;
+mod srgb;
 mod svg;
 
 type CanvasRc = Rc<RefCell<femtovg::Canvas<femtovg::renderer::OpenGl>>>;
@@ -44,10 +66,29 @@ enum ImageData {
         /// used for remote HTML image loading and the property will be used to correctly track dependencies
         /// to graphics items that query for the size.
         upload_pending: Option<core::pin::Pin<Box<Property<bool>>>>,
+        /// The size of the image that was decoded on the CPU side, which differs from the size of the texture if it
+        /// was downscaled to fit the maximum texture size of the GPU. If not present, it is the size of the texture.
+        decoded_size: Option<(u32, u32)>,
     },
     CPUSide {
+        /// The pixels, with straight alpha. They are premultiplied when uploaded to the GPU.
         decoded_image: image::DynamicImage,
     },
+    /// An image that keeps its pixels on the CPU side after uploading them, so that the texture can be evicted
+    /// from the GPU when the texture memory budget is exceeded. It is uploaded again when drawn the next time.
+    Evictable { decoded_image: image::DynamicImage, texture: Rc<Texture> },
+    /// All frames of an animated GIF or APNG. Each frame is uploaded to the GPU separately, when it
+    /// is shown for the first time.
+    Animated(AnimatedImage),
+    /// An image that is still being fetched from a http:// or https:// URL, or decoded ahead of drawing it, in a
+    /// background thread. Until the image has arrived, a transparent 1x1 placeholder is shown and the
+    /// upload_pending property is true.
+    #[cfg(not(target_arch = "wasm32"))]
+    Fetching {
+        fetch: remote_image::PendingFetch,
+        placeholder: Box<CachedImage>,
+        upload_pending: core::pin::Pin<Box<Property<bool>>>,
+    },
 }
 
 impl Drop for ImageData {
@@ -56,16 +97,380 @@ fn drop(&mut self) {
             ImageData::GPUSide { id, canvas, .. } => {
                 canvas.borrow_mut().delete_image(*id);
             }
-            ImageData::CPUSide { .. } => {}
+            ImageData::CPUSide { .. } | ImageData::Evictable { .. } | ImageData::Animated(_) => {}
+            #[cfg(not(target_arch = "wasm32"))]
+            ImageData::Fetching { .. } => {}
+        }
+    }
+}
+
+/// The GPU side of an evictable image, see ImageData::Evictable.
+struct Texture {
+    id: std::cell::Cell<Option<femtovg::ImageId>>,
+    canvas: CanvasRc,
+    /// The flags the texture is created with when it is uploaded again.
+    flags: femtovg::ImageFlags,
+    /// The amount of GPU memory used by the texture, when uploaded.
+    bytes: usize,
+    /// The TextureCache::current_frame in which the texture was last drawn.
+    last_used_frame: std::cell::Cell<u64>,
+}
+
+impl Texture {
+    fn evict(&self) {
+        if let Some(id) = self.id.take() {
+            self.canvas.borrow_mut().delete_image(id);
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        self.evict();
+    }
+}
+
+thread_local!(
+    /// The amount of GPU memory that the textures of images may use, see Backend::set_texture_memory_budget.
+    static TEXTURE_MEMORY_BUDGET: std::cell::Cell<Option<usize>> = Default::default()
+);
+
+/// Keeps track of the textures of evictable images, to evict the least recently drawn ones when the texture
+/// memory budget is exceeded.
+#[derive(Default)]
+struct TextureCache {
+    textures: Vec<Weak<Texture>>,
+    /// Incremented for every rendered frame.
+    current_frame: u64,
+}
+
+impl TextureCache {
+    fn register(&mut self, texture: &Rc<Texture>) {
+        texture.last_used_frame.set(self.current_frame);
+        self.textures.push(Rc::downgrade(texture));
+    }
+
+    /// Evicts the least recently drawn textures until the budget is met, except for the textures drawn in the
+    /// current frame, and starts the next frame. This must be called after the frame was flushed to the GPU.
+    fn finish_frame(&mut self, budget: Option<usize>) {
+        self.textures.retain(|texture| {
+            texture.upgrade().map_or(false, |texture| texture.id.get().is_some())
+        });
+        if let Some(budget) = budget {
+            let textures = self.textures.iter().filter_map(Weak::upgrade).collect::<Vec<_>>();
+            let usage = textures
+                .iter()
+                .map(|texture| (texture.bytes, texture.last_used_frame.get()))
+                .collect::<Vec<_>>();
+            for index in textures_to_evict(&usage, budget, self.current_frame) {
+                textures[index].evict();
+            }
+            self.textures.retain(|texture| {
+                texture.upgrade().map_or(false, |texture| texture.id.get().is_some())
+            });
+        }
+        self.current_frame += 1;
+    }
+}
+
+/// Returns the indices of the textures to evict, given their size in bytes and the frame they were last drawn in,
+/// to bring the total size within the budget. The least recently drawn textures are evicted first. Textures drawn
+/// in the current frame are kept even if that exceeds the budget.
+fn textures_to_evict(usage: &[(usize, u64)], budget: usize, current_frame: u64) -> Vec<usize> {
+    let mut total: usize = usage.iter().map(|(bytes, _)| bytes).sum();
+    let mut by_age = (0..usage.len()).collect::<Vec<_>>();
+    by_age.sort_by_key(|index| usage[*index].1);
+    by_age
+        .into_iter()
+        .take_while(|index| usage[*index].1 < current_frame)
+        .take_while(|index| {
+            let over_budget = total > budget;
+            total -= usage[*index].0;
+            over_budget
+        })
+        .collect()
+}
+
+// Creates a texture with the pixels of the image, which has straight alpha like all the decoded images. The
+// textures of the renderer hold premultiplied alpha instead, so that the colors of transparent pixels don't bleed
+// into their neighbors when the texture is sampled between them, which would draw dark fringes around
+// antialiased edges. With srgb_blending, the colors are converted to linear intensities first.
+// Images larger than max_texture_size are downscaled to fit first, as the texture could not be created otherwise.
+fn upload_image(
+    canvas: &CanvasRc,
+    decoded_image: &image::DynamicImage,
+    flags: femtovg::ImageFlags,
+    srgb_blending: bool,
+    max_texture_size: u32,
+) -> femtovg::ImageId {
+    use std::convert::TryFrom;
+    let downscaled;
+    let decoded_image = match fit_to_texture_size(
+        image::GenericImageView::dimensions(decoded_image),
+        max_texture_size,
+    ) {
+        Some((width, height)) => {
+            downscaled =
+                decoded_image.resize_exact(width, height, image::imageops::FilterType::Triangle);
+            &downscaled
+        }
+        None => decoded_image,
+    };
+    let flags = flags | femtovg::ImageFlags::PREMULTIPLIED;
+    if !srgb_blending && !decoded_image.color().has_alpha() {
+        // Opaque pixels are the same with premultiplied alpha, and can be uploaded as they are.
+        if let Ok(image_source) = femtovg::ImageSource::try_from(decoded_image) {
+            return canvas.borrow_mut().create_image(image_source, flags).unwrap();
+        }
+    }
+    use rgb::FromSlice;
+    let pixels = texture_pixels(decoded_image, srgb_blending);
+    let (width, height) = pixels.dimensions();
+    let image = imgref::Img::new(pixels.as_raw().as_rgba(), width as usize, height as usize);
+    canvas.borrow_mut().create_image(image, flags).unwrap()
+}
+
+// Returns the size that an image of the given size is downscaled to, to fit into a texture of at most
+// max_texture_size pixels in both directions, keeping its aspect ratio. None if the image fits already.
+fn fit_to_texture_size((width, height): (u32, u32), max_texture_size: u32) -> Option<(u32, u32)> {
+    if width <= max_texture_size && height <= max_texture_size {
+        return None;
+    }
+    let scale = max_texture_size as f64 / width.max(height) as f64;
+    let fit = |length: u32| ((length as f64 * scale).round() as u32).max(1).min(max_texture_size);
+    Some((fit(width), fit(height)))
+}
+
+/// The maximum texture size assumed when the GL implementation can't be queried for it. All GPUs in use support
+/// at least this size.
+const FALLBACK_MAX_TEXTURE_SIZE: u32 = 2048;
+
+// Queries the maximum width and height of textures from the current GL context.
+#[cfg(not(target_arch = "wasm32"))]
+fn query_max_texture_size(gl_context: &dyn GLContext) -> u32 {
+    const GL_MAX_TEXTURE_SIZE: u32 = 0x0D33;
+    let get_integerv = gl_context.get_proc_address("glGetIntegerv");
+    if get_integerv.is_null() {
+        return FALLBACK_MAX_TEXTURE_SIZE;
+    }
+    let mut max_texture_size = 0i32;
+    unsafe {
+        let get_integerv: unsafe extern "system" fn(u32, *mut i32) =
+            std::mem::transmute(get_integerv);
+        get_integerv(GL_MAX_TEXTURE_SIZE, &mut max_texture_size);
+    }
+    if max_texture_size > 0 {
+        max_texture_size as u32
+    } else {
+        FALLBACK_MAX_TEXTURE_SIZE
+    }
+}
+
+// Queries the maximum width and height of textures from the WebGL context of the canvas of the window.
+#[cfg(target_arch = "wasm32")]
+fn query_max_texture_size(window: &winit::window::Window) -> u32 {
+    use wasm_bindgen::JsCast;
+    use winit::platform::web::WindowExtWebSys;
+    window
+        .canvas()
+        .get_context("webgl")
+        .ok()
+        .flatten()
+        .and_then(|context| context.dyn_into::<web_sys::WebGlRenderingContext>().ok())
+        .and_then(|context| {
+            context.get_parameter(web_sys::WebGlRenderingContext::MAX_TEXTURE_SIZE).ok()
+        })
+        .and_then(|value| value.as_f64())
+        .map_or(FALLBACK_MAX_TEXTURE_SIZE, |value| value as u32)
+}
+
+// Returns the pixels of the image as they are uploaded to a texture: premultiplied, and linearized with
+// srgb_blending.
+fn texture_pixels(decoded_image: &image::DynamicImage, srgb_blending: bool) -> image::RgbaImage {
+    use rgb::FromSlice;
+    let mut pixels = decoded_image.to_rgba8();
+    if srgb_blending {
+        srgb::linearize_pixels(pixels.as_mut().as_rgba_mut(), false);
+    }
+    premultiply_alpha(pixels.as_mut().as_rgba_mut());
+    pixels
+}
+
+/// Multiplies the color channels of the pixels with straight alpha with their alpha.
+fn premultiply_alpha(pixels: &mut [rgb::RGBA8]) {
+    for pixel in pixels {
+        if pixel.a == 255 {
+            continue;
         }
+        let alpha = pixel.a as u32;
+        let multiply = |channel: u8| ((channel as u32 * alpha + 127) / 255) as u8;
+        *pixel = rgb::RGBA8 {
+            r: multiply(pixel.r),
+            g: multiply(pixel.g),
+            b: multiply(pixel.b),
+            a: pixel.a,
+        };
     }
 }
 
-struct CachedImage(RefCell<ImageData>);
+/// The state of loading an image, see [`GLRenderer::image_load_state`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageLoadState {
+    /// The image data is still being fetched or decoded. Until it has arrived, the image has a size of 1x1 and
+    /// a placeholder is drawn instead.
+    Loading,
+    /// The image is loaded and drawn.
+    Ready,
+    /// The image could not be loaded, because the file or URL could not be read, or its data is not an image.
+    Failed,
+}
+
+struct CachedImage {
+    data: RefCell<ImageData>,
+    /// Set when the data of an image that was fetched or decoded in the background turned out not to be an image.
+    /// The image is then empty.
+    load_failed: std::cell::Cell<bool>,
+}
 
 impl CachedImage {
+    fn new(data: ImageData) -> Self {
+        Self { data: RefCell::new(data), load_failed: Default::default() }
+    }
+
     fn new_on_cpu(decoded_image: image::DynamicImage) -> Self {
-        Self(RefCell::new(ImageData::CPUSide { decoded_image }))
+        Self::new(ImageData::CPUSide { decoded_image })
+    }
+
+    // Creates an image from ARGB pixels, as stored in Resource::EmbeddedRgbaImage. Returns None if the amount of
+    // pixels does not match the size.
+    fn new_from_argb(width: u32, height: u32, pixels: &[u32]) -> Option<Self> {
+        let mut rgba = Vec::with_capacity(pixels.len() * 4);
+        for pixel in pixels {
+            let [a, r, g, b] = pixel.to_be_bytes();
+            rgba.extend_from_slice(&[r, g, b, a]);
+        }
+        let image = image::RgbaImage::from_raw(width, height, rgba)?;
+        Some(Self::new_on_cpu(image::DynamicImage::ImageRgba8(image)))
+    }
+
+    // Decodes the given encoded image data, keeping all frames if it is an animation. The decoders registered by
+    // the application are tried first.
+    fn new_from_data(data: &[u8]) -> image::ImageResult<Self> {
+        if let Some(image) = image_decoders::decode_with_registered_decoders(data) {
+            return Ok(Self::new_on_cpu(image));
+        }
+        Ok(match AnimatedImage::decode(data) {
+            Some(animation) => Self::new(ImageData::Animated(animation)),
+            None => Self::new_on_cpu(decode_image_data(data)?),
+        })
+    }
+
+    // Tints the image with the given color, see colorize_pixels(). This only has an effect before the image is
+    // uploaded to the GPU.
+    fn colorize(&self, color: Color) {
+        match &mut *self.data.borrow_mut() {
+            ImageData::CPUSide { decoded_image } => {
+                let mut pixels = decoded_image.to_rgba8();
+                colorize_pixels(&mut pixels, color);
+                *decoded_image = image::DynamicImage::ImageRgba8(pixels);
+            }
+            ImageData::Animated(animation) => animation.colorize(color),
+            ImageData::GPUSide { .. } | ImageData::Evictable { .. } => {}
+            #[cfg(not(target_arch = "wasm32"))]
+            ImageData::Fetching { .. } => {}
+        }
+    }
+
+    // Starts fetching the image from the given http:// or https:// URL in a background thread, which calls
+    // `notify` when the data has arrived. It is then decoded by poll_fetch.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new_remote(url: &str, notify: impl FnOnce() + Send + 'static) -> Self {
+        Self::new_fetching(remote_image::PendingFetch::start(url.into(), notify))
+    }
+
+    // Starts decoding the image the given file path or embedded data resource refers to in a background thread,
+    // which calls `notify` when it is done. Animated images are only read there, and decoded by poll_fetch. Returns
+    // None for other resources, which can't be decoded in the background.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new_preloading(resource: &Resource, notify: impl FnOnce() + Send + 'static) -> Option<Self> {
+        let (path, embedded_data): (Option<String>, &'static [u8]) = match resource {
+            Resource::AbsoluteFilePath(path) if !remote_image::is_remote_url(path) => {
+                (Some(path.to_string()), &[])
+            }
+            Resource::EmbeddedData(data) => (None, data.as_slice()),
+            _ => return None,
+        };
+        #[cfg(feature = "svg")]
+        let is_svg = is_svg_resource(resource);
+        let decode = move || -> Result<remote_image::FetchedImage, String> {
+            use remote_image::FetchedImage;
+            #[cfg(feature = "svg")]
+            if is_svg {
+                return match &path {
+                    Some(path) => svg::load_from_path(std::path::Path::new(path), None),
+                    None => svg::load_from_data(embedded_data, None),
+                }
+                .map(FetchedImage::Decoded)
+                .map_err(|err| err.to_string());
+            }
+            let data = match &path {
+                Some(path) => std::fs::read(path).map_err(|err| err.to_string())?.into(),
+                None => std::borrow::Cow::Borrowed(embedded_data),
+            };
+            if let Some(image) = image_decoders::decode_with_registered_decoders(&data) {
+                return Ok(FetchedImage::Decoded(image));
+            }
+            if AnimatedImage::may_be_animated(&data) {
+                return Ok(FetchedImage::Encoded(data.into_owned()));
+            }
+            decode_image_data(&data).map(FetchedImage::Decoded).map_err(|err| err.to_string())
+        };
+        Some(Self::new_fetching(remote_image::PendingFetch::spawn(decode, notify)))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new_fetching(fetch: remote_image::PendingFetch) -> Self {
+        Self::new(ImageData::Fetching {
+            fetch,
+            placeholder: Box::new(Self::new_on_cpu(image::DynamicImage::new_rgba8(1, 1))),
+            upload_pending: Box::pin(Property::new(true)),
+        })
+    }
+
+    // Replaces the placeholder of an image that is being fetched or decoded with the image, if it has arrived.
+    // Returns true if the image is still being fetched.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_fetch(&self) -> bool {
+        let mut data = self.data.borrow_mut();
+        let result = match &*data {
+            ImageData::Fetching { fetch, .. } => match fetch.try_take() {
+                Some(result) => result,
+                None => return true,
+            },
+            _ => return false,
+        };
+        let fetched_image = match result.and_then(|fetched| match fetched {
+            remote_image::FetchedImage::Encoded(encoded) => {
+                CachedImage::new_from_data(&encoded).map_err(|err| err.to_string())
+            }
+            remote_image::FetchedImage::Decoded(decoded_image) => {
+                Ok(CachedImage::new_on_cpu(decoded_image))
+            }
+        }) {
+            Ok(image) => image.data.into_inner(),
+            Err(err) => {
+                eprintln!("Error loading image in the background: {}", err);
+                self.load_failed.set(true);
+                ImageData::CPUSide { decoded_image: image::DynamicImage::new_rgba8(1, 1) }
+            }
+        };
+        let previous = std::mem::replace(&mut *data, fetched_image);
+        drop(data);
+        // Notify the items querying the size only after the new image is in place.
+        if let ImageData::Fetching { upload_pending, .. } = &previous {
+            upload_pending.as_ref().set(false);
+        }
+        false
     }
 
     fn new_on_gpu(
@@ -73,54 +478,155 @@ fn new_on_gpu(
         image_id: femtovg::ImageId,
         upload_pending_notifier: Option<core::pin::Pin<Box<Property<bool>>>>,
     ) -> Self {
-        Self(RefCell::new(ImageData::GPUSide {
+        Self::new(ImageData::GPUSide {
             id: image_id,
             canvas: canvas.clone(),
             upload_pending: upload_pending_notifier,
-        }))
+            decoded_size: None,
+        })
     }
 
     // Upload the image to the GPU? if that hasn't happened yet. This function could take just a canvas
     // as parameter, but since an upload requires a current context, this is "enforced" by taking
     // a renderer instead (which implies a current context).
     fn ensure_uploaded_to_gpu(&self, current_renderer: &GLItemRenderer) -> femtovg::ImageId {
-        use std::convert::TryFrom;
+        self.ensure_uploaded_to_gpu_with_flags(current_renderer, femtovg::ImageFlags::empty())
+    }
+
+    // Same as ensure_uploaded_to_gpu, but creates the texture with the given flags. The flags have no effect if the
+    // image was uploaded already, so an image must always be drawn with the same flags.
+    fn ensure_uploaded_to_gpu_with_flags(
+        &self,
+        current_renderer: &GLItemRenderer,
+        flags: femtovg::ImageFlags,
+    ) -> femtovg::ImageId {
+        match &*self.data.borrow() {
+            ImageData::Animated(animation) => {
+                return animation
+                    .current_frame_image()
+                    .ensure_uploaded_to_gpu_with_flags(current_renderer, flags)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ImageData::Fetching { placeholder, .. } => {
+                return placeholder.ensure_uploaded_to_gpu_with_flags(current_renderer, flags)
+            }
+            _ => {}
+        }
 
         let canvas = &current_renderer.shared_data.canvas;
+        let mut texture_cache = current_renderer.shared_data.texture_cache.borrow_mut();
 
-        let img = &mut *self.0.borrow_mut();
+        let max_texture_size = current_renderer.shared_data.max_texture_size;
+        let img = &mut *self.data.borrow_mut();
         if let ImageData::CPUSide { decoded_image } = img {
-            let image_id = match femtovg::ImageSource::try_from(&*decoded_image) {
-                Ok(image_source) => {
-                    canvas.borrow_mut().create_image(image_source, femtovg::ImageFlags::empty())
+            let decoded_size = image::GenericImageView::dimensions(decoded_image);
+            if fit_to_texture_size(decoded_size, max_texture_size).is_some() {
+                eprintln!(
+                    "Warning: the image of {}x{} pixels is larger than the maximum texture size of {} pixels, and is \
+                     downscaled to fit",
+                    decoded_size.0, decoded_size.1, max_texture_size
+                );
+            }
+            let image_id = upload_image(
+                canvas,
+                decoded_image,
+                flags,
+                current_renderer.shared_data.srgb_blending,
+                max_texture_size,
+            );
+            *img = if TEXTURE_MEMORY_BUDGET.with(|budget| budget.get().is_some()) {
+                let (width, height) =
+                    fit_to_texture_size(decoded_size, max_texture_size).unwrap_or(decoded_size);
+                let texture = Rc::new(Texture {
+                    id: Some(image_id).into(),
+                    canvas: canvas.clone(),
+                    flags,
+                    bytes: width as usize * height as usize * 4,
+                    last_used_frame: Default::default(),
+                });
+                texture_cache.register(&texture);
+                ImageData::Evictable {
+                    decoded_image: std::mem::replace(
+                        decoded_image,
+                        image::DynamicImage::new_rgba8(0, 0),
+                    ),
+                    texture,
                 }
-                Err(_) => {
-                    let converted = image::DynamicImage::ImageRgba8(decoded_image.to_rgba8());
-                    let image_source = femtovg::ImageSource::try_from(&converted).unwrap();
-                    canvas.borrow_mut().create_image(image_source, femtovg::ImageFlags::empty())
+            } else {
+                ImageData::GPUSide {
+                    id: image_id,
+                    canvas: canvas.clone(),
+                    upload_pending: None,
+                    decoded_size: Some(decoded_size),
                 }
-            }
-            .unwrap();
-
-            *img = ImageData::GPUSide { id: image_id, canvas: canvas.clone(), upload_pending: None }
+            };
         };
 
         match &img {
             ImageData::GPUSide { id, .. } => *id,
+            ImageData::Evictable { decoded_image, texture } => match texture.id.get() {
+                Some(id) => {
+                    texture.last_used_frame.set(texture_cache.current_frame);
+                    id
+                }
+                None => {
+                    let id = upload_image(
+                        canvas,
+                        decoded_image,
+                        texture.flags,
+                        current_renderer.shared_data.srgb_blending,
+                        max_texture_size,
+                    );
+                    texture.id.set(Some(id));
+                    texture_cache.register(texture);
+                    id
+                }
+            },
             _ => unreachable!(),
         }
     }
 
+    fn is_animated(&self) -> bool {
+        matches!(&*self.data.borrow(), ImageData::Animated(_))
+    }
+
+    // Returns true if the image data has not arrived yet, in which case a placeholder is drawn.
+    fn is_pending(&self) -> bool {
+        match &*self.data.borrow() {
+            ImageData::GPUSide { upload_pending, .. } => upload_pending
+                .as_ref()
+                .map_or(false, |pending_property| pending_property.as_ref().get()),
+            // Register the dependency, so that the state gets queried again once the data has arrived.
+            #[cfg(not(target_arch = "wasm32"))]
+            ImageData::Fetching { upload_pending, .. } => upload_pending.as_ref().get(),
+            ImageData::CPUSide { .. } | ImageData::Evictable { .. } | ImageData::Animated(_) => {
+                false
+            }
+        }
+    }
+
+    fn load_state(&self) -> ImageLoadState {
+        if self.is_pending() {
+            ImageLoadState::Loading
+        } else if self.load_failed.get() {
+            ImageLoadState::Failed
+        } else {
+            ImageLoadState::Ready
+        }
+    }
+
     fn size(&self) -> Size {
         use image::GenericImageView;
 
-        match &*self.0.borrow() {
-            ImageData::GPUSide { id, canvas, upload_pending } => {
+        match &*self.data.borrow() {
+            ImageData::GPUSide { id, canvas, upload_pending, decoded_size } => {
                 if upload_pending
                     .as_ref()
                     .map_or(false, |pending_property| pending_property.as_ref().get())
                 {
                     Ok((1., 1.))
+                } else if let Some((width, height)) = decoded_size {
+                    Ok((*width as f32, *height as f32))
                 } else {
                     canvas
                         .borrow()
@@ -128,18 +634,35 @@ fn size(&self) -> Size {
                         .map(|info| (info.width() as f32, info.height() as f32))
                 }
             }
-            ImageData::CPUSide { decoded_image: data } => {
+            ImageData::CPUSide { decoded_image: data }
+            | ImageData::Evictable { decoded_image: data, .. } => {
                 let (width, height) = data.dimensions();
                 Ok((width as f32, height as f32))
             }
+            ImageData::Animated(animation) => {
+                let size = animation.size();
+                Ok((size.width, size.height))
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ImageData::Fetching { upload_pending, .. } => {
+                // Register the dependency, so that the size gets queried again once the data has arrived.
+                upload_pending.as_ref().get();
+                Ok((1., 1.))
+            }
         }
         .map(|(width, height)| euclid::size2(width, height))
         .unwrap_or_default()
     }
 
+    #[cfg(target_arch = "wasm32")]
+    fn notify_failed(&self) {
+        self.load_failed.set(true);
+        self.notify_loaded();
+    }
+
     #[cfg(target_arch = "wasm32")]
     fn notify_loaded(&self) {
-        if let ImageData::GPUSide { upload_pending, .. } = &*self.0.borrow() {
+        if let ImageData::GPUSide { upload_pending, .. } = &*self.data.borrow() {
             upload_pending.as_ref().map(|pending_property| {
                 pending_property.as_ref().set(false);
             });
@@ -147,111 +670,586 @@ fn notify_loaded(&self) {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+// Decodes the encoded data of a still image, in the format its first bytes indicate, and rotates it upright.
+fn decode_image_data(data: &[u8]) -> image::ImageResult<image::DynamicImage> {
+    let image = match guess_image_format(data) {
+        Some(format) => image::load_from_memory_with_format(data, format)?,
+        None => image::load_from_memory(data)?,
+    };
+    Ok(apply_exif_orientation(image, data))
+}
+
+// Returns the format of the encoded image data from its first bytes. WebP and AVIF images are recognized by their
+// container: image::guess_format takes any RIFF file for WebP, and recognizes AVIF only with a file type box of
+// one particular size. AVIF images can only be decoded with an image crate built with an AVIF decoder, and WebP
+// images with the webp feature.
+fn guess_image_format(data: &[u8]) -> Option<image::ImageFormat> {
+    match data.get(..12) {
+        Some([b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P']) => {
+            Some(image::ImageFormat::WebP)
+        }
+        Some([b'R', b'I', b'F', b'F', ..]) => None,
+        Some([_, _, _, _, b'f', b't', b'y', b'p', b'a', b'v', b'i', b'f' | b's']) => {
+            Some(image::ImageFormat::Avif)
+        }
+        _ => image::guess_format(data).ok(),
+    }
+}
+
+// Photos are often stored unrotated, with an EXIF tag describing how to rotate and mirror them for display.
+// This is applied once when decoding, so that the pixels uploaded to the GPU are already upright.
+fn apply_exif_orientation(image: image::DynamicImage, data: &[u8]) -> image::DynamicImage {
+    if image::guess_format(data).ok() != Some(image::ImageFormat::Jpeg) {
+        return image;
+    }
+    let orientation = exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(data))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        });
+    match orientation {
+        Some(2) => image.fliph(),
+        Some(3) => image.rotate180(),
+        Some(4) => image.flipv(),
+        Some(5) => image.rotate90().fliph(),
+        Some(6) => image.rotate90(),
+        Some(7) => image.rotate270().fliph(),
+        Some(8) => image.rotate270(),
+        _ => image,
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
 enum ImageCacheKey {
     Path(String),
     EmbeddedData(by_address::ByAddress<&'static [u8]>),
+    /// The image of the inner key, tinted with the color of an Image's colorize property (ARGB encoded).
+    Colorized(Box<ImageCacheKey>, u32),
+}
+
+impl ImageCacheKey {
+    fn new(resource: &Resource) -> Option<Self> {
+        match resource {
+            Resource::AbsoluteFilePath(path) => Some(Self::Path(path.to_string())),
+            Resource::EmbeddedData(data) => {
+                Some(Self::EmbeddedData(by_address::ByAddress(data.as_slice())))
+            }
+            Resource::None | Resource::EmbeddedRgbaImage { .. } => None,
+        }
+    }
+}
+
+thread_local!(
+    /// The images that are retained even when no item uses them, see Backend::pin_image_resource.
+    static PINNED_IMAGES: RefCell<std::collections::HashSet<ImageCacheKey>> = Default::default()
+);
+
+fn is_image_pinned(key: &ImageCacheKey) -> bool {
+    PINNED_IMAGES.with(|pinned| pinned.borrow().contains(key))
+}
+
+thread_local!(
+    /// Set when images are not kept in memory between frames, see Backend::set_image_cache_enabled.
+    static IMAGE_CACHE_DISABLED: std::cell::Cell<bool> = Default::default()
+);
+
+thread_local!(
+    /// The text antialiasing requested with Backend::set_text_antialiasing.
+    static TEXT_ANTIALIASING: std::cell::Cell<TextAntialiasing> = Default::default()
+);
+
+// Returns true if the data of the image arrives only after a while, in which case the image must be kept
+// around until then.
+fn is_loaded_asynchronously(resource: &Resource) -> bool {
+    match resource {
+        #[cfg(target_arch = "wasm32")]
+        Resource::AbsoluteFilePath(_) => true,
+        #[cfg(not(target_arch = "wasm32"))]
+        Resource::AbsoluteFilePath(path) => remote_image::is_remote_url(path),
+        _ => false,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+thread_local!(
+    /// The images that are being fetched from the network, see CachedImage::new_remote.
+    static FETCHING_IMAGES: RefCell<Vec<Weak<CachedImage>>> = Default::default()
+);
+
+/// Decodes the remote images whose data has arrived. This is called by the event loop when a fetch
+/// has finished.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn poll_fetching_images() {
+    let images = FETCHING_IMAGES.with(|images| std::mem::take(&mut *images.borrow_mut()));
+    let still_fetching = images
+        .into_iter()
+        .filter(|image| image.upgrade().map_or(false, |image| image.poll_fetch()))
+        .collect::<Vec<_>>();
+    FETCHING_IMAGES.with(|images| images.borrow_mut().extend(still_fetching));
+}
+
+// Look up the given image cache key in the image cache and upgrade the weak reference to a strong one if found,
+// otherwise a new image is created/loaded from the given callback.
+// Images that fail to load are not added to the cache, so that loading them is attempted again the next time.
+fn lookup_image_in_cache_or_create(
+    image_cache: &mut HashMap<ImageCacheKey, Weak<CachedImage>>,
+    cache_key: ImageCacheKey,
+    image_create_fn: impl FnOnce() -> Option<Rc<CachedImage>>,
+) -> Option<Rc<CachedImage>> {
+    Some(match image_cache.entry(cache_key) {
+        std::collections::hash_map::Entry::Occupied(mut existing_entry) => {
+            match existing_entry.get().upgrade() {
+                Some(existing_image) => existing_image,
+                None => {
+                    let new_image = image_create_fn()?;
+                    existing_entry.insert(Rc::downgrade(&new_image));
+                    new_image
+                }
+            }
+        }
+        std::collections::hash_map::Entry::Vacant(vacant_entry) => {
+            let new_image = image_create_fn()?;
+            vacant_entry.insert(Rc::downgrade(&new_image));
+            new_image
+        }
+    })
+}
+
+// Removes the images from the cache that are not used by any item anymore, unless they are pinned. Pinned
+// images are moved into pinned_images, which keeps them alive until they are unpinned.
+fn prune_image_cache(
+    image_cache: &mut HashMap<ImageCacheKey, Weak<CachedImage>>,
+    pinned_images: &mut HashMap<ImageCacheKey, Rc<CachedImage>>,
+) {
+    pinned_images.retain(|key, _| is_image_pinned(key));
+    image_cache.retain(|key, cached_image_weak| {
+        cached_image_weak.upgrade().map_or(false, |cached_image_rc| {
+            if is_image_pinned(key) {
+                pinned_images.entry(key.clone()).or_insert(cached_image_rc);
+                true
+            } else {
+                Rc::strong_count(&cached_image_rc) > 1
+            }
+        })
+    });
+}
+
+// Removes the images from the cache whose key matches, or whose colorized image's original key matches, and
+// unpins them. The images are returned, so that the other references to them can be released as well. Images that
+// are still in use stay valid until their last reference is released.
+fn clear_image_cache(
+    image_cache: &mut HashMap<ImageCacheKey, Weak<CachedImage>>,
+    pinned_images: &mut HashMap<ImageCacheKey, Rc<CachedImage>>,
+    matches: impl Fn(&ImageCacheKey) -> bool,
+) -> Vec<Rc<CachedImage>> {
+    let matches = |key: &ImageCacheKey| match key {
+        ImageCacheKey::Colorized(original_key, _) => matches(original_key),
+        _ => matches(key),
+    };
+    pinned_images.retain(|key, _| !matches(key));
+    let mut cleared_images = Vec::new();
+    image_cache.retain(|key, cached_image_weak| {
+        if !matches(key) {
+            return true;
+        }
+        cleared_images.extend(cached_image_weak.upgrade());
+        false
+    });
+    cleared_images
+}
+
+// Drops the data cached for all items and marks it as outdated, so that it is created again when the items are
+// drawn the next time. The entries themselves are kept, as the items refer to them by index.
+fn invalidate_item_graphics_cache<T: Default>(cache: &mut RenderingCache<T>) {
+    invalidate_item_graphics_cache_entries(cache, |_| true)
+}
+
+// Same as invalidate_item_graphics_cache, but only for the entries whose data matches.
+fn invalidate_item_graphics_cache_entries<T: Default>(
+    cache: &mut RenderingCache<T>,
+    matches: impl Fn(&T) -> bool,
+) {
+    for (_, entry) in cache {
+        if matches(&entry.data) {
+            entry.data = T::default();
+            entry.dependency_tracker.set_dirty();
+        }
+    }
 }
 #[derive(Clone)]
 enum ItemGraphicsCacheEntry {
     Image(Rc<CachedImage>),
+    // The untinted image is kept alongside the colorized one, as it is the one used for the implicit size.
+    ColorizedImage {
+        original_image: Rc<CachedImage>,
+        colorized_image: Rc<CachedImage>,
+        colorize: Color,
+    },
+    // The image that the children of a `Layer` element with `cache_rendering_hint` were rendered into.
+    Layer(Rc<LayerCache<Rc<CachedImage>>>),
 }
 
 impl ItemGraphicsCacheEntry {
     fn as_image(&self) -> &Rc<CachedImage> {
         match self {
             ItemGraphicsCacheEntry::Image(image) => image,
-            //_ => panic!("internal error. image requested for non-image gpu data"),
+            ItemGraphicsCacheEntry::ColorizedImage { original_image, .. } => original_image,
+            _ => panic!("internal error. image requested for non-image gpu data"),
+        }
+    }
+
+    fn as_colorized_image(&self) -> &Rc<CachedImage> {
+        match self {
+            ItemGraphicsCacheEntry::Image(image) => image,
+            ItemGraphicsCacheEntry::ColorizedImage { colorized_image, .. } => colorized_image,
+            _ => panic!("internal error. image requested for non-image gpu data"),
         }
     }
-}
 
-struct FontCache(HashMap<FontCacheKey, femtovg::FontId>);
+    fn colorize(&self) -> Option<Color> {
+        match self {
+            ItemGraphicsCacheEntry::ColorizedImage { colorize, .. } => Some(*colorize),
+            _ => None,
+        }
+    }
 
-impl Default for FontCache {
-    fn default() -> Self {
-        Self(HashMap::new())
+    // Returns true if the entry holds one of the given images.
+    fn uses_any_image(&self, images: &[Rc<CachedImage>]) -> bool {
+        let contains =
+            |image: &Rc<CachedImage>| images.iter().any(|other| Rc::ptr_eq(image, other));
+        match self {
+            ItemGraphicsCacheEntry::Image(image) => contains(image),
+            ItemGraphicsCacheEntry::ColorizedImage { original_image, colorized_image, .. } => {
+                contains(original_image) || contains(colorized_image)
+            }
+            ItemGraphicsCacheEntry::Layer(_) => false,
+        }
     }
 }
 
+#[derive(Default)]
+struct FontCache {
+    fonts: HashMap<FontCacheKey, LoadedFont>,
+    // The fonts with their fallbacks, by request, so that drawing and measuring text does not need to determine
+    // the fallbacks each time.
+    resolved_fonts: HashMap<FontCacheKey, Rc<ResolvedFont>>,
+    // The application_fonts_generation() that resolved_fonts were determined with. Registering a font may change
+    // the fallbacks.
+    application_fonts_generation: usize,
+    // The requested font families that are not installed, with the family of the font used instead.
+    substitutions: BTreeMap<SharedString, SharedString>,
+}
+
+mod bitmap_strikes;
+mod color_glyphs;
+mod font_features;
+mod font_variations;
 mod fonts;
-pub use fonts::register_application_font_from_memory;
 use fonts::*;
+pub use fonts::{
+    available_font_families, register_application_font_from_memory,
+    register_application_font_from_path,
+};
 
 impl FontCache {
-    fn load_single_font(&mut self, canvas: &CanvasRc, request: &FontRequest) -> femtovg::FontId {
-        self.0
-            .entry(FontCacheKey { family: request.family.clone(), weight: request.weight.unwrap() })
+    fn load_single_font(
+        &mut self,
+        canvas: &RefCell<femtovg::Canvas<impl femtovg::Renderer>>,
+        request: &FontRequest,
+    ) -> LoadedFont {
+        let substitutions = &mut self.substitutions;
+        self.fonts
+            .entry(FontCacheKey {
+                family: request.family.clone(),
+                weight: request.weight.unwrap(),
+                style: request.style,
+                numeral_style: request.numeral_style,
+                language: request.language.clone(),
+            })
             .or_insert_with(|| {
-                try_load_app_font(canvas, &request)
-                    .unwrap_or_else(|| load_system_font(canvas, &request))
+                let font = try_load_app_font(canvas, &request)
+                    .unwrap_or_else(|| load_system_font(canvas, &request));
+                if is_substitute(request, &font) && !substitutions.contains_key(&request.family) {
+                    eprintln!(
+                        "Font family {:?} is not available, {:?} is used instead",
+                        request.family.as_str(),
+                        font.family
+                    );
+                    substitutions.insert(request.family.clone(), font.family.as_str().into());
+                }
+                font
             })
             .clone()
     }
 
-    fn font(&mut self, canvas: &CanvasRc, mut request: FontRequest, scale_factor: f32) -> GLFont {
-        request.pixel_size = request.pixel_size.or(Some(DEFAULT_FONT_SIZE * scale_factor));
-        request.weight = request.weight.or(Some(DEFAULT_FONT_WEIGHT));
-
-        let primary_font = self.load_single_font(canvas, &request);
-        let fallbacks = font_fallbacks_for_request(&request);
+    /// Returns the requested font families that were not found, each with the family of the font that is
+    /// drawn instead.
+    fn substitutions(&self) -> Vec<(SharedString, SharedString)> {
+        self.substitutions
+            .iter()
+            .map(|(requested, used)| (requested.clone(), used.clone()))
+            .collect()
+    }
 
-        let fonts = core::iter::once(primary_font)
+    /// Loads the font for the request, followed by the fonts to fall back to for missing glyphs.
+    fn font_chain(
+        &mut self,
+        canvas: &RefCell<femtovg::Canvas<impl femtovg::Renderer>>,
+        request: &FontRequest,
+    ) -> Vec<LoadedFont> {
+        let primary_font = self.load_single_font(canvas, request);
+        let fallbacks = font_fallbacks_for_request(request);
+        core::iter::once(primary_font)
             .chain(
                 fallbacks
                     .iter()
                     .map(|fallback_request| self.load_single_font(canvas, &fallback_request)),
             )
-            .collect::<Vec<_>>();
-
-        GLFont { fonts, canvas: canvas.clone(), pixel_size: request.pixel_size.unwrap() }
+            .collect()
     }
-}
 
-// glutin's WindowedContext tries to enforce being current or not. Since we need the WindowedContext's window() function
-// in the GL renderer regardless whether we're current or not, we wrap the two states back into one type.
-#[cfg(not(target_arch = "wasm32"))]
-enum WindowedContextWrapper {
-    NotCurrent(glutin::WindowedContext<glutin::NotCurrent>),
-    Current(glutin::WindowedContext<glutin::PossiblyCurrent>),
-}
-
-#[cfg(not(target_arch = "wasm32"))]
-impl WindowedContextWrapper {
-    fn window(&self) -> &winit::window::Window {
-        match self {
-            Self::NotCurrent(context) => context.window(),
-            Self::Current(context) => context.window(),
+    /// Returns the fonts that text of the request is drawn with, which are determined once per request. The
+    /// sizes of the request make no difference, and the weight needs to be set.
+    fn resolved_font(
+        &mut self,
+        canvas: &RefCell<femtovg::Canvas<impl femtovg::Renderer>>,
+        request: &FontRequest,
+    ) -> Rc<ResolvedFont> {
+        let generation = application_fonts_generation();
+        if generation != self.application_fonts_generation {
+            self.resolved_fonts.clear();
+            self.application_fonts_generation = generation;
         }
+        let key = FontCacheKey {
+            family: request.family.clone(),
+            weight: request.weight.unwrap(),
+            style: request.style,
+            numeral_style: request.numeral_style,
+            language: request.language.clone(),
+        };
+        if let Some(resolved_font) = self.resolved_fonts.get(&key) {
+            return resolved_font.clone();
+        }
+
+        let chain = self.font_chain(canvas, request);
+
+        let bitmap_strikes = chain[0].bitmap_strikes.clone();
+        // Without a matching face, slant the upright one.
+        let synthetic_oblique = request.style != FontStyle::normal && !chain[0].italic;
+        // Without a bold face, embolden the lighter one.
+        let synthetic_bold = needs_synthetic_bold(request.weight.unwrap(), chain[0].weight);
+        let color_glyphs =
+            chain.iter().filter_map(|font| Some((font.id, font.color_glyphs.clone()?))).collect();
+        let fonts = chain.iter().map(|font| font.id).collect::<Vec<_>>();
+
+        let resolved_font = Rc::new(ResolvedFont {
+            fonts,
+            bitmap_strikes,
+            color_glyphs,
+            synthetic_oblique,
+            synthetic_bold,
+        });
+        self.resolved_fonts.insert(key, resolved_font.clone());
+        resolved_font
     }
 
-    fn make_current(self) -> Self {
-        match self {
-            Self::NotCurrent(not_current_ctx) => {
-                let current_ctx = unsafe { not_current_ctx.make_current().unwrap() };
-                Self::Current(current_ctx)
-            }
-            this @ Self::Current(_) => this,
+    fn font(&mut self, canvas: &CanvasRc, mut request: FontRequest, scale_factor: f32) -> GLFont {
+        request.pixel_size = request.pixel_size.or(Some(DEFAULT_FONT_SIZE * scale_factor));
+        request.weight = request.weight.or(Some(DEFAULT_FONT_WEIGHT));
+
+        GLFont {
+            resolved: self.resolved_font(canvas, &request),
+            requested_tab_width: request.tab_width,
+            canvas: canvas.clone(),
+            pixel_size: request.pixel_size.unwrap(),
         }
     }
+}
 
-    fn make_not_current(self) -> Self {
-        match self {
-            this @ Self::NotCurrent(_) => this,
-            Self::Current(current_ctx_rc) => {
-                Self::NotCurrent(unsafe { current_ctx_rc.make_not_current().unwrap() })
-            }
+/// The error returned when no OpenGL context could be created for a window, for example on a machine without a
+/// usable OpenGL driver or in a remote desktop session. It lists the context configurations that were tried, with
+/// the reason each of them failed.
+#[derive(Debug)]
+pub struct ContextCreationError {
+    failed_attempts: Vec<(&'static str, String)>,
+}
+
+impl std::fmt::Display for ContextCreationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Could not create an OpenGL context for the window")?;
+        for (attempt, error) in &self.failed_attempts {
+            write!(f, "\n  with {}: {}", attempt, error)?;
         }
+        Ok(())
     }
+}
 
-    fn swap_buffers(&mut self) {
+impl std::error::Error for ContextCreationError {}
+
+impl ContextCreationError {
+    fn new(attempt: &'static str, error: impl std::fmt::Display) -> Self {
+        Self { failed_attempts: vec![(attempt, error.to_string())] }
+    }
+}
+
+// A configuration of the GL context to request for a window.
+#[cfg(not(target_arch = "wasm32"))]
+struct ContextAttempt {
+    description: &'static str,
+    gl_request: glutin::GlRequest,
+    profile: Option<glutin::GlProfile>,
+    srgb: bool,
+    hardware_acceleration: Option<bool>,
+}
+
+// The GL contexts to try creating, in order. When the preferred one is not available, lower versions are requested,
+// and finally a software renderer, as available with some drivers.
+#[cfg(not(target_arch = "wasm32"))]
+const CONTEXT_ATTEMPTS: &[ContextAttempt] = &[
+    ContextAttempt {
+        description: "the latest OpenGL version",
+        gl_request: glutin::GlRequest::Latest,
+        profile: None,
+        srgb: true,
+        hardware_acceleration: Some(true),
+    },
+    ContextAttempt {
+        description: "the latest OpenGL version without sRGB framebuffer",
+        gl_request: glutin::GlRequest::Latest,
+        profile: None,
+        srgb: false,
+        hardware_acceleration: Some(true),
+    },
+    ContextAttempt {
+        description: "OpenGL 3.2 core profile or OpenGL ES 3.0",
+        gl_request: glutin::GlRequest::GlThenGles {
+            opengl_version: (3, 2),
+            opengles_version: (3, 0),
+        },
+        profile: Some(glutin::GlProfile::Core),
+        srgb: false,
+        hardware_acceleration: Some(true),
+    },
+    ContextAttempt {
+        description: "OpenGL 2.1 or OpenGL ES 2.0",
+        gl_request: glutin::GlRequest::GlThenGles {
+            opengl_version: (2, 1),
+            opengles_version: (2, 0),
+        },
+        profile: Some(glutin::GlProfile::Compatibility),
+        srgb: false,
+        hardware_acceleration: Some(true),
+    },
+    ContextAttempt {
+        description: "software rendering",
+        gl_request: glutin::GlRequest::GlThenGles {
+            opengl_version: (2, 1),
+            opengles_version: (2, 0),
+        },
+        profile: None,
+        srgb: false,
+        hardware_acceleration: Some(false),
+    },
+];
+
+// Returns what the first of the attempts that succeeds creates, or the errors of all attempts if none does.
+#[cfg(not(target_arch = "wasm32"))]
+fn create_with_fallbacks<Attempt, T>(
+    attempts: impl IntoIterator<Item = (&'static str, Attempt)>,
+    mut create: impl FnMut(Attempt) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, ContextCreationError> {
+    let mut failed_attempts = Vec::new();
+    for (description, attempt) in attempts {
+        match create(attempt) {
+            Ok(created) => return Ok(created),
+            Err(err) => failed_attempts.push((description, err.to_string())),
+        }
+    }
+    Err(ContextCreationError { failed_attempts })
+}
+
+/// The OpenGL context that a GLRenderer draws with: the one of a window, or an off-screen one for rendering without
+/// a window. Everything else about rendering is the same for both.
+#[cfg(not(target_arch = "wasm32"))]
+trait GLContext {
+    fn make_current(self: Box<Self>) -> Box<dyn GLContext>;
+    fn make_not_current(self: Box<Self>) -> Box<dyn GLContext>;
+    /// Shows the drawn frame in the window, if the context has one.
+    fn swap_buffers(&mut self) -> Result<(), glutin::ContextError>;
+    /// Returns the address of the given GL function, or null if the context is not current.
+    fn get_proc_address(&self, symbol: &str) -> *const std::ffi::c_void;
+    /// Enables sRGB blending if the framebuffer supports it, see srgb::enable_framebuffer_srgb.
+    fn enable_framebuffer_srgb(&self) -> bool;
+    /// The window that the context draws into, if it has one.
+    fn window(&self) -> Option<&winit::window::Window>;
+    /// The size of the framebuffer, in physical pixels.
+    fn size(&self) -> winit::dpi::PhysicalSize<u32>;
+}
+
+// glutin's WindowedContext tries to enforce being current or not. Since we need the WindowedContext's window() function
+// in the GL renderer regardless whether we're current or not, we wrap the two states back into one type.
+#[cfg(not(target_arch = "wasm32"))]
+enum WindowedContextWrapper {
+    NotCurrent(glutin::WindowedContext<glutin::NotCurrent>),
+    Current(glutin::WindowedContext<glutin::PossiblyCurrent>),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl GLContext for WindowedContextWrapper {
+    fn make_current(self: Box<Self>) -> Box<dyn GLContext> {
+        match *self {
+            Self::NotCurrent(not_current_ctx) => {
+                let current_ctx = unsafe { not_current_ctx.make_current().unwrap() };
+                Box::new(Self::Current(current_ctx))
+            }
+            this @ Self::Current(_) => Box::new(this),
+        }
+    }
+
+    fn make_not_current(self: Box<Self>) -> Box<dyn GLContext> {
+        match *self {
+            this @ Self::NotCurrent(_) => Box::new(this),
+            Self::Current(current_ctx_rc) => {
+                Box::new(Self::NotCurrent(unsafe { current_ctx_rc.make_not_current().unwrap() }))
+            }
+        }
+    }
+
+    fn swap_buffers(&mut self) -> Result<(), glutin::ContextError> {
+        match self {
+            WindowedContextWrapper::NotCurrent(_) => Ok(()),
+            WindowedContextWrapper::Current(current_ctx) => current_ctx.swap_buffers(),
+        }
+    }
+
+    fn get_proc_address(&self, symbol: &str) -> *const std::ffi::c_void {
+        match self {
+            WindowedContextWrapper::NotCurrent(_) => std::ptr::null(),
+            WindowedContextWrapper::Current(current_ctx) => current_ctx.get_proc_address(symbol),
+        }
+    }
+
+    fn enable_framebuffer_srgb(&self) -> bool {
         match self {
-            WindowedContextWrapper::NotCurrent(_) => {}
+            WindowedContextWrapper::NotCurrent(_) => false,
             WindowedContextWrapper::Current(current_ctx) => {
-                current_ctx.swap_buffers().unwrap();
+                srgb::enable_framebuffer_srgb(current_ctx)
             }
         }
     }
+
+    fn window(&self) -> Option<&winit::window::Window> {
+        match self {
+            Self::NotCurrent(context) => Some(context.window()),
+            Self::Current(context) => Some(context.window()),
+        }
+    }
+
+    fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.window().unwrap().inner_size()
+    }
 }
 
 struct GLRendererData {
@@ -260,7 +1258,7 @@ struct GLRendererData {
     #[cfg(target_arch = "wasm32")]
     window: Rc<winit::window::Window>,
     #[cfg(not(target_arch = "wasm32"))]
-    windowed_context: RefCell<Option<WindowedContextWrapper>>,
+    gl_context: RefCell<Option<Box<dyn GLContext>>>,
     #[cfg(target_arch = "wasm32")]
     event_loop_proxy: Rc<winit::event_loop::EventLoopProxy<eventloop::CustomEvent>>,
     item_graphics_cache: RefCell<RenderingCache<Option<ItemGraphicsCacheEntry>>>,
@@ -268,11 +1266,140 @@ struct GLRendererData {
     // Cache used to avoid repeatedly decoding images from disk. The weak references are
     // drained after flushing the renderer commands to the screen.
     image_cache: RefCell<HashMap<ImageCacheKey, Weak<CachedImage>>>,
+    // Strong references to the images in the cache that are pinned, so they survive when the last
+    // item showing them goes away.
+    pinned_images: RefCell<HashMap<ImageCacheKey, Rc<CachedImage>>>,
+    // The textures that are evicted from the GPU when the texture memory budget is exceeded.
+    texture_cache: RefCell<TextureCache>,
+    // The images loaded for the current frame while the image cache is disabled. They are released once the
+    // frame is flushed, as the GPU may still need them until then.
+    frame_images: RefCell<Vec<ItemGraphicsCacheEntry>>,
+    // The images decoded ahead of the first frame by GLRenderer::warm_up. They are kept until a frame was
+    // drawn, after which the items showing them hold on to them.
+    warmed_up_images: RefCell<Vec<ItemGraphicsCacheEntry>>,
+    // The images decoded in the background by GLRenderer::preload_image. They are kept until an item shows them.
+    preloaded_images: RefCell<Vec<Rc<CachedImage>>>,
 
     loaded_fonts: RefCell<FontCache>,
+
+    // When set, windows with a fractional scale factor (such as 1.5) render their content at the next
+    // integer scale factor into an off-screen image, which is then downsampled to the window. Geometry
+    // continues to use the fractional factor, but text and images are rasterized more crisply.
+    crisp_fractional_scaling: bool,
+    // The off-screen image that frames are rendered into when oversampling, or when retaining the frame.
+    frame_target: RefCell<Option<femtovg::ImageId>>,
+    // Set once parts of the window were requested to be redrawn. From then on, frames are rendered into the
+    // frame_target, which retains the previous frame outside of the damaged regions, and copied to the window.
+    partial_redraw_enabled: std::cell::Cell<bool>,
+
+    // The fraction of the window width and height that is left empty at each edge, for TVs that don't show the
+    // edges of the picture (overscan). The content is scaled down to fit the rest. Set with the
+    // SIXTYFPS_OVERSCAN_MARGIN environment variable, in percent.
+    overscan_margin: f32,
+    // Set with GLRenderer::set_mirrored for right-to-left locales: the content is drawn flipped horizontally about
+    // the middle of the window, except for text and images, which are only placed where they are mirrored to.
+    mirrored: std::cell::Cell<bool>,
+
+    // Text is drawn with the glyph bitmaps embedded in fonts, for the sizes they have them, unless the
+    // SIXTYFPS_NO_BITMAP_STRIKES environment variable is set.
+    use_bitmap_strikes: bool,
+    // When the SIXTYFPS_IMAGE_PLACEHOLDERS environment variable is set, images that failed to load or are still
+    // loading are drawn as a gray box with a cross, to spot missing images during development.
+    show_image_placeholders: bool,
+    // When the SIXTYFPS_PIXEL_SNAPPING environment variable is set, the edges of rectangles and the baselines of
+    // text are rounded to device pixels, so that thin borders and small text are not blurred across two pixels.
+    // It is off by default, as moving content then jumps from pixel to pixel.
+    pixel_snapping: bool,
+    // Set when the framebuffer converts the colors written to it from linear intensities to sRGB, so that they
+    // are blended in linear space. Colors and images are then converted to linear intensities for drawing.
+    srgb_blending: bool,
+    // Set when the window was created with the `transparent-background` property of the `Window` element, so that
+    // the desktop shows through. The window is then cleared with premultiplied colors, which the compositor
+    // expects.
+    transparent_window: bool,
+
+    // The glyph bitmaps uploaded to the GPU, in the color of the text they were drawn for.
+    bitmap_glyph_cache: RefCell<HashMap<BitmapGlyphCacheKey, Option<BitmapGlyphImage>>>,
+    // The color glyph images uploaded to the GPU, in the size of the strike they come from.
+    color_glyph_cache: RefCell<HashMap<ColorGlyphCacheKey, Option<BitmapGlyphImage>>>,
+    // The conic gradients rasterized into images, as femtovg only draws linear and radial gradients. None if the
+    // stops of the gradient can't be parsed, which is reported once.
+    conic_gradient_cache: RefCell<HashMap<ConicGradientCacheKey, Option<femtovg::ImageId>>>,
+
+    // SVG images rendered at the size they are drawn at, by the key of the image in the image_cache. They are
+    // dropped together with the image when it gets pruned from the image_cache.
+    #[cfg(feature = "svg")]
+    svg_rasterizations: RefCell<HashMap<ImageCacheKey, SvgRasterizations>>,
+
+    // The images that are uploaded with other flags than the image in the image_cache, by its key and the flags: the
+    // images of items with `image-rendering: pixelated`, sampled with the nearest pixel, and images that are drawn
+    // much smaller than they are, with mipmaps. None if the image can't be uploaded separately. They are dropped
+    // together with the image when it gets pruned from the image_cache.
+    sampled_images: RefCell<HashMap<(ImageCacheKey, femtovg::ImageFlags), Option<Rc<CachedImage>>>>,
+    // The size at which pixelated images were drawn, to draw them smooth while it changes.
+    image_scales: RefCell<ImageScaleTracker>,
+
+    // The maximum width and height of textures supported by the GL implementation. Larger images are downscaled
+    // to fit when they are uploaded.
+    max_texture_size: u32,
+
+    // Set when the SIXTYFPS_WATCH_IMAGES environment variable is set, to load the images in the image_cache again
+    // when their files change on disk.
+    #[cfg(not(target_arch = "wasm32"))]
+    image_file_watcher: RefCell<Option<image_file_watcher::FileWatcher>>,
+
+    // Set when the GL context was lost, for example after a GPU reset or when the browser reclaimed the WebGL
+    // context. The canvas is then recreated before the next frame, and all images are uploaded again.
+    context_lost: std::cell::Cell<bool>,
 }
 
 impl GLRendererData {
+    // Returns the color to paint with, to draw the given color into the framebuffer.
+    fn paint_color(&self, color: Color) -> femtovg::Color {
+        if self.srgb_blending {
+            srgb::linear_color(color)
+        } else {
+            color.into()
+        }
+    }
+
+    // Drops all images, glyphs and fonts that live in the canvas, after the GL context was lost. The images of
+    // the items are decoded again from their source when drawn the next time, and evictable images are uploaded
+    // again from the pixels they keep on the CPU side. This must be called before the canvas is replaced, so that
+    // the stale images are deleted from the canvas they were created in.
+    fn release_gpu_resources(&self) {
+        invalidate_item_graphics_cache(&mut self.item_graphics_cache.borrow_mut());
+        self.pinned_images.borrow_mut().clear();
+        self.image_cache.borrow_mut().clear();
+        self.frame_images.borrow_mut().clear();
+        self.warmed_up_images.borrow_mut().clear();
+        self.preloaded_images.borrow_mut().clear();
+        #[cfg(feature = "svg")]
+        self.svg_rasterizations.borrow_mut().clear();
+        self.sampled_images.borrow_mut().clear();
+        for texture in self.texture_cache.borrow().textures.iter().filter_map(Weak::upgrade) {
+            texture.evict();
+        }
+
+        let mut canvas = self.canvas.borrow_mut();
+        for image in self.bitmap_glyph_cache.borrow_mut().drain().filter_map(|(_, image)| image) {
+            canvas.delete_image(image.id);
+        }
+        for image in self.color_glyph_cache.borrow_mut().drain().filter_map(|(_, image)| image) {
+            canvas.delete_image(image.id);
+        }
+        for image_id in self.conic_gradient_cache.borrow_mut().drain().filter_map(|(_, id)| id) {
+            canvas.delete_image(image_id);
+        }
+        if let Some(image_id) = self.frame_target.take() {
+            canvas.delete_image(image_id);
+        }
+        // The fonts are registered with the canvas, so they are loaded again into the new one.
+        let mut loaded_fonts = self.loaded_fonts.borrow_mut();
+        loaded_fonts.fonts.clear();
+        loaded_fonts.resolved_fonts.clear();
+    }
+
     #[cfg(target_arch = "wasm32")]
     fn load_html_image(&self, url: &str) -> Rc<CachedImage> {
         let image_id = self
@@ -319,6 +1446,7 @@ fn load_html_image(&self, url: &str) -> Rc<CachedImage> {
                             html_image.width() as usize,
                             html_image.height() as usize,
                             femtovg::PixelFormat::Rgba8,
+                            // The browser uploads the pixels it decoded with straight alpha.
                             femtovg::ImageFlags::empty(),
                         )
                         .unwrap();
@@ -336,72 +1464,191 @@ fn load_html_image(&self, url: &str) -> Rc<CachedImage> {
             })
             .into(),
         ));
+        html_image.set_onerror(Some(
+            &wasm_bindgen::closure::Closure::once_into_js({
+                let cached_image_weak = Rc::downgrade(&cached_image);
+                let window_weak = Rc::downgrade(&self.window);
+                move || {
+                    if let Some(cached_image) = cached_image_weak.upgrade() {
+                        cached_image.notify_failed();
+                    }
+                    if let Some(window) = window_weak.upgrade() {
+                        window.request_redraw();
+                    }
+                }
+            })
+            .into(),
+        ));
         html_image.set_src(&url);
 
         cached_image
     }
 
-    // Look up the given image cache key in the image cache and upgrade the weak reference to a strong one if found,
-    // otherwise a new image is created/loaded from the given callback.
+    // Drops the cached images whose key matches, see clear_image_cache(), together with the images derived from them
+    // and the entries of the items showing them, so that they are loaded again from their source when drawn the next
+    // time.
+    fn clear_cached_images(&self, matches: impl Fn(&ImageCacheKey) -> bool) {
+        let matches = &matches;
+        let cleared_images = clear_image_cache(
+            &mut self.image_cache.borrow_mut(),
+            &mut self.pinned_images.borrow_mut(),
+            matches,
+        );
+        #[cfg(feature = "svg")]
+        self.svg_rasterizations.borrow_mut().retain(|key, _| !matches(key));
+        self.sampled_images.borrow_mut().retain(|(key, _), _| !matches(key));
+        self.preloaded_images
+            .borrow_mut()
+            .retain(|image| !cleared_images.iter().any(|cleared| Rc::ptr_eq(cleared, image)));
+        self.warmed_up_images.borrow_mut().retain(|entry| !entry.uses_any_image(&cleared_images));
+        invalidate_item_graphics_cache_entries(
+            &mut self.item_graphics_cache.borrow_mut(),
+            |entry: &Option<ItemGraphicsCacheEntry>| {
+                entry.as_ref().map_or(false, |entry| entry.uses_any_image(&cleared_images))
+            },
+        );
+    }
+
     fn lookup_image_in_cache_or_create(
         &self,
         cache_key: ImageCacheKey,
-        image_create_fn: impl Fn() -> Rc<CachedImage>,
-    ) -> Rc<CachedImage> {
-        match self.image_cache.borrow_mut().entry(cache_key) {
-            std::collections::hash_map::Entry::Occupied(mut existing_entry) => {
-                existing_entry.get().upgrade().unwrap_or_else(|| {
-                    let new_image = image_create_fn();
-                    existing_entry.insert(Rc::downgrade(&new_image));
-                    new_image
-                })
+        image_create_fn: impl Fn() -> Option<Rc<CachedImage>>,
+    ) -> Option<Rc<CachedImage>> {
+        lookup_image_in_cache_or_create(
+            &mut self.image_cache.borrow_mut(),
+            cache_key,
+            image_create_fn,
+        )
+    }
+
+    /// Returns the factor by which the scene is oversampled for the given window scale factor, if crisp
+    /// fractional scaling is enabled and the scale factor is not an integer.
+    fn supersampling_factor(&self, scale_factor: f32) -> Option<f32> {
+        if !self.crisp_fractional_scaling || scale_factor.fract() == 0. {
+            return None;
+        }
+        Some(scale_factor.ceil() / scale_factor)
+    }
+
+    // Returns the off-screen image that frames are rendered into, (re-)allocating it to the given size if
+    // needed. Also returns whether the image still has the content of the previous frame.
+    fn ensure_frame_target(&self, width: usize, height: usize) -> (femtovg::ImageId, bool) {
+        let mut canvas = self.canvas.borrow_mut();
+        let mut target = self.frame_target.borrow_mut();
+        let flags = femtovg::ImageFlags::FLIP_Y | femtovg::ImageFlags::PREMULTIPLIED;
+        match *target {
+            Some(image_id) => {
+                if canvas.image_size(image_id).ok() != Some((width, height)) {
+                    canvas
+                        .realloc_image(image_id, width, height, femtovg::PixelFormat::Rgba8, flags)
+                        .unwrap();
+                    return (image_id, false);
+                }
+                (image_id, true)
             }
-            std::collections::hash_map::Entry::Vacant(vacant_entry) => {
-                let new_image = image_create_fn();
-                vacant_entry.insert(Rc::downgrade(&new_image));
-                new_image
+            None => {
+                let image_id = canvas
+                    .create_image_empty(width, height, femtovg::PixelFormat::Rgba8, flags)
+                    .unwrap();
+                *target = Some(image_id);
+                (image_id, false)
             }
         }
     }
 
     // Try to load the image the given resource points to
     fn load_image_resource(&self, resource: Resource) -> Option<ItemGraphicsCacheEntry> {
-        Some(ItemGraphicsCacheEntry::Image(match resource {
+        let cache_key = match &resource {
             Resource::None => return None,
-            Resource::AbsoluteFilePath(path) => {
-                self.lookup_image_in_cache_or_create(ImageCacheKey::Path(path.to_string()), || {
-                    #[cfg(not(target_arch = "wasm32"))]
-                    {
-                        #[cfg(feature = "svg")]
-                        if path.ends_with(".svg") {
-                            return Rc::new(CachedImage::new_on_cpu(
-                                svg::load_from_path(std::path::Path::new(&path.as_str())).unwrap(),
-                            ));
-                        }
-                        Rc::new(CachedImage::new_on_cpu(
-                            image::open(std::path::Path::new(&path.as_str())).unwrap(),
-                        ))
-                    }
-                    #[cfg(target_arch = "wasm32")]
-                    self.load_html_image(&path)
-                })
+            // Images made of pixels are not shared between items, as comparing their pixels costs about as
+            // much as converting them.
+            Resource::EmbeddedRgbaImage { .. } => {
+                return decode_image_resource(&resource)
+                    .map(|image| ItemGraphicsCacheEntry::Image(Rc::new(image)))
             }
-            Resource::EmbeddedData(data) => self.lookup_image_in_cache_or_create(
-                ImageCacheKey::EmbeddedData(by_address::ByAddress(data.as_slice())),
-                || {
-                    #[cfg(feature = "svg")]
-                    if data.starts_with(b"<svg") {
-                        return Rc::new(CachedImage::new_on_cpu(
-                            svg::load_from_data(data.as_slice()).unwrap(),
-                        ));
-                    }
-                    Rc::new(CachedImage::new_on_cpu(
-                        image::load_from_memory(data.as_slice()).unwrap(),
-                    ))
-                },
-            ),
-            Resource::EmbeddedRgbaImage { .. } => todo!(),
-        }))
+            _ => ImageCacheKey::new(&resource)?,
+        };
+        self.lookup_image_in_cache_or_create(cache_key, || {
+            #[cfg(target_arch = "wasm32")]
+            if let Resource::AbsoluteFilePath(path) = &resource {
+                return Some(self.load_html_image(&path));
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Resource::AbsoluteFilePath(path) = &resource {
+                if remote_image::is_remote_url(path) {
+                    return Some(load_remote_image(path));
+                }
+            }
+            decode_image_resource(&resource).map(Rc::new)
+        })
+        .map(ItemGraphicsCacheEntry::Image)
+    }
+
+    // Like load_image_resource, but also provides a copy of the image that is tinted with the given color,
+    // unless it is transparent.
+    fn load_colorized_image_resource(
+        &self,
+        resource: Resource,
+        colorize: Color,
+    ) -> Option<ItemGraphicsCacheEntry> {
+        let entry = self.load_image_resource(resource.clone())?;
+        if colorize.alpha() == 0 {
+            return Some(entry);
+        }
+        // FIXME: Images loaded by the browser are not available on the CPU side, so they can't be colorized.
+        #[cfg(target_arch = "wasm32")]
+        if let Resource::AbsoluteFilePath(_) = &resource {
+            return Some(entry);
+        }
+        // FIXME: Remote images are not colorized yet, as their data arrives only later.
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Resource::AbsoluteFilePath(path) = &resource {
+            if remote_image::is_remote_url(path) {
+                return Some(entry);
+            }
+        }
+
+        let create_colorized_image = || {
+            let image = decode_image_resource(&resource)?;
+            image.colorize(colorize);
+            Some(Rc::new(image))
+        };
+        let colorized_image = match ImageCacheKey::new(&resource) {
+            Some(key) => self.lookup_image_in_cache_or_create(
+                ImageCacheKey::Colorized(Box::new(key), colorize.as_argb_encoded()),
+                create_colorized_image,
+            )?,
+            None => create_colorized_image()?,
+        };
+        Some(ItemGraphicsCacheEntry::ColorizedImage {
+            original_image: entry.as_image().clone(),
+            colorized_image,
+            colorize,
+        })
+    }
+
+    // When the image cache is disabled, the image is loaded with the load function for every frame instead of being
+    // kept in the item's rendering cache. Returns None if the image cache is enabled, or if the image is kept
+    // nevertheless because loading it again would restart its animation or its download.
+    fn load_image_for_frame(
+        &self,
+        item_cache: &CachedRenderingData,
+        resource: &Resource,
+        load: impl FnOnce() -> Option<ItemGraphicsCacheEntry>,
+    ) -> Option<Option<ItemGraphicsCacheEntry>> {
+        if !IMAGE_CACHE_DISABLED.with(|disabled| disabled.get())
+            || is_loaded_asynchronously(resource)
+        {
+            return None;
+        }
+        let entry = load();
+        if entry.as_ref().map_or(false, |entry| entry.as_image().is_animated()) {
+            return None;
+        }
+        // Release what was cached before the image cache got disabled.
+        item_cache.release(&mut self.item_graphics_cache.borrow_mut());
+        self.frame_images.borrow_mut().extend(entry.clone());
+        Some(entry)
     }
 
     // Load the image from the specified Resource property (via getter fn), unless it was cached in the item's rendering
@@ -409,8 +1656,15 @@ fn load_image_resource(&self, resource: Resource) -> Option<ItemGraphicsCacheEnt
     fn load_cached_item_image(
         &self,
         item_cache: &CachedRenderingData,
-        source_property_getter: impl FnOnce() -> Resource,
+        source_property_getter: impl Fn() -> Resource,
     ) -> Option<Rc<CachedImage>> {
+        let resource = source_property_getter();
+        if let Some(entry) = self.load_image_for_frame(item_cache, &resource, || {
+            self.load_image_resource(resource.clone())
+        }) {
+            return entry.map(|entry| entry.as_image().clone());
+        }
+
         let mut cache = self.item_graphics_cache.borrow_mut();
         item_cache
             .ensure_up_to_date(&mut cache, || self.load_image_resource(source_property_getter()))
@@ -419,6 +1673,276 @@ fn load_cached_item_image(
                 image.clone()
             })
     }
+
+    // Same as load_cached_item_image, but returns the image tinted with the color of the colorize property.
+    fn load_cached_colorized_item_image(
+        &self,
+        item_cache: &CachedRenderingData,
+        source_property_getter: impl Fn() -> Resource,
+        colorize_property_getter: impl Fn() -> Color,
+    ) -> Option<Rc<CachedImage>> {
+        let resource = source_property_getter();
+        if let Some(entry) = self.load_image_for_frame(item_cache, &resource, || {
+            self.load_colorized_image_resource(resource.clone(), colorize_property_getter())
+        }) {
+            return entry.map(|entry| entry.as_colorized_image().clone());
+        }
+
+        let mut cache = self.item_graphics_cache.borrow_mut();
+        let load = || {
+            self.load_colorized_image_resource(source_property_getter(), colorize_property_getter())
+        };
+        let mut entry = item_cache.ensure_up_to_date(&mut cache, &load);
+        // The entry may have been created for querying the image size, which doesn't depend on the colorize
+        // property.
+        let colorize = Some(colorize_property_getter()).filter(|color| color.alpha() > 0);
+        if entry.is_some() && entry.as_ref().and_then(|entry| entry.colorize()) != colorize {
+            item_cache.release(&mut cache);
+            entry = item_cache.ensure_up_to_date(&mut cache, &load);
+        }
+        entry.map(|gpu_resource| gpu_resource.as_colorized_image().clone())
+    }
+
+    // Returns the SVG image the resource refers to, rendered at the given pixel size and tinted with the colorize
+    // color. Returns None if the resource is not an SVG image.
+    #[cfg(feature = "svg")]
+    fn load_svg_rasterization(
+        &self,
+        resource: &Resource,
+        colorize: Color,
+        size: (u32, u32),
+    ) -> Option<Rc<CachedImage>> {
+        let cache_key = svg_cache_key(resource, colorize)?;
+        self.svg_rasterizations.borrow_mut().entry(cache_key).or_default().get_or_rasterize(
+            size,
+            || {
+                let image = rasterize_svg_resource(resource, Some(size))?;
+                if colorize.alpha() > 0 {
+                    image.colorize(colorize);
+                }
+                Some(image)
+            },
+        )
+    }
+
+    // Returns how the SVG image the resource refers to fits into the area it is drawn in, as specified in the SVG
+    // file. It is read once for the image in the image cache. Returns None if the resource is not an SVG image.
+    #[cfg(feature = "svg")]
+    fn svg_aspect_ratio(&self, resource: &Resource, colorize: Color) -> Option<svg::AspectRatio> {
+        let cache_key = svg_cache_key(resource, colorize)?;
+        let mut svg_rasterizations = self.svg_rasterizations.borrow_mut();
+        let rasterizations = svg_rasterizations.entry(cache_key).or_default();
+        if rasterizations.aspect_ratio.is_none() {
+            rasterizations.aspect_ratio = Some(svg_resource_aspect_ratio(resource));
+        }
+        rasterizations.aspect_ratio.unwrap()
+    }
+
+    // Returns the image the resource refers to, tinted with the colorize color, for uploading it with the given
+    // flags. Returns None for images that are not decoded from a file or embedded data, and for SVG and animated
+    // images.
+    fn load_sampled_image(
+        &self,
+        resource: &Resource,
+        colorize: Color,
+        flags: femtovg::ImageFlags,
+    ) -> Option<Rc<CachedImage>> {
+        #[cfg(feature = "svg")]
+        if is_svg_resource(resource) {
+            return None;
+        }
+        if is_loaded_asynchronously(resource) {
+            return None;
+        }
+        let mut cache_key = ImageCacheKey::new(resource)?;
+        if colorize.alpha() > 0 {
+            cache_key = ImageCacheKey::Colorized(Box::new(cache_key), colorize.as_argb_encoded());
+        }
+        self.sampled_images
+            .borrow_mut()
+            .entry((cache_key, flags))
+            .or_insert_with(|| {
+                let image = decode_image_resource(resource).filter(|image| !image.is_animated())?;
+                if colorize.alpha() > 0 {
+                    image.colorize(colorize);
+                }
+                Some(Rc::new(image))
+            })
+            .clone()
+    }
+}
+
+// Starts loading the image from the given http:// or https:// URL. The event loop is woken up when the data
+// has arrived, to decode the image and redraw the windows.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_remote_image(url: &str) -> Rc<CachedImage> {
+    fetch_in_background(|notify| Some(CachedImage::new_remote(url, notify))).unwrap()
+}
+
+// Creates an image whose data arrives from a background thread, with the given function that takes the function
+// to call from the background thread when the data has arrived. That wakes up the event loop, to take the image and
+// redraw the windows.
+#[cfg(not(target_arch = "wasm32"))]
+fn fetch_in_background(
+    create: impl FnOnce(Box<dyn FnOnce() + Send>) -> Option<CachedImage>,
+) -> Option<Rc<CachedImage>> {
+    let event_loop_proxy =
+        crate::eventloop::with_window_target(|event_loop| event_loop.event_loop_proxy().clone());
+    let image = Rc::new(create(Box::new(move || {
+        event_loop_proxy.send_event(eventloop::CustomEvent::ImageFetched).ok();
+    }))?);
+    FETCHING_IMAGES.with(|images| images.borrow_mut().push(Rc::downgrade(&image)));
+    Some(image)
+}
+
+// Decodes the image the given file path or embedded data resource refers to. If the file can't be read or
+// the data can't be decoded, a warning is printed and None is returned.
+fn decode_image_resource(resource: &Resource) -> Option<CachedImage> {
+    #[cfg(feature = "svg")]
+    if is_svg_resource(resource) {
+        return rasterize_svg_resource(resource, None);
+    }
+    let result = match resource {
+        Resource::AbsoluteFilePath(path) => std::fs::read(std::path::Path::new(&path.as_str()))
+            .map_err(|err| err.to_string())
+            .and_then(|data| CachedImage::new_from_data(&data).map_err(|err| err.to_string())),
+        Resource::EmbeddedData(data) => {
+            CachedImage::new_from_data(data.as_slice()).map_err(|err| err.to_string())
+        }
+        Resource::EmbeddedRgbaImage { width, height, data } => {
+            CachedImage::new_from_argb(*width, *height, data.as_slice())
+                .ok_or_else(|| "the amount of pixels does not match the size of the image".into())
+        }
+        Resource::None => unreachable!("internal error: resource without image data"),
+    };
+    result.map_err(|err| report_image_load_error(resource, err)).ok()
+}
+
+fn report_image_load_error(resource: &Resource, error: impl std::fmt::Display) {
+    match resource {
+        Resource::AbsoluteFilePath(path) => eprintln!("Error loading image {}: {}", path, error),
+        _ => eprintln!("Error loading embedded image: {}", error),
+    }
+}
+
+// Returns true if the resource refers to an SVG image that is rasterized by resvg.
+#[cfg(feature = "svg")]
+fn is_svg_resource(resource: &Resource) -> bool {
+    match resource {
+        // Browsers load SVG files like any other image
+        #[cfg(not(target_arch = "wasm32"))]
+        Resource::AbsoluteFilePath(path) => {
+            path.ends_with(".svg") && !remote_image::is_remote_url(path)
+        }
+        Resource::EmbeddedData(data) => data.starts_with(b"<svg"),
+        _ => false,
+    }
+}
+
+// Renders the SVG image the resource refers to at the given pixel size, or at its intrinsic size. Returns None if the
+// resource is not an SVG image, or if it fails to load.
+// Returns the key of the SVG image the resource refers to, tinted with the colorize color, in the image cache, or
+// None if the resource is not an SVG image.
+#[cfg(feature = "svg")]
+fn svg_cache_key(resource: &Resource, colorize: Color) -> Option<ImageCacheKey> {
+    if !is_svg_resource(resource) {
+        return None;
+    }
+    let cache_key = ImageCacheKey::new(resource)?;
+    if colorize.alpha() > 0 {
+        Some(ImageCacheKey::Colorized(Box::new(cache_key), colorize.as_argb_encoded()))
+    } else {
+        Some(cache_key)
+    }
+}
+
+// Reads how the SVG image fits into the area it is drawn in. Returns None if the resource is not an SVG image or
+// can't be read, in which case it is drawn like any other image.
+#[cfg(feature = "svg")]
+fn svg_resource_aspect_ratio(resource: &Resource) -> Option<svg::AspectRatio> {
+    match resource {
+        #[cfg(not(target_arch = "wasm32"))]
+        Resource::AbsoluteFilePath(path) if is_svg_resource(resource) => {
+            svg::aspect_ratio_from_path(std::path::Path::new(&path.as_str())).ok()
+        }
+        Resource::EmbeddedData(data) if is_svg_resource(resource) => {
+            svg::aspect_ratio_from_data(data.as_slice()).ok()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(feature = "svg")]
+fn rasterize_svg_resource(resource: &Resource, size: Option<(u32, u32)>) -> Option<CachedImage> {
+    if !is_svg_resource(resource) {
+        return None;
+    }
+    let result = match resource {
+        #[cfg(not(target_arch = "wasm32"))]
+        Resource::AbsoluteFilePath(path) => {
+            svg::load_from_path(std::path::Path::new(&path.as_str()), size)
+        }
+        Resource::EmbeddedData(data) => svg::load_from_data(data.as_slice(), size),
+        _ => unreachable!(),
+    };
+    result.map(CachedImage::new_on_cpu).map_err(|err| report_image_load_error(resource, err)).ok()
+}
+
+/// How many sizes of an SVG image are kept around, so that animating an image between a few sizes
+/// doesn't rasterize it for every frame.
+#[cfg(feature = "svg")]
+const MAX_SVG_RASTERIZATIONS: usize = 4;
+
+/// The rasterizations of an SVG image at the pixel sizes it was recently drawn at, with the most recently
+/// used one last, along with what is known about the image independently of its size.
+#[cfg(feature = "svg")]
+#[derive(Default)]
+struct SvgRasterizations {
+    rasterizations: Vec<((u32, u32), Rc<CachedImage>)>,
+    /// How the image fits into the area it is drawn in, once it was read from the SVG file.
+    aspect_ratio: Option<Option<svg::AspectRatio>>,
+}
+
+#[cfg(feature = "svg")]
+impl SvgRasterizations {
+    fn get_or_rasterize(
+        &mut self,
+        size: (u32, u32),
+        rasterize: impl FnOnce() -> Option<CachedImage>,
+    ) -> Option<Rc<CachedImage>> {
+        let rasterizations = &mut self.rasterizations;
+        let image = match rasterizations.iter().position(|(cached_size, _)| *cached_size == size) {
+            Some(index) => rasterizations.remove(index).1,
+            None => Rc::new(rasterize()?),
+        };
+        if rasterizations.len() >= MAX_SVG_RASTERIZATIONS {
+            rasterizations.remove(0);
+        }
+        rasterizations.push((size, image.clone()));
+        Some(image)
+    }
+}
+
+// Multiplies the pixels with the given color. Images where all visible pixels are black, such as icons that
+// only carry an alpha channel, take the color itself and keep their alpha.
+fn colorize_pixels(pixels: &mut image::RgbaImage, color: Color) {
+    let color = color.to_argb_u8();
+    let is_alpha_mask = pixels.pixels().all(|pixel| pixel[3] == 0 || pixel.0[..3] == [0, 0, 0]);
+    let multiply =
+        |component: u8, factor: u8| ((component as u32 * factor as u32 + 127) / 255) as u8;
+    for pixel in pixels.pixels_mut() {
+        let [red, green, blue, alpha] = pixel.0;
+        *pixel = if is_alpha_mask {
+            image::Rgba([color.red, color.green, color.blue, multiply(alpha, color.alpha)])
+        } else {
+            image::Rgba([
+                multiply(red, color.red),
+                multiply(green, color.green),
+                multiply(blue, color.blue),
+                multiply(alpha, color.alpha),
+            ])
+        };
+    }
 }
 
 pub struct GLRenderer {
@@ -430,19 +1954,41 @@ pub(crate) fn new(
         event_loop: &dyn crate::eventloop::EventLoopInterface,
         window_builder: winit::window::WindowBuilder,
         #[cfg(target_arch = "wasm32")] canvas_id: &str,
-    ) -> GLRenderer {
+    ) -> Result<GLRenderer, ContextCreationError> {
+        let transparent_window = window_builder.window.transparent;
         #[cfg(not(target_arch = "wasm32"))]
-        let (windowed_context, renderer) = {
-            let windowed_context = glutin::ContextBuilder::new()
-                .with_vsync(true)
-                .build_windowed(window_builder, event_loop.event_loop_target())
-                .unwrap();
-            let windowed_context = unsafe { windowed_context.make_current().unwrap() };
+        let (windowed_context, renderer, srgb_blending) = {
+            let attempts = CONTEXT_ATTEMPTS.iter().map(|attempt| (attempt.description, attempt));
+            let (windowed_context, renderer) = create_with_fallbacks(attempts, |attempt| {
+                let mut context_builder = glutin::ContextBuilder::new()
+                    .with_vsync(true)
+                    .with_gl(attempt.gl_request)
+                    .with_srgb(attempt.srgb)
+                    .with_hardware_acceleration(attempt.hardware_acceleration)
+                    // The alpha channel is what transparent windows are composited with.
+                    .with_pixel_format(24, 8);
+                if let Some(profile) = attempt.profile {
+                    context_builder = context_builder.with_gl_profile(profile);
+                }
+                let windowed_context = context_builder
+                    .build_windowed(window_builder.clone(), event_loop.event_loop_target())?;
+                let windowed_context =
+                    unsafe { windowed_context.make_current().map_err(|(_, err)| err)? };
+                let renderer = femtovg::renderer::OpenGl::new(|symbol| {
+                    windowed_context.get_proc_address(symbol) as *const _
+                })?;
+                Ok((windowed_context, renderer))
+            })?;
+
+            // When the SIXTYFPS_ADAPTIVE_SYNC environment variable is set, frames that miss the vertical blank
+            // are presented right away instead of waiting for the next one, for variable refresh rate displays.
+            if std::env::var("SIXTYFPS_ADAPTIVE_SYNC").is_ok() {
+                adaptive_sync::enable(&windowed_context);
+            }
 
-            let renderer = femtovg::renderer::OpenGl::new(|symbol| {
-                windowed_context.get_proc_address(symbol) as *const _
-            })
-            .unwrap();
+            // When the SIXTYFPS_SRGB_BLENDING environment variable is set, colors are blended in linear space.
+            let srgb_blending = std::env::var("SIXTYFPS_SRGB_BLENDING").is_ok()
+                && srgb::enable_framebuffer_srgb(&windowed_context);
 
             #[cfg(target_os = "macos")]
             {
@@ -455,7 +2001,7 @@ pub(crate) fn new(
                 }
             }
 
-            (windowed_context, renderer)
+            (windowed_context, renderer, srgb_blending)
         };
 
         #[cfg(target_arch = "wasm32")]
@@ -523,6 +2069,40 @@ pub(crate) fn new(
                 .unwrap();
             resize_closure.forget();
 
+            // The browser only restores a lost WebGL context if the loss is acknowledged by preventing the
+            // default handling of the event. Once restored, the window is redrawn with a new renderer.
+            let context_lost_closure =
+                wasm_bindgen::closure::Closure::wrap(Box::new(|event: web_sys::Event| {
+                    event.prevent_default()
+                }) as Box<dyn FnMut(_)>);
+            let context_restored_closure = wasm_bindgen::closure::Closure::wrap(Box::new({
+                let event_loop_proxy = event_loop_proxy.clone();
+                let window = window.clone();
+                move |_: web_sys::Event| {
+                    window.request_redraw();
+                    event_loop_proxy.send_event(eventloop::CustomEvent::WakeUpAndPoll).ok();
+                }
+            })
+                as Box<dyn FnMut(_)>);
+            {
+                use winit::platform::web::WindowExtWebSys;
+                let canvas = window.canvas();
+                canvas
+                    .add_event_listener_with_callback(
+                        "webglcontextlost",
+                        context_lost_closure.as_ref().unchecked_ref(),
+                    )
+                    .unwrap();
+                canvas
+                    .add_event_listener_with_callback(
+                        "webglcontextrestored",
+                        context_restored_closure.as_ref().unchecked_ref(),
+                    )
+                    .unwrap();
+            }
+            context_lost_closure.forget();
+            context_restored_closure.forget();
+
             {
                 let default_size = window.inner_size().to_logical(window.scale_factor());
                 let new_size = winit::dpi::LogicalSize::new(
@@ -542,20 +2122,83 @@ pub(crate) fn new(
                 }
             }
 
-            let renderer =
-                femtovg::renderer::OpenGl::new_from_html_canvas(&window.canvas()).unwrap();
+            let renderer = femtovg::renderer::OpenGl::new_from_html_canvas(&window.canvas())
+                .map_err(|err| ContextCreationError::new("WebGL", err))?;
             (window, renderer)
         };
+        // WebGL provides no control over the color space that the drawing buffer is blended in.
+        #[cfg(target_arch = "wasm32")]
+        let srgb_blending = false;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let gl_renderer = Self::from_renderer(
+            renderer,
+            srgb_blending,
+            transparent_window,
+            Box::new(WindowedContextWrapper::Current(windowed_context)),
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Ok(gl_renderer) = &gl_renderer {
+            // When the SIXTYFPS_WATCH_IMAGES environment variable is set, image files that change on disk are
+            // loaded again and the windows redrawn, for tools that show a design while it is being edited.
+            if std::env::var("SIXTYFPS_WATCH_IMAGES").is_ok() {
+                let event_loop_proxy = event_loop.event_loop_proxy().clone();
+                *gl_renderer.shared_data.image_file_watcher.borrow_mut() =
+                    Some(image_file_watcher::FileWatcher::start(
+                        image_file_watcher::POLL_INTERVAL,
+                        move || {
+                            event_loop_proxy
+                                .send_event(eventloop::CustomEvent::ImageFileChanged)
+                                .ok();
+                        },
+                    ));
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let gl_renderer = Self::from_renderer(
+            renderer,
+            srgb_blending,
+            transparent_window,
+            window,
+            event_loop_proxy,
+        );
+        gl_renderer
+    }
+
+    /// Creates a renderer that draws into a pbuffer or OSMesa buffer of the given size, without a window, for
+    /// rendering on machines without a display. The frames are read back with flush_renderer.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn new_headless(
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Result<GLRenderer, ContextCreationError> {
+        let (gl_context, renderer) = headless::create_context(size)?;
+        Self::from_renderer(renderer, false, false, gl_context)
+    }
 
-        let canvas = femtovg::Canvas::new(renderer).unwrap();
+    // Sets up the renderer to draw with the given femtovg renderer, whose GL context is current.
+    fn from_renderer(
+        renderer: femtovg::renderer::OpenGl,
+        srgb_blending: bool,
+        transparent_window: bool,
+        #[cfg(not(target_arch = "wasm32"))] gl_context: Box<dyn GLContext>,
+        #[cfg(target_arch = "wasm32")] window: Rc<winit::window::Window>,
+        #[cfg(target_arch = "wasm32")] event_loop_proxy: Rc<
+            winit::event_loop::EventLoopProxy<eventloop::CustomEvent>,
+        >,
+    ) -> Result<GLRenderer, ContextCreationError> {
+        let canvas = femtovg::Canvas::new(renderer)
+            .map_err(|err| ContextCreationError::new("the femtovg canvas", err))?;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let max_texture_size = query_max_texture_size(gl_context.as_ref());
+        #[cfg(target_arch = "wasm32")]
+        let max_texture_size = query_max_texture_size(&window);
 
         let shared_data = GLRendererData {
             canvas: Rc::new(RefCell::new(canvas)),
 
             #[cfg(not(target_arch = "wasm32"))]
-            windowed_context: RefCell::new(Some(WindowedContextWrapper::NotCurrent(unsafe {
-                windowed_context.make_not_current().unwrap()
-            }))),
+            gl_context: RefCell::new(Some(gl_context.make_not_current())),
             #[cfg(target_arch = "wasm32")]
             window,
             #[cfg(target_arch = "wasm32")]
@@ -563,59 +2206,458 @@ pub(crate) fn new(
 
             item_graphics_cache: Default::default(),
             image_cache: Default::default(),
+            texture_cache: Default::default(),
+            frame_images: Default::default(),
+            warmed_up_images: Default::default(),
+            preloaded_images: Default::default(),
+            pinned_images: Default::default(),
             loaded_fonts: Default::default(),
+
+            crisp_fractional_scaling: std::env::var("SIXTYFPS_CRISP_FRACTIONAL_SCALING").is_ok(),
+            overscan_margin: std::env::var("SIXTYFPS_OVERSCAN_MARGIN")
+                .ok()
+                .and_then(|margin| parse_overscan_margin(&margin))
+                .unwrap_or_default(),
+            mirrored: Default::default(),
+            use_bitmap_strikes: std::env::var("SIXTYFPS_NO_BITMAP_STRIKES").is_err(),
+            show_image_placeholders: std::env::var("SIXTYFPS_IMAGE_PLACEHOLDERS").is_ok(),
+            pixel_snapping: std::env::var("SIXTYFPS_PIXEL_SNAPPING").is_ok(),
+            srgb_blending,
+            transparent_window,
+            bitmap_glyph_cache: Default::default(),
+            color_glyph_cache: Default::default(),
+            conic_gradient_cache: Default::default(),
+            #[cfg(feature = "svg")]
+            svg_rasterizations: Default::default(),
+            sampled_images: Default::default(),
+            image_scales: Default::default(),
+            frame_target: Default::default(),
+            partial_redraw_enabled: Default::default(),
+            max_texture_size,
+            #[cfg(not(target_arch = "wasm32"))]
+            image_file_watcher: Default::default(),
+            context_lost: Default::default(),
         };
 
-        GLRenderer { shared_data: Rc::new(shared_data) }
+        Ok(GLRenderer { shared_data: Rc::new(shared_data) })
     }
 
     /// Returns a new item renderer instance. At this point rendering begins and the backend ensures that the
     /// window background was cleared with the specified clear_color.
-    fn new_renderer(&mut self, clear_color: &Color, scale_factor: f32) -> GLItemRenderer {
-        let size = self.window().inner_size();
-
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let ctx = &mut *self.shared_data.windowed_context.borrow_mut();
-            *ctx = ctx.take().unwrap().make_current().into();
-        }
+    /// Starts rendering a frame. If damaged regions are given, in the coordinates of the items, only they are cleared
+    /// and drawn, provided that the previous frame was retained. Otherwise the whole window is drawn.
+    fn new_renderer(
+        &mut self,
+        clear_color: &Color,
+        scale_factor: f32,
+        damaged_regions: Option<&[Rect]>,
+    ) -> GLItemRenderer {
+        let size = self.size();
+
+        self.begin_frame();
+
+        let supersampling_factor = self.shared_data.supersampling_factor(scale_factor);
+        let target_factor = supersampling_factor
+            .or_else(|| Some(1.).filter(|_| self.shared_data.partial_redraw_enabled.get()));
+        let frame_target = target_factor.map(|factor| {
+            self.shared_data.ensure_frame_target(
+                (size.width as f32 * factor).ceil() as usize,
+                (size.height as f32 * factor).ceil() as usize,
+            )
+        });
 
+        let clear_color = window_clear_color(
+            self.shared_data.paint_color(*clear_color),
+            self.shared_data.transparent_window,
+        );
         {
             let mut canvas = self.shared_data.canvas.borrow_mut();
             // We pass 1.0 as dpi / device pixel ratio as femtovg only uses this factor to scale
             // text metrics. Since we do the entire translation from logical pixels to physical
             // pixels on our end, we don't need femtovg to scale a second time.
             canvas.set_size(size.width, size.height, 1.0);
-            canvas.clear_rect(0, 0, size.width, size.height, clear_color.into());
+
+            let overscan =
+                overscan_transform(size.width, size.height, self.shared_data.overscan_margin);
+            let mirrored = self.shared_data.mirrored.get();
+
+            match (frame_target, target_factor) {
+                (Some((target, previous_frame_retained)), Some(factor)) => {
+                    let (width, height) = canvas.image_size(target).unwrap();
+                    let (offset, scale) = overscan.unwrap_or((Default::default(), 1.));
+                    let mirror = if mirrored {
+                        mirror_transform(size.width as f32)
+                    } else {
+                        euclid::default::Transform2D::identity()
+                    };
+                    // The damaged regions are mirrored like the items in them.
+                    let items_to_target = mirror
+                        .then_scale(scale, scale)
+                        .then_translate(offset)
+                        .then_scale(factor, factor);
+                    let redrawn = redrawn_target_rect(
+                        damaged_regions.filter(|_| previous_frame_retained),
+                        &items_to_target,
+                        (width as u32, height as u32),
+                    );
+                    canvas.set_render_target(femtovg::RenderTarget::Image(target));
+                    canvas.clear_rect(
+                        redrawn.min.x,
+                        redrawn.min.y,
+                        redrawn.width(),
+                        redrawn.height(),
+                        clear_color,
+                    );
+                    canvas.save();
+                    canvas.scissor(
+                        redrawn.min.x as f32,
+                        redrawn.min.y as f32,
+                        redrawn.width() as f32,
+                        redrawn.height() as f32,
+                    );
+                    canvas.scale(factor, factor);
+                }
+                _ => canvas.clear_rect(0, 0, size.width, size.height, clear_color),
+            }
+
+            if let Some((offset, scale)) = overscan {
+                canvas.save();
+                canvas.translate(offset.x, offset.y);
+                canvas.scale(scale, scale);
+            }
+            if mirrored {
+                canvas.save();
+                concat_transform(&mut canvas, &mirror_transform(size.width as f32));
+            }
         }
 
-        GLItemRenderer { shared_data: self.shared_data.clone(), scale_factor }
+        GLItemRenderer {
+            shared_data: self.shared_data.clone(),
+            scale_factor,
+            mirrored: self.shared_data.mirrored.get(),
+            frame_target: frame_target.map(|(target, _)| target),
+            state_depth: 0,
+            clip_layers: Vec::new(),
+            finished_clip_layers: Vec::new(),
+            clipped_states: Vec::new(),
+            rectangle_batch: Default::default(),
+        }
     }
 
-    /// Complete the item rendering by calling this function. This will typically flush any remaining/pending
-    /// commands to the underlying graphics subsystem.
-    fn flush_renderer(&mut self, _renderer: GLItemRenderer) {
-        self.shared_data.canvas.borrow_mut().flush();
+    /// Returns an item renderer that draws over the current contents of the framebuffer, for example to show the
+    /// items as an overlay on top of a scene that the application draws itself with OpenGL. Unlike
+    /// [`Self::new_renderer`], nothing is cleared, and the items are drawn straight into the framebuffer, without
+    /// supersampling or overscan. Complete the rendering with [`Self::flush_overlay_renderer`].
+    pub fn new_overlay_renderer(&mut self, scale_factor: f32) -> GLItemRenderer {
+        let size = self.size();
+
+        self.begin_frame();
+
+        {
+            let mut canvas = self.shared_data.canvas.borrow_mut();
+            // Sizing the canvas only sets up the viewport that femtovg draws with, the framebuffer is left alone.
+            canvas.set_size(size.width, size.height, 1.0);
+            canvas.set_render_target(femtovg::RenderTarget::Screen);
+            if self.shared_data.mirrored.get() {
+                canvas.save();
+                concat_transform(&mut canvas, &mirror_transform(size.width as f32));
+            }
+        }
+
+        GLItemRenderer {
+            shared_data: self.shared_data.clone(),
+            scale_factor,
+            mirrored: self.shared_data.mirrored.get(),
+            frame_target: None,
+            state_depth: 0,
+            clip_layers: Vec::new(),
+            finished_clip_layers: Vec::new(),
+            clipped_states: Vec::new(),
+            rectangle_batch: Default::default(),
+        }
+    }
 
+    // Makes the context current, and recreates the canvas if the context was lost since the previous frame.
+    fn begin_frame(&mut self) {
         #[cfg(not(target_arch = "wasm32"))]
         {
-            let mut ctx = self.shared_data.windowed_context.borrow_mut().take().unwrap();
-            ctx.swap_buffers();
+            let ctx = &mut *self.shared_data.gl_context.borrow_mut();
+            *ctx = Some(ctx.take().unwrap().make_current());
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            if !self.shared_data.context_lost.get() && self.is_webgl_context_lost() {
+                self.shared_data.context_lost.set(true);
+            }
+        }
+        if self.shared_data.context_lost.get() {
+            self.recreate_canvas();
+        }
+    }
+
+    /// Complete the item rendering by calling this function. This will typically flush any remaining/pending
+    /// commands to the underlying graphics subsystem. If `read_back` is set, the rendered pixels are read
+    /// back and returned, in the requested alpha convention.
+    fn flush_renderer(
+        &mut self,
+        mut renderer: GLItemRenderer,
+        read_back: Option<AlphaMode>,
+    ) -> Option<image::RgbaImage> {
+        renderer.flush_rectangle_batch();
+        {
+            let mut canvas = self.shared_data.canvas.borrow_mut();
+
+            if renderer.mirrored {
+                canvas.restore();
+            }
+            if self.shared_data.overscan_margin > 0. {
+                canvas.restore();
+            }
 
-            *self.shared_data.windowed_context.borrow_mut() = ctx.make_not_current().into();
+            if let Some(target) = renderer.frame_target {
+                canvas.restore();
+                canvas.set_render_target(femtovg::RenderTarget::Screen);
+                let (width, height) = (canvas.width(), canvas.height());
+                // Drawing the frame over the previous contents of a transparent window would accumulate them.
+                if self.shared_data.transparent_window {
+                    canvas.clear_rect(
+                        0,
+                        0,
+                        width as u32,
+                        height as u32,
+                        femtovg::Color::rgba(0, 0, 0, 0),
+                    );
+                }
+                let mut path = femtovg::Path::new();
+                path.rect(0., 0., width, height);
+                canvas.fill_path(
+                    &mut path,
+                    femtovg::Paint::image(target, 0., 0., width, height, 0.0, 1.0),
+                );
+            }
+
+            canvas.flush();
+
+            for layer in renderer.finished_clip_layers {
+                canvas.delete_image(layer);
+            }
         }
 
-        self.shared_data.image_cache.borrow_mut().retain(|_, cached_image_weak| {
-            cached_image_weak
-                .upgrade()
-                .map_or(false, |cached_image_rc| Rc::strong_count(&cached_image_rc) > 1)
+        self.finish_frame_caches();
+
+        // The pixels are read back before the buffers are swapped, as the contents of the back buffer are
+        // undefined afterwards. With WebGL, this also happens before the browser composites the frame, which
+        // clears the drawing buffer.
+        let pixels = read_back.and_then(|alpha_mode| {
+            let screenshot = self.shared_data.canvas.borrow_mut().screenshot().ok()?;
+            frame_pixels(screenshot.as_ref(), self.size(), alpha_mode)
         });
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut ctx = self.shared_data.gl_context.borrow_mut().take().unwrap();
+            match ctx.swap_buffers() {
+                Ok(()) => {}
+                Err(glutin::ContextError::ContextLost) => {
+                    self.shared_data.context_lost.set(true);
+                    if let Some(window) = ctx.window() {
+                        window.request_redraw();
+                    }
+                }
+                Err(err) => panic!("Error swapping the buffers: {:?}", err),
+            }
+
+            *self.shared_data.gl_context.borrow_mut() = Some(ctx.make_not_current());
+        }
+
+        self.prune_image_caches();
+
+        pixels
+    }
+
+    /// Completes the rendering started with [`Self::new_overlay_renderer`] by flushing the pending drawing
+    /// commands. The buffers are not swapped and the context stays current, so that the application can continue
+    /// drawing and present the frame itself. femtovg changes the GL state while flushing, such as the bound
+    /// program, textures and blending, which the application has to set up again for its own drawing.
+    pub fn flush_overlay_renderer(&mut self, mut renderer: GLItemRenderer) {
+        renderer.flush_rectangle_batch();
+        {
+            let mut canvas = self.shared_data.canvas.borrow_mut();
+            if renderer.mirrored {
+                canvas.restore();
+            }
+            canvas.flush();
+
+            for layer in renderer.finished_clip_layers {
+                canvas.delete_image(layer);
+            }
+        }
+
+        self.finish_frame_caches();
+        self.prune_image_caches();
+    }
+
+    // Releases the resources that were only needed for the frame that was just flushed.
+    fn finish_frame_caches(&self) {
+        self.shared_data.frame_images.borrow_mut().clear();
+        self.shared_data.warmed_up_images.borrow_mut().clear();
+        self.shared_data
+            .preloaded_images
+            .borrow_mut()
+            .retain(|image| image.is_pending() || Rc::strong_count(image) == 1);
+        self.shared_data.image_scales.borrow_mut().finish_frame();
+
+        self.shared_data
+            .texture_cache
+            .borrow_mut()
+            .finish_frame(TEXTURE_MEMORY_BUDGET.with(|budget| budget.get()));
+    }
+
+    fn prune_image_caches(&self) {
+        let mut image_cache = self.shared_data.image_cache.borrow_mut();
+        prune_image_cache(&mut image_cache, &mut self.shared_data.pinned_images.borrow_mut());
+        #[cfg(feature = "svg")]
+        self.shared_data
+            .svg_rasterizations
+            .borrow_mut()
+            .retain(|key, _| image_cache.contains_key(key));
+        self.shared_data
+            .sampled_images
+            .borrow_mut()
+            .retain(|(key, _), _| image_cache.contains_key(key));
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(watcher) = &*self.shared_data.image_file_watcher.borrow() {
+            watcher.watch_only(image_cache.keys().filter_map(|key| match key {
+                ImageCacheKey::Path(path) if !remote_image::is_remote_url(path) => {
+                    Some(path.as_str())
+                }
+                _ => None,
+            }));
+        }
+    }
+
+    /// Drops the images whose files changed on disk since they were loaded, so that they are loaded again when
+    /// they are drawn the next time. This is only the case if the SIXTYFPS_WATCH_IMAGES environment variable is set.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn reload_changed_image_files(&self) {
+        let changed_files = match &*self.shared_data.image_file_watcher.borrow() {
+            Some(watcher) => watcher.take_changed(),
+            None => return,
+        };
+        for path in changed_files {
+            self.clear_image_cache_for(Resource::AbsoluteFilePath(path.into()));
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn is_webgl_context_lost(&self) -> bool {
+        use wasm_bindgen::JsCast;
+        use winit::platform::web::WindowExtWebSys;
+        self.shared_data
+            .window
+            .canvas()
+            .get_context("webgl")
+            .ok()
+            .flatten()
+            .and_then(|context| context.dyn_into::<web_sys::WebGlRenderingContext>().ok())
+            .map_or(false, |context| context.is_context_lost())
+    }
+
+    /// Replaces the canvas with one drawing with a new femtovg renderer, after the GL context was lost. Everything
+    /// that lived in the old canvas is released first and created again on demand. If the renderer can't be created
+    /// yet, for example because the browser didn't restore the WebGL context yet, this is tried again with the next
+    /// frame.
+    fn recreate_canvas(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let renderer = {
+            let ctx = self.shared_data.gl_context.borrow();
+            let ctx = match ctx.as_ref() {
+                Some(ctx) => ctx,
+                None => return,
+            };
+            if self.shared_data.srgb_blending {
+                ctx.enable_framebuffer_srgb();
+            }
+            femtovg::renderer::OpenGl::new(|symbol| ctx.get_proc_address(symbol) as *const _)
+        };
+        #[cfg(target_arch = "wasm32")]
+        let renderer = {
+            use winit::platform::web::WindowExtWebSys;
+            if self.is_webgl_context_lost() {
+                return;
+            }
+            femtovg::renderer::OpenGl::new_from_html_canvas(&self.shared_data.window.canvas())
+        };
+
+        let canvas = match renderer.and_then(femtovg::Canvas::new) {
+            Ok(canvas) => canvas,
+            Err(err) => {
+                eprintln!("Error recreating the renderer after the GL context was lost: {:?}", err);
+                return;
+            }
+        };
+        self.shared_data.release_gpu_resources();
+        *self.shared_data.canvas.borrow_mut() = canvas;
+        self.shared_data.context_lost.set(false);
+    }
+
+    /// Drops the images that were rendered for the scale factor of the previous frames, such as the SVG images
+    /// rasterized at the size they were drawn at and the conic gradients. They are rendered again as needed.
+    fn release_scale_factor_dependent_caches(&self) {
+        #[cfg(feature = "svg")]
+        self.shared_data.svg_rasterizations.borrow_mut().clear();
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        for image_id in
+            self.shared_data.conic_gradient_cache.borrow_mut().drain().filter_map(|(_, id)| id)
+        {
+            canvas.delete_image(image_id);
+        }
+    }
+
+    /// Makes the frames retain their content, so that the window can be redrawn partially from the next frame on.
+    fn enable_partial_redraw(&self) {
+        self.shared_data.partial_redraw_enabled.set(true);
+    }
+
+    /// Maps a position in the window, in physical pixels, to the position in the content, which is drawn
+    /// inset by the overscan margin, and flipped horizontally when mirrored.
+    fn map_to_content(&self, pos: Point) -> Point {
+        let size = self.size();
+        let pos =
+            match overscan_transform(size.width, size.height, self.shared_data.overscan_margin) {
+                Some((offset, scale)) => (pos - offset) / scale,
+                None => pos,
+            };
+        if self.shared_data.mirrored.get() {
+            mirror_transform(size.width as f32).transform_point(pos)
+        } else {
+            pos
+        }
+    }
+
+    /// Mirrors the content of the window horizontally from the next frame on, for user interfaces in right-to-left
+    /// languages: an item at the left edge of the window is drawn at its right edge. Text and images are drawn at
+    /// their mirrored place, but not flipped themselves, so that text stays readable. Mouse and touch positions are
+    /// mirrored the same way before they are delivered to the items. The window has to be redrawn to show the change.
+    pub fn set_mirrored(&self, mirrored: bool) {
+        if self.shared_data.mirrored.replace(mirrored) != mirrored {
+            // The previous frame that was retained for partial redraws doesn't match anymore.
+            if let Some(image_id) = self.shared_data.frame_target.take() {
+                self.shared_data.canvas.borrow_mut().delete_image(image_id);
+            }
+        }
+    }
+
+    /// Returns whether the content of the window is mirrored horizontally, see [`Self::set_mirrored`].
+    pub fn is_mirrored(&self) -> bool {
+        self.shared_data.mirrored.get()
     }
 
     #[cfg(not(target_arch = "wasm32"))]
     fn window(&self) -> std::cell::Ref<winit::window::Window> {
-        std::cell::Ref::map(self.shared_data.windowed_context.borrow(), |ctx| {
-            ctx.as_ref().unwrap().window()
+        std::cell::Ref::map(self.shared_data.gl_context.borrow(), |ctx| {
+            ctx.as_ref().unwrap().window().expect("the renderer draws into a window")
         })
     }
 
@@ -624,6 +2666,111 @@ fn window(&self) -> &winit::window::Window {
         return &self.shared_data.window;
     }
 
+    /// The size of the frames, in physical pixels.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.shared_data.gl_context.borrow().as_ref().unwrap().size()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.window().inner_size()
+    }
+
+    /// Loads the default font, and the fonts it falls back to, and decodes the given images, so that drawing the
+    /// first frame doesn't have to.
+    fn warm_up(&self, resources: &[Resource], scale_factor: f32) {
+        let default_font = FontRequest {
+            family: Default::default(),
+            weight: None,
+            pixel_size: None,
+            style: Default::default(),
+            numeral_style: Default::default(),
+            language: Default::default(),
+            tab_width: None,
+        };
+        self.shared_data.loaded_fonts.borrow_mut().font(
+            &self.shared_data.canvas,
+            default_font,
+            scale_factor,
+        );
+        let images = resources
+            .iter()
+            .filter_map(|resource| self.shared_data.load_image_resource(resource.clone()));
+        self.shared_data.warmed_up_images.borrow_mut().extend(images);
+    }
+
+    /// Starts decoding the image the given resource refers to in a background thread, and adds it to the image
+    /// cache, so that the first item that shows it doesn't have to wait for the decoding. Until the image is
+    /// decoded, items showing it draw nothing and have a size of 1x1, which changes once the image has arrived.
+    /// The image is uploaded to the GPU when it is first drawn, and kept until then.
+    ///
+    /// Images from URLs are loaded as usual, which happens in the background as well. On the web, and for images
+    /// made of pixels, the image is loaded right away.
+    pub fn preload_image(&self, resource: Resource) {
+        self.preload(resource);
+    }
+
+    // Preloads the image, see preload_image(), and returns it. Returns None if the image could not be loaded.
+    fn preload(&self, resource: Resource) -> Option<Rc<CachedImage>> {
+        let image = self.preload_in_background(&resource).or_else(|| {
+            self.shared_data.load_image_resource(resource).map(|entry| entry.as_image().clone())
+        })?;
+        self.shared_data.preloaded_images.borrow_mut().push(image.clone());
+        Some(image)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn preload_in_background(&self, resource: &Resource) -> Option<Rc<CachedImage>> {
+        self.shared_data.lookup_image_in_cache_or_create(ImageCacheKey::new(resource)?, || {
+            fetch_in_background(|notify| CachedImage::new_preloading(resource, notify))
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn preload_in_background(&self, _resource: &Resource) -> Option<Rc<CachedImage>> {
+        None
+    }
+
+    /// Drops all images that were loaded, so that they are loaded again from their source when they are drawn the
+    /// next time, for example after image files changed on disk, or to free memory when the system runs low on it.
+    /// The images of the frame that is being drawn stay valid until it is flushed. The window has to be redrawn to
+    /// show the images that were loaded again.
+    pub fn clear_image_cache(&self) {
+        self.shared_data.clear_cached_images(|_| true);
+    }
+
+    /// Same as [`Self::clear_image_cache`], but only drops the image the given resource refers to, in all the
+    /// colors it is tinted with. This reloads an image file that changed on disk.
+    pub fn clear_image_cache_for(&self, resource: Resource) {
+        if let Some(cleared_key) = ImageCacheKey::new(&resource) {
+            self.shared_data.clear_cached_images(|key| *key == cleared_key);
+        }
+    }
+
+    /// Returns whether the image the given resource refers to is still loading, such as an image from a URL
+    /// whose data has not arrived yet, whether it is ready to be drawn, or whether loading it failed. Images that
+    /// are not shown by any item yet are preloaded, see [`Self::preload_image`].
+    ///
+    /// When called from a binding, the binding is evaluated again once the image has finished loading.
+    pub fn image_load_state(&self, resource: Resource) -> ImageLoadState {
+        if matches!(resource, Resource::None) {
+            return ImageLoadState::Ready;
+        }
+        let cached_image = ImageCacheKey::new(&resource).and_then(|cache_key| {
+            self.shared_data.image_cache.borrow().get(&cache_key).and_then(Weak::upgrade)
+        });
+        match cached_image.or_else(|| self.preload(resource)) {
+            Some(image) => image.load_state(),
+            None => ImageLoadState::Failed,
+        }
+    }
+
+    /// Returns the requested font families that are not available, with the family drawn instead.
+    fn font_substitutions(&self) -> Vec<(SharedString, SharedString)> {
+        self.shared_data.loaded_fonts.borrow().substitutions()
+    }
+
     /// Returns a FontMetrics trait object that can be used to measure text and that matches the given font request as
     /// closely as possible.
     fn font_metrics(&mut self, request: FontRequest, scale_factor: f32) -> Box<dyn FontMetrics> {
@@ -647,742 +2794,6850 @@ fn image_size(
 pub struct GLItemRenderer {
     shared_data: Rc<GLRendererData>,
     scale_factor: f32,
+    /// Whether the frame is drawn mirrored horizontally, see [`GLRenderer::set_mirrored`].
+    mirrored: bool,
+    /// The off-screen image the scene is rendered into, if oversampling for crisp fractional scaling or retaining
+    /// the frame for partial redraws.
+    frame_target: Option<femtovg::ImageId>,
+    /// The number of times save_state() was called without a matching restore_state().
+    state_depth: usize,
+    /// The off-screen images that the children of elliptical `Clip` elements are drawn into.
+    clip_layers: Vec<ClipLayer>,
+    /// Clip layers that have been drawn, and can be deleted after the frame was rendered.
+    finished_clip_layers: Vec<femtovg::ImageId>,
+    /// For each saved state, whether a clip or a transform was applied in it, which restoring the state removes.
+    clipped_states: Vec<bool>,
+    /// The rectangles drawn since anything else was drawn, to be filled together.
+    rectangle_batch: RectangleBatch,
 }
 
-fn rect_to_path(r: Rect) -> femtovg::Path {
-    let mut path = femtovg::Path::new();
-    path.rect(r.min_x(), r.min_y(), r.width(), r.height());
-    path
+/// The children of a `Clip` element with a shape other than a rectangle, or with faded edges, are rendered
+/// into an off-screen image, which is then drawn onto the parent's render target, filling the shape. So are the
+/// children of a `Desaturate` element, which are desaturated before the image is drawn.
+struct ClipLayer {
+    image: femtovg::ImageId,
+    /// The state depth at which the `Clip` element was rendered. The layer is finished when that state is
+    /// restored.
+    state_depth: usize,
+    shape: ClipShape,
+    /// The geometry of the `Clip` element, and the transform from its coordinates to device pixels.
+    clip_rect: Rect,
+    transform: femtovg::Transform2D,
+    /// The lengths over which the content fades out at the left and right, and at the top and bottom edges.
+    fade_horizontal: f32,
+    fade_vertical: f32,
+    /// The saturation that the content is reduced to, 1 for clips.
+    saturation: f32,
+    /// The area of the render target covered by the clip shape, in device pixels.
+    shape_rect: Rect,
+    /// The area of the render target covered by the image, in device pixels.
+    image_rect: euclid::default::Box2D<i32>,
+    previous_render_target: femtovg::RenderTarget,
 }
 
-impl ItemRenderer for GLItemRenderer {
-    fn draw_rectangle(
-        &mut self,
-        pos: Point,
-        rect: std::pin::Pin<&sixtyfps_corelib::items::Rectangle>,
-    ) {
-        let geometry = rect.geometry();
-        if geometry.is_empty() {
-            return;
-        }
-        // TODO: cache path in item to avoid re-tesselation
-        let mut path = rect_to_path(geometry);
-        let paint = femtovg::Paint::color(rect.color().into());
-        self.shared_data.canvas.borrow_mut().save_with(|canvas| {
-            canvas.translate(pos.x, pos.y);
-            canvas.fill_path(&mut path, paint)
-        })
+/// Mixes each of the (premultiplied) pixels with the gray of the same luminance, keeping the given fraction of
+/// its color.
+fn desaturate_pixels(pixels: &mut [rgb::RGBA8], saturation: f32) {
+    for pixel in pixels {
+        let luminance = 0.2126 * pixel.r as f32 + 0.7152 * pixel.g as f32 + 0.0722 * pixel.b as f32;
+        let mix =
+            |channel: u8| (luminance + (channel as f32 - luminance) * saturation).round() as u8;
+        *pixel = rgb::RGBA8 { r: mix(pixel.r), g: mix(pixel.g), b: mix(pixel.b), a: pixel.a };
     }
+}
 
-    fn draw_border_rectangle(
-        &mut self,
-        pos: Point,
-        rect: std::pin::Pin<&sixtyfps_corelib::items::BorderRectangle>,
-    ) {
-        let geometry = rect.geometry();
-        if geometry.is_empty() {
-            return;
-        }
+/// Desaturates what was drawn into the image, which must be the current render target. femtovg cannot run a
+/// shader for that, so the pixels are read back and uploaded again.
+fn desaturate_render_target(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    image: femtovg::ImageId,
+    saturation: f32,
+) {
+    let screenshot = match canvas.screenshot() {
+        Ok(screenshot) if screenshot.width() > 0 && screenshot.height() > 0 => screenshot,
+        _ => return,
+    };
+    // The screenshot starts with the top row, but the image is drawn flipped, with its first row at the bottom.
+    let (width, height) = (screenshot.width(), screenshot.height());
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in screenshot.rows().rev() {
+        pixels.extend_from_slice(row);
+    }
+    desaturate_pixels(&mut pixels, saturation);
+    canvas.update_image(image, imgref::Img::new(pixels.as_slice(), width, height), 0, 0).ok();
+}
 
-        // If the border width exceeds the width, just fill the rectangle.
-        let border_width = rect.border_width().min(rect.width() / 2.);
-        // In CSS the border is entirely towards the inside of the boundary
-        // geometry, while in femtovg the line with for a stroke is 50% in-
-        // and 50% outwards. We choose the CSS model, so the inner rectangle
-        // is adjusted accordingly.
-        let mut path = femtovg::Path::new();
-        path.rounded_rect(
-            geometry.min_x() + border_width / 2.,
-            geometry.min_y() + border_width / 2.,
-            geometry.width() - border_width,
-            geometry.height() - border_width,
-            rect.border_radius(),
-        );
+// Returns the axis aligned bounding box of the rectangle after applying the transform.
+fn transformed_bounds(transform: &femtovg::Transform2D, rect: Rect) -> Rect {
+    let corners = [
+        transform.transform_point(rect.min_x(), rect.min_y()),
+        transform.transform_point(rect.max_x(), rect.min_y()),
+        transform.transform_point(rect.min_x(), rect.max_y()),
+        transform.transform_point(rect.max_x(), rect.max_y()),
+    ];
+    euclid::default::Box2D::from_points(corners.iter().map(|(x, y)| Point::new(*x, *y))).to_rect()
+}
 
-        let fill_paint = femtovg::Paint::color(rect.color().into());
+/// Returns for each byte of the text whether it is laid out right-to-left. Like femtovg, this applies the Unicode
+/// bidirectional algorithm with a left-to-right paragraph direction.
+fn right_to_left_bytes(text: &str) -> Vec<bool> {
+    unicode_bidi::BidiInfo::new(text, Some(unicode_bidi::Level::ltr()))
+        .levels
+        .iter()
+        .map(|level| level.is_rtl())
+        .collect()
+}
 
-        let mut border_paint = femtovg::Paint::color(rect.border_color().into());
-        border_paint.set_line_width(border_width);
+/// Returns the byte offsets at which the glyph clusters of the text start, in logical order.
+fn cluster_starts(metrics: &femtovg::TextMetrics) -> Vec<usize> {
+    let mut starts = metrics.glyphs.iter().map(|glyph| glyph.byte_index).collect::<Vec<_>>();
+    starts.sort_unstable();
+    starts.dedup();
+    starts
+}
 
-        self.shared_data.canvas.borrow_mut().save_with(|canvas| {
-            canvas.translate(pos.x, pos.y);
-            canvas.fill_path(&mut path, fill_paint);
-            canvas.stroke_path(&mut path, border_paint);
-        })
+/// Returns the x coordinate of a text cursor placed in front of the character at the given (UTF-8) byte
+/// offset. In right-to-left text, that is the right edge of the character. Offsets past the last glyph map
+/// to the end of the text.
+fn cursor_x_for_byte_offset(metrics: &femtovg::TextMetrics, text: &str, byte_offset: usize) -> f32 {
+    let right_to_left = right_to_left_bytes(text);
+    let starts = cluster_starts(metrics);
+    // The edges of the glyphs of the cluster starting at the given offset. The x coordinate of a glyph is where its
+    // outline starts, so the right edge is where the advance from the pen position ends.
+    let cluster_edges = |start: usize| {
+        metrics.glyphs.iter().filter(|glyph| glyph.byte_index == start).fold(
+            (f32::MAX, f32::MIN),
+            |(left, right), glyph| {
+                (left.min(glyph.x), right.max(glyph.x - glyph.bearing_x + glyph.advance_x))
+            },
+        )
+    };
+    match starts.iter().find(|start| **start >= byte_offset) {
+        Some(&start) if right_to_left[start] => cluster_edges(start).1,
+        Some(&start) => cluster_edges(start).0,
+        // The end of right-to-left text is at the left edge of its last character.
+        None => match starts.last() {
+            Some(&last) if right_to_left[last] => cluster_edges(last).0,
+            _ => metrics.x + metrics.width(),
+        },
     }
+}
 
-    fn draw_image(&mut self, pos: Point, image: std::pin::Pin<&sixtyfps_corelib::items::Image>) {
-        self.draw_image_impl(
-            pos + euclid::Vector2D::new(image.x(), image.y()),
-            &image.cached_rendering_data,
-            sixtyfps_corelib::items::Image::FIELD_OFFSETS.source.apply_pin(image),
-            Rect::default(),
-            image.width(),
-            image.height(),
-            image.image_fit(),
-        );
+/// Returns the (UTF-8) byte offset in the text at which a text cursor placed at the given x coordinate goes, which
+/// is the offset of the character whose horizontal center is to the right of x, or to the left of x in right-to-left
+/// text. The glyphs of a cluster, such as a character with combining marks, are treated as one, so the offset never
+/// points into the middle of a cluster.
+fn byte_offset_for_x_position(metrics: &femtovg::TextMetrics, text: &str, x: f32) -> usize {
+    let right_to_left = right_to_left_bytes(text);
+    let starts = cluster_starts(metrics);
+    let cluster_end = |start: usize| {
+        starts.iter().copied().find(|next_start| *next_start > start).unwrap_or(text.len())
+    };
+    let mut current_x = 0.;
+    let mut last_cluster = None;
+    let mut glyphs = metrics.glyphs.iter().peekable();
+    while let Some(glyph) = glyphs.next() {
+        let mut cluster_advance = glyph.advance_x;
+        while let Some(next) = glyphs.peek().filter(|next| next.byte_index == glyph.byte_index) {
+            cluster_advance += next.advance_x;
+            glyphs.next();
+        }
+        if current_x + cluster_advance / 2. >= x {
+            debug_assert!(text.is_char_boundary(glyph.byte_index));
+            return if right_to_left[glyph.byte_index] {
+                cluster_end(glyph.byte_index)
+            } else {
+                glyph.byte_index
+            };
+        }
+        current_x += cluster_advance;
+        last_cluster = Some(glyph.byte_index);
     }
+    match last_cluster {
+        Some(start) if right_to_left[start] => start,
+        Some(start) => cluster_end(start),
+        None => text.len(),
+    }
+}
 
-    fn draw_clipped_image(
-        &mut self,
-        pos: Point,
-        clipped_image: std::pin::Pin<&sixtyfps_corelib::items::ClippedImage>,
-    ) {
-        let source_clip_rect = Rect::new(
-            [clipped_image.source_clip_x() as _, clipped_image.source_clip_y() as _].into(),
-            [clipped_image.source_clip_width() as _, clipped_image.source_clip_height() as _]
-                .into(),
-        );
+/// Returns the union of the bounding boxes of the outlines of the glyphs. femtovg places each glyph at the top left
+/// corner of its outline.
+fn ink_bounds(metrics: &femtovg::TextMetrics) -> Rect {
+    metrics
+        .glyphs
+        .iter()
+        .map(|glyph| euclid::rect(glyph.x, glyph.y, glyph.width, glyph.height))
+        .filter(|rect| !rect.is_empty())
+        .fold(None, |bounds: Option<Rect>, rect| {
+            Some(bounds.map_or(rect, |bounds| bounds.union(&rect)))
+        })
+        .unwrap_or_default()
+}
 
-        self.draw_image_impl(
-            pos + euclid::Vector2D::new(clipped_image.x(), clipped_image.y()),
-            &clipped_image.cached_rendering_data,
-            sixtyfps_corelib::items::ClippedImage::FIELD_OFFSETS.source.apply_pin(clipped_image),
-            source_clip_rect,
-            clipped_image.width(),
-            clipped_image.height(),
-            clipped_image.image_fit(),
-        );
-    }
+/// The number of spaces between the tab stops, unless the text asks for another tab width.
+const DEFAULT_TAB_SIZE: f32 = 8.;
 
-    fn draw_text(&mut self, pos: Point, text: std::pin::Pin<&sixtyfps_corelib::items::Text>) {
-        let pos = pos + euclid::Vector2D::new(text.x(), text.y());
-        let max_width = text.width();
-        let max_height = text.height();
+/// Returns the position of the first tab stop after x.
+fn next_tab_stop(x: f32, tab_width: f32) -> f32 {
+    ((x / tab_width).floor() + 1.) * tab_width
+}
 
-        if max_width <= 0. || max_height <= 0. {
-            return;
-        }
+/// Splits a line of text at its tab characters, into the runs of text between them along with the x position at
+/// which each run starts. Also returns the width of the whole line.
+fn tab_runs<'a>(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    line: &'a str,
+    paint: femtovg::Paint,
+    tab_width: f32,
+) -> (Vec<(f32, &'a str)>, f32) {
+    let mut x = 0.;
+    let runs = line
+        .split('\t')
+        .enumerate()
+        .map(|(index, run)| {
+            if index > 0 {
+                x = next_tab_stop(x, tab_width);
+            }
+            let start = x;
+            if !run.is_empty() {
+                x += canvas.measure_text(0., 0., run, paint).unwrap().width();
+            }
+            (start, run)
+        })
+        .collect();
+    (runs, x)
+}
 
+/// Returns the width and the height of a line of text, with its tab characters advancing to the tab stops if a
+/// tab width is given.
+fn line_size(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    line: &str,
+    paint: femtovg::Paint,
+    tab_width: Option<f32>,
+) -> Size {
+    match tab_width.filter(|_| line.contains('\t')) {
+        Some(tab_width) => {
+            let (runs, width) = tab_runs(canvas, line, paint, tab_width);
+            let height = runs
+                .iter()
+                .filter(|(_, run)| !run.is_empty())
+                .map(|(_, run)| canvas.measure_text(0., 0., run, paint).unwrap().height())
+                .fold(0., f32::max);
+            euclid::size2(width, height)
+        }
+        None => {
+            let metrics = canvas.measure_text(0., 0., line, paint).unwrap();
+            euclid::size2(metrics.width(), metrics.height())
+        }
+    }
+}
+
+/// Splits a line into its words, each with the x position it is drawn at so that the spaces between the words are
+/// widened equally and the line fills the given width.
+fn justified_runs<'a>(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    line: &'a str,
+    paint: femtovg::Paint,
+    width: f32,
+) -> Vec<(f32, &'a str)> {
+    let metrics = canvas.measure_text(0., 0., line, paint).unwrap();
+    let extra_space = (width - metrics.width()).max(0.) / line.matches(' ').count() as f32;
+    line.split(' ')
+        .enumerate()
+        .map(|(spaces_before, word)| {
+            let start = word.as_ptr() as usize - line.as_ptr() as usize;
+            let pen_x = metrics
+                .glyphs
+                .iter()
+                .find(|glyph| glyph.byte_index == start)
+                .map_or(0., |glyph| glyph.x - glyph.bearing_x);
+            (pen_x + extra_space * spaces_before as f32, word)
+        })
+        .collect()
+}
+
+/// Returns the line followed by an ellipsis, with the characters that would end beyond max_width along with the
+/// ellipsis left out.
+fn elided_line(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    line: &str,
+    paint: femtovg::Paint,
+    tab_width: Option<f32>,
+    max_width: f32,
+) -> String {
+    let ellipsis_width = canvas.measure_text(0., 0., "…", paint).unwrap().width();
+    let end = byte_offset_beyond_x(canvas, line, paint, tab_width, max_width - ellipsis_width)
+        .unwrap_or_else(|| line.len());
+    format!("{}…", &line[..end])
+}
+
+/// Returns the byte offset of the first character of the line that ends beyond the given x position, if any.
+fn byte_offset_beyond_x(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    line: &str,
+    paint: femtovg::Paint,
+    tab_width: Option<f32>,
+    x: f32,
+) -> Option<usize> {
+    let runs = match tab_width {
+        Some(tab_width) => tab_runs(canvas, line, paint, tab_width).0,
+        None => vec![(0., line)],
+    };
+    runs.into_iter().find_map(|(run_x, run)| {
+        let offset = run.as_ptr() as usize - line.as_ptr() as usize;
+        if run_x >= x {
+            // The tab before the run advances beyond the position.
+            return Some(offset.saturating_sub(1));
+        }
+        let mut current_x = run_x;
+        canvas.measure_text(0., 0., run, paint).unwrap().glyphs.iter().find_map(|glyph| {
+            current_x += glyph.advance_x;
+            if current_x >= x {
+                Some(offset + glyph.byte_index)
+            } else {
+                None
+            }
+        })
+    })
+}
+
+/// Returns the last grapheme cluster boundary of the text at or before the byte index, so that lines are not broken
+/// within a UTF-8 sequence, between a character and its combining marks, or within an emoji sequence.
+fn grapheme_boundary_before(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    let mut cursor = GraphemeCursor::new(index, text.len(), true);
+    if cursor.is_boundary(text, 0).unwrap() {
+        index
+    } else {
+        cursor.prev_boundary(text, 0).unwrap().unwrap_or(0)
+    }
+}
+
+/// Returns the byte index at which break_text breaks the text to fit into max_width, moved back to a grapheme cluster
+/// boundary.
+fn break_text_at_grapheme(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    text: &str,
+    paint: femtovg::Paint,
+    max_width: f32,
+) -> usize {
+    grapheme_boundary_before(text, canvas.break_text(max_width, text, paint).unwrap())
+}
+
+/// Returns the length in bytes of the start of the first word of the text that fits into max_width, to break a word
+/// that is wider than a line anywhere. It is at least one grapheme cluster, so that wrapping always advances.
+fn forced_break_index(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    text: &str,
+    paint: femtovg::Paint,
+    max_width: f32,
+) -> usize {
+    let word = text.split(char::is_whitespace).next().unwrap_or(text);
+    let index = byte_offset_beyond_x(canvas, word, paint, None, max_width).unwrap_or(word.len());
+    grapheme_boundary_before(word, index).max(text.graphemes(true).next().map_or(0, str::len))
+}
+
+/// Splits the text into the lines it is drawn in by draw_text, along with the byte offset at which each line starts.
+/// If max_width is set, the text is wrapped at word boundaries to lines of that width, and words that are wider than
+/// a line are broken between characters. Otherwise it is only broken at newline characters. If tab_width is set, tab characters advance to the next multiple of it when wrapping.
+fn text_lines<'a>(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    text: &'a str,
+    max_width: Option<f32>,
+    paint: femtovg::Paint,
+    tab_width: Option<f32>,
+) -> Vec<(usize, &'a str)> {
+    let offset_of = |line: &str| line.as_ptr() as usize - text.as_ptr() as usize;
+    let max_width = match max_width {
+        Some(max_width) => max_width,
+        None => return text.lines().map(|line| (offset_of(line), line)).collect(),
+    };
+    if let Some(tab_width) = tab_width.filter(|_| text.contains('\t')) {
+        return text
+            .lines()
+            .flat_map(|paragraph| {
+                wrap_line_with_tabs(canvas, paragraph, max_width, paint, tab_width)
+            })
+            .map(|line| (offset_of(line), line))
+            .collect();
+    }
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let index = match break_text_at_grapheme(canvas, &text[start..], paint, max_width) {
+            0 => forced_break_index(canvas, &text[start..], paint, max_width),
+            index => index,
+        };
+        let index = start + index;
+        // trim is there to remove the \n
+        let line = text[start..index].trim();
+        lines.push((offset_of(line), line));
+        start = index;
+    }
+    lines
+}
+
+/// Wraps a line of text that contains tab characters at word boundaries, to lines of the given width. A tab that
+/// advances beyond the width breaks the line.
+fn wrap_line_with_tabs<'a>(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    line: &'a str,
+    max_width: f32,
+    paint: femtovg::Paint,
+    tab_width: f32,
+) -> Vec<&'a str> {
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut x = 0.;
+    let mut run_start = 0;
+    for (index, run) in line.split('\t').enumerate() {
+        if index > 0 {
+            let tab = run_start - 1;
+            x = next_tab_stop(x, tab_width);
+            if x > max_width && tab > line_start {
+                lines.push(line[line_start..tab].trim_end());
+                line_start = run_start;
+                x = 0.;
+            }
+        }
+        let mut start = run_start;
+        let run_end = run_start + run.len();
+        while start < run_end {
+            let index = break_text_at_grapheme(canvas, &line[start..run_end], paint, max_width - x);
+            if start + index == run_end {
+                x += canvas.measure_text(0., 0., &line[start..run_end], paint).unwrap().width();
+                break;
+            }
+            if index == 0 && start == line_start {
+                let end =
+                    start + forced_break_index(canvas, &line[start..run_end], paint, max_width - x);
+                lines.push(&line[line_start..end]);
+                start = end;
+                line_start = end;
+                x = 0.;
+                continue;
+            }
+            let end = start + index;
+            lines.push(line[line_start..end].trim_end());
+            start =
+                end + (line[end..run_end].len() - line[end..run_end].trim_start_matches(' ').len());
+            line_start = start;
+            x = 0.;
+        }
+        run_start = run_end + 1;
+    }
+    if line_start < line.len() || lines.is_empty() {
+        lines.push(&line[line_start..]);
+    }
+    lines
+}
+
+/// Returns the (UTF-8) byte offset in the text at which a text cursor placed at the given position goes, when the
+/// text is laid out in lines by text_lines. The x coordinate is relative to the start of the line.
+fn byte_offset_for_position(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    text: &str,
+    paint: femtovg::Paint,
+    pos: Point,
+    max_width: Option<f32>,
+    tab_width: Option<f32>,
+) -> usize {
+    let lines = text_lines(canvas, text, max_width, paint, tab_width);
+    let line_height = canvas.measure_font(paint).unwrap().height();
+    let line_index = if pos.y > 0. { (pos.y / line_height) as usize } else { 0 };
+    match lines.get(line_index).or_else(|| lines.last()) {
+        Some((_, line)) => {
+            let runs = match tab_width {
+                Some(tab_width) => tab_runs(canvas, line, paint, tab_width).0,
+                None => vec![(0., *line)],
+            };
+            // The run of text between the tab stops that the position is in.
+            let (run_x, run) =
+                runs.iter().rev().find(|(run_x, _)| *run_x <= pos.x).unwrap_or(&runs[0]);
+            let metrics = canvas.measure_text(0., 0., run, paint).unwrap();
+            let run_start = run.as_ptr() as usize - text.as_ptr() as usize;
+            run_start + byte_offset_for_x_position(&metrics, run, pos.x - run_x)
+        }
+        None => 0,
+    }
+}
+
+/// Returns the size of the text when laid out in lines by text_lines.
+fn text_block_size(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    text: &str,
+    max_width: Option<f32>,
+    paint: femtovg::Paint,
+    tab_width: Option<f32>,
+) -> Size {
+    let line_height = canvas.measure_font(paint).unwrap().height();
+    let mut y = 0.;
+    let mut width = 0.;
+    let mut height = 0.;
+    for (_, line) in text_lines(canvas, text, max_width, paint, tab_width) {
+        let mesure = line_size(canvas, line, paint, tab_width);
+        height = y + mesure.height;
+        y += line_height;
+        width = mesure.width.max(width);
+    }
+    euclid::size2(width, height)
+}
+
+/// Returns the top and the bottom of the part of a block of text that is centered with the given vertical
+/// centering, relative to the top of the block. text_height is the size of the block, as text_block_size() returns
+/// it, and line_count the number of its lines.
+fn centered_text_extent(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    paint: femtovg::Paint,
+    centering: TextVerticalCentering,
+    text_height: f32,
+    line_count: usize,
+) -> (f32, f32) {
+    let metrics = canvas.measure_font(paint).unwrap();
+    let (ascent, descent, _) = vertical_font_metrics(&metrics);
+    // The baseline of the last line.
+    let last_baseline = line_count.saturating_sub(1) as f32 * metrics.height() + ascent;
+    match centering {
+        TextVerticalCentering::line_box => (0., text_height),
+        TextVerticalCentering::ascent_descent => (0., last_baseline + descent),
+        TextVerticalCentering::cap_height => (ascent - cap_height(canvas, paint), last_baseline),
+    }
+}
+
+/// Returns the height of the capital letters of the font above the baseline, measured with the H. Fonts without
+/// an H are assumed to have capital letters of 70% of their ascent.
+fn cap_height(canvas: &mut femtovg::Canvas<impl femtovg::Renderer>, paint: femtovg::Paint) -> f32 {
+    canvas
+        .measure_text(0., 0., "H", paint)
+        .ok()
+        .and_then(|metrics| metrics.glyphs.first().map(|glyph| glyph.bearing_y))
+        .filter(|cap_height| *cap_height > 0.)
+        .unwrap_or_else(|| canvas.measure_font(paint).unwrap().ascender() * 0.7)
+}
+
+/// A line of the text of a TextInput, as laid out by layout_text_input.
+struct TextInputLine<'a> {
+    /// The position of the top left corner of the line.
+    pos: Point,
+    /// The (UTF-8) byte offset in the text of the element at which the line starts.
+    byte_offset: usize,
+    /// The text of the line, without the newline character.
+    text: &'a str,
+    /// The metrics of the glyphs of the line, at the position it is drawn at.
+    metrics: femtovg::TextMetrics,
+}
+
+/// Lays out the text of a TextInput that is drawn in the given rectangle, in lines that only break at newline
+/// characters. Each line is aligned horizontally on its own, and the block of lines vertically. Unlike with
+/// str::lines(), a newline at the end of the text starts an empty line, for the cursor to go to.
+fn layout_text_input<'a>(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    rect: Rect,
+    text: &'a str,
+    paint: femtovg::Paint,
+    horizontal_alignment: TextHorizontalAlignment,
+    vertical_alignment: TextVerticalAlignment,
+) -> Vec<TextInputLine<'a>> {
+    let line_height = canvas.measure_font(paint).unwrap().height();
+    let text_height = line_height * text.split('\n').count() as f32;
+    let translate_y = match vertical_alignment {
+        TextVerticalAlignment::top => 0.,
+        TextVerticalAlignment::center => rect.height() / 2. - text_height / 2.,
+        TextVerticalAlignment::bottom => rect.height() - text_height,
+    };
+
+    let mut byte_offset = 0;
+    let mut lines = Vec::new();
+    for (index, line) in text.split('\n').enumerate() {
+        let text_width = canvas.measure_text(0., 0., line, paint).unwrap().width();
+        let translate_x = match horizontal_alignment {
+            // The lines of a TextInput don't wrap, so there is nothing to justify.
+            TextHorizontalAlignment::left | TextHorizontalAlignment::justify => 0.,
+            TextHorizontalAlignment::center => rect.width() / 2. - text_width / 2.,
+            TextHorizontalAlignment::right => rect.width() - text_width,
+        };
+        let pos = rect.origin + euclid::vec2(translate_x, translate_y + index as f32 * line_height);
+        let metrics = canvas.measure_text(pos.x, pos.y, line, paint).unwrap();
+        lines.push(TextInputLine { pos, byte_offset, text: line, metrics });
+        byte_offset += line.len() + 1;
+    }
+    lines
+}
+
+/// A part of a line of a TextInput that is drawn in one go, in one color.
+struct TextInputRun {
+    /// The (UTF-8) byte range of the run in the text of the line.
+    range: std::ops::Range<usize>,
+    /// The x coordinate of the pen position at which the run starts.
+    x: f32,
+    /// Whether the run is part of the selection.
+    selected: bool,
+}
+
+/// Splits a line of a TextInput into the runs before, within and after the given selected (UTF-8) byte range of the
+/// text, so that each glyph is drawn exactly once. Each run is placed at the pen position that its first glyph has
+/// in the layout of the whole line. Empty runs are left out.
+fn text_input_runs(
+    line: &TextInputLine,
+    selection: Option<std::ops::Range<usize>>,
+) -> Vec<TextInputRun> {
+    let len = line.text.len();
+    let to_line_offset = |offset: usize| {
+        let mut offset = offset.saturating_sub(line.byte_offset).min(len);
+        while !line.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    };
+    let (start, end) = match selection {
+        Some(selection) => (to_line_offset(selection.start), to_line_offset(selection.end)),
+        None => (0, 0),
+    };
+    [(0..start, false), (start..end, true), (end..len, false)]
+        .iter()
+        .filter(|(range, _)| !range.is_empty())
+        .map(|(range, selected)| {
+            let x = if range.start == 0 && range.end == len {
+                line.pos.x
+            } else {
+                // The leftmost pen position, which is where the run starts in right-to-left text too.
+                line.metrics
+                    .glyphs
+                    .iter()
+                    .filter(|glyph| range.contains(&glyph.byte_index))
+                    .map(|glyph| glyph.x - glyph.bearing_x)
+                    .reduce(f32::min)
+                    .unwrap_or_else(|| {
+                        cursor_x_for_byte_offset(&line.metrics, line.text, range.start)
+                    })
+            };
+            TextInputRun { range: range.clone(), x, selected: *selected }
+        })
+        .collect()
+}
+
+/// Returns the horizontal scroll offset of a TextInput, which keeps the cursor with the given width visible within
+/// the width of the element. The text spans the given range of x coordinates, and the cursor is at cursor_x, both
+/// relative to the left edge of the element when not scrolled. The previous offset is kept, but not beyond showing
+/// one of the ends of the text at the edge of the element, so that text that fits along with the cursor is not
+/// scrolled. From there, the text scrolls only as far as needed to show the cursor.
+fn text_input_scroll_offset(
+    previous: f32,
+    text_extent: (f32, f32),
+    cursor_x: f32,
+    cursor_width: f32,
+    width: f32,
+) -> f32 {
+    let (text_left, text_right) = text_extent;
+    let offset = previous.max(text_left.min(0.)).min((text_right + cursor_width - width).max(0.));
+    if cursor_x < offset {
+        cursor_x
+    } else if cursor_x + cursor_width > offset + width {
+        cursor_x + cursor_width - width
+    } else {
+        offset
+    }
+}
+
+/// Returns the rectangles that highlight the given (UTF-8) byte range of the text of a TextInput laid out in the
+/// given lines, one for each line that the range covers. On the first line, the highlight extends from the start
+/// of the range to the right edge of the element, on the lines in between over the whole width, and on the last
+/// line from the left edge to the end of the range. A selection within a single line covers just its glyphs.
+fn selection_rects(
+    lines: &[TextInputLine],
+    selection: std::ops::Range<usize>,
+    left: f32,
+    right: f32,
+    line_height: f32,
+) -> Vec<Rect> {
+    lines
+        .iter()
+        .filter(|line| {
+            selection.start <= line.byte_offset + line.text.len()
+                && selection.end > line.byte_offset
+        })
+        .map(|line| {
+            let line_end = line.byte_offset + line.text.len();
+            let x_for_offset = |offset: usize| {
+                cursor_x_for_byte_offset(&line.metrics, line.text, offset - line.byte_offset)
+            };
+            let start_x = if selection.start >= line.byte_offset {
+                x_for_offset(selection.start)
+            } else {
+                left
+            };
+            let end_x = if selection.end <= line_end { x_for_offset(selection.end) } else { right };
+            // In right-to-left text, the selection extends to the left of the anchor.
+            Rect::new(
+                Point::new(start_x.min(end_x), line.pos.y),
+                euclid::size2((end_x - start_x).abs(), line_height),
+            )
+        })
+        .collect()
+}
+
+/// Returns the position of the top of the text cursor placed at the given (UTF-8) byte offset in the text of a
+/// TextInput laid out in the given lines.
+fn cursor_position_in_lines(lines: &[TextInputLine], byte_offset: usize) -> Point {
+    match lines.iter().rev().find(|line| line.byte_offset <= byte_offset) {
+        Some(line) => Point::new(
+            cursor_x_for_byte_offset(&line.metrics, line.text, byte_offset - line.byte_offset),
+            line.pos.y,
+        ),
+        None => Point::default(),
+    }
+}
+
+/// A run of text that draw_text draws in one go: a line, or the part of a line between two tab stops.
+struct TextRun<'a> {
+    /// The position of the top left corner of the run.
+    pos: Point,
+    /// The (UTF-8) byte offset in the text of the element at which the run starts.
+    byte_offset: usize,
+    /// The text of the run, which ends with an ellipsis if the line it is part of was elided.
+    text: Cow<'a, str>,
+}
+
+/// Lays out the text of a Text element drawn at the given position into the runs that draw_text draws: broken
+/// into lines, cut off after max_lines, aligned, elided and split at the tab stops.
+fn layout_text<'a>(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    pos: Point,
+    text: std::pin::Pin<&sixtyfps_corelib::items::Text>,
+    string: &'a str,
+    paint: femtovg::Paint,
+    tab_width: Option<f32>,
+) -> Vec<TextRun<'a>> {
+    let pos = pos + euclid::Vector2D::new(text.x(), text.y());
+    let max_width = text.width();
+    let max_height = text.height();
+    if max_width <= 0. || max_height <= 0. {
+        return Vec::new();
+    }
+
+    // A negative max_lines means no limit.
+    let max_lines = std::convert::TryFrom::try_from(text.max_lines()).ok();
+    if max_lines == Some(0) {
+        return Vec::new();
+    }
+
+    let wrap = text.wrap() == TextWrap::word_wrap;
+    let elide = !wrap && text.overflow() == TextOverflow::elide;
+    let horizontal_alignment = text.horizontal_alignment();
+    let max_line_width = if wrap { Some(max_width) } else { None };
+    let mut lines = text_lines(canvas, string, max_line_width, paint, tab_width);
+    // The lines beyond max_lines are left out, and the last line shown ends with an ellipsis.
+    let cut_off = max_lines.map_or(false, |max_lines| lines.len() > max_lines);
+    let shown_text = match max_lines {
+        Some(max_lines) if cut_off => {
+            lines.truncate(max_lines);
+            let (offset, line) = lines[max_lines - 1];
+            &string[..offset + line.len()]
+        }
+        _ => string,
+    };
+    let text_size = text_block_size(canvas, shown_text, max_line_width, paint, tab_width);
+    let line_height = canvas.measure_font(paint).unwrap().height();
+
+    let mut y = pos.y
+        + match text.vertical_alignment() {
+            TextVerticalAlignment::top => 0.,
+            TextVerticalAlignment::center => {
+                let (top, bottom) = centered_text_extent(
+                    canvas,
+                    paint,
+                    text.vertical_centering(),
+                    text_size.height,
+                    lines.len(),
+                );
+                max_height / 2. - (top + bottom) / 2.
+            }
+            TextVerticalAlignment::bottom => max_height - text_size.height,
+        };
+
+    let mut runs = Vec::new();
+    let line_count = lines.len();
+    for (index, (line_offset, line)) in lines.into_iter().enumerate() {
+        let mut to_draw = Cow::Borrowed(line);
+        if cut_off && index + 1 == line_count {
+            to_draw = Cow::Owned(elided_line(canvas, line.trim_end(), paint, tab_width, max_width));
+        } else if !wrap && line_size(canvas, line, paint, tab_width).width > max_width {
+            if elide {
+                to_draw = Cow::Owned(elided_line(canvas, line, paint, tab_width, max_width));
+            } else if let Some(index) =
+                byte_offset_beyond_x(canvas, line, paint, tab_width, max_width)
+            {
+                to_draw = Cow::Borrowed(&line[..index]);
+            }
+        }
+
+        // The last line of a paragraph is followed by a newline, or by nothing but the spaces that
+        // text_lines() trimmed.
+        let ends_paragraph = string[line_offset + line.len()..]
+            .trim_start_matches(' ')
+            .chars()
+            .next()
+            .map_or(true, |c| c == '\n' || c == '\r');
+        let justify = wrap
+            && horizontal_alignment == TextHorizontalAlignment::justify
+            && matches!(to_draw, Cow::Borrowed(_))
+            && !ends_paragraph
+            && line.contains(' ')
+            && !line.contains('\t');
+
+        let (line_runs, line_width) = match tab_width {
+            _ if justify => (justified_runs(canvas, &to_draw, paint, max_width), max_width),
+            Some(tab_width) if to_draw.contains('\t') => {
+                tab_runs(canvas, &to_draw, paint, tab_width)
+            }
+            _ => (vec![(0., &*to_draw)], line_size(canvas, &to_draw, paint, None).width),
+        };
+        let translate_x = match horizontal_alignment {
+            TextHorizontalAlignment::left | TextHorizontalAlignment::justify => 0.,
+            TextHorizontalAlignment::center => max_width / 2. - line_width / 2.,
+            TextHorizontalAlignment::right => max_width - line_width,
+        };
+        let line_runs = line_runs
+            .into_iter()
+            .filter(|(_, run)| !run.is_empty())
+            .map(|(run_x, run)| {
+                let start = run.as_ptr() as usize - to_draw.as_ptr() as usize;
+                (run_x, start..start + run.len())
+            })
+            .collect::<Vec<_>>();
+        for (run_x, range) in line_runs {
+            runs.push(TextRun {
+                pos: Point::new(pos.x + translate_x + run_x, y),
+                byte_offset: line_offset + range.start,
+                text: match &to_draw {
+                    Cow::Borrowed(line) => Cow::Borrowed(&line[range]),
+                    Cow::Owned(line) => Cow::Owned(line[range].to_owned()),
+                },
+            });
+        }
+        y += line_height;
+    }
+    runs
+}
+
+/// A glyph of a text laid out by the GL backend, see [`GLItemRenderer::text_glyphs`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    /// The (UTF-8) byte offset in the text of the character that the glyph was shaped from. The ellipsis of an
+    /// elided line has the offset of the first character that it replaces.
+    pub byte_index: usize,
+    /// The x coordinate of the left edge of the glyph's outline.
+    pub x: f32,
+    /// The y coordinate of the top edge of the glyph's outline.
+    pub y: f32,
+    /// The horizontal distance from the pen position of the glyph to the pen position of the next glyph.
+    pub advance: f32,
+}
+
+/// The line of text that the position it is drawn at refers to, see [`GLItemRenderer::draw_text_with_baseline`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextBaseline {
+    /// The top of the line: the ascent of the font above the alphabetic baseline.
+    Top,
+    /// The middle between the ascent and the descent of the font.
+    Middle,
+    /// The line that the letters of the Latin alphabet sit on.
+    Alphabetic,
+}
+
+impl Default for TextBaseline {
+    fn default() -> Self {
+        Self::Top
+    }
+}
+
+impl From<TextBaseline> for femtovg::Baseline {
+    fn from(baseline: TextBaseline) -> Self {
+        match baseline {
+            TextBaseline::Top => Self::Top,
+            TextBaseline::Middle => Self::Middle,
+            TextBaseline::Alphabetic => Self::Alphabetic,
+        }
+    }
+}
+
+// Returns how far below the position that femtovg draws text at the alphabetic baseline of the text is, with the
+// baseline of the paint.
+fn alphabetic_baseline_offset(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    paint: femtovg::Paint,
+) -> f32 {
+    let metrics = canvas.measure_font(paint).unwrap();
+    match paint.text_baseline() {
+        femtovg::Baseline::Top => metrics.ascender(),
+        femtovg::Baseline::Middle => (metrics.ascender() + metrics.descender()) / 2.,
+        femtovg::Baseline::Alphabetic => 0.,
+        femtovg::Baseline::Bottom => metrics.descender(),
+    }
+}
+
+// Returns how far the position of text laid out with the paint, which has the top baseline, moves down when the text
+// is drawn with the given baseline instead.
+fn baseline_shift(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    paint: femtovg::Paint,
+    baseline: TextBaseline,
+) -> f32 {
+    let mut baseline_paint = paint;
+    baseline_paint.set_text_baseline(baseline.into());
+    alphabetic_baseline_offset(canvas, paint) - alphabetic_baseline_offset(canvas, baseline_paint)
+}
+
+/// The number of copies of the text that a blurred text shadow is approximated with.
+const TEXT_SHADOW_BLUR_SAMPLES: usize = 9;
+
+/// Returns the passes in which draw_text fills the text of an element with the given color and shadow: the
+/// offset from the position of the text and the color of each, in the order in which they are drawn. The shadow
+/// comes first, and the text itself last. femtovg cannot blur, so a blurred shadow is approximated by copies of
+/// the text spread over a disk with the radius of half the blur, each with the alpha that makes the copies add
+/// up to the alpha of the shadow color where they all overlap.
+fn text_fill_passes(
+    color: Color,
+    shadow_offset: euclid::default::Vector2D<f32>,
+    shadow_blur: f32,
+    shadow_color: Color,
+) -> Vec<(euclid::default::Vector2D<f32>, Color)> {
+    let mut passes = Vec::new();
+    if shadow_color.alpha() > 0 {
+        if shadow_blur <= 0. {
+            passes.push((shadow_offset, shadow_color));
+        } else {
+            let samples = TEXT_SHADOW_BLUR_SAMPLES;
+            let alpha = shadow_color.alpha() as f32 / 255.;
+            let sample_alpha = 1. - (1. - alpha).powf(1. / samples as f32);
+            let sample_color = Color::from_argb_u8(
+                ((sample_alpha * 255.).round() as u8).max(1),
+                shadow_color.red(),
+                shadow_color.green(),
+                shadow_color.blue(),
+            );
+            passes.push((shadow_offset, sample_color));
+            let radius = shadow_blur / 2.;
+            for i in 1..samples {
+                let angle = 2. * std::f32::consts::PI * (i - 1) as f32 / (samples - 1) as f32;
+                let sample_offset = euclid::vec2(angle.cos(), angle.sin()) * radius;
+                passes.push((shadow_offset + sample_offset, sample_color));
+            }
+        }
+    }
+    passes.push((euclid::Vector2D::zero(), color));
+    passes
+}
+
+/// Returns the glyphs of the text runs, at the positions at which they are drawn.
+fn positioned_glyphs(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    runs: &[TextRun],
+    paint: femtovg::Paint,
+) -> Vec<PositionedGlyph> {
+    let mut glyphs = Vec::new();
+    for run in runs {
+        let metrics = canvas.measure_text(run.pos.x, run.pos.y, &run.text, paint).unwrap();
+        glyphs.extend(metrics.glyphs.iter().map(|glyph| PositionedGlyph {
+            byte_index: run.byte_offset + glyph.byte_index,
+            x: glyph.x,
+            y: glyph.y,
+            advance: glyph.advance_x + paint.letter_spacing(),
+        }));
+    }
+    glyphs
+}
+
+/// Returns the rectangles behind the glyphs of the given (UTF-8) byte range of the text laid out in the given runs,
+/// one for each run that has glyphs in the range. They span the advances of the glyphs horizontally and the height
+/// of the line vertically.
+fn highlight_rects(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    runs: &[TextRun],
+    paint: femtovg::Paint,
+    range: std::ops::Range<usize>,
+) -> Vec<Rect> {
+    let line_height = canvas.measure_font(paint).unwrap().height();
+    runs.iter()
+        .filter_map(|run| {
+            let metrics = canvas.measure_text(run.pos.x, run.pos.y, &run.text, paint).unwrap();
+            let (left, right) = metrics
+                .glyphs
+                .iter()
+                .filter(|glyph| range.contains(&(run.byte_offset + glyph.byte_index)))
+                .fold((f32::MAX, f32::MIN), |(left, right), glyph| {
+                    let pen_x = glyph.x - glyph.bearing_x;
+                    (left.min(pen_x), right.max(pen_x + glyph.advance_x + paint.letter_spacing()))
+                });
+            if left > right {
+                return None;
+            }
+            Some(Rect::new(Point::new(left, run.pos.y), euclid::size2(right - left, line_height)))
+        })
+        .collect()
+}
+
+/// Returns the area of the render target, in its pixels, that is cleared and drawn for a frame: the union of the
+/// damaged regions, mapped from the coordinates of the items to the target and rounded out to whole pixels. When the
+/// damage is not known, that is the whole target.
+fn redrawn_target_rect(
+    damaged_regions: Option<&[Rect]>,
+    items_to_target: &euclid::default::Transform2D<f32>,
+    target_size: (u32, u32),
+) -> euclid::default::Box2D<u32> {
+    let target = euclid::default::Box2D::new(euclid::point2(0, 0), target_size.into());
+    let damage = match damaged_regions {
+        Some(regions) if !regions.is_empty() => {
+            regions.iter().fold(Rect::zero(), |union, region| union.union(region))
+        }
+        _ => return target,
+    };
+    let damage = items_to_target.outer_transformed_rect(&damage).round_out().to_box2d();
+    let damage =
+        euclid::default::Box2D::new(damage.min.max(Point::zero()), damage.max.max(Point::zero()));
+    damage.to_u32().intersection(&target).unwrap_or_default()
+}
+
+/// Returns the gradients that fade out the content of a clip rectangle towards its edges: for each faded edge, the
+/// strip along the edge, the point at the edge where the content is transparent, and the point at the inner side
+/// of the strip where the content is fully opaque.
+fn clip_fade_strips(
+    rect: Rect,
+    fade_horizontal: f32,
+    fade_vertical: f32,
+) -> Vec<(Rect, Point, Point)> {
+    let mut strips = Vec::new();
+    let fade_horizontal = fade_horizontal.min(rect.width() / 2.);
+    if fade_horizontal > 0. {
+        let (left, right) = (rect.min_x(), rect.max_x());
+        let y = rect.min_y();
+        strips.push((
+            euclid::rect(left, y, fade_horizontal, rect.height()),
+            Point::new(left, y),
+            Point::new(left + fade_horizontal, y),
+        ));
+        strips.push((
+            euclid::rect(right - fade_horizontal, y, fade_horizontal, rect.height()),
+            Point::new(right, y),
+            Point::new(right - fade_horizontal, y),
+        ));
+    }
+    let fade_vertical = fade_vertical.min(rect.height() / 2.);
+    if fade_vertical > 0. {
+        let (top, bottom) = (rect.min_y(), rect.max_y());
+        let x = rect.min_x();
+        strips.push((
+            euclid::rect(x, top, rect.width(), fade_vertical),
+            Point::new(x, top),
+            Point::new(x, top + fade_vertical),
+        ));
+        strips.push((
+            euclid::rect(x, bottom - fade_vertical, rect.width(), fade_vertical),
+            Point::new(x, bottom),
+            Point::new(x, bottom - fade_vertical),
+        ));
+    }
+    strips
+}
+
+/// Rectangles of the same color, drawn one after the other, which are filled together with a single path instead
+/// of one draw call each.
+#[derive(Default)]
+struct RectangleBatch {
+    color: Color,
+    /// The rectangles, in the coordinates of the canvas, with their border radius.
+    rects: Vec<(Rect, f32)>,
+}
+
+impl RectangleBatch {
+    /// Adds a rectangle to the batch. If the rectangles batched so far need to be filled before it, they are
+    /// returned and the batch starts over: when the color differs, and when a translucent rectangle overlaps
+    /// one of them, as the overlapping area would be blended only once within a single path.
+    fn add(&mut self, color: Color, rect: Rect, radius: f32) -> Option<RectangleBatch> {
+        let split = !self.rects.is_empty()
+            && (self.color != color
+                || (color.alpha() < 255
+                    && self.rects.iter().any(|(batched, _)| batched.intersects(&rect))));
+        let previous = if split { self.take() } else { None };
+        self.color = color;
+        self.rects.push((rect, radius));
+        previous
+    }
+
+    /// Returns the rectangles batched so far, if any, emptying the batch.
+    fn take(&mut self) -> Option<RectangleBatch> {
+        if self.rects.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(self))
+        }
+    }
+
+    fn to_path(&self) -> femtovg::Path {
+        let mut path = femtovg::Path::new();
+        for (rect, radius) in &self.rects {
+            if *radius > 0. {
+                path.rounded_rect(rect.min_x(), rect.min_y(), rect.width(), rect.height(), *radius);
+            } else {
+                path.rect(rect.min_x(), rect.min_y(), rect.width(), rect.height());
+            }
+        }
+        path
+    }
+}
+
+/// Applies the transform to everything drawn on the canvas from now on, in addition to the current transform.
+/// Clip rectangles set afterwards are transformed too, but femtovg approximates their intersection with a clip
+/// set before a rotation by that clip's bounding box.
+fn concat_transform(
+    canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+    transform: &euclid::default::Transform2D<f32>,
+) {
+    canvas.set_transform(
+        transform.m11,
+        transform.m12,
+        transform.m21,
+        transform.m22,
+        transform.m31,
+        transform.m32,
+    );
+}
+
+/// Rounds a point, in the coordinates of a canvas with the given transform, to the nearest device pixel boundary.
+/// Returns the text antialiasing to use for text drawn with the given transform: the requested one, unless
+/// subpixel antialiasing would give the glyphs colored fringes, because the text is rotated, skewed or mirrored, or
+/// because it is blended with what is behind a transparent window.
+fn effective_text_antialiasing(
+    requested: TextAntialiasing,
+    transform: &femtovg::Transform2D,
+    transparent_window: bool,
+) -> TextAntialiasing {
+    let [a, b, c, d, _, _] = transform.0;
+    let axis_aligned = b == 0. && c == 0. && a > 0. && d > 0.;
+    if requested == TextAntialiasing::Subpixel && axis_aligned && !transparent_window {
+        TextAntialiasing::Subpixel
+    } else {
+        TextAntialiasing::Grayscale
+    }
+}
+
+fn snap_point_to_device_pixels(point: Point, transform: &femtovg::Transform2D) -> Point {
+    let (x, y) = transform.transform_point(point.x, point.y);
+    let (x, y) = transform.inversed().transform_point(x.round(), y.round());
+    euclid::point2(x, y)
+}
+
+/// Rounds a length to whole device pixels, at the given scale from canvas coordinates to device pixels. Lengths
+/// that are not empty are kept at least one pixel long.
+fn snap_length_to_device_pixels(length: f32, scale: f32) -> f32 {
+    if length <= 0. || scale <= 0. {
+        return length;
+    }
+    (length * scale).round().max(1.) / scale
+}
+
+/// Rounds the origin and the size of a rectangle, in the coordinates of a canvas with the given transform, to
+/// device pixels. The size is rounded on its own, so that rectangles of the same size, such as the sides of a
+/// 1 pixel border, cover the same number of pixels wherever they are. The transform is assumed to only scale
+/// and translate.
+fn snap_rect_to_device_pixels(rect: Rect, transform: &femtovg::Transform2D) -> Rect {
+    let scale = transform.average_scale();
+    Rect::new(
+        snap_point_to_device_pixels(rect.origin, transform),
+        euclid::size2(
+            snap_length_to_device_pixels(rect.width(), scale),
+            snap_length_to_device_pixels(rect.height(), scale),
+        ),
+    )
+}
+
+/// How the colors of what is drawn are combined with the colors already drawn, see
+/// [`GLItemRenderer::push_blend_mode`]. Only the modes that fixed-function GL blending can compute are available;
+/// modes such as overlay, which depend on the destination color in a non-linear way, would need a shader and are
+/// not supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The colors are drawn over what is below them, the default.
+    Normal,
+    /// The colors are multiplied with the colors below them, which darkens them.
+    Multiply,
+    /// The inverted colors are multiplied with the inverted colors below them, which lightens them.
+    Screen,
+    /// The colors are added to the colors below them.
+    Additive,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+/// Returns the source and destination blend factors that compute the blend mode for colors with premultiplied
+/// alpha, as femtovg draws them. Multiply assumes an opaque destination.
+fn blend_factors(mode: BlendMode) -> (femtovg::BlendFactor, femtovg::BlendFactor) {
+    use femtovg::BlendFactor;
+    match mode {
+        BlendMode::Normal => (BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+        BlendMode::Multiply => (BlendFactor::DstColor, BlendFactor::OneMinusSrcAlpha),
+        BlendMode::Screen => (BlendFactor::One, BlendFactor::OneMinusSrcColor),
+        BlendMode::Additive => (BlendFactor::One, BlendFactor::One),
+    }
+}
+
+/// The appearance of a focus or selection ring drawn around an item with [`GLItemRenderer::draw_focus_ring`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FocusRing {
+    /// The color of the ring.
+    pub color: Color,
+    /// The width of the line of the ring.
+    pub width: f32,
+    /// The distance between the geometry of the item and the inner edge of the ring.
+    pub gap: f32,
+    /// The corner radius of the item. The corners of the ring are rounded around them, at the same distance as
+    /// the straight edges, so the ring is rounded even around square corners.
+    pub radius: f32,
+    /// The length of the dashes of the ring, and of the gaps between them, or 0 for a solid ring. The length is
+    /// adjusted so that the dashes are spread evenly around the ring. A length equal to the width draws dots.
+    pub dash_length: f32,
+}
+
+impl Default for FocusRing {
+    fn default() -> Self {
+        FocusRing {
+            color: Color::from_rgb_u8(0, 0, 0),
+            width: 1.,
+            gap: 2.,
+            radius: 0.,
+            dash_length: 0.,
+        }
+    }
+}
+
+fn rect_to_path(r: Rect) -> femtovg::Path {
+    let mut path = femtovg::Path::new();
+    path.rect(r.min_x(), r.min_y(), r.width(), r.height());
+    path
+}
+
+impl ItemRenderer for GLItemRenderer {
+    fn draw_rectangle(
+        &mut self,
+        pos: Point,
+        rect: std::pin::Pin<&sixtyfps_corelib::items::Rectangle>,
+    ) {
+        let geometry = rect.geometry();
+        if geometry.is_empty() {
+            return;
+        }
+        let geometry = self.snap_rect(geometry.translate(pos.to_vector()));
+        if let Some(batch) = self.rectangle_batch.add(rect.color(), geometry, 0.) {
+            self.fill_rectangles(batch);
+        }
+    }
+
+    fn draw_border_rectangle(
+        &mut self,
+        pos: Point,
+        rect: std::pin::Pin<&sixtyfps_corelib::items::BorderRectangle>,
+    ) {
+        let geometry = rect.geometry();
+        if geometry.is_empty() {
+            return;
+        }
+
+        let geometry = self.snap_rect(geometry.translate(pos.to_vector()));
+        let gradient_paint = self.conic_gradient_paint(
+            geometry,
+            rect.conic_gradient_stops(),
+            euclid::point2(rect.conic_gradient_center_x(), rect.conic_gradient_center_y()),
+            rect.conic_gradient_start_angle(),
+        );
+        let scale = self.shared_data.canvas.borrow().transform().average_scale();
+        // If the border width exceeds the width, just fill the rectangle.
+        let border_width = if rect.border_color().alpha() == 0 {
+            0.
+        } else {
+            effective_stroke_width(rect.border_width(), rect.hairline(), scale)
+                .min(geometry.width() / 2.)
+        };
+        // Without a visible border, the rectangle is only filled, like the rectangles without border.
+        if border_width <= 0. && gradient_paint.is_none() {
+            if let Some(batch) =
+                self.rectangle_batch.add(rect.color(), geometry, rect.border_radius())
+            {
+                self.fill_rectangles(batch);
+            }
+            return;
+        }
+        self.flush_rectangle_batch();
+        // Hairlines are already one device pixel wide.
+        let border_width =
+            if self.shared_data.pixel_snapping && border_width > 0. && !rect.hairline() {
+                snap_length_to_device_pixels(border_width, scale).min(geometry.width() / 2.)
+            } else {
+                border_width
+            };
+        let alignment = rect.stroke_alignment();
+        let (stroke_rect, stroke_radius) =
+            border_stroke_outline(geometry, rect.border_radius(), border_width, alignment);
+        let mut path = femtovg::Path::new();
+        path.rounded_rect(
+            stroke_rect.min_x(),
+            stroke_rect.min_y(),
+            stroke_rect.width(),
+            stroke_rect.height(),
+            stroke_radius,
+        );
+        // The fill reaches the middle of a border inside of the rectangle, and the whole rectangle otherwise.
+        let mut fill_path = if alignment == StrokeAlignment::inside {
+            path.clone()
+        } else {
+            let mut fill_path = femtovg::Path::new();
+            fill_path.rounded_rect(
+                geometry.min_x(),
+                geometry.min_y(),
+                geometry.width(),
+                geometry.height(),
+                rect.border_radius(),
+            );
+            fill_path
+        };
+
+        let fill_paint = gradient_paint
+            .unwrap_or_else(|| femtovg::Paint::color(self.shared_data.paint_color(rect.color())));
+
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        canvas.fill_path(&mut fill_path, fill_paint);
+        if border_width > 0. {
+            let mut border_paint =
+                femtovg::Paint::color(self.shared_data.paint_color(rect.border_color()));
+            border_paint.set_line_width(border_width);
+            canvas.stroke_path(&mut path, border_paint);
+        }
+    }
+
+    fn draw_image(&mut self, pos: Point, image: std::pin::Pin<&sixtyfps_corelib::items::Image>) {
+        if self.mirrored {
+            let rect = image.geometry().translate(pos.to_vector());
+            return self.draw_unmirrored(rect, |renderer| renderer.draw_image(pos, image));
+        }
+        self.flush_rectangle_batch();
+        self.draw_image_impl(
+            pos + euclid::Vector2D::new(image.x(), image.y()),
+            &image.cached_rendering_data,
+            sixtyfps_corelib::items::Image::FIELD_OFFSETS.source.apply_pin(image),
+            image.width(),
+            image.height(),
+            &ImageDrawOptions {
+                source_clip_rect: Rect::default(),
+                image_fit: image.image_fit(),
+                alignment: image_alignment_factors(
+                    image.horizontal_alignment(),
+                    image.vertical_alignment(),
+                ),
+                orientation: ImageOrientation {
+                    rotation_angle: image.rotation_angle(),
+                    flip_horizontal: image.flip_horizontal(),
+                    flip_vertical: image.flip_vertical(),
+                },
+                colorize_property: sixtyfps_corelib::items::Image::FIELD_OFFSETS
+                    .colorize
+                    .apply_pin(image),
+                image_rendering: image.image_rendering(),
+                nine_slice: euclid::default::SideOffsets2D::new(
+                    image.nine_slice_top() as _,
+                    image.nine_slice_right() as _,
+                    image.nine_slice_bottom() as _,
+                    image.nine_slice_left() as _,
+                ),
+                opacity: image.opacity(),
+            },
+        );
+    }
+
+    fn draw_clipped_image(
+        &mut self,
+        pos: Point,
+        clipped_image: std::pin::Pin<&sixtyfps_corelib::items::ClippedImage>,
+    ) {
+        if self.mirrored {
+            let rect = clipped_image.geometry().translate(pos.to_vector());
+            return self
+                .draw_unmirrored(rect, |renderer| renderer.draw_clipped_image(pos, clipped_image));
+        }
+        self.flush_rectangle_batch();
+        let source_clip_rect = Rect::new(
+            [clipped_image.source_clip_x() as _, clipped_image.source_clip_y() as _].into(),
+            [clipped_image.source_clip_width() as _, clipped_image.source_clip_height() as _]
+                .into(),
+        );
+
+        self.draw_image_impl(
+            pos + euclid::Vector2D::new(clipped_image.x(), clipped_image.y()),
+            &clipped_image.cached_rendering_data,
+            sixtyfps_corelib::items::ClippedImage::FIELD_OFFSETS.source.apply_pin(clipped_image),
+            clipped_image.width(),
+            clipped_image.height(),
+            &ImageDrawOptions {
+                source_clip_rect,
+                image_fit: clipped_image.image_fit(),
+                alignment: image_alignment_factors(
+                    clipped_image.horizontal_alignment(),
+                    clipped_image.vertical_alignment(),
+                ),
+                orientation: ImageOrientation {
+                    rotation_angle: clipped_image.rotation_angle(),
+                    flip_horizontal: clipped_image.flip_horizontal(),
+                    flip_vertical: clipped_image.flip_vertical(),
+                },
+                colorize_property: sixtyfps_corelib::items::ClippedImage::FIELD_OFFSETS
+                    .colorize
+                    .apply_pin(clipped_image),
+                image_rendering: clipped_image.image_rendering(),
+                nine_slice: euclid::default::SideOffsets2D::new(
+                    clipped_image.nine_slice_top() as _,
+                    clipped_image.nine_slice_right() as _,
+                    clipped_image.nine_slice_bottom() as _,
+                    clipped_image.nine_slice_left() as _,
+                ),
+                opacity: clipped_image.opacity(),
+            },
+        );
+    }
+
+    fn draw_text(&mut self, pos: Point, text: std::pin::Pin<&sixtyfps_corelib::items::Text>) {
+        self.draw_text_with_baseline(pos, text, TextBaseline::Top);
+    }
+
+    fn draw_text_input(
+        &mut self,
+        pos: Point,
+        text_input: std::pin::Pin<&sixtyfps_corelib::items::TextInput>,
+    ) {
+        if self.mirrored {
+            let rect = text_input.geometry().translate(pos.to_vector());
+            return self
+                .draw_unmirrored(rect, |renderer| renderer.draw_text_input(pos, text_input));
+        }
+        self.flush_rectangle_batch();
+        let width = text_input.width();
+        let height = text_input.height();
+        if width <= 0. || height <= 0. {
+            return;
+        }
+
+        let pos = pos + euclid::Vector2D::new(text_input.x(), text_input.y());
+        let font = self.shared_data.loaded_fonts.borrow_mut().font(
+            &self.shared_data.canvas,
+            text_input.font_request(),
+            self.scale_factor,
+        );
+
+        // The cursor and the selection are mapped to the displayed text, which masks the text in password mode.
+        let text = text_input.displayed_text();
+        let paint = font.paint();
+        let lines = layout_text_input(
+            &mut self.shared_data.canvas.borrow_mut(),
+            Rect::new(pos, euclid::size2(width, height)),
+            &text.text,
+            paint,
+            text_input.horizontal_alignment(),
+            text_input.vertical_alignment(),
+        );
+
+        // When the text is wider than the element, it is scrolled horizontally to keep the cursor in view, and
+        // clipped to the element.
+        let cursor_width = text_input.text_cursor_width() * self.scale_factor;
+        let cursor_index = text_input.cursor_position();
+        let cursor =
+            cursor_position_in_lines(&lines, text.displayed_offset(cursor_index.max(0) as usize));
+        let text_extent = lines.iter().fold((f32::MAX, f32::MIN), |(left, right), line| {
+            (left.min(line.pos.x - pos.x), right.max(line.pos.x + line.metrics.width() - pos.x))
+        });
+        let scroll_offset = text_input_scroll_offset(
+            text_input.horizontal_scroll_offset.get(),
+            text_extent,
+            cursor.x - pos.x,
+            cursor_width,
+            width,
+        );
+        text_input.horizontal_scroll_offset.set(scroll_offset);
+        let overflowing = text_extent.0 < 0. || text_extent.1 + cursor_width > width;
+        if overflowing {
+            let mut canvas = self.shared_data.canvas.borrow_mut();
+            canvas.save();
+            canvas.intersect_scissor(pos.x, pos.y, width, height);
+            canvas.translate(-scroll_offset, 0.);
+        }
+
+        // The selection covers the glyphs from the anchor up to (but excluding) the cursor, which
+        // matches TextInput::selected_text() that's used for copying to the clipboard.
+        let selected = text_input.selection_range();
+        let selection =
+            Some(text.displayed_offset(selected.start)..text.displayed_offset(selected.end))
+                .filter(|_| !selected.is_empty());
+        let (visible_left, visible_right) = (pos.x + scroll_offset, pos.x + scroll_offset + width);
+        let selection_rects = selection.clone().map_or_else(Vec::new, |selection| {
+            selection_rects(&lines, selection, visible_left, visible_right, font.height())
+        });
+
+        if !text_input.invert_selection() {
+            let mut canvas = self.shared_data.canvas.borrow_mut();
+            for selection_rect in &selection_rects {
+                canvas.fill_path(
+                    &mut rect_to_path(*selection_rect),
+                    femtovg::Paint::color(
+                        self.shared_data.paint_color(text_input.selection_background_color()),
+                    ),
+                );
+            }
+        }
+
+        // The selected text is drawn separately from the text before and after it, so that every glyph is drawn
+        // exactly once, in the color for whether it is selected.
+        let highlighted_selection = selection
+            .filter(|_| !text_input.invert_selection())
+            .map(|selection| (selection, text_input.selection_foreground_color()));
+        self.fill_text_input_lines(&font, paint, &lines, text_input.color(), highlighted_selection);
+
+        if text_input.invert_selection() && !selection_rects.is_empty() {
+            // Subtract the colors under the selection from white, leaving the alpha channel as it is.
+            let mut canvas = self.shared_data.canvas.borrow_mut();
+            canvas.save_with(|canvas| {
+                canvas.global_composite_blend_func_separate(
+                    femtovg::BlendFactor::OneMinusDstColor,
+                    femtovg::BlendFactor::Zero,
+                    femtovg::BlendFactor::Zero,
+                    femtovg::BlendFactor::One,
+                );
+                for selection_rect in &selection_rects {
+                    canvas.fill_path(
+                        &mut rect_to_path(*selection_rect),
+                        femtovg::Paint::color(femtovg::Color::white()),
+                    );
+                }
+            });
+        }
+
+        if cursor_index >= 0 && text_input.cursor_visible() {
+            let mut cursor_rect = femtovg::Path::new();
+            cursor_rect.rect(cursor.x, cursor.y, cursor_width, font.height());
+            self.shared_data.canvas.borrow_mut().fill_path(
+                &mut cursor_rect,
+                femtovg::Paint::color(
+                    self.shared_data.paint_color(text_input.effective_cursor_color()),
+                ),
+            );
+        }
+
+        if overflowing {
+            self.shared_data.canvas.borrow_mut().restore();
+        }
+    }
+
+    fn draw_path(&mut self, pos: Point, path: std::pin::Pin<&sixtyfps_corelib::items::Path>) {
+        self.flush_rectangle_batch();
+        let elements = path.elements();
+        if matches!(elements, sixtyfps_corelib::PathData::None) {
+            return;
+        }
+        let mut fpath = femtovg::Path::new();
+        for x in elements.iter_fitted(path.width(), path.height()).iter() {
+            match x {
+                lyon_path::Event::Begin { at } => {
+                    fpath.move_to(at.x, at.y);
+                }
+                lyon_path::Event::Line { from: _, to } => {
+                    fpath.line_to(to.x, to.y);
+                }
+                lyon_path::Event::Quadratic { from: _, ctrl, to } => {
+                    fpath.quad_to(ctrl.x, ctrl.y, to.x, to.y);
+                }
+
+                lyon_path::Event::Cubic { from: _, ctrl1, ctrl2, to } => {
+                    fpath.bezier_to(ctrl1.x, ctrl1.y, ctrl2.x, ctrl2.y, to.x, to.y);
+                }
+                lyon_path::Event::End { last: _, first: _, close } => {
+                    if close {
+                        fpath.close()
+                    }
+                }
+            }
+        }
+
+        let fill_paint = self
+            .conic_gradient_paint(
+                euclid::rect(0., 0., path.width(), path.height()),
+                path.conic_gradient_stops(),
+                euclid::point2(path.conic_gradient_center_x(), path.conic_gradient_center_y()),
+                path.conic_gradient_start_angle(),
+            )
+            .unwrap_or_else(|| {
+                femtovg::Paint::color(self.shared_data.paint_color(path.fill_color()))
+            });
+        let mut border_paint =
+            femtovg::Paint::color(self.shared_data.paint_color(path.stroke_color()));
+        let scale = self.shared_data.canvas.borrow().transform().average_scale();
+        let stroke_width = effective_stroke_width(path.stroke_width(), path.hairline(), scale);
+        border_paint.set_line_width(stroke_width);
+
+        let offset = stroke_offset(path.stroke_alignment(), stroke_width);
+        let mut stroke_path = if offset != 0. {
+            let tolerance = 0.25 / scale.max(f32::EPSILON);
+            let events = elements.iter_fitted(path.width(), path.height());
+            let mut stroke_path = femtovg::Path::new();
+            for (points, closed) in flattened_sub_paths(events.iter(), tolerance) {
+                let points = if closed { offset_polygon(&points, offset) } else { points };
+                if let Some((first, rest)) = points.split_first() {
+                    stroke_path.move_to(first.x, first.y);
+                    for point in rest {
+                        stroke_path.line_to(point.x, point.y);
+                    }
+                    if closed {
+                        stroke_path.close();
+                    }
+                }
+            }
+            Some(stroke_path)
+        } else {
+            None
+        };
+
+        self.shared_data.canvas.borrow_mut().save_with(|canvas| {
+            canvas.translate(pos.x + path.x(), pos.y + path.y());
+            canvas.fill_path(&mut fpath, fill_paint);
+            canvas.stroke_path(stroke_path.as_mut().unwrap_or(&mut fpath), border_paint);
+        })
+    }
+
+    fn draw_line(&mut self, pos: Point, line: std::pin::Pin<&sixtyfps_corelib::items::Line>) {
+        self.flush_rectangle_batch();
+        let stroke_width = line.stroke_width();
+        if stroke_width <= 0. {
+            return;
+        }
+        let mut fpath = femtovg::Path::new();
+        for (from, to) in line.segments() {
+            fpath.move_to(from.x, from.y);
+            fpath.line_to(to.x, to.y);
+        }
+
+        let mut paint = femtovg::Paint::color(self.shared_data.paint_color(line.stroke_color()));
+        paint.set_line_width(stroke_width);
+        paint.set_line_cap(match line.stroke_line_cap() {
+            sixtyfps_corelib::items::LineCap::butt => femtovg::LineCap::Butt,
+            sixtyfps_corelib::items::LineCap::round => femtovg::LineCap::Round,
+            sixtyfps_corelib::items::LineCap::square => femtovg::LineCap::Square,
+        });
+
+        self.shared_data.canvas.borrow_mut().save_with(|canvas| {
+            canvas.translate(pos.x + line.x(), pos.y + line.y());
+            canvas.stroke_path(&mut fpath, paint);
+        })
+    }
+
+    fn draw_box_shadow(
+        &mut self,
+        pos: Point,
+        box_shadow: std::pin::Pin<&sixtyfps_corelib::items::BoxShadow>,
+    ) {
+        self.flush_rectangle_batch();
+        // TODO: cache path in item to avoid re-tesselation
+
+        let blur = box_shadow.blur();
+
+        let shadow_outer_rect: euclid::Rect<f32, euclid::UnknownUnit> = euclid::rect(
+            box_shadow.x() + box_shadow.offset_x() - blur / 2.,
+            box_shadow.y() + box_shadow.offset_y() - blur / 2.,
+            box_shadow.width() + blur,
+            box_shadow.height() + blur,
+        );
+
+        let shadow_inner_rect: euclid::Rect<f32, euclid::UnknownUnit> = euclid::rect(
+            box_shadow.x() + box_shadow.offset_x() + blur / 2.,
+            box_shadow.y() + box_shadow.offset_y() + blur / 2.,
+            box_shadow.width() - blur,
+            box_shadow.height() - blur,
+        );
+
+        let shadow_fill_rect: euclid::Rect<f32, euclid::UnknownUnit> = euclid::rect(
+            shadow_outer_rect.min_x() + blur / 2.,
+            shadow_outer_rect.min_y() + blur / 2.,
+            box_shadow.width(),
+            box_shadow.height(),
+        );
+
+        let paint = femtovg::Paint::box_gradient(
+            shadow_fill_rect.min_x(),
+            shadow_fill_rect.min_y(),
+            shadow_fill_rect.width(),
+            shadow_fill_rect.height(),
+            box_shadow.border_radius(),
+            box_shadow.blur(),
+            self.shared_data.paint_color(box_shadow.color()),
+            self.shared_data.paint_color(Color::from_argb_u8(0, 0, 0, 0)),
+        );
+
+        let mut path = femtovg::Path::new();
+        path.rounded_rect(
+            shadow_outer_rect.min_x(),
+            shadow_outer_rect.min_y(),
+            shadow_outer_rect.width(),
+            shadow_outer_rect.height(),
+            box_shadow.border_radius(),
+        );
+        path.rect(
+            shadow_inner_rect.min_x(),
+            shadow_inner_rect.min_y(),
+            shadow_inner_rect.width(),
+            shadow_inner_rect.height(),
+        );
+        path.solidity(femtovg::Solidity::Hole);
+
+        self.shared_data.canvas.borrow_mut().save_with(|canvas| {
+            canvas.translate(pos.x, pos.y);
+            canvas.fill_path(&mut path, paint);
+
+            let mut shadow_inner_path = femtovg::Path::new();
+            shadow_inner_path.rect(
+                shadow_inner_rect.min_x(),
+                shadow_inner_rect.min_y(),
+                shadow_inner_rect.width(),
+                shadow_inner_rect.height(),
+            );
+            let fill = femtovg::Paint::color(self.shared_data.paint_color(box_shadow.color()));
+            canvas.fill_path(&mut shadow_inner_path, fill);
+        })
+    }
+
+    fn combine_clip(&mut self, pos: Point, clip: std::pin::Pin<&sixtyfps_corelib::items::Clip>) {
+        self.flush_rectangle_batch();
+        let clip_rect = clip.geometry().translate([pos.x, pos.y].into());
+        let (fade_horizontal, fade_vertical) = (clip.fade_horizontal(), clip.fade_vertical());
+        match clip.shape() {
+            ClipShape::rectangle if fade_horizontal <= 0. && fade_vertical <= 0. => {
+                self.shared_data.canvas.borrow_mut().intersect_scissor(
+                    clip_rect.min_x(),
+                    clip_rect.min_y(),
+                    clip_rect.width(),
+                    clip_rect.height(),
+                )
+            }
+            shape => self.push_clip_layer(clip_rect, shape, fade_horizontal, fade_vertical, 1.),
+        }
+        if let Some(clipped) = self.clipped_states.last_mut() {
+            *clipped = true;
+        }
+    }
+
+    fn combine_transform(
+        &mut self,
+        pos: Point,
+        transform: std::pin::Pin<&sixtyfps_corelib::items::Transform>,
+    ) {
+        self.flush_rectangle_batch();
+        concat_transform(
+            &mut self.shared_data.canvas.borrow_mut(),
+            &transform.children_transform(pos),
+        );
+        // Like a clip, the transform only applies to the rectangles batched until the state is restored.
+        if let Some(clipped) = self.clipped_states.last_mut() {
+            *clipped = true;
+        }
+    }
+
+    fn combine_desaturate(
+        &mut self,
+        pos: Point,
+        desaturate: std::pin::Pin<&sixtyfps_corelib::items::Desaturate>,
+    ) {
+        let saturation = desaturate.saturation().max(0.);
+        if saturation >= 1. {
+            return;
+        }
+        self.flush_rectangle_batch();
+        let rect = desaturate.geometry().translate([pos.x, pos.y].into());
+        self.push_clip_layer(rect, ClipShape::rectangle, 0., 0., saturation);
+        if let Some(clipped) = self.clipped_states.last_mut() {
+            *clipped = true;
+        }
+    }
+
+    fn draw_layer(
+        &mut self,
+        pos: Point,
+        layer: std::pin::Pin<&sixtyfps_corelib::items::Layer>,
+        render_children: &mut dyn FnMut(&mut dyn ItemRenderer),
+    ) {
+        let rect = layer.geometry().translate([pos.x, pos.y].into());
+        let scale = self.shared_data.canvas.borrow().transform().average_scale();
+        let pixel_size =
+            ((rect.width() * scale).ceil() as u32, (rect.height() * scale).ceil() as u32);
+        if pixel_size.0 == 0 || pixel_size.1 == 0 {
+            return;
+        }
+        self.flush_rectangle_batch();
+
+        let cache_entry = layer
+            .cached_rendering_data
+            .ensure_up_to_date(&mut self.shared_data.item_graphics_cache.borrow_mut(), || {
+                Some(ItemGraphicsCacheEntry::Layer(Default::default()))
+            });
+        let layer_cache = match cache_entry {
+            Some(ItemGraphicsCacheEntry::Layer(layer_cache)) => layer_cache,
+            _ => return,
+        };
+        let image = match layer_cache.get_or_render(pixel_size, || {
+            self.render_layer(rect, pixel_size, scale, render_children)
+        }) {
+            Some(image) => image,
+            None => return,
+        };
+
+        let image_id = image.ensure_uploaded_to_gpu(&self);
+        let fill_paint = femtovg::Paint::image(
+            image_id,
+            rect.min_x(),
+            rect.min_y(),
+            rect.width(),
+            rect.height(),
+            0.0,
+            1.0,
+        );
+        self.shared_data.canvas.borrow_mut().fill_path(&mut rect_to_path(rect), fill_paint);
+    }
+
+    fn draw_custom_rendering(
+        &mut self,
+        pos: Point,
+        custom_rendering: std::pin::Pin<&sixtyfps_corelib::items::CustomRendering>,
+    ) {
+        let draw = match custom_rendering::custom_renderer(&custom_rendering.renderer()) {
+            Some(draw) => draw,
+            None => return,
+        };
+        self.flush_rectangle_batch();
+        let geometry = custom_rendering.geometry().translate(pos.to_vector());
+        if geometry.is_empty() {
+            return;
+        }
+        let render_target = self.render_target();
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        canvas.save();
+        canvas.translate(geometry.min_x(), geometry.min_y());
+        canvas.intersect_scissor(0., 0., geometry.width(), geometry.height());
+        draw(&mut canvas, geometry.size);
+        canvas.restore();
+        canvas.set_render_target(render_target);
+    }
+
+    fn save_state(&mut self) {
+        self.state_depth += 1;
+        self.clipped_states.push(false);
+        self.shared_data.canvas.borrow_mut().save();
+    }
+
+    fn restore_state(&mut self) {
+        // The batched rectangles must be filled with the clip of the state they were drawn in.
+        if self.clipped_states.pop().unwrap_or(false) {
+            self.flush_rectangle_batch();
+        }
+        self.shared_data.canvas.borrow_mut().restore();
+        if self.clip_layers.last().map_or(false, |layer| layer.state_depth == self.state_depth) {
+            let layer = self.clip_layers.pop().unwrap();
+            self.draw_clip_layer(layer);
+        }
+        self.state_depth -= 1;
+    }
+
+    fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    fn draw_cached_pixmap(
+        &mut self,
+        item_cache: &CachedRenderingData,
+        pos: Point,
+        update_fn: &dyn Fn(&mut dyn FnMut(u32, u32, &[u8])),
+    ) {
+        self.flush_rectangle_batch();
+        let canvas = &self.shared_data.canvas;
+        let mut cache = self.shared_data.item_graphics_cache.borrow_mut();
+
+        let cache_entry = item_cache.ensure_up_to_date(&mut cache, || {
+            let mut cached_image = None;
+            update_fn(&mut |width: u32, height: u32, data: &[u8]| {
+                use rgb::FromSlice;
+                let mut linearized_data;
+                let data = if self.shared_data.srgb_blending {
+                    linearized_data = data.to_vec();
+                    srgb::linearize_pixels(linearized_data.as_rgba_mut(), true);
+                    &linearized_data
+                } else {
+                    data
+                };
+                let img = imgref::Img::new(data.as_rgba(), width as usize, height as usize);
+                if let Some(image_id) =
+                    canvas.borrow_mut().create_image(img, femtovg::ImageFlags::PREMULTIPLIED).ok()
+                {
+                    cached_image = Some(ItemGraphicsCacheEntry::Image(Rc::new(
+                        CachedImage::new_on_gpu(canvas, image_id, None),
+                    )))
+                };
+            });
+            cached_image
+        });
+        let image_id = match cache_entry {
+            Some(entry) => entry.as_image().ensure_uploaded_to_gpu(&self),
+            None => return,
+        };
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+
+        let image_info = canvas.image_info(image_id).unwrap();
+        let (width, height) = (image_info.width() as f32, image_info.height() as f32);
+        let fill_paint = femtovg::Paint::image(image_id, pos.x, pos.y, width, height, 0.0, 1.0);
+        let mut path = femtovg::Path::new();
+        path.rect(pos.x, pos.y, width, height);
+        canvas.fill_path(&mut path, fill_paint);
+    }
+
+    fn as_any(&mut self) -> &mut dyn std::any::Any {
+        // The caller may draw on the canvas directly.
+        self.flush_rectangle_batch();
+        self
+    }
+}
+
+impl GLItemRenderer {
+    // Fills the rectangles of the batch, with a single path.
+    fn fill_rectangles(&self, batch: RectangleBatch) {
+        let mut path = batch.to_path();
+        let paint = femtovg::Paint::color(self.shared_data.paint_color(batch.color));
+        self.shared_data.canvas.borrow_mut().fill_path(&mut path, paint);
+    }
+
+    // Returns the rectangle, in the coordinates of the canvas, with its edges rounded to device pixels when pixel
+    // snapping is enabled.
+    fn snap_rect(&self, rect: Rect) -> Rect {
+        if self.shared_data.pixel_snapping {
+            snap_rect_to_device_pixels(rect, &self.shared_data.canvas.borrow().transform())
+        } else {
+            rect
+        }
+    }
+
+    // Returns the paint that fills the given rectangle, in the coordinates of the canvas, with a conic gradient,
+    // or None if there are no stops or they are invalid. The compiler rejects invalid stops given as string
+    // literals, so only stops computed by a binding can be invalid, and those elements keep their plain fill. The
+    // gradient is rasterized at the size of the rectangle on the screen, and cached by its stops and size.
+    fn conic_gradient_paint(
+        &self,
+        rect: Rect,
+        stops: SharedString,
+        center: Point,
+        start_angle: f32,
+    ) -> Option<femtovg::Paint> {
+        if stops.is_empty() || rect.is_empty() {
+            return None;
+        }
+        let scale = self.shared_data.canvas.borrow().transform().average_scale();
+        let size = (
+            ((rect.width() * scale).ceil() as u32).clamp(1, MAX_CONIC_GRADIENT_IMAGE_SIZE),
+            ((rect.height() * scale).ceil() as u32).clamp(1, MAX_CONIC_GRADIENT_IMAGE_SIZE),
+        );
+        let key = ConicGradientCacheKey {
+            stops,
+            size,
+            center: (center.x.to_bits(), center.y.to_bits()),
+            start_angle: start_angle.to_bits(),
+        };
+
+        let mut cache = self.shared_data.conic_gradient_cache.borrow_mut();
+        if !cache.contains_key(&key) && cache.len() >= MAX_CONIC_GRADIENT_CACHE_SIZE {
+            let mut canvas = self.shared_data.canvas.borrow_mut();
+            for image_id in cache.drain().filter_map(|(_, image_id)| image_id) {
+                canvas.delete_image(image_id);
+            }
+        }
+        let stops = key.stops.clone();
+        let image_id = *cache.entry(key).or_insert_with(|| {
+            let stops = sixtyfps_corelib::graphics::parse_gradient_stops(stops.as_str()).ok()?;
+            let pixels = conic_gradient_pixels(&stops, size, center, start_angle);
+            Some(upload_image(
+                &self.shared_data.canvas,
+                &image::DynamicImage::ImageRgba8(pixels),
+                femtovg::ImageFlags::empty(),
+                self.shared_data.srgb_blending,
+                self.shared_data.max_texture_size,
+            ))
+        });
+        image_id.map(|image_id| {
+            femtovg::Paint::image(
+                image_id,
+                rect.min_x(),
+                rect.min_y(),
+                rect.width(),
+                rect.height(),
+                0.0,
+                1.0,
+            )
+        })
+    }
+
+    // Draws with the mirroring of the frame undone about the vertical center line of the rectangle, in the
+    // coordinates of the canvas, so that the text or image drawn in it is placed mirrored, but not flipped itself.
+    fn draw_unmirrored(&mut self, rect: Rect, draw: impl FnOnce(&mut Self)) {
+        self.flush_rectangle_batch();
+        {
+            let mut canvas = self.shared_data.canvas.borrow_mut();
+            canvas.save();
+            concat_transform(&mut canvas, &mirror_transform(2. * rect.center().x));
+        }
+        self.mirrored = false;
+        draw(self);
+        self.mirrored = true;
+        self.shared_data.canvas.borrow_mut().restore();
+    }
+
+    // Returns what the canvas draws into: the innermost clip layer, the off-screen frame, or the screen.
+    fn render_target(&self) -> femtovg::RenderTarget {
+        self.clip_layers
+            .last()
+            .map(|layer| layer.image)
+            .or(self.frame_target)
+            .map_or(femtovg::RenderTarget::Screen, femtovg::RenderTarget::Image)
+    }
+
+    // Fills the rectangles batched so far. This must be called before anything else is drawn, or the state of
+    // the canvas changes.
+    fn flush_rectangle_batch(&mut self) {
+        if let Some(batch) = self.rectangle_batch.take() {
+            self.fill_rectangles(batch);
+        }
+    }
+
+    /// Returns the glyphs of the text element, positioned exactly as `draw_text` draws them when the element is
+    /// drawn at the given position. This is for drawing decorations or effects that are aligned to the glyphs.
+    /// The slant of a synthesized oblique is not applied to the positions.
+    pub fn text_glyphs(
+        &mut self,
+        pos: Point,
+        text: std::pin::Pin<&sixtyfps_corelib::items::Text>,
+    ) -> Vec<PositionedGlyph> {
+        let font = self.shared_data.loaded_fonts.borrow_mut().font(
+            &self.shared_data.canvas,
+            text.font_request(),
+            self.scale_factor,
+        );
+        let paint = font.paint();
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        let tab_width = Some(font.tab_width(&mut canvas, paint));
+        let string = text.text();
+        let runs = layout_text(&mut canvas, pos, text, string.as_str(), paint, tab_width);
+        positioned_glyphs(&mut canvas, &runs, paint)
+    }
+
+    /// Draws the text element like `draw_text` does, over backgrounds of the given colors behind the glyphs of the
+    /// given (UTF-8) byte ranges of its text, such as the matches of a search. A range that spans several lines of
+    /// wrapped text is highlighted on each of them.
+    pub fn draw_text_with_highlights(
+        &mut self,
+        pos: Point,
+        text: std::pin::Pin<&sixtyfps_corelib::items::Text>,
+        highlights: &[(std::ops::Range<usize>, Color)],
+    ) {
+        self.flush_rectangle_batch();
+        if text.width() > 0. && text.height() > 0. {
+            let font = self.shared_data.loaded_fonts.borrow_mut().font(
+                &self.shared_data.canvas,
+                text.font_request(),
+                self.scale_factor,
+            );
+            let paint = font.paint();
+            let mut canvas = self.shared_data.canvas.borrow_mut();
+            let tab_width = Some(font.tab_width(&mut canvas, paint));
+            let string = text.text();
+            let runs = layout_text(&mut canvas, pos, text, string.as_str(), paint, tab_width);
+            for (range, color) in highlights {
+                let fill = femtovg::Paint::color(self.shared_data.paint_color(*color));
+                for rect in highlight_rects(&mut canvas, &runs, paint, range.clone()) {
+                    canvas.fill_path(&mut rect_to_path(rect), fill);
+                }
+            }
+        }
+        self.draw_text(pos, text);
+    }
+
+    /// Draws the text element like `draw_text` does, with the text positioned by the given baseline of its lines
+    /// instead of their top. The text looks the same with any baseline, as the lines are laid out the same way,
+    /// but text drawn directly on the canvas with the same baseline then lines up with it, see
+    /// [`Self::text_baselines`].
+    pub fn draw_text_with_baseline(
+        &mut self,
+        pos: Point,
+        text: std::pin::Pin<&sixtyfps_corelib::items::Text>,
+        baseline: TextBaseline,
+    ) {
+        if self.mirrored {
+            let rect = text.geometry().translate(pos.to_vector());
+            return self.draw_unmirrored(rect, |renderer| {
+                renderer.draw_text_with_baseline(pos, text, baseline)
+            });
+        }
+        self.flush_rectangle_batch();
+        if text.width() <= 0. || text.height() <= 0. {
+            return;
+        }
+
+        let font = self.shared_data.loaded_fonts.borrow_mut().font(
+            &self.shared_data.canvas,
+            text.font_request(),
+            self.scale_factor,
+        );
+        let mut paint = font.paint();
+
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        let tab_width = Some(font.tab_width(&mut canvas, paint));
         let string = text.text();
-        let string = string.as_str();
-        let vertical_alignment = text.vertical_alignment();
-        let horizontal_alignment = text.horizontal_alignment();
+        let runs = layout_text(&mut canvas, pos, text, string.as_str(), paint, tab_width);
+        let baseline_shift = baseline_shift(&mut canvas, paint, baseline);
+        paint.set_text_baseline(baseline.into());
+        let passes = text_fill_passes(
+            text.color(),
+            euclid::vec2(text.drop_shadow_offset_x(), text.drop_shadow_offset_y()),
+            text.drop_shadow_blur(),
+            text.drop_shadow_color(),
+        );
+        let main_pass = passes.len() - 1;
+        // femtovg only rasterizes the coverage of glyphs, in grayscale. What subpixel antialiasing can do with it
+        // is to place the baselines on the pixel grid, which keeps the horizontal strokes of the glyphs sharp.
+        let snap_baselines = self.shared_data.pixel_snapping
+            || self.text_antialiasing(&canvas) == TextAntialiasing::Subpixel;
+        for (pass, (offset, color)) in passes.into_iter().enumerate() {
+            paint.set_color(self.shared_data.paint_color(color));
+            for run in &runs {
+                let y = if snap_baselines {
+                    let ascender = canvas.measure_font(paint).unwrap().ascender();
+                    let baseline = euclid::point2(run.pos.x, run.pos.y + ascender);
+                    snap_point_to_device_pixels(baseline, &canvas.transform()).y - ascender
+                } else {
+                    run.pos.y
+                };
+                let (x, y) = (run.pos.x + offset.x, y + baseline_shift + offset.y);
+                // The color glyphs are drawn as images, which the shadow does not tint, so the shadow leaves them out.
+                if pass == main_pass {
+                    self.fill_text(&mut canvas, &font, x, y, &run.text, paint);
+                } else {
+                    font.fill_text(&mut canvas, x, y, &run.text, paint);
+                }
+            }
+        }
+    }
+
+    /// Returns the position of the given baseline of each line of the text element when it is drawn at the given
+    /// position, at the start of the line. This is for aligning text drawn by other means to the text of the
+    /// element.
+    pub fn text_baselines(
+        &mut self,
+        pos: Point,
+        text: std::pin::Pin<&sixtyfps_corelib::items::Text>,
+        baseline: TextBaseline,
+    ) -> Vec<Point> {
         let font = self.shared_data.loaded_fonts.borrow_mut().font(
             &self.shared_data.canvas,
-            text.font_request(),
+            text.font_request(),
+            self.scale_factor,
+        );
+        let paint = font.paint();
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        let tab_width = Some(font.tab_width(&mut canvas, paint));
+        let string = text.text();
+        let runs = layout_text(&mut canvas, pos, text, string.as_str(), paint, tab_width);
+        let baseline_shift = baseline_shift(&mut canvas, paint, baseline);
+        runs.iter().map(|run| euclid::point2(run.pos.x, run.pos.y + baseline_shift)).collect()
+    }
+
+    /// Draws everything until the matching [`Self::pop_blend_mode`] with the given blend mode. The calls can be
+    /// nested, and must be balanced within the same state saved with `save_state()`.
+    pub fn push_blend_mode(&mut self, mode: BlendMode) {
+        self.flush_rectangle_batch();
+        let (source_factor, destination_factor) = blend_factors(mode);
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        canvas.save();
+        canvas.global_composite_blend_func(source_factor, destination_factor);
+    }
+
+    /// Restores the blend mode that was in use before the last call to [`Self::push_blend_mode`].
+    pub fn pop_blend_mode(&mut self) {
+        self.flush_rectangle_batch();
+        self.shared_data.canvas.borrow_mut().restore();
+    }
+
+    /// Draws a focus or selection ring around the given geometry of an item, which is relative to the given
+    /// position like the geometry of the items drawn by the other functions. The ring stays outside of the
+    /// item, at the gap of the ring, except at the edges of the window, where it is moved inside of the item so that
+    /// it is not cut off.
+    pub fn draw_focus_ring(&mut self, pos: Point, geometry: Rect, ring: &FocusRing) {
+        self.flush_rectangle_batch();
+        if ring.width <= 0. || ring.color.alpha() == 0 {
+            return;
+        }
+        let geometry = geometry.translate(pos.to_vector());
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        let transform = canvas.transform();
+        // The visible area of the render target, in the coordinates of the item.
+        let corners = [
+            (0., 0.),
+            (canvas.width(), 0.),
+            (0., canvas.height()),
+            (canvas.width(), canvas.height()),
+        ];
+        let inverse = transform.inversed();
+        let bounds = Rect::from_points(corners.iter().map(|&(x, y)| {
+            let (x, y) = inverse.transform_point(x, y);
+            Point::new(x, y)
+        }));
+        let (outline, radius) = focus_ring_outline(geometry, ring, bounds);
+
+        let mut path = femtovg::Path::new();
+        if ring.dash_length > 0. {
+            let tolerance = 0.25 / transform.average_scale().max(f32::EPSILON);
+            let polygon = rounded_rect_polygon(outline, radius, tolerance);
+            for dash in dashed_polygon(&polygon, ring.dash_length) {
+                if let Some((first, rest)) = dash.split_first() {
+                    path.move_to(first.x, first.y);
+                    for point in rest {
+                        path.line_to(point.x, point.y);
+                    }
+                }
+            }
+        } else {
+            path.rounded_rect(
+                outline.min_x(),
+                outline.min_y(),
+                outline.width(),
+                outline.height(),
+                radius,
+            );
+        }
+        let mut paint = femtovg::Paint::color(self.shared_data.paint_color(ring.color));
+        paint.set_line_width(ring.width);
+        canvas.stroke_path(&mut path, paint);
+    }
+
+    // Renders the children of a `Layer` element into a new image of the given size in pixels, which covers the
+    // given rectangle of the current coordinate system at the given scale.
+    fn render_layer(
+        &mut self,
+        rect: Rect,
+        pixel_size: (u32, u32),
+        scale: f32,
+        render_children: &mut dyn FnMut(&mut dyn ItemRenderer),
+    ) -> Option<Rc<CachedImage>> {
+        let previous_render_target = self.render_target();
+        let image = {
+            let mut canvas = self.shared_data.canvas.borrow_mut();
+            let image = canvas
+                .create_image_empty(
+                    pixel_size.0 as usize,
+                    pixel_size.1 as usize,
+                    femtovg::PixelFormat::Rgba8,
+                    femtovg::ImageFlags::FLIP_Y | femtovg::ImageFlags::PREMULTIPLIED,
+                )
+                .ok()?;
+            canvas.save();
+            canvas.set_render_target(femtovg::RenderTarget::Image(image));
+            canvas.clear_rect(
+                0,
+                0,
+                pixel_size.0,
+                pixel_size.1,
+                femtovg::Color::rgbaf(0., 0., 0., 0.),
+            );
+            canvas.reset_scissor();
+            canvas.reset_transform();
+            canvas.scale(scale, scale);
+            canvas.translate(-rect.min_x(), -rect.min_y());
+            image
+        };
+
+        // The clip layers of the children are drawn onto the image, not onto the clip layers of the parents.
+        let clip_layers = std::mem::take(&mut self.clip_layers);
+        let frame_target = std::mem::replace(&mut self.frame_target, Some(image));
+        render_children(self);
+        self.flush_rectangle_batch();
+        self.clip_layers = clip_layers;
+        self.frame_target = frame_target;
+
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        canvas.set_render_target(previous_render_target);
+        canvas.restore();
+        Some(Rc::new(CachedImage::new_on_gpu(&self.shared_data.canvas, image, None)))
+    }
+
+    // Starts rendering into a new off-screen image, covering the given rectangle of the current coordinate
+    // system. The image is drawn as an ellipse when the current state is restored.
+    fn push_clip_layer(
+        &mut self,
+        clip_rect: Rect,
+        shape: ClipShape,
+        fade_horizontal: f32,
+        fade_vertical: f32,
+        saturation: f32,
+    ) {
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        let transform = canvas.transform();
+        let shape_rect = transformed_bounds(&transform, clip_rect);
+        let image_rect = shape_rect.to_box2d().round_out().to_i32();
+        let (width, height) = (image_rect.width().max(1), image_rect.height().max(1));
+
+        let image = canvas
+            .create_image_empty(
+                width as usize,
+                height as usize,
+                femtovg::PixelFormat::Rgba8,
+                femtovg::ImageFlags::FLIP_Y | femtovg::ImageFlags::PREMULTIPLIED,
+            )
+            .unwrap();
+        let previous_render_target = self.render_target();
+
+        canvas.set_render_target(femtovg::RenderTarget::Image(image));
+        canvas.clear_rect(0, 0, width as u32, height as u32, femtovg::Color::rgbaf(0., 0., 0., 0.));
+        // The children are clipped by the shape when the layer is drawn, so the scissor of the parent only
+        // needs to apply then.
+        canvas.reset_scissor();
+        canvas.reset_transform();
+        canvas.translate(-image_rect.min.x as f32, -image_rect.min.y as f32);
+        let [a, b, c, d, e, f] = transform.0;
+        canvas.set_transform(a, b, c, d, e, f);
+        // Rectangles are clipped right away, so that the whole layer can be drawn.
+        if shape == ClipShape::rectangle {
+            canvas.intersect_scissor(
+                clip_rect.min_x(),
+                clip_rect.min_y(),
+                clip_rect.width(),
+                clip_rect.height(),
+            );
+        }
+
+        self.clip_layers.push(ClipLayer {
+            image,
+            state_depth: self.state_depth,
+            shape,
+            clip_rect,
+            transform,
+            fade_horizontal,
+            fade_vertical,
+            saturation,
+            shape_rect,
+            image_rect,
+            previous_render_target,
+        });
+    }
+
+    fn draw_clip_layer(&mut self, layer: ClipLayer) {
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        let image_rect = layer.image_rect.to_f32();
+
+        let fade_strips =
+            clip_fade_strips(layer.clip_rect, layer.fade_horizontal, layer.fade_vertical);
+        if !fade_strips.is_empty() {
+            // Multiply the alpha of the layer with gradients along the faded edges, while it is still the
+            // render target.
+            canvas.save_with(|canvas| {
+                canvas.reset_scissor();
+                canvas.reset_transform();
+                canvas.translate(-image_rect.min.x, -image_rect.min.y);
+                let [a, b, c, d, e, f] = layer.transform.0;
+                canvas.set_transform(a, b, c, d, e, f);
+                canvas.global_composite_operation(femtovg::CompositeOperation::DestinationIn);
+                for (strip, transparent, opaque) in &fade_strips {
+                    canvas.fill_path(
+                        &mut rect_to_path(*strip),
+                        femtovg::Paint::linear_gradient(
+                            transparent.x,
+                            transparent.y,
+                            opaque.x,
+                            opaque.y,
+                            femtovg::Color::rgbaf(0., 0., 0., 0.),
+                            femtovg::Color::rgbaf(0., 0., 0., 1.),
+                        ),
+                    );
+                }
+            });
+        }
+
+        if layer.saturation < 1. {
+            desaturate_render_target(&mut canvas, layer.image, layer.saturation);
+        }
+
+        canvas.set_render_target(layer.previous_render_target);
+        canvas.save_with(|canvas| {
+            canvas.reset_transform();
+            let mut path = femtovg::Path::new();
+            match layer.shape {
+                ClipShape::ellipse => path.ellipse(
+                    layer.shape_rect.center().x,
+                    layer.shape_rect.center().y,
+                    layer.shape_rect.width() / 2.,
+                    layer.shape_rect.height() / 2.,
+                ),
+                // The content was clipped to the rectangle already when rendering the layer.
+                ClipShape::rectangle => path.rect(
+                    image_rect.min.x,
+                    image_rect.min.y,
+                    image_rect.width(),
+                    image_rect.height(),
+                ),
+            }
+            canvas.fill_path(
+                &mut path,
+                femtovg::Paint::image(
+                    layer.image,
+                    image_rect.min.x,
+                    image_rect.min.y,
+                    image_rect.width(),
+                    image_rect.height(),
+                    0.0,
+                    1.0,
+                ),
+            );
+        });
+        self.finished_clip_layers.push(layer.image);
+    }
+
+    // Draws the lines of a TextInput in the given color, except for the glyphs of the selected (UTF-8) byte range
+    // of the text, if given, which are drawn in the color given with it.
+    fn fill_text_input_lines(
+        &self,
+        font: &GLFont,
+        mut paint: femtovg::Paint,
+        lines: &[TextInputLine],
+        color: Color,
+        selection: Option<(std::ops::Range<usize>, Color)>,
+    ) {
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        for line in lines {
+            for run in text_input_runs(line, selection.as_ref().map(|(range, _)| range.clone())) {
+                let color = match &selection {
+                    Some((_, selected_color)) if run.selected => *selected_color,
+                    _ => color,
+                };
+                paint.set_color(self.shared_data.paint_color(color));
+                let (x, y, text) = (run.x, line.pos.y, &line.text[run.range]);
+                let painted_text = PaintedText { x, y, text, paint, color };
+                if self.fill_text_with_bitmap_strikes(&mut canvas, font, &painted_text).is_none() {
+                    self.fill_text(&mut canvas, font, x, y, text, paint);
+                }
+            }
+        }
+    }
+
+    // Returns how text drawn with the current transform of the canvas is antialiased.
+    fn text_antialiasing(
+        &self,
+        canvas: &femtovg::Canvas<femtovg::renderer::OpenGl>,
+    ) -> TextAntialiasing {
+        effective_text_antialiasing(
+            TEXT_ANTIALIASING.with(|mode| mode.get()),
+            &canvas.transform(),
+            self.shared_data.transparent_window,
+        )
+    }
+
+    // Draws the text with the outlines of the font, then the color glyphs on top, which femtovg leaves out.
+    fn fill_text(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: &GLFont,
+        x: f32,
+        y: f32,
+        text: &str,
+        paint: femtovg::Paint,
+    ) -> femtovg::TextMetrics {
+        let metrics = font.fill_text(canvas, x, y, text, paint);
+        if !font.resolved.color_glyphs.is_empty() {
+            self.fill_color_glyphs(canvas, font, &metrics);
+        }
+        metrics
+    }
+
+    // Draws the images of the glyphs that come from fonts with color glyphs, such as emoji, scaled from the
+    // strike closest to the font size.
+    fn fill_color_glyphs(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: &GLFont,
+        metrics: &femtovg::TextMetrics,
+    ) {
+        let mut glyph_cache = self.shared_data.color_glyph_cache.borrow_mut();
+        if glyph_cache.len() > MAX_COLOR_GLYPH_CACHE_SIZE {
+            for image in glyph_cache.drain().filter_map(|(_, image)| image) {
+                canvas.delete_image(image.id);
+            }
+        }
+
+        for glyph in &metrics.glyphs {
+            let color_glyphs =
+                match font.resolved.color_glyphs.iter().find(|(id, _)| *id == glyph.font_id) {
+                    Some((_, color_glyphs)) => color_glyphs,
+                    None => continue,
+                };
+            let ppem = match color_glyphs.strike_for_size(font.pixel_size) {
+                Some(ppem) => ppem,
+                None => continue,
+            };
+            let key = ColorGlyphCacheKey {
+                color_glyphs: by_address::ByAddress(color_glyphs.clone()),
+                ppem,
+                glyph_id: glyph.codepoint as u16,
+            };
+            let image = *glyph_cache.entry(key).or_insert_with(|| {
+                use rgb::FromSlice;
+                let mut color_glyph = color_glyphs.glyph(glyph.codepoint as u16, ppem)?;
+                if self.shared_data.srgb_blending {
+                    srgb::linearize_pixels(color_glyph.pixels.as_mut().as_rgba_mut(), false);
+                }
+                premultiply_alpha(color_glyph.pixels.as_mut().as_rgba_mut());
+                let (width, height) = color_glyph.pixels.dimensions();
+                let id = canvas
+                    .create_image(
+                        imgref::Img::new(
+                            color_glyph.pixels.as_raw().as_rgba(),
+                            width as usize,
+                            height as usize,
+                        ),
+                        femtovg::ImageFlags::GENERATE_MIPMAPS | femtovg::ImageFlags::PREMULTIPLIED,
+                    )
+                    .ok()?;
+                Some(BitmapGlyphImage {
+                    id,
+                    left: color_glyph.left,
+                    top: color_glyph.top,
+                    width: color_glyph.width,
+                    height: color_glyph.height,
+                })
+            });
+            let image = match image {
+                Some(image) => image,
+                None => continue,
+            };
+
+            // The pen position on the baseline, see femtovg's text layout.
+            let scale = font.pixel_size / ppem as f32;
+            let left = glyph.x - glyph.bearing_x + image.left as f32 * scale;
+            let top = glyph.y + glyph.bearing_y - image.top as f32 * scale;
+            let (width, height) = (image.width as f32 * scale, image.height as f32 * scale);
+            let mut path = femtovg::Path::new();
+            path.rect(left, top, width, height);
+            canvas.fill_path(
+                &mut path,
+                femtovg::Paint::image(image.id, left, top, width, height, 0.0, 1.0),
+            );
+        }
+    }
+
+    // Draws the text with the embedded bitmaps of the font, if it has a strike for the font size that
+    // includes all the glyphs. Returns None if the text needs to be drawn with the outlines instead.
+    fn fill_text_with_bitmap_strikes(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: &GLFont,
+        painted_text: &PaintedText,
+    ) -> Option<femtovg::TextMetrics> {
+        let PaintedText { x, y, text, paint, color } = *painted_text;
+        let bitmap_strikes = font.resolved.bitmap_strikes.as_ref().filter(|_| {
+            self.shared_data.use_bitmap_strikes
+                && !font.resolved.synthetic_oblique
+                && !font.resolved.synthetic_bold
+        })?;
+        let ppem = font.pixel_size.round();
+        if (font.pixel_size - ppem).abs() > 0.01 || !bitmap_strikes.has_strike(ppem as u16) {
+            return None;
+        }
+
+        let metrics = canvas.measure_text(x, y, text, paint).ok()?;
+        let mut glyph_cache = self.shared_data.bitmap_glyph_cache.borrow_mut();
+        if glyph_cache.len() > MAX_BITMAP_GLYPH_CACHE_SIZE {
+            for image in glyph_cache.drain().filter_map(|(_, image)| image) {
+                canvas.delete_image(image.id);
+            }
+        }
+
+        let mut glyphs = Vec::with_capacity(metrics.glyphs.len());
+        for glyph in &metrics.glyphs {
+            if glyph.font_id != font.resolved.fonts[0] {
+                return None;
+            }
+            let key = BitmapGlyphCacheKey {
+                bitmap_strikes: by_address::ByAddress(bitmap_strikes.clone()),
+                ppem: ppem as u16,
+                glyph_id: glyph.codepoint as u16,
+                color: color.as_argb_encoded(),
+            };
+            let image = *glyph_cache.entry(key).or_insert_with(|| {
+                let bitmap = bitmap_strikes.glyph(glyph.codepoint as u16, ppem as u16)?;
+                let color = color.to_argb_u8();
+                let mut pixels = bitmap
+                    .coverage
+                    .iter()
+                    .map(|coverage| rgb::RGBA8 {
+                        r: color.red,
+                        g: color.green,
+                        b: color.blue,
+                        a: (*coverage as u32 * color.alpha as u32 / 255) as u8,
+                    })
+                    .collect::<Vec<_>>();
+                if self.shared_data.srgb_blending {
+                    srgb::linearize_pixels(&mut pixels, false);
+                }
+                premultiply_alpha(&mut pixels);
+                let id = canvas
+                    .create_image(
+                        imgref::Img::new(
+                            pixels.as_slice(),
+                            bitmap.width as usize,
+                            bitmap.height as usize,
+                        ),
+                        femtovg::ImageFlags::NEAREST | femtovg::ImageFlags::PREMULTIPLIED,
+                    )
+                    .ok()?;
+                Some(BitmapGlyphImage {
+                    id,
+                    left: bitmap.left,
+                    top: bitmap.top,
+                    width: bitmap.width,
+                    height: bitmap.height,
+                })
+            });
+            match image {
+                Some(image) => glyphs.push((glyph, image)),
+                // A glyph with an outline but no bitmap is missing from the strike, rather than blank.
+                None if glyph.width > 0. => return None,
+                None => {}
+            }
+        }
+
+        for (glyph, image) in glyphs {
+            // The pen position on the baseline, see femtovg's text layout.
+            let left = (glyph.x - glyph.bearing_x).round() + image.left as f32;
+            let top = (glyph.y + glyph.bearing_y).round() - image.top as f32;
+            let (width, height) = (image.width as f32, image.height as f32);
+            let mut path = femtovg::Path::new();
+            path.rect(left, top, width, height);
+            canvas.fill_path(
+                &mut path,
+                femtovg::Paint::image(image.id, left, top, width, height, 0.0, 1.0),
+            );
+        }
+        Some(metrics)
+    }
+
+    fn draw_image_impl(
+        &mut self,
+        pos: Point,
+        item_cache: &CachedRenderingData,
+        source_property: std::pin::Pin<&Property<Resource>>,
+        target_width: f32,
+        target_height: f32,
+        options: &ImageDrawOptions,
+    ) {
+        let ImageDrawOptions {
+            source_clip_rect,
+            image_fit,
+            alignment,
+            orientation,
+            colorize_property,
+            image_rendering,
+            nine_slice,
+            opacity,
+        } = *options;
+        if target_width <= 0. || target_height < 0. {
+            return;
+        }
+        let alpha = match image_paint_alpha(opacity) {
+            Some(alpha) => alpha,
+            None => return,
+        };
+
+        let cached_image = self.shared_data.load_cached_colorized_item_image(
+            item_cache,
+            || source_property.get(),
+            || colorize_property.get(),
+        );
+
+        if self.shared_data.show_image_placeholders {
+            if let Some((mut box_path, mut cross_path)) = image_placeholder_paths(
+                &source_property.get(),
+                cached_image.as_deref(),
+                target_width,
+                target_height,
+            ) {
+                let mut canvas = self.shared_data.canvas.borrow_mut();
+                canvas.save_with(|canvas| {
+                    canvas.translate(pos.x, pos.y);
+                    canvas.fill_path(
+                        &mut box_path,
+                        femtovg::Paint::color(
+                            self.shared_data.paint_color(Color::from_rgb_u8(0xd0, 0xd0, 0xd0)),
+                        ),
+                    );
+                    let mut stroke = femtovg::Paint::color(
+                        self.shared_data.paint_color(Color::from_rgb_u8(0x80, 0x80, 0x80)),
+                    );
+                    stroke.set_line_width(1.);
+                    canvas.stroke_path(&mut box_path, stroke);
+                    canvas.stroke_path(&mut cross_path, stroke);
+                });
+                return;
+            }
+        }
+
+        let cached_image = match cached_image {
+            Some(image) => image,
+            None => return,
+        };
+
+        let image_size = cached_image.size();
+
+        let (source_origin, source_width, source_height) = if source_clip_rect.is_empty() {
+            (Point::zero(), image_size.width, image_size.height)
+        } else {
+            (source_clip_rect.origin, source_clip_rect.width() as _, source_clip_rect.height() as _)
+        };
+
+        // Parts of the clip rectangle outside of the image are left empty, so that the pixels at the edge of the
+        // image are not stretched into them.
+        let sampled_rect = match effective_source_clip_rect(source_clip_rect, image_size) {
+            Some(rect) => rect,
+            None => return,
+        };
+
+        if !nine_slice.is_zero() {
+            // The nine parts are laid out in the frame of the unrotated image, which the orientation then maps
+            // onto the element without any further scaling for rotations in quarter turns.
+            let frame_size = if (orientation.rotation_angle / 90.).round().rem_euclid(2.) == 1. {
+                euclid::size2(target_height, target_width)
+            } else {
+                euclid::size2(target_width, target_height)
+            };
+            let transform = image_transform(
+                frame_size,
+                euclid::size2(target_width, target_height),
+                ImageFit::fill,
+                (0.5, 0.5),
+                orientation,
+            );
+            let image_id = cached_image.ensure_uploaded_to_gpu(&self);
+            let mut canvas = self.shared_data.canvas.borrow_mut();
+            let canvas_scale = canvas.transform().average_scale();
+            let parts = nine_slice_rects(sampled_rect, nine_slice, frame_size, canvas_scale);
+            canvas.save_with(|canvas| {
+                canvas.translate(pos.x, pos.y);
+                canvas.set_transform(
+                    transform.m11,
+                    transform.m12,
+                    transform.m21,
+                    transform.m22,
+                    transform.m31,
+                    transform.m32,
+                );
+                for (source, target) in &parts {
+                    let scale_x = target.width() / source.width();
+                    let scale_y = target.height() / source.height();
+                    let mut path = femtovg::Path::new();
+                    path.rect(target.min_x(), target.min_y(), target.width(), target.height());
+                    canvas.fill_path(
+                        &mut path,
+                        femtovg::Paint::image(
+                            image_id,
+                            target.min_x() - source.min_x() * scale_x,
+                            target.min_y() - source.min_y() * scale_y,
+                            image_size.width * scale_x,
+                            image_size.height * scale_y,
+                            0.0,
+                            alpha,
+                        ),
+                    );
+                }
+            });
+            return;
+        }
+
+        let source_size = euclid::size2(source_width, source_height);
+        let target_size = euclid::size2(target_width, target_height);
+        let transform =
+            image_transform(source_size, target_size, image_fit, alignment, orientation);
+        let clip_rect = image_fit_clip_rect(image_fit, target_size);
+        // SVG images are not stretched by default: they are fitted as specified in the SVG file, which keeps their
+        // aspect ratio unless it says otherwise.
+        #[cfg(feature = "svg")]
+        let svg_aspect_ratio = if image_fit == ImageFit::fill {
+            self.shared_data.svg_aspect_ratio(&source_property.get(), colorize_property.get())
+        } else {
+            None
+        };
+        #[cfg(feature = "svg")]
+        let (transform, clip_rect) = match svg_aspect_ratio {
+            Some(aspect_ratio) => {
+                svg_image_transform(aspect_ratio, source_size, target_size, orientation)
+            }
+            None => (transform, clip_rect),
+        };
+
+        let canvas_scale = self.shared_data.canvas.borrow().transform().average_scale();
+        let pixel_size = (
+            (image_size.width * transform.m11.hypot(transform.m12) * canvas_scale).ceil() as u32,
+            (image_size.height * transform.m21.hypot(transform.m22) * canvas_scale).ceil() as u32,
+        );
+
+        // SVG images are rendered at the size they end up on the screen, instead of scaling the
+        // rasterization at their intrinsic size. The paint below maps any image to the intrinsic size.
+        #[cfg(feature = "svg")]
+        let cached_image = {
+            if pixel_size.0 == 0
+                || pixel_size.1 == 0
+                || pixel_size == (image_size.width as u32, image_size.height as u32)
+            {
+                cached_image
+            } else {
+                self.shared_data
+                    .load_svg_rasterization(
+                        &source_property.get(),
+                        colorize_property.get(),
+                        pixel_size,
+                    )
+                    .unwrap_or(cached_image)
+            }
+        };
+
+        // Pixelated images are drawn smooth while their size changes, for example during an animation, and
+        // switch to nearest pixel sampling once it settles.
+        // Images that are drawn much smaller than they are get mipmaps.
+        let sampling_flags = if image_rendering == ImageRendering::pixelated
+            && !self.shared_data.image_scales.borrow_mut().is_scaling(item_cache, pixel_size)
+        {
+            femtovg::ImageFlags::NEAREST
+        } else {
+            minification_flags(image_size, pixel_size)
+        };
+        let sampled_image = if sampling_flags.is_empty() {
+            None
+        } else {
+            self.shared_data.load_sampled_image(
+                &source_property.get(),
+                colorize_property.get(),
+                sampling_flags,
+            )
+        };
+
+        let fill_paint = femtovg::Paint::image(
+            match sampled_image {
+                Some(image) => image.ensure_uploaded_to_gpu_with_flags(&self, sampling_flags),
+                None => cached_image.ensure_uploaded_to_gpu(&self),
+            },
+            -source_origin.x,
+            -source_origin.y,
+            image_size.width,
+            image_size.height,
+            0.0,
+            alpha,
+        );
+
+        let mut path = femtovg::Path::new();
+        path.rect(
+            sampled_rect.min_x() - source_origin.x,
+            sampled_rect.min_y() - source_origin.y,
+            sampled_rect.width(),
+            sampled_rect.height(),
+        );
+
+        self.shared_data.canvas.borrow_mut().save_with(|canvas| {
+            canvas.translate(pos.x, pos.y);
+            if let Some(clip_rect) = clip_rect {
+                canvas.intersect_scissor(
+                    clip_rect.min_x(),
+                    clip_rect.min_y(),
+                    clip_rect.width(),
+                    clip_rect.height(),
+                );
+            }
+            canvas.set_transform(
+                transform.m11,
+                transform.m12,
+                transform.m21,
+                transform.m22,
+                transform.m31,
+                transform.m32,
+            );
+            canvas.fill_path(&mut path, fill_paint);
+        })
+    }
+}
+
+/// Returns the flags to upload an image of the given size with, when it is drawn at the given size in physical
+/// pixels. Images that are drawn at half their size or less in both directions get mipmaps, so that they don't
+/// alias. Mipmaps take a third more texture memory, so other images are uploaded without them.
+fn minification_flags(image_size: Size, pixel_size: (u32, u32)) -> femtovg::ImageFlags {
+    if pixel_size.0 > 0
+        && pixel_size.1 > 0
+        && image_size.width >= 2. * pixel_size.0 as f32
+        && image_size.height >= 2. * pixel_size.1 as f32
+    {
+        femtovg::ImageFlags::GENERATE_MIPMAPS
+    } else {
+        femtovg::ImageFlags::empty()
+    }
+}
+
+/// Returns the rectangle and the corner radius that the border of a rectangle with the given geometry and corner
+/// radius is stroked along. In CSS the border is entirely towards the inside of the boundary geometry, while in
+/// femtovg the line width for a stroke is 50% in- and 50% outwards, so for `inside` the rectangle is inset by half
+/// the border width. For `outside` the radius grows with the rectangle, so that the inner edge of the border
+/// follows the rounded corners of the fill.
+fn border_stroke_outline(
+    geometry: Rect,
+    radius: f32,
+    border_width: f32,
+    alignment: StrokeAlignment,
+) -> (Rect, f32) {
+    let offset = stroke_offset(alignment, border_width);
+    let radius = if radius > 0. && offset > 0. { radius + offset } else { radius };
+    (geometry.inflate(offset, offset), radius)
+}
+
+/// Returns the rectangle and the corner radius that a focus ring is stroked along, around the given geometry:
+/// the geometry inflated by the gap and half the width of the ring, with the corners rounded by as much more than
+/// the radius of the item. At the edges where the ring would be cut off by the given bounds, the visible area of the
+/// render target, it is moved inwards to lie just inside of them, over the item, so that it stays visible.
+fn focus_ring_outline(geometry: Rect, ring: &FocusRing, bounds: Rect) -> (Rect, f32) {
+    let offset = ring.gap + ring.width / 2.;
+    let mut outline = geometry.inflate(offset, offset);
+    let visible = bounds.inflate(-ring.width / 2., -ring.width / 2.);
+    if !visible.is_empty() {
+        let min = outline.min().max(visible.min());
+        let max = outline.max().min(visible.max());
+        // An item entirely outside of the bounds keeps its ring, which is not visible anyway.
+        if min.x < max.x && min.y < max.y {
+            outline = Rect::from_points([min, max].iter().copied());
+        }
+    }
+    let radius = (ring.radius.max(0.) + offset).max(0.);
+    (outline, radius.min(outline.width() / 2.).min(outline.height() / 2.))
+}
+
+/// Returns the points of the outline of the rounded rectangle, clockwise from the end of its top edge, with the
+/// corners flattened into lines within the given tolerance.
+fn rounded_rect_polygon(rect: Rect, radius: f32, tolerance: f32) -> Vec<Point> {
+    let radius = radius.max(0.).min(rect.width() / 2.).min(rect.height() / 2.);
+    if radius <= 0. {
+        return vec![
+            rect.min(),
+            euclid::point2(rect.max_x(), rect.min_y()),
+            rect.max(),
+            euclid::point2(rect.min_x(), rect.max_y()),
+        ];
+    }
+    // The number of segments of a quarter circle for which the chords stay within the tolerance of the arc.
+    let segment_angle = 2. * (1. - tolerance.max(f32::EPSILON) / radius).max(-1.).acos();
+    let segments = ((std::f32::consts::FRAC_PI_2 / segment_angle).ceil() as usize).clamp(1, 64);
+    let inner = rect.inflate(-radius, -radius);
+    let centers = [
+        euclid::point2(inner.max_x(), inner.min_y()),
+        inner.max(),
+        euclid::point2(inner.min_x(), inner.max_y()),
+        inner.min(),
+    ];
+    let mut points = Vec::with_capacity(4 * (segments + 1));
+    for (corner, center) in centers.iter().enumerate() {
+        let start_angle = (corner as f32 - 1.) * std::f32::consts::FRAC_PI_2;
+        for step in 0..=segments {
+            let angle = start_angle + std::f32::consts::FRAC_PI_2 * step as f32 / segments as f32;
+            points.push(euclid::point2(
+                center.x + radius * angle.cos(),
+                center.y + radius * angle.sin(),
+            ));
+        }
+    }
+    points
+}
+
+/// Splits the outline of the closed polygon into dashes of about the given length, with gaps as long in between.
+/// The length is adjusted so that a whole number of dashes and gaps goes around the polygon.
+fn dashed_polygon(points: &[Point], dash_length: f32) -> Vec<Vec<Point>> {
+    if points.len() < 2 || dash_length <= 0. {
+        return Vec::new();
+    }
+    // The distance along the outline of each point, followed by the length of the whole outline.
+    let mut distances = vec![0.];
+    let mut perimeter = 0.;
+    for (from, to) in points.iter().zip(points.iter().cycle().skip(1)) {
+        perimeter += (*to - *from).length();
+        distances.push(perimeter);
+    }
+    if perimeter <= 0. {
+        return Vec::new();
+    }
+    let dash_count = (perimeter / (2. * dash_length)).round().max(1.) as usize;
+    let dash_length = perimeter / (2 * dash_count) as f32;
+    let point_at = |distance: f32| {
+        let edge =
+            distances[1..].iter().position(|&end| end >= distance).unwrap_or(points.len() - 1);
+        let (from, to) = (points[edge], points[(edge + 1) % points.len()]);
+        let length = distances[edge + 1] - distances[edge];
+        if length > 0. {
+            from.lerp(to, (distance - distances[edge]) / length)
+        } else {
+            from
+        }
+    };
+    (0..dash_count)
+        .map(|dash| {
+            let start = (2 * dash) as f32 * dash_length;
+            let end = start + dash_length;
+            let corners = points
+                .iter()
+                .zip(&distances)
+                .filter(|(_, &distance)| distance > start && distance < end)
+                .map(|(point, _)| *point);
+            std::iter::once(point_at(start))
+                .chain(corners)
+                .chain(std::iter::once(point_at(end)))
+                .collect()
+        })
+        .collect()
+}
+
+/// Splits the path into its sub-paths, with the curves flattened into lines within the given tolerance. Returns
+/// the points of each sub-path, and whether it is closed.
+fn flattened_sub_paths(
+    events: impl Iterator<Item = lyon_path::PathEvent>,
+    tolerance: f32,
+) -> Vec<(Vec<Point>, bool)> {
+    use lyon_path::iterator::PathIterator;
+    let mut sub_paths = Vec::new();
+    let mut points = Vec::new();
+    for event in events.flattened(tolerance) {
+        match event {
+            lyon_path::Event::Begin { at } => points.push(Point::new(at.x, at.y)),
+            lyon_path::Event::Line { to, .. } => points.push(Point::new(to.x, to.y)),
+            lyon_path::Event::End { close, .. } => {
+                let mut sub_path: Vec<Point> = std::mem::take(&mut points);
+                sub_path.dedup();
+                // A closed sub-path that explicitly returns to its start.
+                if close && sub_path.len() > 1 && sub_path.first() == sub_path.last() {
+                    sub_path.pop();
+                }
+                sub_paths.push((sub_path, close));
+            }
+            _ => {}
+        }
+    }
+    sub_paths
+}
+
+/// Moves the edges of the closed polygon outwards by the given distance, or inwards when it is negative, moving
+/// each corner along the bisector of its edges. The polygon can be wound either way. Sharp corners are moved by at
+/// most four times the distance, like a miter limit.
+fn offset_polygon(points: &[Point], distance: f32) -> Vec<Point> {
+    let count = points.len();
+    if count < 3 {
+        return points.to_vec();
+    }
+    // Twice the signed area, which is positive for clockwise polygons in the y-down coordinate system.
+    let area: f32 = (0..count)
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % count]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum();
+    let orientation = if area >= 0. { 1. } else { -1. };
+    let outward_normal = |from: Point, to: Point| {
+        let direction = (to - from).normalize();
+        euclid::vec2(direction.y, -direction.x) * orientation
+    };
+    (0..count)
+        .map(|i| {
+            let point = points[i];
+            let before = outward_normal(points[(i + count - 1) % count], point);
+            let after = outward_normal(point, points[(i + 1) % count]);
+            // The corner moves along the sum of the normals, far enough for both edges to move by the distance.
+            let cos = before.dot(after);
+            let shift = if cos > -0.875 {
+                (before + after) * (distance / (1. + cos))
+            } else if cos > -0.999 {
+                (before + after).normalize() * (distance * 4.)
+            } else {
+                // The edges turn back onto each other.
+                before * distance
+            };
+            point + shift
+        })
+        .collect()
+}
+
+/// Returns the alpha of the paint that an image with the given opacity is filled with, or None when the image is
+/// fully transparent and not drawn at all.
+fn image_paint_alpha(opacity: f32) -> Option<f32> {
+    if opacity > 0. {
+        Some(opacity.min(1.))
+    } else {
+        None
+    }
+}
+
+/// Rasterizes a conic gradient into an image of the given size. The center is given as a fraction of the size, and
+/// the gradient starts at the start angle, in degrees clockwise from the top, and goes around clockwise. The colors
+/// are interpolated with premultiplied alpha, so that fading to a transparent stop doesn't darken.
+fn conic_gradient_pixels(
+    stops: &[sixtyfps_corelib::graphics::GradientStop],
+    size: (u32, u32),
+    center: Point,
+    start_angle: f32,
+) -> image::RgbaImage {
+    let center = Point::new(center.x * size.0 as f32, center.y * size.1 as f32);
+    image::RgbaImage::from_fn(size.0, size.1, |x, y| {
+        let offset = euclid::point2(x as f32 + 0.5, y as f32 + 0.5) - center;
+        let angle = offset.x.atan2(-offset.y).to_degrees() - start_angle;
+        let position = angle.rem_euclid(360.) / 360.;
+        image::Rgba(gradient_color_at(stops, position))
+    })
+}
+
+/// Returns the color of the gradient at the given position, as straight RGBA.
+fn gradient_color_at(stops: &[sixtyfps_corelib::graphics::GradientStop], position: f32) -> [u8; 4] {
+    let premultiplied = |color: Color| {
+        let color = color.to_argb_f32();
+        [color.red * color.alpha, color.green * color.alpha, color.blue * color.alpha, color.alpha]
+    };
+    let next = stops.iter().position(|stop| stop.position > position).unwrap_or(stops.len());
+    let [red, green, blue, alpha] = match next {
+        0 => premultiplied(stops[0].color),
+        next if next == stops.len() => premultiplied(stops[next - 1].color),
+        next => {
+            let (from, to) = (&stops[next - 1], &stops[next]);
+            let t = (position - from.position) / (to.position - from.position);
+            let (from, to) = (premultiplied(from.color), premultiplied(to.color));
+            let channel = |index: usize| from[index] + (to[index] - from[index]) * t;
+            [channel(0), channel(1), channel(2), channel(3)]
+        }
+    };
+    let unpremultiply = |channel: f32| {
+        if alpha > 0. {
+            (channel / alpha * 255.).round().min(255.) as u8
+        } else {
+            0
+        }
+    };
+    [unpremultiply(red), unpremultiply(green), unpremultiply(blue), (alpha * 255.).round() as u8]
+}
+
+/// Returns the color to clear the window with. femtovg blends with premultiplied alpha, which is also what the
+/// compositor expects from transparent windows, so their clear color is premultiplied as well. Opaque windows
+/// are cleared with the color as it is.
+fn window_clear_color(color: femtovg::Color, transparent_window: bool) -> femtovg::Color {
+    if transparent_window {
+        femtovg::Color::rgbaf(color.r * color.a, color.g * color.a, color.b * color.a, color.a)
+    } else {
+        color
+    }
+}
+
+// Returns the paths of the placeholder that is drawn instead of an image that failed to load, or whose data has not
+// arrived yet: a box filling the element and a diagonal cross. Returns None if the image can be drawn, or if there
+// is no image to draw.
+fn image_placeholder_paths(
+    source: &Resource,
+    image: Option<&CachedImage>,
+    width: f32,
+    height: f32,
+) -> Option<(femtovg::Path, femtovg::Path)> {
+    if matches!(source, Resource::None)
+        || image.map_or(false, |image| image.load_state() == ImageLoadState::Ready)
+    {
+        return None;
+    }
+    let mut box_path = femtovg::Path::new();
+    box_path.rect(0., 0., width, height);
+    let mut cross_path = femtovg::Path::new();
+    cross_path.move_to(0., 0.);
+    cross_path.line_to(width, height);
+    cross_path.move_to(width, 0.);
+    cross_path.line_to(0., height);
+    Some((box_path, cross_path))
+}
+
+// Converts pixels with premultiplied alpha to straight alpha, in place.
+// Parses the value of the SIXTYFPS_OVERSCAN_MARGIN environment variable, a percentage of the window size such
+// as `5` or `5%`, into a fraction.
+fn parse_overscan_margin(value: &str) -> Option<f32> {
+    let percent: f32 = value.trim().trim_end_matches('%').trim().parse().ok()?;
+    // Keep some room to draw into.
+    Some((percent / 100.).max(0.).min(0.4))
+}
+
+/// Returns the transform that flips the content of a window of the given width horizontally about its middle. It
+/// is its own inverse.
+fn mirror_transform(width: f32) -> euclid::default::Transform2D<f32> {
+    euclid::default::Transform2D::scale(-1., 1.).then_translate(euclid::vec2(width, 0.))
+}
+
+// Returns the offset and the scale that fit the content of a window of the given size into the area inside
+// the overscan margin, or None if there is no margin.
+fn overscan_transform(
+    width: u32,
+    height: u32,
+    margin: f32,
+) -> Option<(euclid::default::Vector2D<f32>, f32)> {
+    if margin <= 0. {
+        return None;
+    }
+    Some((euclid::vec2(width as f32 * margin, height as f32 * margin), 1. - 2. * margin))
+}
+
+/// Converts the pixels read back from the framebuffer after rendering a frame, which must cover the whole window
+/// at its physical size. Returns None otherwise, such as when the WebGL drawing buffer was not resized yet.
+fn frame_pixels(
+    screenshot: imgref::ImgRef<rgb::RGBA8>,
+    physical_size: winit::dpi::PhysicalSize<u32>,
+    alpha_mode: AlphaMode,
+) -> Option<image::RgbaImage> {
+    if (screenshot.width() as u32, screenshot.height() as u32)
+        != (physical_size.width, physical_size.height)
+    {
+        return None;
+    }
+    let mut data = Vec::with_capacity(screenshot.width() * screenshot.height() * 4);
+    for pixel in screenshot.pixels() {
+        data.extend_from_slice(&[pixel.r, pixel.g, pixel.b, pixel.a]);
+    }
+    let mut pixels =
+        image::RgbaImage::from_raw(screenshot.width() as u32, screenshot.height() as u32, data)?;
+    // femtovg blends with premultiplied alpha, so that is what ends up in the framebuffer.
+    if alpha_mode == AlphaMode::Straight {
+        unpremultiply_alpha(&mut pixels);
+    }
+    Some(pixels)
+}
+
+fn unpremultiply_alpha(pixels: &mut image::RgbaImage) {
+    for pixel in pixels.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 || alpha == 255 {
+            continue;
+        }
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+/// The properties of the `Image` and `ClippedImage` elements that define how the image is drawn into the element.
+#[derive(Clone, Copy)]
+struct ImageDrawOptions<'a> {
+    /// The part of the image that is drawn, in image pixels. An empty rectangle draws all of the image.
+    source_clip_rect: Rect,
+    image_fit: ImageFit,
+    /// The horizontal and vertical alignment, as a fraction of the space left by the image.
+    alignment: (f32, f32),
+    orientation: ImageOrientation,
+    /// The color that the opaque pixels of the image are tinted with, if not transparent.
+    colorize_property: std::pin::Pin<&'a Property<Color>>,
+    image_rendering: ImageRendering,
+    /// The borders of the image that are not stretched, in image pixels.
+    nine_slice: euclid::default::SideOffsets2D<f32>,
+    opacity: f32,
+}
+
+/// How an image is rotated and mirrored within its element.
+#[derive(Clone, Copy, Default)]
+struct ImageOrientation {
+    /// Clockwise, in degrees
+    rotation_angle: f32,
+    flip_horizontal: bool,
+    flip_vertical: bool,
+}
+
+/// Returns the transform that maps the (clipped) source image, in image pixels starting at the origin, into
+/// the target rectangle of the image element. The image is flipped and rotated around its center, and the
+/// bounding box of the rotated image is what gets fit into the target according to `image_fit`. That way the
+/// width and height are swapped for rotations by 90 or 270 degrees. With `ImageFit::none`, the image keeps its
+/// size, one image pixel per logical pixel. When the image ends up smaller or larger than the target, it is
+/// placed according to the alignment factors returned by [`image_alignment_factors`].
+fn image_transform(
+    source_size: Size,
+    target_size: Size,
+    image_fit: ImageFit,
+    alignment: (f32, f32),
+    orientation: ImageOrientation,
+) -> euclid::default::Transform2D<f32> {
+    let (sin, cos) = rotation_sin_cos(orientation.rotation_angle);
+    let rotated_size = rotated_image_size(source_size, orientation.rotation_angle);
+
+    let (scale_x, scale_y) = match image_fit {
+        ImageFit::fill => {
+            (target_size.width / rotated_size.width, target_size.height / rotated_size.height)
+        }
+        ImageFit::contain => {
+            let ratio = f32::max(
+                target_size.width / rotated_size.width,
+                target_size.height / rotated_size.height,
+            );
+            (ratio, ratio)
+        }
+        ImageFit::none => (1., 1.),
+    };
+    let drawn_size: Size =
+        euclid::size2(rotated_size.width * scale_x, rotated_size.height * scale_y);
+    let center = euclid::vec2(
+        (target_size.width - drawn_size.width) * alignment.0 + drawn_size.width / 2.,
+        (target_size.height - drawn_size.height) * alignment.1 + drawn_size.height / 2.,
+    );
+
+    euclid::default::Transform2D::translation(-source_size.width / 2., -source_size.height / 2.)
+        .then_scale(
+            if orientation.flip_horizontal { -1. } else { 1. },
+            if orientation.flip_vertical { -1. } else { 1. },
+        )
+        .then(&euclid::default::Transform2D::new(cos, sin, -sin, cos, 0., 0.))
+        .then_scale(scale_x, scale_y)
+        .then_translate(center)
+}
+
+/// Returns the transform that fits an SVG image into the element like the `preserveAspectRatio` attribute of the
+/// SVG file specifies, along with the rectangle that the image is clipped to, if any. See image_transform.
+#[cfg(feature = "svg")]
+fn svg_image_transform(
+    aspect_ratio: svg::AspectRatio,
+    source_size: Size,
+    target_size: Size,
+    orientation: ImageOrientation,
+) -> (euclid::default::Transform2D<f32>, Option<Rect>) {
+    if aspect_ratio.stretch {
+        let transform =
+            image_transform(source_size, target_size, ImageFit::fill, (0.5, 0.5), orientation);
+        return (transform, None);
+    }
+    if aspect_ratio.slice {
+        let transform = image_transform(
+            source_size,
+            target_size,
+            ImageFit::contain,
+            aspect_ratio.alignment,
+            orientation,
+        );
+        return (transform, Some(Rect::from_size(target_size)));
+    }
+    // The whole image fits into the element: it is stretched to the largest size with its aspect ratio that fits,
+    // which is then placed in the element.
+    let rotated_size = rotated_image_size(source_size, orientation.rotation_angle);
+    let ratio =
+        f32::min(target_size.width / rotated_size.width, target_size.height / rotated_size.height);
+    let drawn_size = rotated_size * ratio;
+    let offset = euclid::vec2(
+        (target_size.width - drawn_size.width) * aspect_ratio.alignment.0,
+        (target_size.height - drawn_size.height) * aspect_ratio.alignment.1,
+    );
+    let transform =
+        image_transform(source_size, drawn_size, ImageFit::fill, (0.5, 0.5), orientation)
+            .then_translate(offset);
+    (transform, None)
+}
+
+/// Returns the rectangle, relative to the image element, that the image is clipped to when drawn with the given
+/// fit, if any. Images that keep their aspect ratio or their natural size are cut off at the bounds of the element
+/// when larger.
+fn image_fit_clip_rect(image_fit: ImageFit, target_size: Size) -> Option<Rect> {
+    match image_fit {
+        ImageFit::contain | ImageFit::none => Some(Rect::from_size(target_size)),
+        ImageFit::fill => None,
+    }
+}
+
+/// Returns the area of the image, in image pixels, that is drawn for the given source clip rectangle: the whole
+/// image if the rectangle is empty, otherwise the part of the rectangle that lies within the image. Returns None
+/// if nothing of the image is inside the rectangle.
+fn effective_source_clip_rect(source_clip_rect: Rect, image_size: Size) -> Option<Rect> {
+    let image_rect = Rect::from_size(image_size);
+    if source_clip_rect.is_empty() {
+        Some(image_rect)
+    } else {
+        source_clip_rect.intersection(&image_rect)
+    }
+}
+
+/// Splits the source area of an image, in image pixels, into the nine parts of a nine-slice image and returns each
+/// of them together with the rectangle it is drawn into within a target of the given size. The corners keep the
+/// size of their insets, the edges stretch along one axis and the center fills the rest. Insets that don't fit
+/// into the source, or corners that don't fit into the target, are shrunk proportionally. The inner boundaries
+/// of the target are snapped to device pixels, so that no seams appear between the parts.
+fn nine_slice_rects(
+    source: Rect,
+    insets: euclid::default::SideOffsets2D<f32>,
+    target_size: Size,
+    pixel_scale: f32,
+) -> Vec<(Rect, Rect)> {
+    let fit = |start: f32, end: f32, available: f32| {
+        let (start, end) = (start.max(0.), end.max(0.));
+        let ratio = if start + end > available { available.max(0.) / (start + end) } else { 1. };
+        (start * ratio, end * ratio)
+    };
+    let snap = |value: f32| {
+        if pixel_scale > 0. {
+            (value * pixel_scale).round() / pixel_scale
+        } else {
+            value
+        }
+    };
+
+    let (source_left, source_right) = fit(insets.left, insets.right, source.width());
+    let (source_top, source_bottom) = fit(insets.top, insets.bottom, source.height());
+    let (target_left, target_right) = fit(source_left, source_right, target_size.width);
+    let (target_top, target_bottom) = fit(source_top, source_bottom, target_size.height);
+
+    let source_xs = [
+        source.min_x(),
+        source.min_x() + source_left,
+        source.max_x() - source_right,
+        source.max_x(),
+    ];
+    let source_ys = [
+        source.min_y(),
+        source.min_y() + source_top,
+        source.max_y() - source_bottom,
+        source.max_y(),
+    ];
+    let target_left = snap(target_left);
+    let target_top = snap(target_top);
+    let target_xs = [
+        0.,
+        target_left,
+        snap(target_size.width - target_right).max(target_left),
+        target_size.width,
+    ];
+    let target_ys = [
+        0.,
+        target_top,
+        snap(target_size.height - target_bottom).max(target_top),
+        target_size.height,
+    ];
+
+    let mut parts = Vec::with_capacity(9);
+    for row in 0..3 {
+        for column in 0..3 {
+            let source = Rect::new(
+                euclid::point2(source_xs[column], source_ys[row]),
+                euclid::size2(
+                    source_xs[column + 1] - source_xs[column],
+                    source_ys[row + 1] - source_ys[row],
+                ),
+            );
+            let target = Rect::new(
+                euclid::point2(target_xs[column], target_ys[row]),
+                euclid::size2(
+                    target_xs[column + 1] - target_xs[column],
+                    target_ys[row + 1] - target_ys[row],
+                ),
+            );
+            if !source.is_empty() && !target.is_empty() {
+                parts.push((source, target));
+            }
+        }
+    }
+    parts
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct BitmapGlyphCacheKey {
+    bitmap_strikes: by_address::ByAddress<Rc<bitmap_strikes::BitmapStrikes>>,
+    ppem: u16,
+    glyph_id: u16,
+    color: u32,
+}
+
+#[derive(Clone, Copy)]
+struct BitmapGlyphImage {
+    id: femtovg::ImageId,
+    left: i32,
+    top: i32,
+    width: u32,
+    height: u32,
+}
+
+/// Text to fill at a pen position on the baseline, with the paint of its outlines and the color of its glyphs.
+#[derive(Clone, Copy)]
+struct PaintedText<'a> {
+    x: f32,
+    y: f32,
+    text: &'a str,
+    paint: femtovg::Paint,
+    /// The color of the glyph bitmaps, which the paint already has for the outlines.
+    color: Color,
+}
+
+// Limits the number of glyph images, which may grow when the color of text is animated.
+const MAX_BITMAP_GLYPH_CACHE_SIZE: usize = 4096;
+
+#[derive(PartialEq, Eq, Hash)]
+struct ColorGlyphCacheKey {
+    color_glyphs: by_address::ByAddress<Rc<color_glyphs::ColorGlyphs>>,
+    ppem: u16,
+    glyph_id: u16,
+}
+
+// Limits the number of color glyph images, which are large since they're taken from the bigger strikes.
+const MAX_COLOR_GLYPH_CACHE_SIZE: usize = 512;
+
+#[derive(PartialEq, Eq, Hash)]
+struct ConicGradientCacheKey {
+    stops: SharedString,
+    size: (u32, u32),
+    // The bits of the f32 center and start angle.
+    center: (u32, u32),
+    start_angle: u32,
+}
+
+// Limits the number of conic gradient images, which may grow when the size or the stops of a gradient are animated.
+const MAX_CONIC_GRADIENT_CACHE_SIZE: usize = 64;
+// Limits the size of conic gradient images. Larger gradients are scaled up, which is hardly visible with the
+// smooth color transitions of a gradient.
+const MAX_CONIC_GRADIENT_IMAGE_SIZE: u32 = 2048;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct FontCacheKey {
+    family: SharedString,
+    weight: i32,
+    style: FontStyle,
+    numeral_style: NumeralStyle,
+    language: SharedString,
+}
+
+// The slant of synthesized oblique text, the default angle of CSS' `font-style: oblique`.
+const SYNTHETIC_OBLIQUE_ANGLE: f32 = 14. * std::f32::consts::PI / 180.;
+
+// Returns true if text of the requested weight is drawn with a face that is so much lighter that it needs to
+// be emboldened, which is the case when a bold weight is requested from a family without bold faces.
+fn needs_synthetic_bold(requested_weight: i32, font_weight: i32) -> bool {
+    requested_weight >= 600 && font_weight <= requested_weight - 200
+}
+
+// The width by which the outlines of synthesized bold glyphs are stroked, and their advances widened.
+fn synthetic_bold_strength(pixel_size: f32) -> f32 {
+    pixel_size / 24.
+}
+
+// Returns the paint for drawing and measuring text with the fonts, with the glyph advances widened by the
+// emboldening if the text is drawn in synthetic bold, so that the layout leaves room for it. femtovg caches
+// the shaped words by font and size only, which works as the fonts are loaded separately for each weight.
+fn text_paint(fonts: &[femtovg::FontId], pixel_size: f32, synthetic_bold: bool) -> femtovg::Paint {
+    let mut paint = femtovg::Paint::default();
+    paint.set_font(fonts);
+    paint.set_font_size(pixel_size);
+    paint.set_text_baseline(femtovg::Baseline::Top);
+    if synthetic_bold {
+        paint.set_letter_spacing(synthetic_bold_strength(pixel_size));
+    }
+    paint
+}
+
+/// The fonts that text of a font request is drawn with.
+struct ResolvedFont {
+    /// The font for the request, followed by the fonts to fall back to for missing glyphs.
+    fonts: Vec<femtovg::FontId>,
+    /// The bitmap strikes of the primary font
+    bitmap_strikes: Option<Rc<bitmap_strikes::BitmapStrikes>>,
+    /// The color glyph images of the fonts that have them, such as emoji fonts.
+    color_glyphs: Vec<(femtovg::FontId, Rc<color_glyphs::ColorGlyphs>)>,
+    /// True if italic or oblique text was requested but the font has no such face, so the text needs
+    /// to be slanted when drawing.
+    synthetic_oblique: bool,
+    /// True if a bold weight was requested but the font has no such face, so the glyphs need to be
+    /// emboldened when drawing.
+    synthetic_bold: bool,
+}
+
+struct GLFont {
+    resolved: Rc<ResolvedFont>,
+    /// The distance between tab stops that the text asks for, if not the default.
+    requested_tab_width: Option<f32>,
+    pixel_size: f32,
+    canvas: CanvasRc,
+}
+
+impl GLFont {
+    fn measure(&self, text: &str) -> femtovg::TextMetrics {
+        self.canvas.borrow_mut().measure_text(0., 0., text, self.paint()).unwrap()
+    }
+
+    fn height(&self) -> f32 {
+        self.canvas.borrow_mut().measure_font(self.paint()).unwrap().height()
+    }
+
+    /// Draws the text like `Canvas::fill_text`, slanted around the baseline if the font needs a
+    /// synthetic oblique, and with the outlines stroked if it needs a synthetic bold.
+    fn fill_text(
+        &self,
+        canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+        x: f32,
+        y: f32,
+        text: &str,
+        paint: femtovg::Paint,
+    ) -> femtovg::TextMetrics {
+        if self.resolved.synthetic_oblique {
+            let baseline = y + alphabetic_baseline_offset(canvas, paint);
+            canvas.save();
+            canvas.translate(0., baseline);
+            canvas.skew_x(-SYNTHETIC_OBLIQUE_ANGLE);
+            canvas.translate(0., -baseline);
+        }
+        let metrics = canvas.fill_text(x, y, text, paint).unwrap();
+        if self.resolved.synthetic_bold {
+            let mut stroke_paint = paint;
+            stroke_paint.set_line_width(synthetic_bold_strength(self.pixel_size));
+            canvas.stroke_text(x, y, text, stroke_paint).unwrap();
+        }
+        if self.resolved.synthetic_oblique {
+            canvas.restore();
+        }
+        metrics
+    }
+
+    fn paint(&self) -> femtovg::Paint {
+        text_paint(&self.resolved.fonts, self.pixel_size, self.resolved.synthetic_bold)
+    }
+
+    /// Returns the distance between the tab stops, by default the width of eight spaces.
+    fn tab_width(
+        &self,
+        canvas: &mut femtovg::Canvas<impl femtovg::Renderer>,
+        paint: femtovg::Paint,
+    ) -> f32 {
+        self.requested_tab_width.unwrap_or_else(|| {
+            DEFAULT_TAB_SIZE * canvas.measure_text(0., 0., " ", paint).unwrap().width()
+        })
+    }
+
+    fn text_size(&self, text: &str, max_width: Option<f32>) -> Size {
+        let paint = self.paint();
+        let mut canvas = self.canvas.borrow_mut();
+        let tab_width = Some(self.tab_width(&mut canvas, paint));
+        text_block_size(&mut canvas, text, max_width, paint, tab_width)
+    }
+}
+
+struct GLFontMetrics {
+    request: FontRequest,
+    scale_factor: f32,
+    shared_data: Rc<GLRendererData>,
+}
+
+impl FontMetrics for GLFontMetrics {
+    fn text_size(&self, text: &str) -> Size {
+        self.font().text_size(text, None)
+    }
+
+    fn text_size_for_width(&self, text: &str, max_width: f32) -> Size {
+        self.font().text_size(text, Some(max_width))
+    }
+
+    fn text_ink_bounds(&self, text: &str) -> Rect {
+        ink_bounds(&self.font().measure(text))
+    }
+
+    fn text_offset_for_x_position<'a>(&self, text: &'a str, x: f32) -> usize {
+        byte_offset_for_x_position(&self.font().measure(text), text, x)
+    }
+
+    fn text_offset_for_position(&self, text: &str, pos: Point, wrap_width: Option<f32>) -> usize {
+        let font = self.font();
+        let paint = font.paint();
+        let mut canvas = self.shared_data.canvas.borrow_mut();
+        let tab_width = Some(font.tab_width(&mut canvas, paint));
+        byte_offset_for_position(&mut canvas, text, paint, pos, wrap_width, tab_width)
+    }
+
+    fn height(&self) -> f32 {
+        self.measure_font().height()
+    }
+
+    fn ascent(&self) -> f32 {
+        vertical_font_metrics(&self.measure_font()).0
+    }
+
+    fn descent(&self) -> f32 {
+        vertical_font_metrics(&self.measure_font()).1
+    }
+
+    fn line_gap(&self) -> f32 {
+        vertical_font_metrics(&self.measure_font()).2
+    }
+}
+
+// Returns the ascent, the (positive) descent and the line gap of the font metrics. femtovg only provides the
+// line height, rounded to whole pixels, so the line gap is what remains of it after the ascent and descent.
+fn vertical_font_metrics(metrics: &femtovg::FontMetrics) -> (f32, f32, f32) {
+    let ascent = metrics.ascender();
+    let descent = -metrics.descender();
+    (ascent, descent, (metrics.height() - ascent - descent).max(0.))
+}
+
+impl GLFontMetrics {
+    fn measure_font(&self) -> femtovg::FontMetrics {
+        self.shared_data.canvas.borrow_mut().measure_font(self.font().paint()).unwrap()
+    }
+
+    fn font(&self) -> GLFont {
+        self.shared_data.loaded_fonts.borrow_mut().font(
+            &self.shared_data.canvas,
+            self.request.clone(),
             self.scale_factor,
+        )
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn create_gl_window_with_canvas_id(canvas_id: String) -> ComponentWindow {
+    let platform_window = GraphicsWindow::new(move |event_loop, window_builder| {
+        GLRenderer::new(event_loop, window_builder, &canvas_id)
+    });
+    let window = Rc::new(sixtyfps_corelib::window::Window::new(platform_window.clone()));
+    platform_window.self_weak.set(Rc::downgrade(&window)).ok().unwrap();
+    ComponentWindow(window)
+}
+
+#[doc(hidden)]
+#[cold]
+pub fn use_modules() {
+    sixtyfps_corelib::use_modules();
+}
+
+pub type NativeWidgets = ();
+pub type NativeGlobals = ();
+pub mod native_widgets {}
+pub const HAS_NATIVE_STYLE: bool = false;
+pub const IS_AVAILABLE: bool = true;
+
+type ClipboardResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// The clipboard is connected to when first used. It is None if that failed, for example when there is no
+// display to connect to, in which case copying and pasting text does nothing.
+thread_local!(pub(crate) static CLIPBOARD : RefCell<Option<Box<dyn copypasta::ClipboardProvider>>> =
+    RefCell::new(create_clipboard(|| Ok(Box::new(copypasta::ClipboardContext::new()?)))));
+
+fn create_clipboard(
+    create_provider: impl FnOnce() -> ClipboardResult<Box<dyn copypasta::ClipboardProvider>>,
+) -> Option<Box<dyn copypasta::ClipboardProvider>> {
+    create_provider().map_err(|err| eprintln!("The clipboard is not available: {}", err)).ok()
+}
+
+// The primary selection through X11 (including XWayland). It is None if there is no X11 display to connect to.
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "macos",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "emscripten"
+    )),
+    feature = "x11"
+))]
+thread_local!(static PRIMARY_SELECTION: RefCell<Option<copypasta::x11_clipboard::X11ClipboardContext<copypasta::x11_clipboard::Primary>>> =
+    RefCell::new(copypasta::x11_clipboard::X11ClipboardContext::new().ok()));
+
+// The primary selection on Wayland, with the display connection of the event loop that it was created with. It is
+// connected when first used, and dropped by drop_wayland_primary_selection when the event loop ends, before the
+// display connection is closed.
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "macos",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "emscripten"
+    )),
+    feature = "wayland"
+))]
+thread_local!(static WAYLAND_PRIMARY_SELECTION: RefCell<Option<(usize, copypasta::wayland_clipboard::Primary)>> =
+    RefCell::new(None));
+
+/// Drops the Wayland primary selection, which must be done before the display connection of the event loop that
+/// it uses is closed.
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "macos",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "emscripten"
+    )),
+    feature = "wayland"
+))]
+pub(crate) fn drop_wayland_primary_selection() {
+    WAYLAND_PRIMARY_SELECTION.with(|selection| selection.borrow_mut().take());
+}
+
+/// Calls the function with the primary selection: the one of the Wayland compositor when running on Wayland, and
+/// the X11 one otherwise. Returns None if there is no primary selection to connect to.
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "macos",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "emscripten"
+    )),
+    any(feature = "x11", feature = "wayland")
+))]
+fn with_primary_selection<T>(
+    action: impl FnOnce(&mut dyn copypasta::ClipboardProvider) -> T,
+) -> Option<T> {
+    #[cfg(feature = "wayland")]
+    {
+        // Only ask the event loop for its Wayland display on Wayland, as creating an event loop without any
+        // display panics.
+        let display = std::env::var_os("WAYLAND_DISPLAY").and_then(|_| {
+            use winit::platform::unix::EventLoopWindowTargetExtUnix;
+            crate::eventloop::with_window_target(|target| {
+                target.event_loop_target().wayland_display()
+            })
+        });
+        if let Some(display) = display {
+            return WAYLAND_PRIMARY_SELECTION.with(|selection| {
+                let mut selection = selection.borrow_mut();
+                if selection
+                    .as_ref()
+                    .map_or(true, |(connection, _)| *connection != display as usize)
+                {
+                    // SAFETY: the display is the connection of the event loop of this thread, which stays open
+                    // until the event loop is dropped. That happens at the end of `eventloop::run`, which drops
+                    // the primary selection first, or when the thread exits, after this thread local, which was
+                    // initialized after the one of the event loop.
+                    let (primary, _) = unsafe {
+                        copypasta::wayland_clipboard::create_clipboards_from_external(display)
+                    };
+                    *selection = Some((display as usize, primary));
+                }
+                selection.as_mut().map(|(_, primary)| action(primary))
+            });
+        }
+    }
+    #[cfg(feature = "x11")]
+    return PRIMARY_SELECTION
+        .with(|selection| selection.borrow_mut().as_mut().map(|selection| action(selection)));
+    #[allow(unreachable_code)]
+    None
+}
+
+// The clipboard with images, provided by arboard. It is kept for the lifetime of the thread, as the images copied
+// to the clipboard are served from it on X11. It is None if there is no clipboard to connect to.
+#[cfg(all(not(target_arch = "wasm32"), feature = "clipboard-image"))]
+thread_local!(static IMAGE_CLIPBOARD: RefCell<Option<arboard::Clipboard>> = RefCell::new(arboard::Clipboard::new().ok()));
+
+// Images from the clipboard come as RGBA pixels, but on Windows they are usually copied in a format without alpha
+// channel, leaving the alpha of all pixels at zero. Such images are made opaque. Returns None if the amount of
+// data does not match the size of the image.
+#[cfg(all(not(target_arch = "wasm32"), feature = "clipboard-image"))]
+fn opaque_if_without_alpha(mut rgba: Vec<u8>, width: u32, height: u32) -> Option<Vec<u8>> {
+    if rgba.len() != width as usize * height as usize * 4 {
+        return None;
+    }
+    if rgba.chunks_exact(4).all(|pixel| pixel[3] == 0) {
+        rgba.chunks_exact_mut(4).for_each(|pixel| pixel[3] = 0xff);
+    }
+    Some(rgba)
+}
+
+pub struct Backend;
+impl sixtyfps_corelib::backend::Backend for Backend {
+    fn create_window(&'static self) -> ComponentWindow {
+        let platform_window = GraphicsWindow::new(|event_loop, window_builder| {
+            GLRenderer::new(
+                event_loop,
+                window_builder,
+                #[cfg(target_arch = "wasm32")]
+                "canvas",
+            )
+        });
+        let window = Rc::new(sixtyfps_corelib::window::Window::new(platform_window.clone()));
+        platform_window.self_weak.set(Rc::downgrade(&window)).ok().unwrap();
+        ComponentWindow(window)
+    }
+
+    fn run_event_loop(&'static self) {
+        crate::eventloop::run();
+    }
+
+    fn register_application_font_from_memory(
+        &'static self,
+        data: &'static [u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self::register_application_font_from_memory(data)
+    }
+
+    fn register_application_font_from_path(
+        &'static self,
+        path: &std::path::Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self::register_application_font_from_path(path)
+    }
+
+    fn available_font_families(&'static self) -> Vec<String> {
+        self::available_font_families()
+    }
+
+    fn set_clipboard_text(&'static self, text: String) {
+        CLIPBOARD.with(|clipboard| {
+            clipboard.borrow_mut().as_mut().map(|clipboard| clipboard.set_contents(text).ok())
+        });
+    }
+
+    fn clipboard_text(&'static self) -> Option<String> {
+        CLIPBOARD.with(|clipboard| {
+            clipboard.borrow_mut().as_mut().and_then(|clipboard| clipboard.get_contents().ok())
+        })
+    }
+
+    fn set_primary_selection_text(&'static self, _text: String) {
+        #[cfg(all(
+            unix,
+            not(any(
+                target_os = "macos",
+                target_os = "android",
+                target_os = "ios",
+                target_os = "emscripten"
+            )),
+            any(feature = "x11", feature = "wayland")
+        ))]
+        with_primary_selection(|selection| selection.set_contents(_text).ok());
+    }
+
+    fn primary_selection_text(&'static self) -> Option<String> {
+        #[cfg(all(
+            unix,
+            not(any(
+                target_os = "macos",
+                target_os = "android",
+                target_os = "ios",
+                target_os = "emscripten"
+            )),
+            any(feature = "x11", feature = "wayland")
+        ))]
+        return with_primary_selection(|selection| selection.get_contents().ok()).flatten();
+        #[allow(unreachable_code)]
+        None
+    }
+
+    fn set_clipboard_image(&'static self, _rgba: &[u8], _width: u32, _height: u32) {
+        #[cfg(all(not(target_arch = "wasm32"), feature = "clipboard-image"))]
+        if _rgba.len() == _width as usize * _height as usize * 4 {
+            let image = arboard::ImageData {
+                width: _width as usize,
+                height: _height as usize,
+                bytes: _rgba.into(),
+            };
+            IMAGE_CLIPBOARD.with(|clipboard| {
+                clipboard.borrow_mut().as_mut().map(|clipboard| clipboard.set_image(image).ok())
+            });
+        }
+    }
+
+    fn clipboard_image(&'static self) -> Option<(Vec<u8>, u32, u32)> {
+        #[cfg(all(not(target_arch = "wasm32"), feature = "clipboard-image"))]
+        return IMAGE_CLIPBOARD.with(|clipboard| {
+            let image = clipboard.borrow_mut().as_mut()?.get_image().ok()?;
+            let (width, height) = (image.width as u32, image.height as u32);
+            let rgba = opaque_if_without_alpha(image.bytes.into_owned(), width, height)?;
+            Some((rgba, width, height))
+        });
+        #[allow(unreachable_code)]
+        None
+    }
+
+    fn pin_image_resource(&'static self, resource: Resource) {
+        if let Some(key) = ImageCacheKey::new(&resource) {
+            PINNED_IMAGES.with(|pinned| pinned.borrow_mut().insert(key));
+        }
+    }
+
+    fn unpin_image_resource(&'static self, resource: Resource) {
+        // The images are released from the windows' caches the next time they are rendered.
+        if let Some(key) = ImageCacheKey::new(&resource) {
+            PINNED_IMAGES.with(|pinned| pinned.borrow_mut().remove(&key));
+        }
+    }
+
+    fn set_texture_memory_budget(&'static self, budget: Option<usize>) {
+        // Only images uploaded after setting a budget can be evicted, as the others don't keep their pixels.
+        TEXTURE_MEMORY_BUDGET.with(|current| current.set(budget));
+    }
+
+    fn set_image_cache_enabled(&'static self, enabled: bool) {
+        IMAGE_CACHE_DISABLED.with(|disabled| disabled.set(!enabled));
+    }
+
+    fn set_text_antialiasing(&'static self, mode: TextAntialiasing) {
+        TEXT_ANTIALIASING.with(|current| current.set(mode));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "svg")]
+    fn svg_images_keep_their_aspect_ratio() {
+        let fitted = |aspect_ratio, orientation| {
+            let (transform, clip_rect) = svg_image_transform(
+                aspect_ratio,
+                euclid::size2(10., 20.),
+                euclid::size2(40., 40.),
+                orientation,
+            );
+            (transform.outer_transformed_rect(&euclid::rect(0., 0., 10., 20.)), clip_rect)
+        };
+        let centered = svg::AspectRatio { stretch: false, slice: false, alignment: (0.5, 0.5) };
+        // A 10x20 image in a 40x40 element is scaled uniformly to fit, and centered.
+        assert_eq!(fitted(centered, Default::default()), (euclid::rect(10., 0., 20., 40.), None));
+        let at_the_end = svg::AspectRatio { alignment: (1., 1.), ..centered };
+        assert_eq!(fitted(at_the_end, Default::default()).0, euclid::rect(20., 0., 20., 40.));
+        // Rotated by a quarter turn, the image is 20x10, and fits the width.
+        let rotated = ImageOrientation { rotation_angle: 90., ..Default::default() };
+        let (bounds, _) = fitted(centered, rotated);
+        assert!((bounds.width() - 40.).abs() < 0.001 && (bounds.height() - 20.).abs() < 0.001);
+        assert!((bounds.min_y() - 10.).abs() < 0.001, "{:?}", bounds);
+
+        let sliced = svg::AspectRatio { slice: true, ..centered };
+        assert_eq!(
+            fitted(sliced, Default::default()),
+            (euclid::rect(0., -20., 40., 80.), Some(euclid::rect(0., 0., 40., 40.)))
         );
-        let wrap = text.wrap() == TextWrap::word_wrap;
-        let text_size = font.text_size(string, if wrap { Some(max_width) } else { None });
-        let mut paint = font.paint();
-        paint.set_color(text.color().into());
+        let stretched = svg::AspectRatio { stretch: true, ..centered };
+        assert_eq!(fitted(stretched, Default::default()), (euclid::rect(0., 0., 40., 40.), None));
+    }
+
+    #[test]
+    fn image_transform_quarter_turn() {
+        // A 40x20 image rotated by 90 degrees fills a 20x40 element without being stretched.
+        let transform = image_transform(
+            euclid::size2(40., 20.),
+            euclid::size2(20., 40.),
+            ImageFit::fill,
+            (0.5, 0.5),
+            ImageOrientation { rotation_angle: 90., ..Default::default() },
+        );
+        let expected = [0., 1., -1., 0., 20., 0.];
+        let actual = transform.to_array();
+        for (actual, expected) in actual.iter().zip(expected.iter()) {
+            assert!((actual - expected).abs() < 0.001, "{:?} != {:?}", actual, expected);
+        }
+
+        // The top left corner of the image ends up in the top right corner of the element.
+        let top_left = transform.transform_point(euclid::point2(0., 0.));
+        assert!((top_left - euclid::point2(20., 0.)).length() < 0.001);
+        let bottom_right = transform.transform_point(euclid::point2(40., 20.));
+        assert!((bottom_right - euclid::point2(0., 40.)).length() < 0.001);
+    }
+
+    // Blends a color with premultiplied alpha over another one, as GL does with the given blend factors.
+    fn blend(
+        source: [f32; 4],
+        destination: [f32; 4],
+        (source_factor, destination_factor): (femtovg::BlendFactor, femtovg::BlendFactor),
+    ) -> [f32; 4] {
+        let factor = |factor: femtovg::BlendFactor, channel: usize| {
+            use femtovg::BlendFactor::*;
+            match factor {
+                Zero => 0.,
+                One => 1.,
+                SrcColor => source[channel],
+                OneMinusSrcColor => 1. - source[channel],
+                DstColor => destination[channel],
+                OneMinusDstColor => 1. - destination[channel],
+                SrcAlpha => source[3],
+                OneMinusSrcAlpha => 1. - source[3],
+                DstAlpha => destination[3],
+                OneMinusDstAlpha => 1. - destination[3],
+                SrcAlphaSaturate => unimplemented!(),
+            }
+        };
+        let mut result = [0.; 4];
+        for (channel, result) in result.iter_mut().enumerate() {
+            *result = (source[channel] * factor(source_factor, channel)
+                + destination[channel] * factor(destination_factor, channel))
+            .min(1.);
+        }
+        result
+    }
+
+    #[test]
+    fn transform_rotates_children_around_origin() {
+        let transform = Box::pin(sixtyfps_corelib::items::Transform::default());
+        transform.as_ref().x.set(10.);
+        transform.as_ref().y.set(20.);
+        transform.as_ref().origin_x.set(30.);
+        transform.as_ref().origin_y.set(40.);
+        transform.as_ref().scale_x.set(1.);
+        transform.as_ref().scale_y.set(1.);
+        transform.as_ref().rotation_angle.set(45.);
+
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        canvas.set_size(200, 200, 1.);
+        // The scale of the window, which the transform of the element is combined with.
+        canvas.scale(2., 2.);
+        concat_transform(
+            &mut canvas,
+            &transform.as_ref().children_transform(Point::new(100., 50.)),
+        );
+        let composed = canvas.transform();
+
+        let assert_maps_to = |from: (f32, f32), to: (f32, f32)| {
+            let (x, y) = composed.transform_point(from.0, from.1);
+            assert!(
+                (x - to.0).abs() < 0.001 && (y - to.1).abs() < 0.001,
+                "{:?} -> {:?}",
+                from,
+                (x, y)
+            );
+        };
+        // The origin, at (140, 110) in the coordinates the element is drawn in, stays in place.
+        assert_maps_to((140., 110.), (280., 220.));
+        // A point 10 to the right of the origin is rotated clockwise by 45 degrees around it.
+        let diagonal = 10. * std::f32::consts::FRAC_1_SQRT_2;
+        assert_maps_to((150., 110.), ((140. + diagonal) * 2., (110. + diagonal) * 2.));
+        // A point 10 below the origin ends up to the bottom left of it.
+        assert_maps_to((140., 120.), ((140. - diagonal) * 2., (110. + diagonal) * 2.));
+        assert!((composed.average_scale() - 2.).abs() < 0.001);
+    }
+
+    #[test]
+    fn transform_scales_and_shears_before_rotating() {
+        let transform = Box::pin(sixtyfps_corelib::items::Transform::default());
+        transform.as_ref().scale_x.set(2.);
+        transform.as_ref().scale_y.set(3.);
+        transform.as_ref().shear_x.set(0.5);
+        let matrix = transform.as_ref().children_transform(Point::default());
+        assert_eq!(matrix.transform_point(euclid::point2(1., 0.)), euclid::point2(2., 0.));
+        // The shear moves points horizontally in proportion to their (scaled) y coordinate.
+        assert_eq!(matrix.transform_point(euclid::point2(0., 1.)), euclid::point2(1.5, 3.));
+    }
+
+    #[test]
+    fn transparent_window_clear_color_is_premultiplied() {
+        let color = femtovg::Color::rgbaf(1., 0.5, 0., 0.5);
+        assert_eq!(window_clear_color(color, false), color);
+        assert_eq!(window_clear_color(color, true), femtovg::Color::rgbaf(0.5, 0.25, 0., 0.5));
+        let transparent = femtovg::Color::rgbaf(1., 1., 1., 0.);
+        assert_eq!(window_clear_color(transparent, true).a, 0.);
+        assert_eq!(window_clear_color(transparent, true).r, 0.);
+    }
+
+    #[test]
+    fn conic_gradient_starts_with_the_first_stop() {
+        let stops =
+            sixtyfps_corelib::graphics::parse_gradient_stops("#ff0000, #00ff00 50%, #0000ff")
+                .unwrap();
+        let center = euclid::point2(0.5, 0.5);
+        let pixels = conic_gradient_pixels(&stops, (11, 11), center, 0.);
+        // Straight above the center, at the top.
+        assert_eq!(pixels.get_pixel(5, 0).0, [0xff, 0, 0, 0xff]);
+        // Straight below the center, half way around.
+        assert_eq!(pixels.get_pixel(5, 10).0, [0, 0xff, 0, 0xff]);
+
+        // Starting at 90 degrees, the gradient starts to the right of the center.
+        let pixels = conic_gradient_pixels(&stops, (11, 11), center, 90.);
+        assert_eq!(pixels.get_pixel(10, 5).0, [0xff, 0, 0, 0xff]);
+        assert_eq!(pixels.get_pixel(0, 5).0, [0, 0xff, 0, 0xff]);
+
+        // With the center at the top left, the top right is at 90 degrees, a quarter of the way around.
+        let pixels = conic_gradient_pixels(&stops, (11, 11), euclid::point2(0., 0.), 0.);
+        let [red, green, blue, alpha] = pixels.get_pixel(10, 0).0;
+        assert!(
+            (red as i32 - 0x80).abs() <= 8 && (green as i32 - 0x80).abs() <= 8,
+            "{:?}",
+            (red, green)
+        );
+        assert_eq!((blue, alpha), (0, 0xff));
+    }
+
+    #[test]
+    fn gradient_fades_to_transparent_without_darkening() {
+        let stops = sixtyfps_corelib::graphics::parse_gradient_stops("#ffffff, #00000000").unwrap();
+        assert_eq!(gradient_color_at(&stops, 0.5), [0xff, 0xff, 0xff, 0x80]);
+        assert_eq!(gradient_color_at(&stops, 1.), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn gradient_stops_are_parsed_like_css() {
+        use sixtyfps_corelib::graphics::{parse_gradient_stops, GradientStop};
+        let stop = |position, red, green, blue, alpha| GradientStop {
+            position,
+            color: Color::from_argb_u8(alpha, red, green, blue),
+        };
+        assert_eq!(
+            parse_gradient_stops("#f00, #00ff0080 20%, #00f, #0000ff 10%, #fff8").unwrap(),
+            vec![
+                stop(0., 0xff, 0, 0, 0xff),
+                stop(0.2, 0, 0xff, 0, 0x80),
+                stop(0.2, 0, 0, 0xff, 0xff),
+                stop(0.2, 0, 0, 0xff, 0xff),
+                stop(1., 0xff, 0xff, 0xff, 0x88),
+            ]
+        );
+        assert_eq!(
+            parse_gradient_stops("#000, #000, #000 80%, #000")
+                .unwrap()
+                .iter()
+                .map(|stop| stop.position)
+                .collect::<Vec<_>>(),
+            vec![0., 0.4, 0.8, 1.]
+        );
+        assert!(parse_gradient_stops("#f00").is_err());
+        assert!(parse_gradient_stops("#f00, red").is_err());
+        assert!(parse_gradient_stops("#f00, #00f 50").is_err());
+        assert!(parse_gradient_stops("#f00, , #00f").is_err());
+    }
+
+    #[test]
+    fn image_paint_alpha_is_the_opacity() {
+        assert_eq!(image_paint_alpha(1.), Some(1.));
+        assert_eq!(image_paint_alpha(0.25), Some(0.25));
+        assert_eq!(image_paint_alpha(2.), Some(1.));
+        assert_eq!(image_paint_alpha(0.), None);
+        assert_eq!(image_paint_alpha(-1.), None);
+    }
+
+    #[test]
+    fn border_is_stroked_along_the_aligned_outline() {
+        let geometry: Rect = euclid::rect(10., 10., 100., 50.);
+        // The stroke covers the outline inflated by half the border width.
+        let stroked_bounds = |alignment| {
+            let (outline, radius) = border_stroke_outline(geometry, 0., 10., alignment);
+            assert_eq!(radius, 0.);
+            outline.inflate(5., 5.)
+        };
+        assert_eq!(stroked_bounds(StrokeAlignment::inside), geometry);
+        assert_eq!(stroked_bounds(StrokeAlignment::center), euclid::rect(5., 5., 110., 60.));
+        assert_eq!(stroked_bounds(StrokeAlignment::outside), euclid::rect(0., 0., 120., 70.));
+        // Outside of rounded corners, the inner edge of the border follows the corners of the fill.
+        assert_eq!(border_stroke_outline(geometry, 8., 10., StrokeAlignment::outside).1, 13.);
+        assert_eq!(border_stroke_outline(geometry, 8., 10., StrokeAlignment::center).1, 8.);
+    }
+
+    #[test]
+    fn focus_ring_surrounds_the_item_at_its_gap() {
+        let geometry: Rect = euclid::rect(50., 40., 100., 30.);
+        let window: Rect = euclid::rect(0., 0., 800., 600.);
+        let ring = FocusRing { width: 2., gap: 3., ..FocusRing::default() };
+        // The stroke covers the outline inflated by half the width of the ring.
+        let (outline, radius) = focus_ring_outline(geometry, &ring, window);
+        assert_eq!(outline.inflate(1., 1.), geometry.inflate(5., 5.));
+        // The corners are rounded around the corners of the item, even square ones.
+        assert_eq!(radius, 4.);
+        let (_, radius) = focus_ring_outline(geometry, &FocusRing { radius: 6., ..ring }, window);
+        assert_eq!(radius, 10.);
+        let (_, radius) = focus_ring_outline(geometry, &FocusRing { radius: 100., ..ring }, window);
+        assert_eq!(radius, 19.);
+
+        // At the edges of the window, the ring is moved inside of them.
+        let (outline, _) = focus_ring_outline(euclid::rect(0., 40., 100., 30.), &ring, window);
+        assert_eq!(outline.inflate(1., 1.), euclid::rect(0., 35., 105., 40.));
+        let (outline, _) = focus_ring_outline(euclid::rect(0., 0., 800., 600.), &ring, window);
+        assert_eq!(outline.inflate(1., 1.), window);
+    }
+
+    #[test]
+    fn dashed_focus_ring_spreads_the_dashes_evenly() {
+        let outline: Rect = euclid::rect(10., 10., 100., 50.);
+        let polygon = rounded_rect_polygon(outline, 5., 0.1);
+        assert_eq!(Rect::from_points(polygon.iter().copied()), outline);
+
+        let dashes = dashed_polygon(&polygon, 6.);
+        let length =
+            |dash: &Vec<Point>| dash.windows(2).map(|w| (w[1] - w[0]).length()).sum::<f32>();
+        let perimeter = length(&polygon.iter().chain(polygon.first()).copied().collect());
+        assert_eq!(dashes.len(), (perimeter / 12.).round() as usize);
+        let dash_length = perimeter / (2 * dashes.len()) as f32;
+        for dash in &dashes {
+            assert!((length(dash) - dash_length).abs() < 0.01);
+        }
+        // The dashes follow the outline, starting at the top right.
+        let bounds = Rect::from_points(dashes.iter().flatten().copied());
+        assert!(outline.inflate(0.01, 0.01).contains_rect(&bounds));
+        assert_eq!(dashes[0][0], euclid::point2(105., 10.));
+
+        let square = rounded_rect_polygon(outline, 0., 0.1);
+        assert_eq!(square.len(), 4);
+        assert!(dashed_polygon(&square, 0.).is_empty());
+    }
+
+    #[test]
+    fn hairline_strokes_are_one_device_pixel_wide() {
+        for scale_factor in [1., 2.] {
+            let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+            canvas.scale(scale_factor, scale_factor);
+            let scale = canvas.transform().average_scale();
+            assert_eq!(effective_stroke_width(5., true, scale) * scale, 1.);
+            assert_eq!(effective_stroke_width(5., false, scale) * scale, 5. * scale_factor);
+        }
+    }
+
+    #[test]
+    fn closed_paths_are_stroked_along_the_aligned_outline() {
+        let mut builder = lyon_path::Path::builder();
+        builder.begin(lyon_path::math::point(10., 10.));
+        builder.line_to(lyon_path::math::point(110., 10.));
+        builder.line_to(lyon_path::math::point(110., 60.));
+        builder.line_to(lyon_path::math::point(10., 60.));
+        builder.end(true);
+        // An open path is stroked centered whatever the alignment.
+        builder.begin(lyon_path::math::point(0., 100.));
+        builder.line_to(lyon_path::math::point(50., 100.));
+        builder.end(false);
+        let path = builder.build();
+
+        let sub_paths = flattened_sub_paths(path.iter(), 0.1);
+        assert_eq!(sub_paths.len(), 2);
+        let (rect, closed) = &sub_paths[0];
+        assert!(closed);
+        assert_eq!(rect.len(), 4);
+        assert!(!sub_paths[1].1);
+
+        // The bounds of the stroke of width 10, around the polygon.
+        let stroked_bounds = |alignment| -> Rect {
+            let polygon = offset_polygon(rect, stroke_offset(alignment, 10.));
+            let bounds = Rect::from_points(polygon.iter().copied()).inflate(5., 5.);
+            euclid::rect(
+                bounds.min_x().round(),
+                bounds.min_y().round(),
+                bounds.width().round(),
+                bounds.height().round(),
+            )
+        };
+        assert_eq!(stroked_bounds(StrokeAlignment::inside), euclid::rect(10., 10., 100., 50.));
+        assert_eq!(stroked_bounds(StrokeAlignment::center), euclid::rect(5., 5., 110., 60.));
+        assert_eq!(stroked_bounds(StrokeAlignment::outside), euclid::rect(0., 0., 120., 70.));
+
+        // The winding of the polygon doesn't matter.
+        let reversed: Vec<Point> = rect.iter().rev().copied().collect();
+        let outset = offset_polygon(&reversed, 5.);
+        assert_eq!(Rect::from_points(outset.iter().copied()), euclid::rect(5., 5., 110., 60.));
+    }
+
+    #[test]
+    fn images_drawn_much_smaller_get_mipmaps() {
+        let image_size: Size = euclid::size2(1000., 800.);
+        assert_eq!(
+            minification_flags(image_size, (100, 80)),
+            femtovg::ImageFlags::GENERATE_MIPMAPS
+        );
+        assert_eq!(
+            minification_flags(image_size, (500, 400)),
+            femtovg::ImageFlags::GENERATE_MIPMAPS
+        );
+        assert_eq!(minification_flags(image_size, (600, 400)), femtovg::ImageFlags::empty());
+        assert_eq!(minification_flags(image_size, (1000, 800)), femtovg::ImageFlags::empty());
+        assert_eq!(minification_flags(image_size, (2000, 1600)), femtovg::ImageFlags::empty());
+        assert_eq!(minification_flags(image_size, (0, 0)), femtovg::ImageFlags::empty());
+    }
+
+    #[test]
+    fn text_shadow_is_filled_at_its_offset_before_the_text() {
+        let color = Color::from_rgb_u8(0, 0, 0);
+        let shadow_color = Color::from_argb_u8(128, 255, 0, 0);
+        let passes = text_fill_passes(color, euclid::vec2(3., 4.), 0., shadow_color);
+        assert_eq!(
+            passes,
+            vec![(euclid::vec2(3., 4.), shadow_color), (euclid::vec2(0., 0.), color)]
+        );
+
+        let passes = text_fill_passes(color, euclid::vec2(3., 4.), 0., Color::default());
+        assert_eq!(passes, vec![(euclid::vec2(0., 0.), color)]);
+    }
+
+    #[test]
+    fn blurred_text_shadow_adds_up_to_the_shadow_alpha() {
+        let color = Color::from_rgb_u8(0, 0, 0);
+        let shadow_color = Color::from_argb_u8(128, 255, 0, 0);
+        let passes = text_fill_passes(color, euclid::vec2(3., 4.), 6., shadow_color);
+        assert_eq!(passes.len(), TEXT_SHADOW_BLUR_SAMPLES + 1);
+        assert_eq!(passes.last(), Some(&(euclid::vec2(0., 0.), color)));
+        let shadow_passes = &passes[..TEXT_SHADOW_BLUR_SAMPLES];
+        assert_eq!(shadow_passes[0].0, euclid::vec2(3., 4.));
+        for (offset, _) in shadow_passes {
+            assert!((*offset - euclid::vec2(3., 4.)).length() <= 3. + 1e-4);
+        }
+        let covered = 1.
+            - shadow_passes
+                .iter()
+                .fold(1., |uncovered, (_, color)| uncovered * (1. - color.alpha() as f32 / 255.));
+        assert!((covered - 128. / 255.).abs() < 0.02, "{}", covered);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "needs a display to create an OpenGL context on"]
+    fn transparent_clear_and_blending_in_offscreen_context() {
+        use winit::platform::unix::EventLoopExtUnix;
+        let event_loop = winit::event_loop::EventLoop::<()>::new_any_thread();
+        let context = glutin::ContextBuilder::new()
+            .with_pixel_format(24, 8)
+            .build_headless(&event_loop, glutin::dpi::PhysicalSize::new(4, 4))
+            .expect("cannot create a headless OpenGL context");
+        let context = unsafe { context.make_current().unwrap() };
+        let renderer =
+            femtovg::renderer::OpenGl::new(|symbol| context.get_proc_address(symbol) as *const _)
+                .unwrap();
+        let mut canvas = femtovg::Canvas::new(renderer).unwrap();
+        canvas.set_size(4, 4, 1.);
+        let target = canvas
+            .create_image_empty(4, 4, femtovg::PixelFormat::Rgba8, femtovg::ImageFlags::empty())
+            .unwrap();
+        canvas.set_render_target(femtovg::RenderTarget::Image(target));
+        canvas.clear_rect(
+            0,
+            0,
+            4,
+            4,
+            window_clear_color(femtovg::Color::rgbaf(1., 1., 1., 0.), true),
+        );
+        // A translucent red rectangle on the left half.
+        let mut path = femtovg::Path::new();
+        path.rect(0., 0., 2., 4.);
+        canvas.fill_path(&mut path, femtovg::Paint::color(femtovg::Color::rgbaf(1., 0., 0., 0.5)));
+        canvas.flush();
+        let pixels = canvas.screenshot().unwrap();
+        let left = pixels.buf()[0];
+        let right = pixels.buf()[3];
+        assert_eq!(right.a, 0);
+        // The rectangle is composited against the transparent backdrop with premultiplied alpha.
+        assert!((left.a as i32 - 128).abs() <= 1, "{:?}", left);
+        assert!((left.r as i32 - 128).abs() <= 1, "{:?}", left);
+        assert_eq!((left.g, left.b), (0, 0));
+        canvas.set_render_target(femtovg::RenderTarget::Screen);
+        canvas.delete_image(target);
+    }
+
+    #[test]
+    fn desaturated_pixels_keep_their_luminance() {
+        let red = rgb::RGBA8 { r: 255, g: 0, b: 0, a: 255 };
+        let mut pixels = [red, rgb::RGBA8 { r: 0, g: 0, b: 0, a: 0 }];
+        desaturate_pixels(&mut pixels, 0.);
+        assert_eq!(pixels[0], rgb::RGBA8 { r: 54, g: 54, b: 54, a: 255 });
+        assert_eq!(pixels[1], rgb::RGBA8 { r: 0, g: 0, b: 0, a: 0 });
+
+        let mut pixels = [red];
+        desaturate_pixels(&mut pixels, 0.5);
+        assert_eq!(pixels[0], rgb::RGBA8 { r: 155, g: 27, b: 27, a: 255 });
+        let mut pixels = [red];
+        desaturate_pixels(&mut pixels, 1.);
+        assert_eq!(pixels[0], red);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "needs a display to create an OpenGL context on"]
+    fn red_rectangle_is_drawn_gray_when_desaturated() {
+        use winit::platform::unix::EventLoopExtUnix;
+        let event_loop = winit::event_loop::EventLoop::<()>::new_any_thread();
+        let context = glutin::ContextBuilder::new()
+            .with_pixel_format(24, 8)
+            .build_headless(&event_loop, glutin::dpi::PhysicalSize::new(4, 4))
+            .expect("cannot create a headless OpenGL context");
+        let context = unsafe { context.make_current().unwrap() };
+        let renderer =
+            femtovg::renderer::OpenGl::new(|symbol| context.get_proc_address(symbol) as *const _)
+                .unwrap();
+        let mut canvas = femtovg::Canvas::new(renderer).unwrap();
+        canvas.set_size(4, 4, 1.);
+        let layer = canvas
+            .create_image_empty(
+                4,
+                4,
+                femtovg::PixelFormat::Rgba8,
+                femtovg::ImageFlags::FLIP_Y | femtovg::ImageFlags::PREMULTIPLIED,
+            )
+            .unwrap();
+        canvas.set_render_target(femtovg::RenderTarget::Image(layer));
+        canvas.clear_rect(0, 0, 4, 4, femtovg::Color::rgbaf(0., 0., 0., 0.));
+        // A red rectangle on the top half.
+        let mut path = femtovg::Path::new();
+        path.rect(0., 0., 4., 2.);
+        canvas.fill_path(&mut path, femtovg::Paint::color(femtovg::Color::rgb(255, 0, 0)));
+        desaturate_render_target(&mut canvas, layer, 0.);
+        let pixels = canvas.screenshot().unwrap();
+        let (top, bottom) = (pixels.buf()[0], pixels.buf()[15]);
+        assert!((top.r as i32 - 54).abs() <= 1, "{:?}", top);
+        assert_eq!((top.r, top.a), (top.g, 255), "{:?}", top);
+        assert_eq!(top.g, top.b, "{:?}", top);
+        assert_eq!(bottom.a, 0, "{:?}", bottom);
+        canvas.set_render_target(femtovg::RenderTarget::Screen);
+        canvas.delete_image(layer);
+    }
+
+    #[test]
+    fn textures_hold_premultiplied_alpha() {
+        // A half-transparent white pixel next to a transparent one, as at the antialiased edge of an icon.
+        let straight =
+            image::RgbaImage::from_raw(2, 1, vec![255, 255, 255, 128, 0, 0, 0, 0]).unwrap();
+        let texels = texture_pixels(&image::DynamicImage::ImageRgba8(straight), false).into_raw();
+        // Cached pixmaps are provided premultiplied already, and are uploaded as they are. Both paths end up with
+        // the same texels.
+        assert_eq!(texels, vec![128, 128, 128, 128, 0, 0, 0, 0]);
+
+        // Sampling halfway between the texels averages them. Blended over black, the result is as bright as it
+        // is opaque, like white should be. Averaging straight alpha would have mixed in the black of the
+        // transparent pixel, to be darkened once more by the alpha when blending.
+        let halfway = |channel: usize| (texels[channel] as u32 + texels[channel + 4] as u32) / 2;
+        assert_eq!(halfway(0), halfway(3));
+        // Half of the white, premultiplied with half of the alpha.
+        let straight_halfway = 127 * 64 / 255;
+        assert!(straight_halfway < halfway(3));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "needs a display to create an OpenGL context on"]
+    fn scaled_images_have_no_dark_fringes() {
+        use winit::platform::unix::EventLoopExtUnix;
+        let event_loop = winit::event_loop::EventLoop::<()>::new_any_thread();
+        let context = glutin::ContextBuilder::new()
+            .with_pixel_format(24, 8)
+            .build_headless(&event_loop, glutin::dpi::PhysicalSize::new(8, 1))
+            .expect("cannot create a headless OpenGL context");
+        let context = unsafe { context.make_current().unwrap() };
+        let renderer =
+            femtovg::renderer::OpenGl::new(|symbol| context.get_proc_address(symbol) as *const _)
+                .unwrap();
+        let canvas: CanvasRc = Rc::new(RefCell::new(femtovg::Canvas::new(renderer).unwrap()));
+        canvas.borrow_mut().set_size(8, 1, 1.);
+        let target = canvas
+            .borrow_mut()
+            .create_image_empty(8, 1, femtovg::PixelFormat::Rgba8, femtovg::ImageFlags::empty())
+            .unwrap();
+
+        let straight =
+            image::RgbaImage::from_raw(2, 1, vec![255, 255, 255, 128, 0, 0, 0, 0]).unwrap();
+        let decoded = upload_image(
+            &canvas,
+            &image::DynamicImage::ImageRgba8(straight),
+            femtovg::ImageFlags::empty(),
+            false,
+            FALLBACK_MAX_TEXTURE_SIZE,
+        );
+        let pixmap = canvas
+            .borrow_mut()
+            .create_image(
+                imgref::Img::new(
+                    &[rgb::RGBA8::new(128, 128, 128, 128), rgb::RGBA8::new(0, 0, 0, 0)][..],
+                    2,
+                    1,
+                ),
+                femtovg::ImageFlags::PREMULTIPLIED,
+            )
+            .unwrap();
+
+        for image in [decoded, pixmap].iter().copied() {
+            let mut canvas = canvas.borrow_mut();
+            canvas.set_render_target(femtovg::RenderTarget::Image(target));
+            canvas.clear_rect(0, 0, 8, 1, femtovg::Color::rgbaf(0., 0., 0., 0.));
+            // Stretched, so that most pixels are sampled between the two texels.
+            let mut path = femtovg::Path::new();
+            path.rect(0., 0., 8., 1.);
+            canvas.fill_path(&mut path, femtovg::Paint::image(image, 0., 0., 8., 1., 0., 1.));
+            let pixels = canvas.screenshot().unwrap();
+            for pixel in pixels.buf() {
+                // White drawn over a transparent background stays as bright as it is opaque.
+                assert!((pixel.r as i32 - pixel.a as i32).abs() <= 1, "{:?}", pixels.buf());
+            }
+        }
+        let mut canvas = canvas.borrow_mut();
+        canvas.set_render_target(femtovg::RenderTarget::Screen);
+        canvas.delete_image(target);
+        canvas.delete_image(decoded);
+        canvas.delete_image(pixmap);
+    }
+
+    #[test]
+    fn blend_modes_combine_with_the_background() {
+        let background = [0.8, 0.4, 0.2, 1.];
+        let rectangle = [0.5, 0.5, 1., 1.];
+        let assert_close = |actual: [f32; 4], expected: [f32; 4]| {
+            for (actual, expected) in actual.iter().zip(expected.iter()) {
+                assert!((actual - expected).abs() < 0.001, "{:?} != {:?}", actual, expected);
+            }
+        };
+
+        assert_close(blend(rectangle, background, blend_factors(BlendMode::Normal)), rectangle);
+        assert_close(
+            blend(rectangle, background, blend_factors(BlendMode::Multiply)),
+            [0.4, 0.2, 0.2, 1.],
+        );
+        assert_close(
+            blend(rectangle, background, blend_factors(BlendMode::Screen)),
+            [0.9, 0.7, 1., 1.],
+        );
+        assert_close(
+            blend(rectangle, background, blend_factors(BlendMode::Additive)),
+            [1., 0.9, 1., 1.],
+        );
+
+        // A translucent rectangle, with premultiplied alpha, only multiplies in part.
+        assert_close(
+            blend([0.25, 0.25, 0.5, 0.5], background, blend_factors(BlendMode::Multiply)),
+            [0.6, 0.3, 0.2, 1.],
+        );
+    }
+
+    #[test]
+    fn border_at_fractional_position_is_snapped_to_device_pixels() {
+        // A canvas scaled by 1.5 and translated by a fraction of a pixel, as with a fractional scale factor.
+        let transform = femtovg::Transform2D([1.5, 0., 0., 1.5, 0.3, 0.]);
+        let geometry = Rect::new(euclid::point2(10.2, 20.7), euclid::size2(30.1, 0.5));
+        let snapped = snap_rect_to_device_pixels(geometry, &transform);
+        let (left, top) = transform.transform_point(snapped.min_x(), snapped.min_y());
+        let (right, bottom) = transform.transform_point(snapped.max_x(), snapped.max_y());
+        for value in &[left, top, right, bottom] {
+            assert!((value - value.round()).abs() < 0.001, "{:?} is not snapped", snapped);
+        }
+        assert!((left - 16.).abs() < 0.001);
+        assert!((top - 31.).abs() < 0.001);
+        // The width is rounded on its own and a thin rectangle still covers one pixel.
+        assert!((right - left - 45.).abs() < 0.001);
+        assert!((bottom - top - 1.).abs() < 0.001);
+
+        // A 1 pixel border stays 1 device pixel wide at any scale.
+        assert_eq!(snap_length_to_device_pixels(1., 1.), 1.);
+        assert!((snap_length_to_device_pixels(1. / 1.25, 1.25) * 1.25 - 1.).abs() < 0.001);
+        assert!((snap_length_to_device_pixels(1.5, 1.5) * 1.5 - 2.).abs() < 0.001);
+        assert_eq!(snap_length_to_device_pixels(0., 1.5), 0.);
+    }
+
+    #[test]
+    fn subpixel_text_antialiasing_only_applies_to_axis_aligned_text() {
+        let identity = femtovg::Transform2D::identity();
+        let scaled = femtovg::Transform2D([1.5, 0., 0., 1.5, 10.3, 4.]);
+        let mut rotated = femtovg::Transform2D::identity();
+        rotated.rotate(0.5);
+        let mirrored = femtovg::Transform2D([-1., 0., 0., 1., 100., 0.]);
+
+        assert_eq!(TextAntialiasing::default(), TextAntialiasing::Grayscale);
+        assert_eq!(
+            effective_text_antialiasing(TextAntialiasing::Grayscale, &identity, false),
+            TextAntialiasing::Grayscale
+        );
+        assert_eq!(
+            effective_text_antialiasing(TextAntialiasing::Subpixel, &identity, false),
+            TextAntialiasing::Subpixel
+        );
+        assert_eq!(
+            effective_text_antialiasing(TextAntialiasing::Subpixel, &scaled, false),
+            TextAntialiasing::Subpixel
+        );
+        assert_eq!(
+            effective_text_antialiasing(TextAntialiasing::Subpixel, &rotated, false),
+            TextAntialiasing::Grayscale
+        );
+        assert_eq!(
+            effective_text_antialiasing(TextAntialiasing::Subpixel, &mirrored, false),
+            TextAntialiasing::Grayscale
+        );
+        assert_eq!(
+            effective_text_antialiasing(TextAntialiasing::Subpixel, &identity, true),
+            TextAntialiasing::Grayscale
+        );
+    }
+
+    #[test]
+    fn text_antialiasing_is_set_through_the_backend() {
+        use sixtyfps_corelib::backend::Backend as _;
+        let backend: &'static super::Backend = &super::Backend;
+        backend.set_text_antialiasing(TextAntialiasing::Subpixel);
+        assert_eq!(TEXT_ANTIALIASING.with(|mode| mode.get()), TextAntialiasing::Subpixel);
+        backend.set_text_antialiasing(TextAntialiasing::Grayscale);
+        assert_eq!(TEXT_ANTIALIASING.with(|mode| mode.get()), TextAntialiasing::Grayscale);
+    }
+
+    #[test]
+    fn source_clip_rect_is_clamped_to_the_image() {
+        let rect =
+            |x: f32, y: f32, w: f32, h: f32| Rect::new(euclid::point2(x, y), euclid::size2(w, h));
+        let image_size = euclid::size2(100., 100.);
+        assert_eq!(
+            effective_source_clip_rect(rect(50., 80., 100., 40.), image_size),
+            Some(rect(50., 80., 50., 20.))
+        );
+        assert_eq!(
+            effective_source_clip_rect(rect(-20., -10., 40., 30.), image_size),
+            Some(rect(0., 0., 20., 20.))
+        );
+        assert_eq!(
+            effective_source_clip_rect(rect(-10., -10., 200., 200.), image_size),
+            Some(rect(0., 0., 100., 100.))
+        );
+        assert_eq!(effective_source_clip_rect(rect(100., 0., 20., 20.), image_size), None);
+        // An empty clip rectangle draws the whole image.
+        assert_eq!(
+            effective_source_clip_rect(rect(10., 10., 0., 0.), image_size),
+            Some(rect(0., 0., 100., 100.))
+        );
+        assert_eq!(
+            effective_source_clip_rect(rect(10., 10., 0., 20.), image_size),
+            Some(rect(0., 0., 100., 100.))
+        );
+
+        // The clamped area is drawn into the corresponding part of the target: with the clip rectangle at
+        // (50, 80) of size 100x40 scaled into a 200x80 element, only the top left quarter gets drawn.
+        let transform = image_transform(
+            euclid::size2(100., 40.),
+            euclid::size2(200., 80.),
+            ImageFit::fill,
+            (0.5, 0.5),
+            ImageOrientation::default(),
+        );
+        let sampled = effective_source_clip_rect(rect(50., 80., 100., 40.), image_size).unwrap();
+        let drawn = transform.outer_transformed_rect(&sampled.translate(-euclid::vec2(50., 80.)));
+        assert_eq!(drawn, rect(0., 0., 100., 40.));
+    }
+
+    #[test]
+    fn nine_slice_rects_stretch_edges_and_center() {
+        let rect =
+            |x: f32, y: f32, w: f32, h: f32| Rect::new(euclid::point2(x, y), euclid::size2(w, h));
+        // A 30x30 image with 10 pixel insets drawn into a 100x50 element.
+        let parts = nine_slice_rects(
+            rect(0., 0., 30., 30.),
+            euclid::default::SideOffsets2D::new(10., 10., 10., 10.),
+            euclid::size2(100., 50.),
+            1.,
+        );
+        assert_eq!(
+            parts,
+            vec![
+                (rect(0., 0., 10., 10.), rect(0., 0., 10., 10.)),
+                (rect(10., 0., 10., 10.), rect(10., 0., 80., 10.)),
+                (rect(20., 0., 10., 10.), rect(90., 0., 10., 10.)),
+                (rect(0., 10., 10., 10.), rect(0., 10., 10., 30.)),
+                (rect(10., 10., 10., 10.), rect(10., 10., 80., 30.)),
+                (rect(20., 10., 10., 10.), rect(90., 10., 10., 30.)),
+                (rect(0., 20., 10., 10.), rect(0., 40., 10., 10.)),
+                (rect(10., 20., 10., 10.), rect(10., 40., 80., 10.)),
+                (rect(20., 20., 10., 10.), rect(90., 40., 10., 10.)),
+            ]
+        );
+
+        // The parts are taken from the clipped source area.
+        let parts = nine_slice_rects(
+            rect(5., 5., 30., 30.),
+            euclid::default::SideOffsets2D::new(10., 10., 10., 10.),
+            euclid::size2(100., 50.),
+            1.,
+        );
+        assert_eq!(parts[0], (rect(5., 5., 10., 10.), rect(0., 0., 10., 10.)));
+        assert_eq!(parts[8], (rect(25., 25., 10., 10.), rect(90., 40., 10., 10.)));
+    }
+
+    #[test]
+    fn nine_slice_rects_shrink_insets_that_do_not_fit() {
+        let rect =
+            |x: f32, y: f32, w: f32, h: f32| Rect::new(euclid::point2(x, y), euclid::size2(w, h));
+        // Insets larger than the 20x20 image are scaled down proportionally, leaving no center.
+        let parts = nine_slice_rects(
+            rect(0., 0., 20., 20.),
+            euclid::default::SideOffsets2D::new(30., 10., 10., 30.),
+            euclid::size2(100., 100.),
+            1.,
+        );
+        assert_eq!(
+            parts,
+            vec![
+                (rect(0., 0., 15., 15.), rect(0., 0., 15., 15.)),
+                (rect(15., 0., 5., 15.), rect(95., 0., 5., 15.)),
+                (rect(0., 15., 15., 5.), rect(0., 95., 15., 5.)),
+                (rect(15., 15., 5., 5.), rect(95., 95., 5., 5.)),
+            ]
+        );
+
+        // An element smaller than the corners shrinks them.
+        let parts = nine_slice_rects(
+            rect(0., 0., 30., 30.),
+            euclid::default::SideOffsets2D::new(10., 10., 10., 10.),
+            euclid::size2(10., 40.),
+            1.,
+        );
+        assert_eq!(parts[0], (rect(0., 0., 10., 10.), rect(0., 0., 5., 10.)));
+        assert_eq!(parts.len(), 6);
+    }
+
+    #[test]
+    fn nine_slice_rects_snap_to_device_pixels() {
+        let rect =
+            |x: f32, y: f32, w: f32, h: f32| Rect::new(euclid::point2(x, y), euclid::size2(w, h));
+        // At a scale factor of 1.5, an inset of 3 ends at 4.5 device pixels and is snapped to 5.
+        let parts = nine_slice_rects(
+            rect(0., 0., 9., 9.),
+            euclid::default::SideOffsets2D::new(3., 3., 3., 3.),
+            euclid::size2(20., 20.),
+            1.5,
+        );
+        assert_eq!(parts.len(), 9);
+        for (_, target) in &parts {
+            for value in &[target.min_x(), target.max_x(), target.min_y(), target.max_y()] {
+                let device = value * 1.5;
+                assert!((device - device.round()).abs() < 0.001, "{:?}", target);
+            }
+        }
+        // The parts still tile the whole element.
+        assert_eq!(parts[0].1.max_x(), parts[1].1.min_x());
+        assert_eq!(parts[1].1.max_x(), parts[2].1.min_x());
+        assert_eq!(parts[8].1.max(), euclid::point2(20., 20.));
+    }
+
+    #[test]
+    fn image_transform_rotate_in_quarter_turns() {
+        // Rotating right four times brings the image back to where it started, without rounding errors.
+        for quarter_turns in 0..=4 {
+            let angle = quarter_turns as f32 * 90.;
+            let size = rotated_image_size(euclid::size2(40., 20.), angle);
+            assert_eq!(
+                size,
+                if quarter_turns % 2 == 0 {
+                    euclid::size2(40., 20.)
+                } else {
+                    euclid::size2(20., 40.)
+                }
+            );
+            let transform = image_transform(
+                euclid::size2(40., 20.),
+                size,
+                ImageFit::fill,
+                (0.5, 0.5),
+                ImageOrientation { rotation_angle: angle, ..Default::default() },
+            );
+            let top_left = transform.transform_point(euclid::point2(0., 0.));
+            let expected = [(0., 0.), (20., 0.), (40., 20.), (0., 40.), (0., 0.)][quarter_turns];
+            assert_eq!((top_left.x, top_left.y), expected);
+        }
+    }
+
+    #[test]
+    fn overscan_margin() {
+        assert_eq!(parse_overscan_margin("5"), Some(0.05));
+        assert_eq!(parse_overscan_margin(" 10 % "), Some(0.1));
+        assert_eq!(parse_overscan_margin("-3"), Some(0.));
+        assert_eq!(parse_overscan_margin("wide"), None);
+        assert!(overscan_transform(1920, 1080, 0.).is_none());
+
+        // With 5% on each side, the content of a 1080p window fits into 1728x972 pixels in the middle.
+        let (offset, scale) = overscan_transform(1920, 1080, 0.05).unwrap();
+        assert_eq!(offset, euclid::vec2(96., 54.));
+        assert!((1920. * scale - 1728.).abs() < 0.01);
+        assert!((1080. * scale - 972.).abs() < 0.01);
+    }
+
+    #[test]
+    fn clip_fade_strips_along_edges() {
+        let strips = clip_fade_strips(euclid::rect(10., 20., 100., 50.), 0., 10.);
+        assert_eq!(
+            strips,
+            vec![
+                (euclid::rect(10., 20., 100., 10.), Point::new(10., 20.), Point::new(10., 30.)),
+                (euclid::rect(10., 60., 100., 10.), Point::new(10., 70.), Point::new(10., 60.)),
+            ]
+        );
+
+        // The fades are limited to half of the size, so that they don't overlap.
+        let strips = clip_fade_strips(euclid::rect(0., 0., 20., 50.), 30., 0.);
+        assert_eq!(
+            strips,
+            vec![
+                (euclid::rect(0., 0., 10., 50.), Point::new(0., 0.), Point::new(10., 0.)),
+                (euclid::rect(10., 0., 10., 50.), Point::new(20., 0.), Point::new(10., 0.)),
+            ]
+        );
+        assert!(clip_fade_strips(euclid::rect(0., 0., 20., 50.), 0., 0.).is_empty());
+    }
+
+    #[cfg(all(
+        unix,
+        not(any(
+            target_os = "macos",
+            target_os = "android",
+            target_os = "ios",
+            target_os = "emscripten"
+        )),
+        any(feature = "x11", feature = "wayland")
+    ))]
+    #[test]
+    #[ignore = "needs an X11 or Wayland display"]
+    fn primary_selection_round_trip() {
+        use sixtyfps_corelib::backend::Backend as _;
+        let backend: &'static Backend = &Backend;
+        backend.set_primary_selection_text("selected text".into());
+        assert_eq!(backend.primary_selection_text().as_deref(), Some("selected text"));
+    }
+
+    #[test]
+    fn text_offset_for_x_position_with_astral_plane_characters() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let mut paint = femtovg::Paint::default();
+        paint.set_font(&[font]);
+        paint.set_font_size(20.);
+
+        // The emoji and the musical symbol are four bytes long in UTF-8, and surrogate pairs in UTF-16.
+        let text = "a\u{1F600}b\u{1D11E}c";
+        let metrics = canvas.measure_text(0., 0., text, paint).unwrap();
+        let advances = metrics.glyphs.iter().map(|glyph| glyph.advance_x).collect::<Vec<_>>();
+        assert_eq!(
+            metrics.glyphs.iter().map(|glyph| glyph.byte_index).collect::<Vec<_>>(),
+            vec![0, 1, 5, 6, 10]
+        );
+
+        let emoji_start = advances[0];
+        assert_eq!(byte_offset_for_x_position(&metrics, text, 0.), 0);
+        assert_eq!(byte_offset_for_x_position(&metrics, text, emoji_start + advances[1] * 0.4), 1);
+        assert_eq!(byte_offset_for_x_position(&metrics, text, emoji_start + advances[1] * 0.6), 5);
+        let clef_start = advances[..3].iter().sum::<f32>();
+        assert_eq!(byte_offset_for_x_position(&metrics, text, clef_start + advances[3] * 0.6), 10);
+        assert_eq!(byte_offset_for_x_position(&metrics, text, 1000.), text.len());
+        for x in 0..100 {
+            assert!(text.is_char_boundary(byte_offset_for_x_position(&metrics, text, x as f32)));
+        }
+    }
+
+    #[test]
+    fn text_offset_for_x_position_keeps_clusters_together() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let mut paint = femtovg::Paint::default();
+        paint.set_font(&[font]);
+        paint.set_font_size(20.);
+
+        // An x followed by a combining acute accent, which is shaped as one cluster of two glyphs.
+        let text = "x\u{301}y";
+        let metrics = canvas.measure_text(0., 0., text, paint).unwrap();
+        assert_eq!(
+            metrics.glyphs.iter().map(|glyph| glyph.byte_index).collect::<Vec<_>>(),
+            vec![0, 0, 3]
+        );
+        let cluster_width = metrics.glyphs[0].advance_x + metrics.glyphs[1].advance_x;
+        assert_eq!(byte_offset_for_x_position(&metrics, text, cluster_width * 0.4), 0);
+        assert_eq!(byte_offset_for_x_position(&metrics, text, cluster_width * 0.6), 3);
+    }
+
+    #[test]
+    fn positioned_glyphs_match_the_drawn_text() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let text = text_element();
+        text.x.set(5.);
+        text.y.set(7.);
+        text.width.set(200.);
+        text.height.set(100.);
+        text.horizontal_alignment.set(TextHorizontalAlignment::center);
+        text.vertical_alignment.set(TextVerticalAlignment::bottom);
+
+        let string = "Hi, yo";
+        let runs =
+            layout_text(&mut canvas, Point::new(10., 20.), text.as_ref(), string, paint, None);
+        let glyphs = positioned_glyphs(&mut canvas, &runs, paint);
+        let measured = canvas.measure_text(0., 0., string, paint).unwrap();
+        let origin = Point::new(
+            15. + 100. - measured.width() / 2.,
+            27. + 100. - line_size(&mut canvas, string, paint, None).height,
+        );
+        let expected = canvas.measure_text(origin.x, origin.y, string, paint).unwrap();
+        assert_eq!(glyphs.len(), string.chars().count());
+        for (glyph, expected) in glyphs.iter().zip(&expected.glyphs) {
+            assert_eq!(glyph.byte_index, expected.byte_index);
+            assert_eq!((glyph.x, glyph.y), (expected.x, expected.y));
+            assert_eq!(glyph.advance, expected.advance_x);
+        }
+
+        // The byte indices of the glyphs of later lines and runs are relative to the whole text.
+        text.horizontal_alignment.set(TextHorizontalAlignment::left);
+        text.vertical_alignment.set(TextVerticalAlignment::top);
+        let string = "a\nb\tc";
+        let runs =
+            layout_text(&mut canvas, Point::default(), text.as_ref(), string, paint, Some(50.));
+        let glyphs = positioned_glyphs(&mut canvas, &runs, paint);
+        assert_eq!(glyphs.iter().map(|glyph| glyph.byte_index).collect::<Vec<_>>(), vec![0, 2, 4]);
+        let line_height = canvas.measure_font(paint).unwrap().height();
+        assert!(glyphs[1].y > glyphs[0].y + line_height / 2.);
+        let c = canvas.measure_text(55., 7. + line_height, "c", paint).unwrap();
+        assert_eq!((glyphs[2].x, glyphs[2].y), (c.glyphs[0].x, c.glyphs[0].y));
+
+        // The ellipsis of an elided line stands for the first character that it replaces.
+        text.width.set(canvas.measure_text(0., 0., "ab…", paint).unwrap().width() + 1.);
+        text.overflow.set(TextOverflow::elide);
+        let runs = layout_text(&mut canvas, Point::default(), text.as_ref(), "abcdef", paint, None);
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].text, "ab…");
+        let glyphs = positioned_glyphs(&mut canvas, &runs, paint);
+        assert_eq!(glyphs.iter().map(|glyph| glyph.byte_index).collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn wrapped_text_size_matches_the_drawn_lines() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let line_height = canvas.measure_font(paint).unwrap().height();
+        let string = "The quick brown fox jumps over the lazy dog\nand\tthen runs away";
+        for &width in &[60., 120., 250., 1000.] {
+            let size = text_block_size(&mut canvas, string, Some(width), paint, Some(50.));
+            let text = text_element();
+            text.width.set(width);
+            text.height.set(1000.);
+            text.wrap.set(TextWrap::word_wrap);
+            let runs =
+                layout_text(&mut canvas, Point::default(), text.as_ref(), string, paint, Some(50.));
+            let mut line_tops = runs.iter().map(|run| run.pos.y).collect::<Vec<_>>();
+            line_tops.dedup();
+            let last_line = &string[runs.last().unwrap().byte_offset..];
+            let last_line_height = line_size(&mut canvas, last_line, paint, Some(50.)).height;
+            assert_eq!(
+                size.height,
+                (line_tops.len() - 1) as f32 * line_height + last_line_height,
+                "width {}",
+                width
+            );
+        }
+    }
+
+    // A Text element with the default values of the .60 language, where they differ from the Rust defaults.
+    fn text_element() -> std::pin::Pin<Box<sixtyfps_corelib::items::Text>> {
+        let text = Box::pin(sixtyfps_corelib::items::Text::default());
+        text.max_lines.set(-1);
+        text
+    }
+
+    #[test]
+    fn lines_beyond_max_lines_are_cut_off() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let text = text_element();
+        text.width.set(300.);
+        text.height.set(500.);
+        text.wrap.set(TextWrap::word_wrap);
+        let string = "line one\nline two\nline three\nline four\nline five";
+        let layout = |canvas: &mut femtovg::Canvas<_>| {
+            layout_text(canvas, Point::default(), text.as_ref(), string, paint, None)
+                .into_iter()
+                .map(|run| (run.pos.y, run.text.into_owned()))
+                .collect::<Vec<_>>()
+        };
+
+        let all_lines = layout(&mut canvas);
+        assert_eq!(all_lines.len(), 5);
+
+        // The second line ends with an ellipsis, even though it is not cut off horizontally.
+        text.max_lines.set(2);
+        let lines = layout(&mut canvas);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], all_lines[0]);
+        assert_eq!(lines[1].0, all_lines[1].0);
+        assert!(lines[1].1.ends_with('…'), "{:?}", lines[1]);
+        assert!(all_lines[1].1.starts_with(lines[1].1.trim_end_matches('…')));
+        let elided_width = canvas.measure_text(0., 0., &lines[1].1, paint).unwrap().width();
+        assert!(elided_width <= text.as_ref().width());
+
+        // Vertically centered, the lines that are shown are centered.
+        text.vertical_alignment.set(TextVerticalAlignment::center);
+        let centered = layout(&mut canvas);
+        let shown_height = text_block_size(
+            &mut canvas,
+            "line one\nline two",
+            Some(text.as_ref().width()),
+            paint,
+            None,
+        )
+        .height;
+        assert_eq!(centered[0].0, (500. - shown_height) / 2.);
+        text.vertical_alignment.set(TextVerticalAlignment::top);
+
+        text.max_lines.set(5);
+        assert_eq!(layout(&mut canvas), all_lines);
+        text.max_lines.set(0);
+        assert!(layout(&mut canvas).is_empty());
+    }
+
+    #[test]
+    fn vertical_centering_of_text() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let text = text_element();
+        text.width.set(200.);
+        text.height.set(100.);
+        text.vertical_alignment.set(TextVerticalAlignment::center);
+        let mut baseline = |centering| {
+            text.vertical_centering.set(centering);
+            let runs =
+                layout_text(&mut canvas, Point::default(), text.as_ref(), "Label", paint, None);
+            runs[0].pos.y + canvas.measure_font(paint).unwrap().ascender()
+        };
+        let line_box = baseline(TextVerticalCentering::line_box);
+        let ascent_descent = baseline(TextVerticalCentering::ascent_descent);
+        let cap_height = baseline(TextVerticalCentering::cap_height);
+
+        let metrics = canvas.measure_font(paint).unwrap();
+        let (ascent, descent, _) = vertical_font_metrics(&metrics);
+        // The line box is centered, which is as high as the glyphs of the text.
+        let text_height = canvas.measure_text(0., 0., "Label", paint).unwrap().height();
+        assert_eq!(line_box, 50. - text_height / 2. + ascent);
+        // Centering the ascent and the descent leaves out the line gap, if the font has one.
+        assert!((ascent_descent - (50. + (ascent - descent) / 2.)).abs() < 0.001);
+        // The capital letters of DejaVu Sans are 73% of the font size high, so their middle is in the middle of
+        // the element.
+        let cap = canvas.measure_text(0., 0., "H", paint).unwrap().glyphs[0].bearing_y;
+        assert!((cap - 20. * 0.729).abs() < 0.5, "{}", cap);
+        assert!((cap_height - (50. + cap / 2.)).abs() < 0.001);
+        let expected_difference = (cap + text_height) / 2. - ascent;
+        assert!((cap_height - line_box - expected_difference).abs() < 0.001);
+    }
+
+    #[test]
+    fn text_drawn_with_another_baseline() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let metrics = canvas.measure_font(paint).unwrap();
+        assert_eq!(TextBaseline::default(), TextBaseline::Top);
+        assert_eq!(baseline_shift(&mut canvas, paint, TextBaseline::Top), 0.);
+        assert_eq!(
+            baseline_shift(&mut canvas, paint, TextBaseline::Alphabetic),
+            metrics.ascender()
+        );
+        let middle = baseline_shift(&mut canvas, paint, TextBaseline::Middle);
+        assert!((middle - (metrics.ascender() - metrics.descender()) / 2.).abs() < 0.001);
+
+        // Drawn at the alphabetic baseline, which is lower by the ascent, the glyphs end up where they are with the
+        // top baseline.
+        let glyphs = |canvas: &mut femtovg::Canvas<_>, baseline: TextBaseline| {
+            let mut baseline_paint = paint;
+            baseline_paint.set_text_baseline(baseline.into());
+            let y = 10. + baseline_shift(canvas, paint, baseline);
+            let metrics = canvas.measure_text(5., y, "Hxg", baseline_paint).unwrap();
+            metrics.glyphs.iter().map(|glyph| (glyph.x, glyph.y)).collect::<Vec<_>>()
+        };
+        let top = glyphs(&mut canvas, TextBaseline::Top);
+        assert_eq!(glyphs(&mut canvas, TextBaseline::Alphabetic), top);
+        assert_eq!(glyphs(&mut canvas, TextBaseline::Middle), top);
+    }
+
+    #[test]
+    fn justified_lines_fill_the_width() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let text = text_element();
+        let width =
+            canvas.measure_text(0., 0., "incomprehensibility", paint).unwrap().width() + 10.;
+        text.width.set(width);
+        text.height.set(500.);
+        text.wrap.set(TextWrap::word_wrap);
+        text.horizontal_alignment.set(TextHorizontalAlignment::justify);
+        let string = "a few words to wrap over several lines\nshort line\nincomprehensibility is";
+        let runs = layout_text(&mut canvas, Point::default(), text.as_ref(), string, paint, None);
+        // The right edge of the last run of each line, by the y position of the line.
+        let mut line_ends = Vec::<(f32, f32)>::new();
+        for run in &runs {
+            let end = run.pos.x + canvas.measure_text(0., 0., &run.text, paint).unwrap().width();
+            match line_ends.last_mut() {
+                Some((y, line_end)) if *y == run.pos.y => *line_end = end,
+                _ => line_ends.push((run.pos.y, end)),
+            }
+        }
+        assert!(line_ends.len() >= 5, "{:?}", line_ends);
 
-        let mut canvas = self.shared_data.canvas.borrow_mut();
+        // All lines of the first paragraph but its last one end at the right edge.
+        let (first_paragraph, rest) = line_ends.split_at(line_ends.len() - 3);
+        let (last, lines) = first_paragraph.split_last().unwrap();
+        for (_, line_end) in lines {
+            assert!((line_end - width).abs() < 0.01, "{:?}", line_ends);
+        }
+        // The last line of each paragraph and a line with a single word keep their natural width.
+        let natural_width = |canvas: &mut femtovg::Canvas<_>, line: &str| {
+            canvas.measure_text(0., 0., line, paint).unwrap().width()
+        };
+        let last_line = runs.iter().find(|run| run.pos.y == last.0).unwrap();
+        assert_eq!(last.1, natural_width(&mut canvas, &last_line.text));
+        assert_eq!(rest[0].1, natural_width(&mut canvas, "short line"));
+        assert_eq!(rest[1].1, natural_width(&mut canvas, "incomprehensibility"));
+        assert_eq!(rest[2].1, natural_width(&mut canvas, "is"));
+    }
+
+    #[test]
+    fn highlights_follow_wrapped_lines() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let text = text_element();
+        text.width.set(canvas.measure_text(0., 0., "find the needle", paint).unwrap().width() + 1.);
+        text.height.set(200.);
+        text.wrap.set(TextWrap::word_wrap);
+
+        let string = "find the needle in the haystack of needles";
+        let runs = layout_text(&mut canvas, Point::default(), text.as_ref(), string, paint, None);
+        assert!(runs.len() >= 3);
+        let line_height = canvas.measure_font(paint).unwrap().height();
+        // The pen position and the advance of each glyph, with its byte index.
+        let pens = |metrics: femtovg::TextMetrics| {
+            metrics
+                .glyphs
+                .iter()
+                .map(|glyph| (glyph.byte_index, glyph.x - glyph.bearing_x, glyph.advance_x))
+                .collect::<Vec<_>>()
+        };
 
-        let font_metrics = canvas.measure_font(paint).unwrap();
+        // The first match is within the first line, and aligned with its glyphs.
+        let first = string.find("needle").unwrap();
+        let rects = highlight_rects(&mut canvas, &runs, paint, first..first + 6);
+        let line = pens(canvas.measure_text(0., 0., "find the needle", paint).unwrap());
+        let needle =
+            line.into_iter().filter(|(byte_index, ..)| *byte_index >= first).collect::<Vec<_>>();
+        assert_eq!(rects.len(), 1);
+        assert_eq!(rects[0].min_x(), needle[0].1);
+        assert_eq!(rects[0].max_x(), needle[5].1 + needle[5].2);
+        assert_eq!((rects[0].min_y(), rects[0].height()), (0., line_height));
+
+        // The second, disjoint match is on a later line.
+        let other = string.rfind("needle").unwrap();
+        let other_rects = highlight_rects(&mut canvas, &runs, paint, other..other + 6);
+        assert_eq!(other_rects.len(), 1);
+        assert!(other_rects[0].min_y() >= 2. * line_height);
+        assert!(!other_rects[0].intersects(&rects[0]));
+
+        // A range over the end of a line is highlighted on both lines it spans.
+        let second = string.find("haystack").unwrap();
+        let rects = highlight_rects(&mut canvas, &runs, paint, first + 3..second + 3);
+        assert!(rects.len() >= 2);
+        assert_eq!(rects[0].min_x(), needle[3].1);
+        assert_eq!(rects[1].min_x(), 0.);
+        assert_eq!(rects[1].min_y(), line_height);
+        let last = rects.last().unwrap();
+        let haystack_run = runs.iter().find(|run| run.text.contains("haystack")).unwrap();
+        let haystack_line = pens(
+            canvas
+                .measure_text(haystack_run.pos.x, haystack_run.pos.y, &haystack_run.text, paint)
+                .unwrap(),
+        );
+        let (_, y_x, y_advance) = haystack_line
+            .into_iter()
+            .find(|(byte_index, ..)| haystack_run.byte_offset + byte_index == second + 2)
+            .unwrap();
+        assert_eq!(last.max_x(), y_x + y_advance);
+        assert_eq!(last.min_y(), haystack_run.pos.y);
 
-        let mut y = pos.y
-            + match vertical_alignment {
-                TextVerticalAlignment::top => 0.,
-                TextVerticalAlignment::center => max_height / 2. - text_size.height / 2.,
-                TextVerticalAlignment::bottom => max_height - text_size.height,
-            };
+        // Ranges outside of the text are not highlighted.
+        assert!(highlight_rects(&mut canvas, &runs, paint, 100..110).is_empty());
+    }
 
-        let mut draw_line = |canvas: &mut femtovg::Canvas<_>, to_draw: &str| {
-            let text_metrics = canvas.measure_text(0., 0., to_draw, paint).unwrap();
-            let translate_x = match horizontal_alignment {
-                TextHorizontalAlignment::left => 0.,
-                TextHorizontalAlignment::center => max_width / 2. - text_metrics.width() / 2.,
-                TextHorizontalAlignment::right => max_width - text_metrics.width(),
-            };
-            canvas.fill_text(pos.x + translate_x, y, to_draw, paint).unwrap();
-            y += font_metrics.height();
+    #[test]
+    fn tabs_advance_to_the_tab_stops() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let width = |canvas: &mut femtovg::Canvas<_>, text| {
+            canvas.measure_text(0., 0., text, paint).unwrap().width()
         };
+        let tab_width = 100.;
+        let a = width(&mut canvas, "a");
+        let long = "a long run of text";
+        let long_width = width(&mut canvas, long);
+        assert!(long_width > tab_width && long_width < 2. * tab_width);
+
+        let line = format!("a\t{}\tb\t\tc", long);
+        let (runs, line_width) = tab_runs(&mut canvas, &line, paint, tab_width);
+        assert_eq!(runs, vec![(0., "a"), (100., long), (300., "b"), (400., ""), (500., "c")]);
+        assert_eq!(line_width, 500. + width(&mut canvas, "c"));
+        assert_eq!(line_size(&mut canvas, &line, paint, Some(tab_width)).width, line_width);
+        // Without a tab width, the tabs are left to the font.
+        assert!(line_size(&mut canvas, "a\tb", paint, None).width < tab_width);
+
+        // Tab stops are relative to the start of the line, and a tab that goes beyond the width breaks it.
+        let text = "a\tb\tc\nd\te";
+        assert_eq!(
+            text_lines(&mut canvas, text, Some(150.), paint, Some(tab_width)),
+            vec![(0, "a\tb"), (4, "c"), (6, "d\te")]
+        );
+        assert_eq!(
+            text_lines(&mut canvas, text, Some(500.), paint, Some(tab_width)),
+            vec![(0, "a\tb\tc"), (6, "d\te")]
+        );
+        // Runs are wrapped at word boundaries, after which the tab stops start over.
+        let text = format!("a\t{}\tb", long);
+        let lines =
+            text_lines(&mut canvas, &text, Some(tab_width + a + 40.), paint, Some(tab_width));
+        assert_eq!(lines[0].1, "a\ta");
+        assert_eq!(lines.last().unwrap().1, "text\tb");
+
+        assert_eq!(byte_offset_beyond_x(&mut canvas, &line, paint, Some(tab_width), 50.), Some(1));
+        assert_eq!(
+            byte_offset_beyond_x(&mut canvas, &line, paint, Some(tab_width), 100.5),
+            Some(2)
+        );
+        assert_eq!(byte_offset_beyond_x(&mut canvas, &line, paint, Some(tab_width), 1000.), None);
+        let after_b = Point::new(300. + width(&mut canvas, "b") + 1., 0.);
+        assert_eq!(
+            byte_offset_for_position(&mut canvas, &line, paint, after_b, None, Some(tab_width)),
+            line.find('b').unwrap() + 1
+        );
+    }
 
-        if wrap {
-            let mut start = 0;
-            while start < string.len() {
-                let index = canvas.break_text(max_width, &string[start..], paint).unwrap();
-                if index == 0 {
-                    // FIXME the word is too big to be shown, but we should still break, ideally
-                    break;
-                }
-                let index = start + index;
-                // trim is there to remove the \n
-                draw_line(&mut canvas, string[start..index].trim());
-                start = index;
-            }
-        } else {
-            let elide = text.overflow() == TextOverflow::elide;
-            'lines: for line in string.lines() {
-                let text_metrics = canvas.measure_text(0., 0., line, paint).unwrap();
-                if text_metrics.width() > max_width {
-                    let w = max_width
-                        - if elide {
-                            canvas.measure_text(0., 0., "…", paint).unwrap().width()
-                        } else {
-                            0.
-                        };
-                    let mut current_x = 0.;
-                    for glyph in text_metrics.glyphs {
-                        current_x += glyph.advance_x;
-                        if current_x >= w {
-                            let txt = &line[..glyph.byte_index];
-                            if elide {
-                                let elided = format!("{}…", txt);
-                                draw_line(&mut canvas, &elided);
-                            } else {
-                                draw_line(&mut canvas, txt);
-                            }
-                            continue 'lines;
-                        }
-                    }
+    #[test]
+    fn words_wider_than_a_line_are_broken_between_characters() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let word = "Donaudampfschifffahrtsgesellschaftskapitän";
+        let max_width = canvas.measure_text(0., 0., "Donau", paint).unwrap().width() + 1.;
+
+        let text = format!("A {} sails", word);
+        let lines = text_lines(&mut canvas, &text, Some(max_width), paint, None);
+        assert!(lines.len() > 3);
+        assert_eq!(lines[0], (0, "A"));
+        assert_eq!(lines[1], (2, "Donau"));
+        // The rest of the word shares its line with the following words, if they fit.
+        assert!(lines.last().unwrap().1.ends_with("sails"));
+        let joined = lines.iter().map(|(_, line)| *line).collect::<String>();
+        assert_eq!(joined.replace(' ', ""), text.replace(' ', ""));
+        for (_, line) in &lines {
+            assert!(canvas.measure_text(0., 0., line, paint).unwrap().width() <= max_width);
+        }
+
+        // A line that is narrower than any character still shows one character per line.
+        let lines = text_lines(&mut canvas, "äbc", Some(1.), paint, None);
+        assert_eq!(lines, vec![(0, "ä"), (2, "b"), (3, "c")]);
+
+        // Words after tabs are broken the same way.
+        let text = format!("\t{}", word);
+        let lines = text_lines(&mut canvas, &text, Some(max_width), paint, Some(max_width / 2.));
+        assert!(lines.len() > 3);
+        // Like a space, the tab at which the line breaks is left out.
+        assert_eq!(lines.iter().map(|(_, line)| *line).collect::<String>(), word);
+    }
+
+    #[test]
+    fn wrapped_lines_keep_grapheme_clusters_intact() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        // Combining diacritics, an emoji ZWJ sequence, a flag and an emoji with a skin tone modifier.
+        let text =
+            "Cafe\u{301} nai\u{308}ve\u{301}\u{327} \u{1F469}\u{200D}\u{1F469}\u{200D}\u{1F467} \
+                    \u{1F1E9}\u{1F1EA}\u{1F44B}\u{1F3FD}a\u{301}b\u{301}c\u{301}";
+        let boundaries = text
+            .grapheme_indices(true)
+            .map(|(index, _)| index)
+            .chain(std::iter::once(text.len()))
+            .collect::<Vec<_>>();
+        for max_width in [1., 10., 25., 40., 60., 100., 1000.] {
+            for tab_width in [None, Some(30.)] {
+                for (start, line) in
+                    text_lines(&mut canvas, text, Some(max_width), paint, tab_width)
+                {
+                    assert!(boundaries.contains(&start), "{:?} starts within a cluster", line);
+                    assert!(
+                        boundaries.contains(&(start + line.len())),
+                        "{:?} ends within a cluster",
+                        line
+                    );
                 }
-                draw_line(&mut canvas, line);
             }
         }
+
+        assert_eq!(grapheme_boundary_before("e\u{301}x", 2), 0);
+        assert_eq!(grapheme_boundary_before("e\u{301}x", 3), 3);
+        assert_eq!(grapheme_boundary_before("\u{1F469}\u{200D}\u{1F467}", 5), 0);
+        assert_eq!(grapheme_boundary_before("ab", 5), 2);
     }
 
-    fn draw_text_input(
-        &mut self,
-        pos: Point,
-        text_input: std::pin::Pin<&sixtyfps_corelib::items::TextInput>,
-    ) {
-        let width = text_input.width();
-        let height = text_input.height();
-        if width <= 0. || height <= 0. {
-            return;
-        }
+    #[test]
+    fn text_offset_for_position_in_wrapped_text() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let mut paint = femtovg::Paint::default();
+        paint.set_font(&[font]);
+        paint.set_font_size(20.);
+        paint.set_text_baseline(femtovg::Baseline::Top);
 
-        let pos = pos + euclid::Vector2D::new(text_input.x(), text_input.y());
-        let font = self.shared_data.loaded_fonts.borrow_mut().font(
-            &self.shared_data.canvas,
-            text_input.font_request(),
-            self.scale_factor,
+        let text = "Hello World";
+        let hello_width = canvas.measure_text(0., 0., "Hello ", paint).unwrap().width();
+        let wrap_width = Some(hello_width + 5.);
+        assert_eq!(
+            text_lines(&mut canvas, text, wrap_width, paint, None),
+            vec![(0, "Hello"), (6, "World")]
         );
+        let line_height = canvas.measure_font(paint).unwrap().height();
+        let offset = |canvas: &mut femtovg::Canvas<_>, x: f32, y: f32| {
+            byte_offset_for_position(canvas, text, paint, Point::new(x, y), wrap_width, None)
+        };
 
-        let metrics = self.draw_text_impl(
-            pos,
-            width,
-            height,
-            &text_input.text(),
-            text_input.font_request(),
-            text_input.color(),
-            text_input.horizontal_alignment(),
-            text_input.vertical_alignment(),
+        // Clicks on the first line
+        assert_eq!(offset(&mut canvas, 0., line_height / 2.), 0);
+        assert_eq!(offset(&mut canvas, 1000., line_height / 2.), 5);
+        let he_width = canvas.measure_text(0., 0., "He", paint).unwrap().width();
+        assert_eq!(offset(&mut canvas, he_width, line_height / 2.), 2);
+        // Clicks on the second line
+        assert_eq!(offset(&mut canvas, 0., line_height * 1.5), 6);
+        let wo_width = canvas.measure_text(0., 0., "Wo", paint).unwrap().width();
+        assert_eq!(offset(&mut canvas, wo_width, line_height * 1.5), 8);
+        assert_eq!(offset(&mut canvas, 1000., line_height * 1.5), text.len());
+        // Clicks above or below the text go to the first or last line
+        assert_eq!(offset(&mut canvas, he_width, -10.), 2);
+        assert_eq!(offset(&mut canvas, wo_width, line_height * 5.), 8);
+
+        // Without wrapping, the text is all in one line
+        assert_eq!(
+            byte_offset_for_position(
+                &mut canvas,
+                text,
+                paint,
+                Point::new(0., line_height * 1.5),
+                None,
+                None
+            ),
+            0
         );
+    }
 
-        // This way of drawing selected text isn't quite 100% correct. Due to femtovg only being able to
-        // have a simple rectangular selection - due to the use of the scissor clip - the selected text is
-        // drawn *over* the unselected text. If the selection background color is transparent, then that means
-        // that glyphs are blended twice, which may lead to artifacts.
-        // It would be better to draw the selected text and non-selected text without overlap.
-        if text_input.has_selection() {
-            let (anchor_pos, cursor_pos) = text_input.selection_anchor_and_cursor();
-            let mut selection_start_x = 0.;
-            let mut selection_end_x = 0.;
-            for glyph in &metrics.glyphs {
-                if glyph.byte_index == anchor_pos {
-                    selection_start_x = glyph.x;
-                }
-                if glyph.byte_index == (cursor_pos as i32 - 1).max(0) as usize {
-                    selection_end_x = glyph.x + glyph.advance_x;
-                }
-            }
+    #[test]
+    fn right_to_left_text_is_laid_out_in_visual_order() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let mut paint = femtovg::Paint::default();
+        paint.set_font(&[font]);
+        paint.set_font_size(20.);
+
+        // "Shalom" in Hebrew, each letter is two bytes long in UTF-8. The first letter is drawn rightmost.
+        let text = "\u{5E9}\u{5DC}\u{5D5}\u{5DD}";
+        let metrics = canvas.measure_text(0., 0., text, paint).unwrap();
+        assert_eq!(
+            metrics.glyphs.iter().map(|glyph| glyph.byte_index).collect::<Vec<_>>(),
+            vec![6, 4, 2, 0]
+        );
+        assert!(metrics.glyphs.windows(2).all(|pair| pair[0].x < pair[1].x));
 
-            let selection_rect = Rect::new(
-                [selection_start_x, pos.y].into(),
-                [selection_end_x - selection_start_x, font.height()].into(),
-            );
+        // The cursor at the start of the text is at its right edge, and at the end of the text at its left edge.
+        let first = metrics.glyphs.last().unwrap();
+        assert_eq!(
+            cursor_x_for_byte_offset(&metrics, text, 0),
+            first.x - first.bearing_x + first.advance_x
+        );
+        assert_eq!(cursor_x_for_byte_offset(&metrics, text, 2), first.x - first.bearing_x);
+        assert_eq!(cursor_x_for_byte_offset(&metrics, text, text.len()), metrics.glyphs[0].x);
+
+        assert_eq!(byte_offset_for_x_position(&metrics, text, 1000.), 0);
+        assert_eq!(byte_offset_for_x_position(&metrics, text, -10.), text.len());
+        let first_center = first.x + first.advance_x / 2.;
+        assert_eq!(byte_offset_for_x_position(&metrics, text, first_center + 1.), 0);
+        assert_eq!(byte_offset_for_x_position(&metrics, text, first_center - 1.), 2);
+    }
 
-            {
-                let mut canvas = self.shared_data.canvas.borrow_mut();
-                canvas.fill_path(
-                    &mut rect_to_path(selection_rect),
-                    femtovg::Paint::color(text_input.selection_background_color().into()),
-                );
+    #[test]
+    fn ink_bounds_of_text() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let mut paint = femtovg::Paint::default();
+        paint.set_font(&[font]);
+        paint.set_font_size(100.);
+        paint.set_text_baseline(femtovg::Baseline::Top);
+        let baseline = canvas.measure_font(paint).unwrap().ascender().round();
+
+        // A lowercase x sits on the baseline, below the top of the line, and within its advance.
+        let metrics = canvas.measure_text(0., 0., "x", paint).unwrap();
+        let bounds = ink_bounds(&metrics);
+        assert!(bounds.min_y() > 0.);
+        assert!((bounds.max_y() - baseline).abs() < 0.5);
+        assert!(bounds.min_x() >= 0. && bounds.max_x() <= metrics.width());
+        assert!(bounds.height() < metrics.height());
+
+        // The descender of a g extends below the baseline, and the bounds cover all glyphs.
+        let metrics = canvas.measure_text(0., 0., "xg", paint).unwrap();
+        let bounds = ink_bounds(&metrics);
+        assert!(bounds.max_y() > baseline + 10.);
+        assert!(bounds.max_x() > metrics.width() / 2.);
+
+        // Spaces have no outline.
+        let metrics = canvas.measure_text(0., 0., "  ", paint).unwrap();
+        assert!(ink_bounds(&metrics).is_empty());
+    }
 
-                canvas.save();
-                canvas.intersect_scissor(
-                    selection_rect.min_x(),
-                    selection_rect.min_y(),
-                    selection_rect.width(),
-                    selection_rect.height(),
-                );
-            }
+    #[test]
+    fn italic_and_upright_fonts_are_cached_separately() {
+        isolate_application_fonts();
+        register_application_font_from_memory(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let canvas = RefCell::new(femtovg::Canvas::new(femtovg::renderer::Void).unwrap());
+        let mut cache = FontCache::default();
+        let request = |style| FontRequest {
+            family: "DejaVu Sans".into(),
+            weight: Some(DEFAULT_FONT_WEIGHT),
+            pixel_size: Some(DEFAULT_FONT_SIZE),
+            style,
+            numeral_style: Default::default(),
+            language: Default::default(),
+            tab_width: None,
+        };
 
-            self.draw_text_impl(
-                pos,
-                text_input.width(),
-                text_input.height(),
-                &text_input.text(),
-                text_input.font_request(),
-                text_input.selection_foreground_color().into(),
-                text_input.horizontal_alignment(),
-                text_input.vertical_alignment(),
-            );
+        let upright = cache.load_single_font(&canvas, &request(FontStyle::normal));
+        let italic = cache.load_single_font(&canvas, &request(FontStyle::italic));
+        assert_ne!(upright.id, italic.id);
+        assert_eq!(cache.load_single_font(&canvas, &request(FontStyle::italic)).id, italic.id);
+        assert_eq!(cache.load_single_font(&canvas, &request(FontStyle::normal)).id, upright.id);
+        // DejaVu Sans comes without its oblique face, so the upright one is used and needs slanting.
+        assert!(!upright.italic);
+        assert!(!italic.italic);
+    }
 
-            self.shared_data.canvas.borrow_mut().restore();
+    #[test]
+    fn resolved_fonts_are_cached_until_a_font_is_registered() {
+        isolate_application_fonts();
+        register_application_font_from_memory(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let canvas = RefCell::new(femtovg::Canvas::new(femtovg::renderer::Void).unwrap());
+        let mut cache = FontCache::default();
+        let request = |pixel_size| FontRequest {
+            family: "DejaVu Sans".into(),
+            weight: Some(DEFAULT_FONT_WEIGHT),
+            pixel_size: Some(pixel_size),
+            style: Default::default(),
+            numeral_style: Default::default(),
+            language: Default::default(),
+            tab_width: None,
         };
 
-        let cursor_index = text_input.cursor_position();
-        if cursor_index >= 0 && text_input.cursor_visible() {
-            let cursor_x = metrics
-                .glyphs
-                .iter()
-                .find_map(|glyph| {
-                    if glyph.byte_index == cursor_index as usize {
-                        Some(glyph.x)
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or_else(|| pos.x + metrics.width());
-            let mut cursor_rect = femtovg::Path::new();
-            cursor_rect.rect(
-                cursor_x,
-                pos.y,
-                text_input.text_cursor_width() * self.scale_factor,
-                font.height(),
+        // The fallbacks are determined once, for all sizes.
+        let resolved = cache.resolved_font(&canvas, &request(12.));
+        assert!(Rc::ptr_eq(&cache.resolved_font(&canvas, &request(12.)), &resolved));
+        assert!(Rc::ptr_eq(&cache.resolved_font(&canvas, &request(30.)), &resolved));
+
+        // A registered font may be a new fallback.
+        register_application_font_from_memory(include_bytes!(
+            "test-data/fonts/NotoEmoji-Regular.ttf"
+        ))
+        .unwrap();
+        let updated = cache.resolved_font(&canvas, &request(12.));
+        assert!(!Rc::ptr_eq(&updated, &resolved));
+        assert!(updated.fonts.len() > resolved.fonts.len());
+    }
+
+    #[test]
+    fn fonts_registered_on_another_thread_before_rendering() {
+        let application_fonts = isolate_application_fonts();
+        std::thread::spawn(move || {
+            use_application_fonts(application_fonts);
+            register_application_font_from_memory(include_bytes!(
+                "test-data/fonts/Cantarell-VF.otf"
+            ))
+            .unwrap()
+        })
+        .join()
+        .unwrap();
+
+        let canvas = RefCell::new(femtovg::Canvas::new(femtovg::renderer::Void).unwrap());
+        let mut cache = FontCache::default();
+        let request = FontRequest {
+            family: "Cantarell".into(),
+            weight: Some(DEFAULT_FONT_WEIGHT),
+            pixel_size: Some(DEFAULT_FONT_SIZE),
+            style: Default::default(),
+            numeral_style: Default::default(),
+            language: Default::default(),
+            tab_width: None,
+        };
+        let font = cache.load_single_font(&canvas, &request);
+        assert_eq!(font.family, "Cantarell");
+        assert!(cache.substitutions().is_empty());
+
+        let resolved = cache.resolved_font(&canvas, &request);
+        let mut paint = femtovg::Paint::default();
+        paint.set_font(&resolved.fonts);
+        paint.set_font_size(DEFAULT_FONT_SIZE);
+        let word_width =
+            canvas.borrow_mut().measure_text(0., 0., "Cantarell", paint).unwrap().width();
+        assert!(word_width > 0.);
+        let lines = text_lines(
+            &mut canvas.borrow_mut(),
+            "Cantarell text",
+            Some(word_width + 1.),
+            paint,
+            None,
+        );
+        assert_eq!(lines, vec![(0, "Cantarell"), (10, "text")]);
+    }
+
+    #[test]
+    fn registered_font_families_are_available() {
+        isolate_application_fonts();
+        register_application_font_from_memory(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        register_application_font_from_memory(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let families = available_font_families();
+        assert_eq!(families.iter().filter(|family| *family == "DejaVu Sans").count(), 1);
+        assert!(families.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn missing_font_families_are_reported_as_substituted() {
+        isolate_application_fonts();
+        register_application_font_from_memory(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let canvas = RefCell::new(femtovg::Canvas::new(femtovg::renderer::Void).unwrap());
+        let mut cache = FontCache::default();
+        let request = |family: &str| FontRequest {
+            family: family.into(),
+            weight: Some(DEFAULT_FONT_WEIGHT),
+            pixel_size: Some(DEFAULT_FONT_SIZE),
+            style: Default::default(),
+            numeral_style: Default::default(),
+            language: Default::default(),
+            tab_width: None,
+        };
+
+        cache.load_single_font(&canvas, &request("DejaVu Sans"));
+        cache.load_single_font(&canvas, &request(""));
+        cache.load_single_font(&canvas, &request("sans-serif"));
+        assert!(cache.substitutions().is_empty());
+
+        let substitute = cache.load_single_font(&canvas, &request("No Such Font Family"));
+        cache.load_single_font(&canvas, &request("No Such Font Family"));
+        let substitutions = cache.substitutions();
+        assert_eq!(substitutions.len(), 1);
+        assert_eq!(substitutions[0].0, "No Such Font Family");
+        assert_eq!(substitutions[0].1, substitute.family.as_str());
+        assert!(!substitute.family.is_empty());
+    }
+
+    #[test]
+    fn fonts_registered_from_files() {
+        isolate_application_fonts();
+        let canvas = RefCell::new(femtovg::Canvas::new(femtovg::renderer::Void).unwrap());
+        let request = FontRequest {
+            family: "Cantarell".into(),
+            weight: Some(DEFAULT_FONT_WEIGHT),
+            pixel_size: Some(DEFAULT_FONT_SIZE),
+            style: Default::default(),
+            numeral_style: Default::default(),
+            language: Default::default(),
+            tab_width: None,
+        };
+        assert!(try_load_app_font(&canvas, &request).is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Cantarell-VF.otf");
+        std::fs::write(&path, include_bytes!("test-data/fonts/Cantarell-VF.otf")).unwrap();
+        register_application_font_from_path(&path).unwrap();
+
+        let font = try_load_app_font(&canvas, &request).unwrap();
+        let mut paint = femtovg::Paint::default();
+        paint.set_font(&[font.id]);
+        paint.set_font_size(DEFAULT_FONT_SIZE);
+        assert!(canvas.borrow_mut().measure_text(0., 0., "Cantarell", paint).unwrap().width() > 0.);
+
+        assert!(register_application_font_from_path(&dir.path().join("missing.ttf")).is_err());
+        let text_file = dir.path().join("text.ttf");
+        std::fs::write(&text_file, "not a font").unwrap();
+        assert!(register_application_font_from_path(&text_file).is_err());
+    }
+
+    #[test]
+    fn synthetic_bold_for_fonts_without_bold_faces() {
+        isolate_application_fonts();
+        register_application_font_from_memory(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let canvas = RefCell::new(femtovg::Canvas::new(femtovg::renderer::Void).unwrap());
+        let mut cache = FontCache::default();
+        let mut load = |weight| {
+            cache.load_single_font(
+                &canvas,
+                &FontRequest {
+                    family: "DejaVu Sans".into(),
+                    weight: Some(weight),
+                    pixel_size: Some(DEFAULT_FONT_SIZE),
+                    style: Default::default(),
+                    numeral_style: Default::default(),
+                    language: Default::default(),
+                    tab_width: None,
+                },
+            )
+        };
+        let regular = load(400);
+        let bold = load(700);
+        assert_eq!(bold.weight, 400);
+        assert!(needs_synthetic_bold(700, bold.weight));
+        assert!(!needs_synthetic_bold(400, regular.weight));
+        assert!(!needs_synthetic_bold(500, bold.weight));
+        assert!(!needs_synthetic_bold(700, 700));
+
+        let width = |font: &LoadedFont, synthetic_bold| {
+            let paint = text_paint(&[font.id], 20., synthetic_bold);
+            canvas.borrow_mut().measure_text(0., 0., "Emboldened", paint).unwrap().width()
+        };
+        let regular_width = width(&regular, false);
+        let bold_width = width(&bold, true);
+        assert!(
+            bold_width > regular_width + 5.,
+            "{} is not wider than {}",
+            bold_width,
+            regular_width
+        );
+    }
+
+    #[test]
+    fn weights_of_variable_fonts() {
+        isolate_application_fonts();
+        register_application_font_from_memory(include_bytes!("test-data/fonts/Cantarell-VF.otf"))
+            .unwrap();
+        let canvas = RefCell::new(femtovg::Canvas::new(femtovg::renderer::Void).unwrap());
+        let mut cache = FontCache::default();
+        let mut advance = |weight| {
+            let font = cache.load_single_font(
+                &canvas,
+                &FontRequest {
+                    family: "Cantarell".into(),
+                    weight: Some(weight),
+                    pixel_size: Some(DEFAULT_FONT_SIZE),
+                    style: Default::default(),
+                    numeral_style: Default::default(),
+                    language: Default::default(),
+                    tab_width: None,
+                },
             );
-            self.shared_data
-                .canvas
-                .borrow_mut()
-                .fill_path(&mut cursor_rect, femtovg::Paint::color(text_input.color().into()));
+            let mut paint = femtovg::Paint::default();
+            paint.set_font(&[font.id]);
+            paint.set_font_size(50.);
+            canvas.borrow_mut().measure_text(0., 0., "Variable", paint).unwrap().width()
+        };
+
+        let light = advance(350);
+        let bold = advance(650);
+        assert!(bold > light + 1., "{} is not wider than {}", bold, light);
+    }
+
+    #[test]
+    fn emoji_fall_back_to_the_emoji_font() {
+        isolate_application_fonts();
+        register_application_font_from_memory(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        register_application_font_from_memory(include_bytes!(
+            "test-data/fonts/NotoEmoji-Regular.ttf"
+        ))
+        .unwrap();
+        let canvas = RefCell::new(femtovg::Canvas::new(femtovg::renderer::Void).unwrap());
+        let mut cache = FontCache::default();
+        let chain = cache.font_chain(
+            &canvas,
+            &FontRequest {
+                family: "DejaVu Sans".into(),
+                weight: Some(DEFAULT_FONT_WEIGHT),
+                pixel_size: Some(DEFAULT_FONT_SIZE),
+                style: Default::default(),
+                numeral_style: Default::default(),
+                language: Default::default(),
+                tab_width: None,
+            },
+        );
+        let primary_font = chain[0].id;
+        let emoji_font = chain.last().unwrap().id;
+        assert_ne!(primary_font, emoji_font);
+
+        let mut paint = femtovg::Paint::default();
+        paint.set_font(&chain.iter().map(|font| font.id).collect::<Vec<_>>());
+        paint.set_font_size(DEFAULT_FONT_SIZE);
+        let metrics = canvas.borrow_mut().measure_text(0., 0., "a\u{1F600}", paint).unwrap();
+        let fonts = metrics.glyphs.iter().map(|glyph| glyph.font_id).collect::<Vec<_>>();
+        assert_eq!(fonts, vec![primary_font, emoji_font]);
+        assert_ne!(metrics.glyphs[1].codepoint, 0);
+    }
+
+    #[test]
+    fn vertical_metrics_of_font() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let mut paint = femtovg::Paint::default();
+        paint.set_font(&[font]);
+        paint.set_font_size(20.);
+        let metrics = canvas.measure_font(paint).unwrap();
+
+        let (ascent, descent, line_gap) = vertical_font_metrics(&metrics);
+        // DejaVu Sans has an ascender of 1901 and a descender of -483 font units, with 2048 units per em.
+        assert!((ascent - 1901. / 2048. * 20.).abs() < 0.01);
+        assert!((descent - 483. / 2048. * 20.).abs() < 0.01);
+        assert!(line_gap >= 0.);
+        assert!((ascent + descent + line_gap - metrics.height()).abs() <= 0.5);
+    }
+
+    #[test]
+    fn unavailable_clipboard() {
+        use sixtyfps_corelib::backend::Backend as _;
+        CLIPBOARD.with(|clipboard| {
+            *clipboard.borrow_mut() = create_clipboard(|| Err("no display".into()));
+        });
+        let backend: &'static Backend = &Backend;
+        backend.set_clipboard_text("copied".into());
+        assert_eq!(backend.clipboard_text(), None);
+    }
+
+    #[test]
+    fn image_from_rgba_pixels() {
+        let rgba = [0xff, 0, 0, 0xff, 0, 0xff, 0, 0x80, 0, 0, 0xff, 0, 0x10, 0x20, 0x30, 0x40];
+        let resource = Resource::from_rgba8(2, 2, &rgba).unwrap();
+        let image = decode_image_resource(&resource).unwrap();
+        assert_eq!(image.size(), Size::new(2., 2.));
+        match &*image.data.borrow() {
+            ImageData::CPUSide { decoded_image } => {
+                assert_eq!(decoded_image.to_rgba8().into_raw(), rgba.to_vec())
+            }
+            _ => panic!("the image should be on the CPU side"),
         }
+
+        assert!(Resource::from_rgba8(3, 2, &rgba).is_none());
+        let mismatched =
+            Resource::EmbeddedRgbaImage { width: 3, height: 2, data: [0u32; 4].into() };
+        assert!(decode_image_resource(&mismatched).is_none());
     }
 
-    fn draw_path(&mut self, pos: Point, path: std::pin::Pin<&sixtyfps_corelib::items::Path>) {
-        let elements = path.elements();
-        if matches!(elements, sixtyfps_corelib::PathData::None) {
+    #[cfg(all(not(target_arch = "wasm32"), feature = "clipboard-image"))]
+    #[test]
+    fn clipboard_image_alpha() {
+        let translucent = vec![1, 2, 3, 0, 4, 5, 6, 0x80];
+        assert_eq!(opaque_if_without_alpha(translucent.clone(), 2, 1), Some(translucent));
+        assert_eq!(
+            opaque_if_without_alpha(vec![1, 2, 3, 0, 4, 5, 6, 0], 2, 1),
+            Some(vec![1, 2, 3, 0xff, 4, 5, 6, 0xff])
+        );
+        assert_eq!(opaque_if_without_alpha(vec![1, 2, 3, 0], 2, 1), None);
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), feature = "clipboard-image"))]
+    #[test]
+    fn clipboard_image_round_trip() {
+        use sixtyfps_corelib::backend::Backend as _;
+        if IMAGE_CLIPBOARD.with(|clipboard| clipboard.borrow().is_none()) {
+            // No clipboard to test with.
             return;
         }
-        let mut fpath = femtovg::Path::new();
-        for x in elements.iter_fitted(path.width(), path.height()).iter() {
-            match x {
-                lyon_path::Event::Begin { at } => {
-                    fpath.move_to(at.x, at.y);
-                }
-                lyon_path::Event::Line { from: _, to } => {
-                    fpath.line_to(to.x, to.y);
-                }
-                lyon_path::Event::Quadratic { from: _, ctrl, to } => {
-                    fpath.quad_to(ctrl.x, ctrl.y, to.x, to.y);
-                }
+        let backend: &'static Backend = &Backend;
+        let rgba = [0xff, 0, 0, 0xff, 0, 0xff, 0, 0xff];
+        backend.set_clipboard_image(&rgba, 2, 1);
+        assert_eq!(backend.clipboard_image(), Some((rgba.to_vec(), 2, 1)));
+    }
 
-                lyon_path::Event::Cubic { from: _, ctrl1, ctrl2, to } => {
-                    fpath.bezier_to(ctrl1.x, ctrl1.y, ctrl2.x, ctrl2.y, to.x, to.y);
-                }
-                lyon_path::Event::End { last: _, first: _, close } => {
-                    if close {
-                        fpath.close()
-                    }
-                }
-            }
-        }
+    #[test]
+    fn least_recently_drawn_textures_are_evicted() {
+        // Three 1000 byte textures drawn in frames 3, 1 and 2.
+        let usage = [(1000, 3), (1000, 1), (1000, 2)];
+        assert_eq!(textures_to_evict(&usage, 3000, 4), Vec::<usize>::new());
+        assert_eq!(textures_to_evict(&usage, 2500, 4), vec![1]);
+        assert_eq!(textures_to_evict(&usage, 1000, 4), vec![1, 2]);
+        // Textures drawn in the current frame stay, even when over budget.
+        assert_eq!(textures_to_evict(&usage, 0, 3), vec![1, 2]);
+    }
 
-        let fill_paint = femtovg::Paint::color(path.fill_color().into());
-        let mut border_paint = femtovg::Paint::color(path.stroke_color().into());
-        border_paint.set_line_width(path.stroke_width());
+    #[test]
+    fn pinned_images_survive_pruning() {
+        let logo_key = ImageCacheKey::Path("/logo.png".into());
+        let photo_key = ImageCacheKey::Path("/photo.png".into());
+        let logo = Rc::new(CachedImage::new_on_cpu(image::DynamicImage::new_rgba8(1, 1)));
+        let photo = Rc::new(CachedImage::new_on_cpu(image::DynamicImage::new_rgba8(1, 1)));
+
+        let mut image_cache = HashMap::new();
+        let mut pinned_images = HashMap::new();
+        image_cache.insert(logo_key.clone(), Rc::downgrade(&logo));
+        image_cache.insert(photo_key.clone(), Rc::downgrade(&photo));
+        PINNED_IMAGES.with(|pinned| pinned.borrow_mut().insert(logo_key.clone()));
+
+        // The scene showing both images is rendered, then replaced by one that shows neither.
+        prune_image_cache(&mut image_cache, &mut pinned_images);
+        let logo_weak = Rc::downgrade(&logo);
+        drop((logo, photo));
+        prune_image_cache(&mut image_cache, &mut pinned_images);
+        assert!(image_cache[&logo_key].upgrade().is_some());
+        assert!(!image_cache.contains_key(&photo_key));
+
+        PINNED_IMAGES.with(|pinned| pinned.borrow_mut().remove(&logo_key));
+        prune_image_cache(&mut image_cache, &mut pinned_images);
+        assert!(image_cache.is_empty());
+        assert!(logo_weak.upgrade().is_none());
+    }
 
-        self.shared_data.canvas.borrow_mut().save_with(|canvas| {
-            canvas.translate(pos.x + path.x(), pos.y + path.y());
-            canvas.fill_path(&mut fpath, fill_paint);
-            canvas.stroke_path(&mut fpath, border_paint);
-        })
+    #[test]
+    fn context_creation_falls_back() {
+        let attempts = CONTEXT_ATTEMPTS.iter().map(|attempt| (attempt.description, attempt));
+        let mut tried = Vec::new();
+        let created = create_with_fallbacks(attempts, |attempt| {
+            tried.push(attempt.description);
+            if attempt.srgb {
+                Err(glutin::CreationError::NoAvailablePixelFormat.into())
+            } else {
+                Ok(attempt.description)
+            }
+        });
+        assert_eq!(created.unwrap(), CONTEXT_ATTEMPTS[1].description);
+        assert_eq!(tried.len(), 2);
     }
 
-    fn draw_box_shadow(
-        &mut self,
-        pos: Point,
-        box_shadow: std::pin::Pin<&sixtyfps_corelib::items::BoxShadow>,
-    ) {
-        // TODO: cache path in item to avoid re-tesselation
+    #[test]
+    fn context_creation_failure_is_an_error() {
+        let attempts = CONTEXT_ATTEMPTS.iter().map(|attempt| (attempt.description, attempt));
+        let result = create_with_fallbacks(attempts, |_| -> Result<(), _> {
+            Err(glutin::CreationError::OpenGlVersionNotSupported.into())
+        });
+        let err = result.unwrap_err();
+        assert_eq!(err.failed_attempts.len(), CONTEXT_ATTEMPTS.len());
+        let message = err.to_string();
+        assert!(message.starts_with("Could not create an OpenGL context for the window"));
+        for attempt in CONTEXT_ATTEMPTS {
+            assert!(message.contains(attempt.description));
+        }
+    }
 
-        let blur = box_shadow.blur();
+    #[test]
+    fn images_reloaded_after_context_loss() {
+        let mut cache = RenderingCache::<Option<ItemGraphicsCacheEntry>>::default();
+        let item_cache = CachedRenderingData::default();
+        let loads = std::cell::Cell::new(0);
+        let load = || {
+            loads.set(loads.get() + 1);
+            Some(ItemGraphicsCacheEntry::Image(Rc::new(CachedImage::new_on_cpu(
+                image::DynamicImage::new_rgba8(2, 1),
+            ))))
+        };
 
-        let shadow_outer_rect: euclid::Rect<f32, euclid::UnknownUnit> = euclid::rect(
-            box_shadow.x() + box_shadow.offset_x() - blur / 2.,
-            box_shadow.y() + box_shadow.offset_y() - blur / 2.,
-            box_shadow.width() + blur,
-            box_shadow.height() + blur,
-        );
+        let image = item_cache.ensure_up_to_date(&mut cache, load).unwrap().as_image().clone();
+        assert!(Rc::ptr_eq(
+            item_cache.ensure_up_to_date(&mut cache, load).unwrap().as_image(),
+            &image
+        ));
+        assert_eq!(loads.get(), 1);
+
+        // Losing the context drops the cached image, which is loaded again when the item is drawn.
+        let image_weak = Rc::downgrade(&image);
+        drop(image);
+        invalidate_item_graphics_cache(&mut cache);
+        assert!(image_weak.upgrade().is_none());
+        let reloaded = item_cache.ensure_up_to_date(&mut cache, load).unwrap();
+        assert_eq!(loads.get(), 2);
+        assert_eq!(reloaded.as_image().size(), euclid::size2(2., 1.));
+    }
 
-        let shadow_inner_rect: euclid::Rect<f32, euclid::UnknownUnit> = euclid::rect(
-            box_shadow.x() + box_shadow.offset_x() + blur / 2.,
-            box_shadow.y() + box_shadow.offset_y() + blur / 2.,
-            box_shadow.width() - blur,
-            box_shadow.height() - blur,
-        );
+    #[test]
+    fn cleared_images_are_decoded_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("icon.png");
+        image::RgbaImage::new(2, 2).save(&path).unwrap();
+        let resource = Resource::AbsoluteFilePath(path.to_string_lossy().as_ref().into());
+        let key = ImageCacheKey::new(&resource).unwrap();
+        let other_key = ImageCacheKey::Path("/other.png".into());
+
+        let mut image_cache = HashMap::new();
+        let mut pinned_images = HashMap::new();
+        let mut item_graphics_cache = RenderingCache::<Option<ItemGraphicsCacheEntry>>::default();
+        let item_cache = CachedRenderingData::default();
+        let decodes = std::cell::Cell::new(0);
+        let load = |image_cache: &mut HashMap<_, _>| {
+            lookup_image_in_cache_or_create(image_cache, key.clone(), || {
+                decodes.set(decodes.get() + 1);
+                decode_image_resource(&resource).map(Rc::new)
+            })
+            .map(ItemGraphicsCacheEntry::Image)
+        };
 
-        let shadow_fill_rect: euclid::Rect<f32, euclid::UnknownUnit> = euclid::rect(
-            shadow_outer_rect.min_x() + blur / 2.,
-            shadow_outer_rect.min_y() + blur / 2.,
-            box_shadow.width(),
-            box_shadow.height(),
+        let image = item_cache
+            .ensure_up_to_date(&mut item_graphics_cache, || load(&mut image_cache))
+            .unwrap()
+            .as_image()
+            .clone();
+        let other = Rc::new(CachedImage::new_on_cpu(image::DynamicImage::new_rgba8(1, 1)));
+        image_cache.insert(other_key.clone(), Rc::downgrade(&other));
+        assert_eq!(decodes.get(), 1);
+
+        // The file changes on disk, and only its image is dropped from the caches.
+        image::RgbaImage::new(3, 1).save(&path).unwrap();
+        let cleared = clear_image_cache(&mut image_cache, &mut pinned_images, |cleared_key| {
+            *cleared_key == key
+        });
+        assert_eq!(cleared.len(), 1);
+        assert!(Rc::ptr_eq(&cleared[0], &image));
+        assert!(image_cache.contains_key(&other_key));
+        invalidate_item_graphics_cache_entries(
+            &mut item_graphics_cache,
+            |entry: &Option<ItemGraphicsCacheEntry>| {
+                entry.as_ref().map_or(false, |entry| entry.uses_any_image(&cleared))
+            },
         );
 
-        let paint = femtovg::Paint::box_gradient(
-            shadow_fill_rect.min_x(),
-            shadow_fill_rect.min_y(),
-            shadow_fill_rect.width(),
-            shadow_fill_rect.height(),
-            box_shadow.border_radius(),
-            box_shadow.blur(),
-            box_shadow.color().into(),
-            Color::from_argb_u8(0, 0, 0, 0).into(),
-        );
+        // The image that is still in use by the frame being drawn stays valid.
+        assert_eq!(image.size(), euclid::size2(2., 2.));
 
-        let mut path = femtovg::Path::new();
-        path.rounded_rect(
-            shadow_outer_rect.min_x(),
-            shadow_outer_rect.min_y(),
-            shadow_outer_rect.width(),
-            shadow_outer_rect.height(),
-            box_shadow.border_radius(),
-        );
-        path.rect(
-            shadow_inner_rect.min_x(),
-            shadow_inner_rect.min_y(),
-            shadow_inner_rect.width(),
-            shadow_inner_rect.height(),
-        );
-        path.solidity(femtovg::Solidity::Hole);
+        let reloaded = item_cache
+            .ensure_up_to_date(&mut item_graphics_cache, || load(&mut image_cache))
+            .unwrap();
+        assert_eq!(decodes.get(), 2);
+        assert_eq!(reloaded.as_image().size(), euclid::size2(3., 1.));
+    }
 
-        self.shared_data.canvas.borrow_mut().save_with(|canvas| {
-            canvas.translate(pos.x, pos.y);
-            canvas.fill_path(&mut path, paint);
+    #[test]
+    #[ignore = "needs a display or OSMesa to create an OpenGL context on"]
+    fn headless_renderer_reads_back_the_drawn_items() {
+        let mut renderer = GLRenderer::new_headless(winit::dpi::PhysicalSize::new(8, 4))
+            .expect("cannot create a headless renderer");
+        let rectangle = Box::pin(sixtyfps_corelib::items::Rectangle::default());
+        rectangle.width.set(4.);
+        rectangle.height.set(4.);
+        rectangle.color.set(Color::from_rgb_u8(255, 0, 0));
+        let draw = |renderer: &mut GLRenderer| {
+            let mut item_renderer = renderer.new_renderer(&Color::from_rgb_u8(0, 0, 255), 1., None);
+            item_renderer.draw_rectangle(Point::default(), rectangle.as_ref());
+            renderer.flush_renderer(item_renderer, Some(AlphaMode::Straight)).unwrap()
+        };
 
-            let mut shadow_inner_path = femtovg::Path::new();
-            shadow_inner_path.rect(
-                shadow_inner_rect.min_x(),
-                shadow_inner_rect.min_y(),
-                shadow_inner_rect.width(),
-                shadow_inner_rect.height(),
-            );
-            let fill = femtovg::Paint::color(box_shadow.color().into());
-            canvas.fill_path(&mut shadow_inner_path, fill);
-        })
+        let pixels = draw(&mut renderer);
+        assert_eq!(pixels.dimensions(), (8, 4));
+        assert_eq!(pixels.get_pixel(1, 1).0, [255, 0, 0, 255]);
+        assert_eq!(pixels.get_pixel(6, 1).0, [0, 0, 255, 255]);
+
+        // Resizing switches to a new off-screen buffer, which is drawn into the same way.
+        renderer.resize_headless(winit::dpi::PhysicalSize::new(16, 8)).unwrap();
+        let pixels = draw(&mut renderer);
+        assert_eq!(pixels.dimensions(), (16, 8));
+        assert_eq!(pixels.get_pixel(1, 1).0, [255, 0, 0, 255]);
+        assert_eq!(pixels.get_pixel(14, 6).0, [0, 0, 255, 255]);
     }
 
-    fn combine_clip(&mut self, pos: Point, clip: std::pin::Pin<&sixtyfps_corelib::items::Clip>) {
-        let clip_rect = clip.geometry().translate([pos.x, pos.y].into());
-        self.shared_data.canvas.borrow_mut().intersect_scissor(
-            clip_rect.min_x(),
-            clip_rect.min_y(),
-            clip_rect.width(),
-            clip_rect.height(),
-        );
+    #[test]
+    fn mirrored_items_are_drawn_from_the_right_edge() {
+        let mirror = mirror_transform(100.);
+        // An element aligned to the left edge is drawn at the right edge.
+        let left_aligned = euclid::rect(0., 10., 30., 20.);
+        assert_eq!(mirror.outer_transformed_rect(&left_aligned), euclid::rect(70., 10., 30., 20.));
+        // Positions in the window map back to the items the same way.
+        assert_eq!(mirror.transform_point(Point::new(95., 5.)), Point::new(5., 5.));
+
+        // Text and images are placed at their mirrored position, without being flipped.
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        concat_transform(&mut canvas, &mirror);
+        concat_transform(&mut canvas, &mirror_transform(2. * left_aligned.center().x));
+        let transform = canvas.transform();
+        assert_eq!(transform.transform_point(0., 10.), (70., 10.));
+        assert_eq!(transform.transform_point(30., 30.), (100., 30.));
     }
 
-    fn save_state(&mut self) {
-        self.shared_data.canvas.borrow_mut().save();
+    #[test]
+    #[ignore = "needs a display or OSMesa to create an OpenGL context on"]
+    fn mirrored_rendering_draws_left_aligned_items_at_the_right_edge() {
+        let mut renderer = GLRenderer::new_headless(winit::dpi::PhysicalSize::new(8, 4))
+            .expect("cannot create a headless renderer");
+        renderer.set_mirrored(true);
+        let rectangle = Box::pin(sixtyfps_corelib::items::Rectangle::default());
+        rectangle.width.set(2.);
+        rectangle.height.set(4.);
+        rectangle.color.set(Color::from_rgb_u8(255, 0, 0));
+        let clip = Box::pin(sixtyfps_corelib::items::Clip::default());
+        clip.width.set(1.);
+        clip.height.set(4.);
+        let mut item_renderer = renderer.new_renderer(&Color::from_rgb_u8(0, 0, 255), 1., None);
+        item_renderer.combine_clip(Point::default(), clip.as_ref());
+        item_renderer.draw_rectangle(Point::default(), rectangle.as_ref());
+        let pixels = renderer.flush_renderer(item_renderer, Some(AlphaMode::Straight)).unwrap();
+
+        assert_eq!(pixels.get_pixel(7, 1).0, [255, 0, 0, 255]);
+        // The clip rectangle is mirrored too.
+        assert_eq!(pixels.get_pixel(6, 1).0, [0, 0, 255, 255]);
+        assert_eq!(pixels.get_pixel(0, 1).0, [0, 0, 255, 255]);
+        assert_eq!(renderer.map_to_content(Point::new(7.5, 1.)), Point::new(0.5, 1.));
     }
 
-    fn restore_state(&mut self) {
-        self.shared_data.canvas.borrow_mut().restore();
+    #[test]
+    #[ignore = "needs a display or OSMesa to create an OpenGL context on"]
+    fn custom_drawing_is_clipped_to_the_item() {
+        let mut renderer = GLRenderer::new_headless(winit::dpi::PhysicalSize::new(8, 4))
+            .expect("cannot create a headless renderer");
+        let mut item_renderer = renderer.new_renderer(&Color::from_rgb_u8(0, 0, 255), 1., None);
+        let drawn_size = Rc::new(std::cell::Cell::new(Size::default()));
+        register_custom_renderer("clipped", {
+            let drawn_size = drawn_size.clone();
+            move |canvas, size| {
+                drawn_size.set(size);
+                // A path that extends beyond the item on all sides, and state that must not leak.
+                let mut path = femtovg::Path::new();
+                path.rect(-10., -10., 40., 40.);
+                canvas.fill_path(&mut path, femtovg::Paint::color(femtovg::Color::rgb(255, 0, 0)));
+                canvas.translate(100., 100.);
+                canvas.set_global_alpha(0.);
+            }
+        });
+        let custom_rendering = Box::pin(sixtyfps_corelib::items::CustomRendering::default());
+        custom_rendering.x.set(1.);
+        custom_rendering.y.set(1.);
+        custom_rendering.width.set(4.);
+        custom_rendering.height.set(2.);
+        custom_rendering.renderer.set("clipped".into());
+        item_renderer.draw_custom_rendering(Point::new(1., 0.), custom_rendering.as_ref());
+        assert_eq!(drawn_size.get(), Size::new(4., 2.));
+        // Drawing continues with the state from before the custom drawing.
+        let rectangle = Box::pin(sixtyfps_corelib::items::Rectangle::default());
+        rectangle.width.set(1.);
+        rectangle.height.set(1.);
+        rectangle.color.set(Color::from_rgb_u8(0, 255, 0));
+        item_renderer.draw_rectangle(Point::default(), rectangle.as_ref());
+        let pixels = renderer.flush_renderer(item_renderer, Some(AlphaMode::Straight)).unwrap();
+
+        for x in 2..6 {
+            for y in 1..3 {
+                assert_eq!(pixels.get_pixel(x, y).0, [255, 0, 0, 255], "pixel {}, {}", x, y);
+            }
+        }
+        assert_eq!(pixels.get_pixel(1, 1).0, [0, 0, 255, 255]);
+        assert_eq!(pixels.get_pixel(6, 1).0, [0, 0, 255, 255]);
+        assert_eq!(pixels.get_pixel(3, 3).0, [0, 0, 255, 255]);
+        assert_eq!(pixels.get_pixel(0, 0).0, [0, 255, 0, 255]);
+    }
+
+    #[test]
+    #[ignore = "needs a display or OSMesa to create an OpenGL context on"]
+    fn overlay_rendering_preserves_the_framebuffer() {
+        let mut renderer = GLRenderer::new_headless(winit::dpi::PhysicalSize::new(8, 4))
+            .expect("cannot create a headless renderer");
+        // The application's own scene, which fills the framebuffer with blue.
+        let item_renderer = renderer.new_renderer(&Color::from_rgb_u8(0, 0, 255), 1., None);
+        renderer.flush_renderer(item_renderer, None);
+
+        let rectangle = Box::pin(sixtyfps_corelib::items::Rectangle::default());
+        rectangle.width.set(4.);
+        rectangle.height.set(4.);
+        rectangle.color.set(Color::from_rgb_u8(255, 0, 0));
+        let mut item_renderer = renderer.new_overlay_renderer(1.);
+        item_renderer.draw_rectangle(Point::default(), rectangle.as_ref());
+        renderer.flush_overlay_renderer(item_renderer);
+
+        let screenshot = renderer.shared_data.canvas.borrow_mut().screenshot().unwrap();
+        let pixels =
+            frame_pixels(screenshot.as_ref(), renderer.size(), AlphaMode::Straight).unwrap();
+        assert_eq!(pixels.get_pixel(1, 1).0, [255, 0, 0, 255]);
+        assert_eq!(pixels.get_pixel(6, 1).0, [0, 0, 255, 255]);
     }
 
-    fn scale_factor(&self) -> f32 {
-        self.scale_factor
+    #[test]
+    fn oversized_images_fit_the_maximum_texture_size() {
+        assert_eq!(fit_to_texture_size((4096, 2048), 4096), None);
+        assert_eq!(fit_to_texture_size((8192, 2048), 4096), Some((4096, 1024)));
+        assert_eq!(fit_to_texture_size((1000, 60000), 16384), Some((273, 16384)));
+        // A very thin image keeps at least one pixel.
+        assert_eq!(fit_to_texture_size((100000, 1), 1000), Some((1000, 1)));
     }
 
-    fn draw_cached_pixmap(
-        &mut self,
-        item_cache: &CachedRenderingData,
-        pos: Point,
-        update_fn: &dyn Fn(&mut dyn FnMut(u32, u32, &[u8])),
-    ) {
-        let canvas = &self.shared_data.canvas;
-        let mut cache = self.shared_data.item_graphics_cache.borrow_mut();
+    #[test]
+    #[ignore = "needs a display or OSMesa to create an OpenGL context on"]
+    fn oversized_image_is_uploaded_downscaled() {
+        let mut renderer = GLRenderer::new_headless(winit::dpi::PhysicalSize::new(8, 4))
+            .expect("cannot create a headless renderer");
+        // Allocating an image larger than the texture size of a real GPU would take gigabytes.
+        Rc::get_mut(&mut renderer.shared_data).unwrap().max_texture_size = 4;
+
+        let image = CachedImage::new_on_cpu(image::DynamicImage::new_rgba8(10, 6));
+        let item_renderer = renderer.new_renderer(&Color::from_rgb_u8(0, 0, 0), 1., None);
+        let image_id = image.ensure_uploaded_to_gpu(&item_renderer);
+        renderer.flush_renderer(item_renderer, None);
+
+        let info = renderer.shared_data.canvas.borrow().image_info(image_id).unwrap();
+        assert_eq!((info.width(), info.height()), (4, 2));
+        // The layout still sees the size of the decoded image.
+        assert_eq!(image.size(), euclid::size2(10., 6.));
+    }
 
-        let cache_entry = item_cache.ensure_up_to_date(&mut cache, || {
-            let mut cached_image = None;
-            update_fn(&mut |width: u32, height: u32, data: &[u8]| {
-                use rgb::FromSlice;
-                let img = imgref::Img::new(data.as_rgba(), width as usize, height as usize);
-                if let Some(image_id) =
-                    canvas.borrow_mut().create_image(img, femtovg::ImageFlags::PREMULTIPLIED).ok()
-                {
-                    cached_image = Some(ItemGraphicsCacheEntry::Image(Rc::new(
-                        CachedImage::new_on_gpu(canvas, image_id, None),
-                    )))
-                };
-            });
-            cached_image
+    // Encodes a 16x8 JPEG with a red left half and a blue right half, tagged with the given EXIF orientation.
+    fn jpeg_with_exif_orientation(orientation: u8) -> Vec<u8> {
+        let image = image::RgbImage::from_fn(16, 8, |x, _| {
+            if x < 8 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
         });
-        let image_id = match cache_entry {
-            Some(ItemGraphicsCacheEntry::Image(image)) => image.ensure_uploaded_to_gpu(&self),
-            None => return,
-        };
-        let mut canvas = self.shared_data.canvas.borrow_mut();
+        let mut jpeg = Vec::new();
+        image::DynamicImage::ImageRgb8(image)
+            .write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(100))
+            .unwrap();
 
-        let image_info = canvas.image_info(image_id).unwrap();
-        let (width, height) = (image_info.width() as f32, image_info.height() as f32);
-        let fill_paint = femtovg::Paint::image(image_id, pos.x, pos.y, width, height, 0.0, 1.0);
-        let mut path = femtovg::Path::new();
-        path.rect(pos.x, pos.y, width, height);
-        canvas.fill_path(&mut path, fill_paint);
+        // A big-endian TIFF structure with a single IFD entry: the orientation as SHORT.
+        let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0".to_vec();
+        exif.extend_from_slice(&[orientation, 0, 0, 0, 0, 0, 0]);
+        let mut app1 = vec![0xff, 0xe1];
+        app1.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        app1.extend_from_slice(&exif);
+        // Insert the APP1 segment right after the start of image marker.
+        jpeg.splice(2..2, app1);
+        jpeg
     }
 
-    fn as_any(&mut self) -> &mut dyn std::any::Any {
-        self
-    }
-}
+    #[test]
+    fn exif_orientation_is_applied() {
+        use image::GenericImageView;
 
-impl GLItemRenderer {
-    fn draw_text_impl(
-        &mut self,
-        pos: Point,
-        max_width: f32,
-        max_height: f32,
-        text: &str,
-        font_request: FontRequest,
-        color: Color,
-        horizontal_alignment: TextHorizontalAlignment,
-        vertical_alignment: TextVerticalAlignment,
-    ) -> femtovg::TextMetrics {
-        let font = self.shared_data.loaded_fonts.borrow_mut().font(
-            &self.shared_data.canvas,
-            font_request,
-            self.scale_factor,
-        );
+        let is_red = |pixel: image::Rgba<u8>| pixel[0] > 200 && pixel[2] < 50;
+        let is_blue = |pixel: image::Rgba<u8>| pixel[2] > 200 && pixel[0] < 50;
 
-        let mut paint = font.paint();
-        paint.set_color(color.into());
+        let data = jpeg_with_exif_orientation(6);
+        let image = apply_exif_orientation(image::load_from_memory(&data).unwrap(), &data);
+        // Rotated by 90 degrees clockwise: the red half ends up at the top.
+        assert_eq!(image.dimensions(), (8, 16));
+        assert!(is_red(image.get_pixel(0, 0)));
+        assert!(is_blue(image.get_pixel(7, 15)));
 
-        let mut canvas = self.shared_data.canvas.borrow_mut();
-        let (text_width, text_height) = {
-            let text_metrics = canvas.measure_text(0., 0., &text, paint).unwrap();
-            let font_metrics = canvas.measure_font(paint).unwrap();
-            (text_metrics.width(), font_metrics.height())
-        };
+        let data = jpeg_with_exif_orientation(1);
+        let image = apply_exif_orientation(image::load_from_memory(&data).unwrap(), &data);
+        assert_eq!(image.dimensions(), (16, 8));
+        assert!(is_red(image.get_pixel(0, 0)));
+    }
 
-        let translate_x = match horizontal_alignment {
-            TextHorizontalAlignment::left => 0.,
-            TextHorizontalAlignment::center => max_width / 2. - text_width / 2.,
-            TextHorizontalAlignment::right => max_width - text_width,
-        };
+    #[test]
+    fn image_formats_are_recognized_by_their_container() {
+        use image::ImageFormat;
+        let webp = include_bytes!("test_images/lossy.webp");
+        assert_eq!(guess_image_format(webp), Some(ImageFormat::WebP));
+        // Other RIFF files, such as WAV audio, are not taken for WebP images.
+        assert_eq!(guess_image_format(b"RIFF\x24\0\0\0WAVEfmt \x10\0\0\0"), None);
+        // The file type box of AVIF images grows with the brands they are compatible with.
+        let avif = b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf";
+        assert_eq!(guess_image_format(avif), Some(ImageFormat::Avif));
+        assert_eq!(guess_image_format(b"\0\0\0\x18ftypheic\0\0\0\0mif1heic"), None);
+        assert_eq!(guess_image_format(&jpeg_with_exif_orientation(1)), Some(ImageFormat::Jpeg));
+    }
 
-        let translate_y = match vertical_alignment {
-            TextVerticalAlignment::top => 0.,
-            TextVerticalAlignment::center => max_height / 2. - text_height / 2.,
-            TextVerticalAlignment::bottom => max_height - text_height,
-        };
+    #[cfg(feature = "webp")]
+    #[test]
+    fn webp_image_is_decoded() {
+        let image = CachedImage::new_from_data(include_bytes!("test_images/lossy.webp")).unwrap();
+        assert_eq!(image.size(), euclid::size2(15., 7.));
+    }
 
-        canvas.fill_text(pos.x + translate_x, pos.y + translate_y, text, paint).unwrap()
+    #[test]
+    fn avif_image_without_decoder_is_reported_as_unsupported() {
+        // The image crate decodes AVIF images only when built with its AVIF decoder, which needs the dav1d library.
+        let avif = b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf";
+        assert!(matches!(CachedImage::new_from_data(avif), Err(image::ImageError::Unsupported(_))));
     }
 
-    fn draw_image_impl(
-        &mut self,
-        pos: Point,
-        item_cache: &CachedRenderingData,
-        source_property: std::pin::Pin<&Property<Resource>>,
-        source_clip_rect: Rect,
-        target_width: f32,
-        target_height: f32,
-        image_fit: ImageFit,
-    ) {
-        if target_width <= 0. || target_height < 0. {
-            return;
+    #[test]
+    fn registered_image_decoder_is_used_for_matching_data() {
+        // A format of a magic number, the width and the height, followed by the gray value of the pixels.
+        let decoded = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        register_image_decoder({
+            let decoded = decoded.clone();
+            move |data| {
+                let (width, height, pixels) = match data {
+                    [b'G', b'R', b'A', b'Y', width, height, pixels @ ..] => (width, height, pixels),
+                    _ => return None,
+                };
+                decoded.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let data = pixels.iter().flat_map(|&gray| vec![gray, gray, gray, 255]).collect();
+                Some(DecodedImage::Rgba { width: *width as u32, height: *height as u32, data })
+            }
+        });
+
+        static GRAY: &[u8] = b"GRAY\x03\x01\x00\x80\xff";
+        let image = decode_image_resource(&Resource::EmbeddedData(GRAY.into())).unwrap();
+        assert_eq!(decoded.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(image.size(), euclid::size2(3., 1.));
+        match &*image.data.borrow() {
+            ImageData::CPUSide { decoded_image } => {
+                assert_eq!(decoded_image.to_rgba8().get_pixel(1, 0).0, [0x80, 0x80, 0x80, 255])
+            }
+            _ => panic!("the image should be decoded on the CPU side"),
         }
 
-        let cached_image =
-            match self.shared_data.load_cached_item_image(item_cache, || source_property.get()) {
-                Some(image) => image,
-                None => return,
-            };
+        // Other images are left to the built-in decoders.
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgba8(2, 2)
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+        assert_eq!(CachedImage::new_from_data(&png).unwrap().size(), euclid::size2(2., 2.));
+        assert_eq!(decoded.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
 
-        let image_id = cached_image.ensure_uploaded_to_gpu(&self);
-        let image_size = cached_image.size();
+    #[test]
+    fn unpremultiply() {
+        let mut pixels = image::RgbaImage::from_raw(
+            4,
+            1,
+            vec![64, 0, 32, 128, 10, 20, 30, 255, 0, 0, 0, 0, 200, 0, 0, 100],
+        )
+        .unwrap();
+        unpremultiply_alpha(&mut pixels);
+        assert_eq!(
+            pixels.into_raw(),
+            vec![128, 0, 64, 128, 10, 20, 30, 255, 0, 0, 0, 0, 255, 0, 0, 100]
+        );
+    }
 
-        let (source_width, source_height) = if source_clip_rect.is_empty() {
-            (image_size.width, image_size.height)
-        } else {
-            (source_clip_rect.width() as _, source_clip_rect.height() as _)
+    #[test]
+    fn partial_redraws_are_scissored_to_the_damaged_regions() {
+        let identity = euclid::default::Transform2D::identity();
+        let rect = |x, y, w, h| {
+            euclid::default::Box2D::new(euclid::point2(x, y), euclid::point2(x + w, y + h))
         };
-
-        let fill_paint = femtovg::Paint::image(
-            image_id,
-            -source_clip_rect.min_x(),
-            -source_clip_rect.min_y(),
-            image_size.width,
-            image_size.height,
-            0.0,
-            1.0,
+        let small = [Rect::new(Point::new(10.5, 20.2), euclid::size2(5., 5.))];
+        assert_eq!(redrawn_target_rect(Some(&small), &identity, (100, 80)), rect(10, 20, 6, 6));
+
+        // The damage is unknown, so everything is redrawn.
+        assert_eq!(redrawn_target_rect(None, &identity, (100, 80)), rect(0, 0, 100, 80));
+        assert_eq!(redrawn_target_rect(Some(&[]), &identity, (100, 80)), rect(0, 0, 100, 80));
+
+        let regions = [
+            Rect::new(Point::new(10., 10.), euclid::size2(10., 10.)),
+            Rect::new(Point::new(50., 30.), euclid::size2(70., 10.)),
+        ];
+        assert_eq!(redrawn_target_rect(Some(&regions), &identity, (100, 80)), rect(10, 10, 90, 30));
+        let oversampled = euclid::default::Transform2D::scale(2., 2.);
+        assert_eq!(
+            redrawn_target_rect(Some(&small), &oversampled, (200, 160)),
+            rect(21, 40, 10, 11)
         );
+        let outside = [Rect::new(Point::new(-20., -20.), euclid::size2(10., 10.))];
+        assert!(redrawn_target_rect(Some(&outside), &identity, (100, 80)).is_empty());
+    }
 
-        let mut path = femtovg::Path::new();
-        path.rect(0., 0., source_width, source_height);
-
-        self.shared_data.canvas.borrow_mut().save_with(|canvas| {
-            canvas.translate(pos.x, pos.y);
+    #[test]
+    fn frames_are_captured_at_the_physical_size() {
+        let physical_size = winit::dpi::LogicalSize::new(100., 50.).to_physical::<u32>(2.);
+        let pixel = rgb::RGBA8 { r: 64, g: 0, b: 32, a: 128 };
+        let framebuffer = imgref::ImgVec::new(vec![pixel; 200 * 100], 200, 100);
+        let pixels =
+            frame_pixels(framebuffer.as_ref(), physical_size, AlphaMode::Straight).unwrap();
+        assert_eq!(pixels.dimensions(), (200, 100));
+        assert_eq!(pixels.get_pixel(199, 99).0, [128, 0, 64, 128]);
+        let pixels =
+            frame_pixels(framebuffer.as_ref(), physical_size, AlphaMode::Premultiplied).unwrap();
+        assert_eq!(pixels.get_pixel(0, 0).0, [64, 0, 32, 128]);
+
+        // A drawing buffer that does not cover the window is not a capture of the window.
+        let framebuffer = imgref::ImgVec::new(vec![pixel; 100 * 50], 100, 50);
+        assert!(frame_pixels(framebuffer.as_ref(), physical_size, AlphaMode::Straight).is_none());
+    }
 
-            match image_fit {
-                ImageFit::fill => {
-                    canvas.scale(target_width / source_width, target_height / source_height);
-                }
-                ImageFit::contain => {
-                    let ratio =
-                        f32::max(target_width / source_width, target_height / source_height);
-                    canvas.scale(ratio, ratio)
-                }
+    #[test]
+    fn text_input_scrolls_to_keep_the_cursor_visible() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let (width, cursor_width) = (100., 2.);
+        let rect = Rect::new(Point::new(10., 20.), euclid::size2(width, 30.));
+        let typed = "The quick brown fox jumps over the lazy dog";
+
+        for alignment in [
+            TextHorizontalAlignment::left,
+            TextHorizontalAlignment::center,
+            TextHorizontalAlignment::right,
+        ]
+        .iter()
+        {
+            let layout = |canvas: &mut femtovg::Canvas<_>, text, cursor, previous| {
+                let lines = layout_text_input(
+                    canvas,
+                    rect,
+                    text,
+                    paint,
+                    *alignment,
+                    TextVerticalAlignment::top,
+                );
+                let line = &lines[0];
+                let extent =
+                    (line.pos.x - rect.min_x(), line.pos.x + line.metrics.width() - rect.min_x());
+                let cursor_x = cursor_position_in_lines(&lines, cursor).x - rect.min_x();
+                let offset =
+                    text_input_scroll_offset(previous, extent, cursor_x, cursor_width, width);
+                (cursor_x - offset, offset)
             };
 
-            canvas.fill_path(&mut path, fill_paint);
-        })
+            let mut offset = 0.;
+            for end in 1..=typed.len() {
+                let (visible_cursor_x, new_offset) =
+                    layout(&mut canvas, &typed[..end], end, offset);
+                assert!(visible_cursor_x >= 0., "{:?} {}", alignment, end);
+                assert!(visible_cursor_x + cursor_width <= width, "{:?} {}", alignment, end);
+                offset = new_offset;
+            }
+            assert!(offset > 0.);
+
+            // Moving the cursor back within the visible text doesn't scroll, moving it to the start scrolls back.
+            let (_, same_offset) = layout(&mut canvas, typed, typed.len() - 2, offset);
+            assert_eq!(same_offset, offset);
+            let (visible_cursor_x, _) = layout(&mut canvas, typed, 0, offset);
+            assert!(visible_cursor_x >= 0. && visible_cursor_x < width);
+
+            // Text that fits is not scrolled, except to show the cursor after the end of right-aligned text.
+            let expected_offset =
+                if *alignment == TextHorizontalAlignment::right { cursor_width } else { 0. };
+            assert_eq!(layout(&mut canvas, "Hi", 2, offset).1, expected_offset);
+        }
     }
-}
 
-#[derive(Clone, PartialEq, Eq, Hash)]
-struct FontCacheKey {
-    family: SharedString,
-    weight: i32,
-}
+    #[test]
+    fn selected_text_is_filled_exactly_once() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let rect = Rect::new(Point::new(10., 20.), euclid::size2(300., 100.));
+        let text = "Hello World\nAgain";
+        let lines = layout_text_input(
+            &mut canvas,
+            rect,
+            text,
+            paint,
+            TextHorizontalAlignment::center,
+            TextVerticalAlignment::top,
+        );
 
-struct GLFont {
-    fonts: Vec<femtovg::FontId>,
-    pixel_size: f32,
-    canvas: CanvasRc,
-}
+        // The fills of the text: each byte of a line is in exactly one of them, and the selected ones are in the
+        // selected fill.
+        let fills = |selection: Option<std::ops::Range<usize>>| {
+            lines
+                .iter()
+                .map(|line| {
+                    let runs = text_input_runs(line, selection.clone());
+                    let mut covered = vec![0; line.text.len()];
+                    for run in &runs {
+                        for byte in run.range.clone() {
+                            covered[byte] += 1;
+                        }
+                    }
+                    assert!(covered.iter().all(|count| *count == 1));
+                    runs.iter()
+                        .map(|run| (&line.text[run.range.clone()], run.selected))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fills(None), vec![vec![("Hello World", false)], vec![("Again", false)]]);
+        assert_eq!(
+            fills(Some(2..7)),
+            vec![vec![("He", false), ("llo W", true), ("orld", false)], vec![("Again", false)]]
+        );
+        assert_eq!(
+            fills(Some(6..14)),
+            vec![vec![("Hello ", false), ("World", true)], vec![("Ag", true), ("ain", false)]]
+        );
 
-impl GLFont {
-    fn measure(&self, text: &str) -> femtovg::TextMetrics {
-        self.canvas.borrow_mut().measure_text(0., 0., text, self.paint()).unwrap()
+        // The runs are placed where their glyphs are in the layout of the whole line.
+        let runs = text_input_runs(&lines[0], Some(2..7));
+        let line = &lines[0].metrics;
+        assert_eq!(runs[0].x, lines[0].pos.x);
+        assert_eq!(runs[1].x, line.glyphs[2].x - line.glyphs[2].bearing_x);
+        assert_eq!(runs[2].x, line.glyphs[7].x - line.glyphs[7].bearing_x);
     }
 
-    fn height(&self) -> f32 {
-        self.canvas.borrow_mut().measure_font(self.paint()).unwrap().height()
+    #[test]
+    fn password_text_input_shows_a_mask_glyph_per_character() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let rect = Rect::new(Point::new(10., 20.), euclid::size2(300., 100.));
+        let text_input = Box::pin(sixtyfps_corelib::items::TextInput::default());
+        text_input.text.set("hunt2".into());
+        text_input.input_type.set(sixtyfps_corelib::items::InputType::password);
+
+        let text = text_input.as_ref().displayed_text();
+        let lines = layout_text_input(
+            &mut canvas,
+            rect,
+            &text.text,
+            paint,
+            TextHorizontalAlignment::left,
+            TextVerticalAlignment::top,
+        );
+        let glyphs = &lines[0].metrics.glyphs;
+        assert_eq!(glyphs.len(), 5);
+        assert!(glyphs.iter().all(|glyph| glyph.c == '\u{2022}'));
+
+        // The cursor after the third character of the text is after the third bullet.
+        let cursor = cursor_position_in_lines(&lines, text.displayed_offset(3));
+        assert_eq!(cursor.x, glyphs[3].x);
     }
 
-    fn paint(&self) -> femtovg::Paint {
-        let mut paint = femtovg::Paint::default();
-        paint.set_font(&self.fonts);
-        paint.set_font_size(self.pixel_size);
-        paint.set_text_baseline(femtovg::Baseline::Top);
-        paint
+    #[test]
+    fn selections_across_lines_are_highlighted_per_line() {
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let font = canvas.add_font_mem(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let paint = text_paint(&[font], 20., false);
+        let line_height = canvas.measure_font(paint).unwrap().height();
+        let rect = Rect::new(Point::new(10., 20.), euclid::size2(300., 100.));
+        let text = "Hello\nWorld";
+        let lines = layout_text_input(
+            &mut canvas,
+            rect,
+            text,
+            paint,
+            TextHorizontalAlignment::left,
+            TextVerticalAlignment::top,
+        );
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[1].byte_offset, lines[1].text), (6, "World"));
+        assert_eq!(lines[1].pos, Point::new(10., 20. + line_height));
+
+        // From "llo" on the first line to "Wo" on the second.
+        let rects = selection_rects(&lines, 2..8, rect.min_x(), rect.max_x(), line_height);
+        assert_eq!(rects.len(), 2);
+        // The selection starts at the left edge of the first "l", and ends at the left edge of the "r".
+        let hello = canvas.measure_text(10., 20., "Hello", paint).unwrap();
+        assert_eq!(rects[0].min_x(), hello.glyphs[2].x);
+        assert_eq!((rects[0].max_x(), rects[0].min_y()), (rect.max_x(), 20.));
+        let world = canvas.measure_text(10., 20. + line_height, "World", paint).unwrap();
+        assert_eq!((rects[1].min_x(), rects[1].min_y()), (rect.min_x(), 20. + line_height));
+        assert_eq!(rects[1].max_x(), world.glyphs[2].x);
+        assert!(rects.iter().all(|rect| rect.height() == line_height));
+
+        // The lines in between are highlighted over the whole width, and a selection within one line only
+        // covers its glyphs.
+        let lines = layout_text_input(
+            &mut canvas,
+            rect,
+            "a\nb\nc",
+            paint,
+            TextHorizontalAlignment::left,
+            TextVerticalAlignment::top,
+        );
+        let rects = selection_rects(&lines, 0..5, rect.min_x(), rect.max_x(), line_height);
+        assert_eq!(rects.len(), 3);
+        assert_eq!((rects[1].min_x(), rects[1].max_x()), (rect.min_x(), rect.max_x()));
+        let rects = selection_rects(&lines, 2..3, rect.min_x(), rect.max_x(), line_height);
+        assert_eq!(rects.len(), 1);
+        assert!(rects[0].max_x() < rect.max_x());
+
+        // A newline at the end starts a line for the cursor.
+        let lines = layout_text_input(
+            &mut canvas,
+            rect,
+            "a\n",
+            paint,
+            TextHorizontalAlignment::left,
+            TextVerticalAlignment::top,
+        );
+        assert_eq!(cursor_position_in_lines(&lines, 2), Point::new(10., 20. + line_height));
     }
 
-    fn text_size(&self, text: &str, max_width: Option<f32>) -> Size {
-        let paint = self.paint();
-        let mut canvas = self.canvas.borrow_mut();
-        let font_metrics = canvas.measure_font(paint).unwrap();
-        let mut y = 0.;
-        let mut width = 0.;
-        let mut height = 0.;
-        let mut start = 0;
-        if let Some(max_width) = max_width {
-            while start < text.len() {
-                let index = canvas.break_text(max_width, &text[start..], paint).unwrap();
-                if index == 0 {
-                    break;
-                }
-                let index = start + index;
-                let mesure = canvas.measure_text(0., 0., &text[start..index], paint).unwrap();
-                start = index;
-                height = y + mesure.height();
-                y += font_metrics.height();
-                width = mesure.width().max(width);
-            }
-        } else {
-            for line in text.lines() {
-                let mesure = canvas.measure_text(0., 0., line, paint).unwrap();
-                height = y + mesure.height();
-                y += font_metrics.height();
-                width = mesure.width().max(width);
-            }
+    #[test]
+    fn same_colored_rectangles_are_filled_together() {
+        let red = Color::from_rgb_u8(255, 0, 0);
+        let blue = Color::from_rgb_u8(0, 0, 255);
+        let rect = |x: f32, y: f32| Rect::new(Point::new(x, y), euclid::size2(10., 10.));
+
+        let mut batch = RectangleBatch::default();
+        for i in 0..1000 {
+            assert!(batch
+                .add(red, rect((i % 100) as f32 * 5., (i / 100) as f32 * 5.), 0.)
+                .is_none());
         }
-        euclid::size2(width, height)
+        let filled = batch.take().unwrap();
+        assert_eq!((filled.color, filled.rects.len()), (red, 1000));
+        assert!(batch.take().is_none());
+
+        // A change of color fills the rectangles batched before.
+        assert!(batch.add(red, rect(0., 0.), 0.).is_none());
+        assert!(batch.add(red, rect(20., 0.), 2.).is_none());
+        let filled = batch.add(blue, rect(5., 0.), 0.).unwrap();
+        assert_eq!((filled.color, filled.rects.len()), (red, 2));
+        assert_eq!(batch.take().unwrap().rects, vec![(rect(5., 0.), 0.)]);
+
+        // Overlapping translucent rectangles must be blended one over the other.
+        let translucent = Color::from_argb_u8(128, 255, 0, 0);
+        assert!(batch.add(translucent, rect(0., 0.), 0.).is_none());
+        assert!(batch.add(translucent, rect(20., 0.), 0.).is_none());
+        assert_eq!(batch.add(translucent, rect(25., 5.), 0.).unwrap().rects.len(), 2);
     }
-}
 
-struct GLFontMetrics {
-    request: FontRequest,
-    scale_factor: f32,
-    shared_data: Rc<GLRendererData>,
-}
+    #[test]
+    fn image_transform_without_orientation() {
+        let transform = image_transform(
+            euclid::size2(40., 20.),
+            euclid::size2(80., 20.),
+            ImageFit::fill,
+            (0.5, 0.5),
+            ImageOrientation::default(),
+        );
+        assert_eq!(transform, euclid::default::Transform2D::scale(2., 1.));
+    }
 
-impl FontMetrics for GLFontMetrics {
-    fn text_size(&self, text: &str) -> Size {
-        self.font().text_size(text, None)
+    #[test]
+    fn image_fit_none_keeps_the_natural_size() {
+        let target = euclid::size2(100., 100.);
+        let transform = image_transform(
+            euclid::size2(200., 200.),
+            target,
+            ImageFit::none,
+            (0.5, 0.5),
+            ImageOrientation::default(),
+        );
+        // Drawn at 200px, centered on the 100px element...
+        let drawn = transformed_bounds(
+            &femtovg::Transform2D(transform.to_array()),
+            Rect::from_size(euclid::size2(200., 200.)),
+        );
+        assert_eq!(drawn, euclid::rect(-50., -50., 200., 200.));
+        // ...and cut off at its bounds.
+        let clip_rect = image_fit_clip_rect(ImageFit::none, target).unwrap();
+        assert_eq!(drawn.intersection(&clip_rect), Some(Rect::from_size(target)));
+        assert_eq!(image_fit_clip_rect(ImageFit::fill, target), None);
+
+        // Smaller images are centered without being scaled up.
+        let transform = image_transform(
+            euclid::size2(20., 10.),
+            target,
+            ImageFit::none,
+            (0.5, 0.5),
+            ImageOrientation::default(),
+        );
+        assert_eq!(transform, euclid::default::Transform2D::translation(40., 45.));
     }
 
-    fn text_offset_for_x_position<'a>(&self, text: &'a str, x: f32) -> usize {
-        let metrics = self.font().measure(text);
-        let mut current_x = 0.;
-        for glyph in metrics.glyphs {
-            if current_x + glyph.advance_x / 2. >= x {
-                return glyph.byte_index;
-            }
-            current_x += glyph.advance_x;
-        }
-        return text.len();
+    #[test]
+    fn image_alignment_places_the_image_within_the_target() {
+        use sixtyfps_corelib::items::{ImageHorizontalAlignment, ImageVerticalAlignment};
+        let center = image_alignment_factors(
+            ImageHorizontalAlignment::center,
+            ImageVerticalAlignment::center,
+        );
+        let bottom_right = image_alignment_factors(
+            ImageHorizontalAlignment::right,
+            ImageVerticalAlignment::bottom,
+        );
+        let target = euclid::size2(100., 100.);
+        let offset = |source_size, image_fit, alignment| {
+            let transform = image_transform(
+                source_size,
+                target,
+                image_fit,
+                alignment,
+                ImageOrientation::default(),
+            );
+            transform.transform_point(euclid::point2(0., 0.))
+        };
+
+        // Smaller than the target: the space left is distributed around the image.
+        let small = euclid::size2(20., 10.);
+        assert_eq!(offset(small, ImageFit::none, center), euclid::point2(40., 45.));
+        assert_eq!(offset(small, ImageFit::none, bottom_right), euclid::point2(80., 90.));
+
+        // Larger than the target: the image is moved so that the aligned part stays visible.
+        let wide = euclid::size2(200., 100.);
+        assert_eq!(offset(wide, ImageFit::contain, center), euclid::point2(-50., 0.));
+        assert_eq!(offset(wide, ImageFit::contain, bottom_right), euclid::point2(-100., 0.));
+        assert_eq!(offset(wide, ImageFit::none, bottom_right), euclid::point2(-100., 0.));
+
+        // Stretched images fill the target, whatever the alignment.
+        assert_eq!(offset(wide, ImageFit::fill, bottom_right), euclid::point2(0., 0.));
     }
 
-    fn height(&self) -> f32 {
-        self.shared_data.canvas.borrow_mut().measure_font(self.font().paint()).unwrap().height()
+    #[test]
+    fn colorize_white_square_red() {
+        let image = CachedImage::new_on_cpu(image::DynamicImage::ImageRgba8(
+            image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 255, 255, 255])),
+        ));
+        image.colorize(Color::from_rgb_u8(255, 0, 0));
+        match &*image.data.borrow() {
+            ImageData::CPUSide { decoded_image } => assert!(decoded_image
+                .to_rgba8()
+                .pixels()
+                .all(|pixel| *pixel == image::Rgba([255, 0, 0, 255]))),
+            _ => unreachable!(),
+        };
     }
-}
 
-impl GLFontMetrics {
-    fn font(&self) -> GLFont {
-        self.shared_data.loaded_fonts.borrow_mut().font(
-            &self.shared_data.canvas,
-            self.request.clone(),
-            self.scale_factor,
-        )
+    #[test]
+    fn colorize_alpha_mask() {
+        let mut pixels =
+            image::RgbaImage::from_raw(3, 1, vec![0, 0, 0, 255, 0, 0, 0, 128, 0, 0, 0, 0]).unwrap();
+        colorize_pixels(&mut pixels, Color::from_argb_u8(255, 255, 0, 0));
+        assert_eq!(pixels.into_raw(), vec![255, 0, 0, 255, 255, 0, 0, 128, 255, 0, 0, 0]);
+
+        // Images with colors are multiplied instead, and the alpha of the color applies too.
+        let mut pixels = image::RgbaImage::from_raw(1, 1, vec![255, 128, 0, 255]).unwrap();
+        colorize_pixels(&mut pixels, Color::from_argb_u8(128, 0, 255, 255));
+        assert_eq!(pixels.into_raw(), vec![0, 128, 0, 128]);
     }
-}
 
-#[cfg(target_arch = "wasm32")]
-pub fn create_gl_window_with_canvas_id(canvas_id: String) -> ComponentWindow {
-    let platform_window = GraphicsWindow::new(move |event_loop, window_builder| {
-        GLRenderer::new(event_loop, window_builder, &canvas_id)
-    });
-    let window = Rc::new(sixtyfps_corelib::window::Window::new(platform_window.clone()));
-    platform_window.self_weak.set(Rc::downgrade(&window)).ok().unwrap();
-    ComponentWindow(window)
-}
+    #[test]
+    fn clip_layer_bounds() {
+        // Scales by 2 and translates by (10, 5)
+        let transform = femtovg::Transform2D([2., 0., 0., 2., 10., 5.]);
+        let bounds = transformed_bounds(&transform, euclid::rect(1., 2., 24., 24.));
+        assert_eq!(bounds, euclid::rect(12., 9., 48., 48.));
+    }
 
-#[doc(hidden)]
-#[cold]
-pub fn use_modules() {
-    sixtyfps_corelib::use_modules();
-}
+    #[test]
+    fn missing_image_file_is_not_loaded() {
+        let resource = Resource::AbsoluteFilePath("/nonexistent/sixtyfps/image.png".into());
+        assert!(decode_image_resource(&resource).is_none());
+        #[cfg(feature = "svg")]
+        assert!(decode_image_resource(&Resource::AbsoluteFilePath(
+            "/nonexistent/sixtyfps/image.svg".into()
+        ))
+        .is_none());
+    }
 
-pub type NativeWidgets = ();
-pub type NativeGlobals = ();
-pub mod native_widgets {}
-pub const HAS_NATIVE_STYLE: bool = false;
-pub const IS_AVAILABLE: bool = true;
+    #[test]
+    fn truncated_image_data_is_not_loaded() {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgba8(16, 16)
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+        png.truncate(png.len() / 2);
+        let png: &'static [u8] = Box::leak(png.into_boxed_slice());
+        assert!(decode_image_resource(&Resource::EmbeddedData(png.into())).is_none());
+    }
 
-thread_local!(pub(crate) static CLIPBOARD : std::cell::RefCell<copypasta::ClipboardContext> = std::cell::RefCell::new(copypasta::ClipboardContext::new().unwrap()));
+    #[test]
+    fn placeholder_for_failed_image() {
+        let missing = Resource::AbsoluteFilePath("/nonexistent/sixtyfps/image.png".into());
+        let failed = decode_image_resource(&missing);
+        let (box_path, cross_path) =
+            image_placeholder_paths(&missing, failed.as_ref(), 40., 30.).unwrap();
+        assert!(!box_path.is_empty());
+        // femtovg doesn't export its path verbs, so compare their debug representation.
+        assert_eq!(
+            format!("{:?}", cross_path.verbs().collect::<Vec<_>>()),
+            "[MoveTo(0.0, 0.0), LineTo(40.0, 30.0), MoveTo(40.0, 0.0), LineTo(0.0, 30.0)]"
+        );
 
-pub struct Backend;
-impl sixtyfps_corelib::backend::Backend for Backend {
-    fn create_window(&'static self) -> ComponentWindow {
-        let platform_window = GraphicsWindow::new(|event_loop, window_builder| {
-            GLRenderer::new(
-                event_loop,
-                window_builder,
-                #[cfg(target_arch = "wasm32")]
-                "canvas",
-            )
-        });
-        let window = Rc::new(sixtyfps_corelib::window::Window::new(platform_window.clone()));
-        platform_window.self_weak.set(Rc::downgrade(&window)).ok().unwrap();
-        ComponentWindow(window)
+        // Images that are available, and elements without an image, get no placeholder.
+        let loaded = CachedImage::new_on_cpu(image::DynamicImage::new_rgba8(1, 1));
+        assert!(image_placeholder_paths(&missing, Some(&loaded), 40., 30.).is_none());
+        assert!(image_placeholder_paths(&Resource::None, None, 40., 30.).is_none());
     }
 
-    fn run_event_loop(&'static self) {
-        crate::eventloop::run();
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn remote_image_replaces_placeholder() {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgba8(3, 2)
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+        remote_image::set_fetcher(move |_| Ok(png.clone()));
+
+        let (notify, notified) = std::sync::mpsc::channel();
+        let image = CachedImage::new_remote("https://example.com/image.png", move || {
+            notify.send(()).unwrap()
+        });
+        assert_eq!(image.size(), euclid::size2(1., 1.));
+        assert!(image.is_pending());
+
+        notified.recv_timeout(std::time::Duration::from_secs(10)).unwrap();
+        assert!(!image.poll_fetch());
+        assert_eq!(image.size(), euclid::size2(3., 2.));
+        assert!(!image.is_pending());
     }
 
-    fn register_application_font_from_memory(
-        &'static self,
-        data: &'static [u8],
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self::register_application_font_from_memory(data)
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn load_state_of_remote_images() {
+        let (notify, notified) = std::sync::mpsc::channel();
+        let fetched = |url: &str| {
+            let notify = notify.clone();
+            CachedImage::new_remote(url, move || notify.send(()).unwrap())
+        };
+        remote_image::set_fetcher(|url| {
+            if url.ends_with(".png") {
+                let mut png = Vec::new();
+                image::DynamicImage::new_rgba8(3, 2)
+                    .write_to(&mut png, image::ImageOutputFormat::Png)
+                    .unwrap();
+                Ok(png)
+            } else {
+                Ok(b"not an image".to_vec())
+            }
+        });
+
+        let image = Rc::new(fetched("https://example.com/image.png"));
+        let state = Box::pin(Property::new(ImageLoadState::Ready));
+        state.as_ref().set_binding({
+            let image = image.clone();
+            move || image.load_state()
+        });
+        assert_eq!(state.as_ref().get(), ImageLoadState::Loading);
+        notified.recv_timeout(std::time::Duration::from_secs(10)).unwrap();
+        // The state is only updated once the fetched data is taken.
+        assert_eq!(state.as_ref().get(), ImageLoadState::Loading);
+        assert!(!image.poll_fetch());
+        assert_eq!(state.as_ref().get(), ImageLoadState::Ready);
+
+        let broken = fetched("https://example.com/broken");
+        assert_eq!(broken.load_state(), ImageLoadState::Loading);
+        notified.recv_timeout(std::time::Duration::from_secs(10)).unwrap();
+        assert!(!broken.poll_fetch());
+        assert_eq!(broken.load_state(), ImageLoadState::Failed);
+        assert!(image_placeholder_paths(
+            &Resource::AbsoluteFilePath("https://example.com/broken".into()),
+            Some(&broken),
+            40.,
+            30.
+        )
+        .is_some());
     }
 
-    fn set_clipboard_text(&'static self, text: String) {
-        use copypasta::ClipboardProvider;
-        CLIPBOARD.with(|clipboard| clipboard.borrow_mut().set_contents(text).ok());
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn preloaded_image_is_in_the_cache_when_first_drawn() {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgba8(3, 2)
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+        let resource = Resource::EmbeddedData((&*Box::leak(png.into_boxed_slice())).into());
+        let cache_key = ImageCacheKey::new(&resource).unwrap();
+        let mut image_cache = HashMap::new();
+
+        let (notify, notified) = std::sync::mpsc::channel();
+        let preloaded =
+            lookup_image_in_cache_or_create(&mut image_cache, cache_key.clone(), || {
+                CachedImage::new_preloading(&resource, move || notify.send(()).unwrap())
+                    .map(Rc::new)
+            })
+            .unwrap();
+        assert!(preloaded.is_pending());
+
+        notified.recv_timeout(std::time::Duration::from_secs(10)).unwrap();
+        assert!(!preloaded.poll_fetch());
+
+        // Drawing the image takes the decoded image from the cache, instead of decoding it again.
+        let drawn = lookup_image_in_cache_or_create(&mut image_cache, cache_key, || {
+            panic!("the preloaded image should be in the cache")
+        })
+        .unwrap();
+        assert!(Rc::ptr_eq(&drawn, &preloaded));
+        assert!(matches!(&*drawn.data.borrow(), ImageData::CPUSide { .. }));
+        assert_eq!(drawn.size(), euclid::size2(3., 2.));
     }
 
-    fn clipboard_text(&'static self) -> Option<String> {
-        use copypasta::ClipboardProvider;
-        CLIPBOARD.with(|clipboard| clipboard.borrow_mut().get_contents().ok())
+    #[cfg(feature = "svg")]
+    #[test]
+    fn svg_rasterized_per_size() {
+        static SVG: &[u8] = br#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16">
+            <circle cx="8" cy="8" r="8" fill="black"/>
+        </svg>"#;
+        let resource = Resource::EmbeddedData(SVG.into());
+        let mut rasterizations = SvgRasterizations::default();
+        let mut rasterize = |size| {
+            rasterizations
+                .get_or_rasterize(size, || rasterize_svg_resource(&resource, Some(size)))
+                .unwrap()
+        };
+
+        let small = rasterize((32, 32));
+        let large = rasterize((64, 64));
+        assert!(!Rc::ptr_eq(&small, &large));
+        assert_eq!(small.size(), euclid::size2(32., 32.));
+        assert_eq!(large.size(), euclid::size2(64., 64.));
+        // Drawing at a size again reuses its rasterization
+        assert!(Rc::ptr_eq(&rasterize((32, 32)), &small));
+
+        // Only the most recently used sizes are kept
+        for size in 1..=MAX_SVG_RASTERIZATIONS as u32 {
+            rasterize((size, size));
+        }
+        assert!(!Rc::ptr_eq(&rasterize((32, 32)), &small));
     }
 }
@@ -0,0 +1,318 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+#![cfg(not(target_arch = "wasm32"))]
+/*!
+Loading of images from `http://` and `https://` URLs on native platforms.
+
+The data is fetched by a small pool of background threads, so that the event loop doesn't block and many
+images don't start as many threads. The thread calls a notification function once the data has arrived,
+and the main thread then picks it up with [`PendingFetch::try_take`]. Dropping the [`PendingFetch`]
+cancels it: a fetch that didn't start yet is skipped, and a download stops at the next chunk of its body.
+On the web, the browser loads remote images instead, see `load_html_image`.
+
+The same mechanism decodes image files and embedded images ahead of drawing them, see
+`GLRenderer::preload_image`.
+*/
+
+use once_cell::sync::Lazy;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+
+/// The result of fetching the data of a URL. The error is a description for diagnostics.
+pub(crate) type FetchResult = Result<Vec<u8>, String>;
+
+/// A function that fetches the data of a URL. It is called in a background thread, and should give up when the
+/// flag is set, as the data is no longer needed.
+pub(crate) type FetchFn = dyn Fn(&str, &AtomicBool) -> FetchResult + Send + Sync;
+
+/// The number of background threads that fetch and decode images.
+const WORKER_COUNT: usize = 4;
+
+/// The size of the chunks in which the body of a response is read, between which cancellation is checked.
+#[cfg(feature = "http")]
+const READ_CHUNK_SIZE: usize = 16 * 1024;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The queue of the jobs of the background threads, which are started with the first job.
+static JOBS: Lazy<Mutex<mpsc::Sender<Job>>> = Lazy::new(|| {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..WORKER_COUNT {
+        let receiver = receiver.clone();
+        std::thread::spawn(move || loop {
+            // The lock is released before the job runs, so that the other threads can take the next jobs.
+            let job = receiver.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).recv();
+            match job {
+                Ok(job) => job(),
+                Err(_) => return,
+            }
+        });
+    }
+    Mutex::new(sender)
+});
+
+/// The image that a background thread provides: the encoded data fetched from a URL, or an image that was
+/// decoded ahead of drawing it.
+pub(crate) enum FetchedImage {
+    Encoded(Vec<u8>),
+    Decoded(image::DynamicImage),
+}
+
+thread_local!(
+    /// The function used by fetches started from this thread, see set_fetcher.
+    static FETCHER: RefCell<Arc<FetchFn>> = RefCell::new(Arc::new(default_fetch))
+);
+
+/// Returns true if the path is a URL that is fetched over the network.
+pub(crate) fn is_remote_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Replaces the function that fetches the data of URLs, for the fetches started from the current
+/// thread. This is used by tests to serve images without a network.
+#[cfg(test)]
+pub(crate) fn set_fetcher(fetcher: impl Fn(&str) -> FetchResult + Send + Sync + 'static) {
+    FETCHER.with(|current| {
+        *current.borrow_mut() = Arc::new(move |url: &str, _: &AtomicBool| fetcher(url))
+    });
+}
+
+#[cfg(feature = "http")]
+fn default_fetch(url: &str, cancelled: &AtomicBool) -> FetchResult {
+    let response = ureq::get(url).call().map_err(|err| err.to_string())?;
+    read_until_cancelled(response.into_reader(), cancelled)
+}
+
+/// Reads all the data, in chunks, unless the flag is set in between.
+#[cfg(feature = "http")]
+fn read_until_cancelled(mut reader: impl std::io::Read, cancelled: &AtomicBool) -> FetchResult {
+    let mut data = Vec::new();
+    let mut chunk = vec![0; READ_CHUNK_SIZE];
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err("the fetch was cancelled".into());
+        }
+        match reader.read(&mut chunk) {
+            Ok(0) => return Ok(data),
+            Ok(read) => data.extend_from_slice(&chunk[..read]),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+}
+
+#[cfg(not(feature = "http"))]
+fn default_fetch(url: &str, _cancelled: &AtomicBool) -> FetchResult {
+    Err(format!("cannot load {}: the http feature of the GL backend is not enabled", url))
+}
+
+/// The image that is being fetched or decoded in a background thread. Dropping it cancels the fetch:
+/// the thread then skips or stops it, discards the data and doesn't notify.
+pub(crate) struct PendingFetch {
+    receiver: mpsc::Receiver<Result<FetchedImage, String>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PendingFetch {
+    /// Starts fetching the URL. `notify` is called from the background thread when the result is
+    /// available.
+    pub fn start(url: String, notify: impl FnOnce() + Send + 'static) -> Self {
+        let fetcher = FETCHER.with(|fetcher| fetcher.borrow().clone());
+        Self::spawn_cancellable(
+            move |cancelled| fetcher(&url, cancelled).map(FetchedImage::Encoded),
+            notify,
+        )
+    }
+
+    /// Calls `load` in a background thread, and `notify` from that thread when the result is available.
+    pub fn spawn(
+        load: impl FnOnce() -> Result<FetchedImage, String> + Send + 'static,
+        notify: impl FnOnce() + Send + 'static,
+    ) -> Self {
+        Self::spawn_cancellable(move |_| load(), notify)
+    }
+
+    /// Like `spawn`, with `load` called with the flag that is set when the fetch is cancelled.
+    fn spawn_cancellable(
+        load: impl FnOnce(&AtomicBool) -> Result<FetchedImage, String> + Send + 'static,
+        notify: impl FnOnce() + Send + 'static,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let thread_cancelled = cancelled.clone();
+        let job: Job = Box::new(move || {
+            if thread_cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            let result = load(&thread_cancelled);
+            if thread_cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            if sender.send(result).is_ok() {
+                notify();
+            }
+        });
+        JOBS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).send(job).ok();
+        Self { receiver, cancelled }
+    }
+
+    /// Returns the result of the fetch once it is available.
+    pub fn try_take(&self) -> Option<Result<FetchedImage, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err("the fetch was aborted".into())),
+        }
+    }
+}
+
+impl Drop for PendingFetch {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wait_for_result(fetch: &PendingFetch, notified: &mpsc::Receiver<()>) -> FetchResult {
+        notified.recv_timeout(std::time::Duration::from_secs(10)).unwrap();
+        fetch.try_take().unwrap().map(|fetched| match fetched {
+            FetchedImage::Encoded(data) => data,
+            FetchedImage::Decoded(_) => panic!("fetched data should not be decoded"),
+        })
+    }
+
+    #[test]
+    fn mock_fetcher() {
+        set_fetcher(|url| if url.ends_with("ok") { Ok(vec![1, 2, 3]) } else { Err("404".into()) });
+        let (notify, notified) = mpsc::channel();
+
+        let fetch = PendingFetch::start("http://localhost/ok".into(), {
+            let notify = notify.clone();
+            move || notify.send(()).unwrap()
+        });
+        assert_eq!(wait_for_result(&fetch, &notified), Ok(vec![1, 2, 3]));
+
+        let fetch = PendingFetch::start("http://localhost/missing".into(), move || {
+            notify.send(()).unwrap()
+        });
+        assert_eq!(wait_for_result(&fetch, &notified), Err("404".into()));
+    }
+
+    #[test]
+    fn cancelled_fetch_does_not_notify() {
+        let (release, released) = mpsc::channel::<()>();
+        let released = std::sync::Mutex::new(released);
+        set_fetcher(move |_| {
+            released.lock().unwrap().recv().ok();
+            Ok(Vec::new())
+        });
+        let (notify, notified) = mpsc::channel();
+        let fetch =
+            PendingFetch::start("http://localhost/slow".into(), move || notify.send(()).unwrap());
+        drop(fetch);
+        release.send(()).unwrap();
+        assert!(notified.recv_timeout(std::time::Duration::from_millis(200)).is_err());
+    }
+
+    #[test]
+    fn fetches_share_a_bounded_number_of_threads() {
+        let running = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let max_running = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (notify, notified) = mpsc::channel();
+        let fetches = (0..3 * WORKER_COUNT)
+            .map(|_| {
+                let (running, max_running) = (running.clone(), max_running.clone());
+                let notify = notify.clone();
+                PendingFetch::spawn(
+                    move || {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_running.fetch_max(now, Ordering::SeqCst);
+                        std::thread::sleep(std::time::Duration::from_millis(20));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        Err("done".into())
+                    },
+                    move || notify.send(()).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        for _ in &fetches {
+            notified.recv_timeout(std::time::Duration::from_secs(10)).unwrap();
+        }
+        assert!(max_running.load(Ordering::SeqCst) <= WORKER_COUNT);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn cancelled_download_stops_reading() {
+        // A body that never ends, which is cancelled after its first chunks were read.
+        struct Endless<'a> {
+            reads: usize,
+            cancelled: &'a AtomicBool,
+        }
+        impl std::io::Read for Endless<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.reads += 1;
+                if self.reads == 3 {
+                    self.cancelled.store(true, Ordering::Relaxed);
+                }
+                Ok(buf.len())
+            }
+        }
+        let cancelled = AtomicBool::new(false);
+        let mut body = Endless { reads: 0, cancelled: &cancelled };
+        assert!(read_until_cancelled(&mut body, &cancelled).is_err());
+        assert_eq!(body.reads, 3);
+
+        let data = [1u8; 40000];
+        assert_eq!(read_until_cancelled(&data[..], &AtomicBool::new(false)), Ok(data.to_vec()));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn fetch_from_local_server() {
+        use std::io::{BufRead, Write};
+
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgba8(3, 2)
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/image.png", listener.local_addr().unwrap());
+        let served = png.clone();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\n\r\n",
+                served.len()
+            )
+            .unwrap();
+            stream.write_all(&served).unwrap();
+        });
+
+        let (notify, notified) = mpsc::channel();
+        let fetch = PendingFetch::start(url, move || notify.send(()).unwrap());
+        let data = wait_for_result(&fetch, &notified).unwrap();
+        assert_eq!(data, png);
+        assert_eq!(image::load_from_memory(&data).unwrap().to_rgba8().dimensions(), (3, 2));
+    }
+}
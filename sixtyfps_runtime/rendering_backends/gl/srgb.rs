@@ -0,0 +1,145 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+/*!
+Gamma-correct blending, with an sRGB framebuffer.
+
+Colors are specified in sRGB, where the values are not proportional to the intensity of the light. Blending
+them as they are, as femtovg does, makes translucent colors and anti-aliased edges look too dark: 50% white
+over black ends up as the sRGB value 128, while half the intensity of white is the sRGB value 188.
+
+femtovg has no notion of color spaces: it passes the colors of the paints and the texels of the images
+through to the framebuffer. With `GL_FRAMEBUFFER_SRGB` enabled on an sRGB-capable framebuffer, OpenGL
+treats the values written to it as linear intensities, blends them in linear space, and encodes the result
+to sRGB. So the colors and the pixels of the images are converted to linear intensities before they are
+given to femtovg, and a solid color still ends up as its sRGB value in the window.
+
+The off-screen images that femtovg renders into are not sRGB, so they store the linear intensities as they
+are, and are encoded when drawn to the window. With 8 bits per channel, this loses some precision in dark
+colors.
+*/
+
+use sixtyfps_corelib::graphics::Color;
+
+/// Converts a channel of an sRGB color, in the range 0 to 1, to a linear intensity.
+pub(crate) fn to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Returns the color to paint with to get the given color in an sRGB framebuffer. The alpha stays linear.
+pub(crate) fn linear_color(color: Color) -> femtovg::Color {
+    let color = color.to_argb_f32();
+    femtovg::Color::rgbaf(
+        to_linear(color.red),
+        to_linear(color.green),
+        to_linear(color.blue),
+        color.alpha,
+    )
+}
+
+/// Converts the color channels of the pixels to linear intensities, for uploading images that are drawn
+/// into an sRGB framebuffer. Premultiplied pixels are unpremultiplied for the conversion.
+pub(crate) fn linearize_pixels(pixels: &mut [rgb::RGBA8], premultiplied: bool) {
+    let table: Vec<u8> =
+        (0..=255).map(|value| (to_linear(value as f32 / 255.) * 255.).round() as u8).collect();
+    for pixel in pixels {
+        if !premultiplied || pixel.a == 255 {
+            pixel.r = table[pixel.r as usize];
+            pixel.g = table[pixel.g as usize];
+            pixel.b = table[pixel.b as usize];
+        } else if pixel.a > 0 {
+            let alpha = pixel.a as f32 / 255.;
+            let convert = |value: u8| {
+                (to_linear((value as f32 / 255. / alpha).min(1.)) * alpha * 255.).round() as u8
+            };
+            pixel.r = convert(pixel.r);
+            pixel.g = convert(pixel.g);
+            pixel.b = convert(pixel.b);
+        }
+    }
+}
+
+/// Enables the conversion of the colors written to the framebuffer of the current context from linear
+/// intensities to sRGB. Returns false if the framebuffer is not sRGB-capable, in which case colors are
+/// blended as they are.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn enable_framebuffer_srgb(
+    context: &glutin::WindowedContext<glutin::PossiblyCurrent>,
+) -> bool {
+    const GL_FRAMEBUFFER_SRGB: u32 = 0x8DB9;
+    if !context.get_pixel_format().srgb {
+        return false;
+    }
+    let enable = context.get_proc_address("glEnable");
+    if enable.is_null() {
+        return false;
+    }
+    unsafe {
+        let enable: unsafe extern "system" fn(u32) = std::mem::transmute(enable);
+        enable(GL_FRAMEBUFFER_SRGB);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Encodes a linear intensity to sRGB, as the framebuffer does.
+    fn to_srgb(value: f32) -> f32 {
+        if value <= 0.0031308 {
+            value * 12.92
+        } else {
+            1.055 * value.powf(1. / 2.4) - 0.055
+        }
+    }
+
+    // Blends the source over the destination the way OpenGL does with the blend function of femtovg, and
+    // encodes the result for the framebuffer, as an 8 bit value.
+    fn blend_over(source: f32, alpha: f32, destination: f32, srgb_framebuffer: bool) -> u8 {
+        let blended = source * alpha + destination * (1. - alpha);
+        let encoded = if srgb_framebuffer { to_srgb(blended) } else { blended };
+        (encoded * 255.).round() as u8
+    }
+
+    #[test]
+    fn solid_colors_keep_their_value() {
+        for value in 0..=255u8 {
+            let color = linear_color(Color::from_rgb_u8(value, 0, 255));
+            assert_eq!((to_srgb(color.r) * 255.).round() as u8, value);
+            assert_eq!((to_srgb(color.b) * 255.).round() as u8, 255);
+        }
+    }
+
+    #[test]
+    fn translucent_white_over_black_is_blended_linearly() {
+        let white = linear_color(Color::from_argb_u8(128, 255, 255, 255));
+        let black = linear_color(Color::from_rgb_u8(0, 0, 0));
+        assert_eq!(blend_over(white.r, white.a, black.r, true), 188);
+        // Blending the sRGB values as they are gives the naive midpoint.
+        assert_eq!(blend_over(1., 128. / 255., 0., false), 128);
+    }
+
+    #[test]
+    fn pixels_are_linearized() {
+        let mut pixels = [
+            rgb::RGBA8 { r: 255, g: 188, b: 0, a: 255 },
+            rgb::RGBA8 { r: 94, g: 0, b: 128, a: 128 },
+            rgb::RGBA8 { r: 0, g: 0, b: 0, a: 0 },
+        ];
+        linearize_pixels(&mut pixels, true);
+        assert_eq!(pixels[0], rgb::RGBA8 { r: 255, g: 128, b: 0, a: 255 });
+        assert_eq!(pixels[1], rgb::RGBA8 { r: 64, g: 0, b: 128, a: 128 });
+        assert_eq!(pixels[2], rgb::RGBA8 { r: 0, g: 0, b: 0, a: 0 });
+    }
+}
@@ -9,27 +9,147 @@
 LICENSE END */
 #![cfg(feature = "svg")]
 
+// The SVG images are rasterized at their intrinsic size, unless a pixel size is given. In that case the
+// image is scaled uniformly to cover that size, as the aspect ratio is preserved.
+
 #[cfg(not(target_arch = "wasm32"))]
-pub fn load_from_path(path: &std::path::Path) -> Result<image::DynamicImage, usvg::Error> {
-    load_image(usvg::Tree::from_file(path, &Default::default())?)
+pub fn load_from_path(
+    path: &std::path::Path,
+    size: Option<(u32, u32)>,
+) -> Result<image::DynamicImage, usvg::Error> {
+    load_image(usvg::Tree::from_file(path, &Default::default())?, size)
+}
+
+pub fn load_from_data(
+    slice: &[u8],
+    size: Option<(u32, u32)>,
+) -> Result<image::DynamicImage, usvg::Error> {
+    load_image(usvg::Tree::from_data(slice, &Default::default())?, size)
+}
+
+/// How an SVG image is fitted into the area it is drawn in, following the `preserveAspectRatio` attribute of the
+/// root element of the SVG file. Without the attribute, the image keeps its aspect ratio and is centered.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AspectRatio {
+    /// The image is stretched to the area, for `none`.
+    pub stretch: bool,
+    /// The image keeping its aspect ratio covers the area and is cut off, for `slice`. Otherwise the whole image
+    /// fits into the area, for `meet`.
+    pub slice: bool,
+    /// Where the image is placed horizontally and vertically in the area, as the fraction of the space left over
+    /// that lies before it.
+    pub alignment: (f32, f32),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn aspect_ratio_from_path(path: &std::path::Path) -> Result<AspectRatio, usvg::Error> {
+    Ok(aspect_ratio(&usvg::Tree::from_file(path, &Default::default())?))
+}
+
+pub fn aspect_ratio_from_data(slice: &[u8]) -> Result<AspectRatio, usvg::Error> {
+    Ok(aspect_ratio(&usvg::Tree::from_data(slice, &Default::default())?))
 }
 
-pub fn load_from_data(slice: &[u8]) -> Result<image::DynamicImage, usvg::Error> {
-    load_image(usvg::Tree::from_data(slice, &Default::default())?)
+fn aspect_ratio(tree: &usvg::Tree) -> AspectRatio {
+    use usvg::Align;
+    let aspect = tree.svg_node().view_box.aspect;
+    let alignment = match aspect.align {
+        Align::None | Align::XMidYMid => (0.5, 0.5),
+        Align::XMinYMin => (0., 0.),
+        Align::XMidYMin => (0.5, 0.),
+        Align::XMaxYMin => (1., 0.),
+        Align::XMinYMid => (0., 0.5),
+        Align::XMaxYMid => (1., 0.5),
+        Align::XMinYMax => (0., 1.),
+        Align::XMidYMax => (0.5, 1.),
+        Align::XMaxYMax => (1., 1.),
+    };
+    AspectRatio { stretch: aspect.align == Align::None, slice: aspect.slice, alignment }
 }
 
-fn load_image(tree: usvg::Tree) -> Result<image::DynamicImage, usvg::Error> {
-    // FIXME: get the size from the actual image
-    let size = tree.svg_node().size.to_screen_size();
-    //let mut result = image::DynamicImage::new_rgba8(size.width(), size.height());
+fn load_image(
+    tree: usvg::Tree,
+    size: Option<(u32, u32)>,
+) -> Result<image::DynamicImage, usvg::Error> {
+    let intrinsic_size = tree.svg_node().size;
+    let fit_to = match size {
+        Some((width, height)) => usvg::FitTo::Zoom(f64::max(
+            width as f64 / intrinsic_size.width(),
+            height as f64 / intrinsic_size.height(),
+        ) as f32),
+        None => usvg::FitTo::Original,
+    };
+    let size = fit_to.fit_to(intrinsic_size.to_screen_size()).ok_or(usvg::Error::InvalidSize)?;
     let mut buffer =
         vec![0u8; size.width() as usize * size.height() as usize * tiny_skia::BYTES_PER_PIXEL];
     let skya_buffer =
         tiny_skia::PixmapMut::from_bytes(buffer.as_mut_slice(), size.width(), size.height())
             .ok_or(usvg::Error::InvalidSize)?;
-    resvg::render(&tree, usvg::FitTo::Original, skya_buffer);
-    Ok(image::DynamicImage::ImageRgba8(
-        image::RgbaImage::from_raw(size.width(), size.height(), buffer)
-            .ok_or(usvg::Error::InvalidSize)?,
-    ))
+    resvg::render(&tree, fit_to, skya_buffer);
+    // tiny-skia renders with premultiplied alpha, while decoded images have straight alpha.
+    let mut pixels = image::RgbaImage::from_raw(size.width(), size.height(), buffer)
+        .ok_or(usvg::Error::InvalidSize)?;
+    super::unpremultiply_alpha(&mut pixels);
+    Ok(image::DynamicImage::ImageRgba8(pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &[u8] = br#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="20">
+        <rect x="0" y="0" width="10" height="20" fill="red"/>
+    </svg>"#;
+
+    #[test]
+    fn rasterize_at_size() {
+        let intrinsic = load_from_data(SQUARE, None).unwrap();
+        assert_eq!(image::GenericImageView::dimensions(&intrinsic), (10, 20));
+
+        let small = load_from_data(SQUARE, Some((25, 50))).unwrap();
+        let large = load_from_data(SQUARE, Some((40, 80))).unwrap();
+        assert_eq!(image::GenericImageView::dimensions(&small), (25, 50));
+        assert_eq!(image::GenericImageView::dimensions(&large), (40, 80));
+        assert_eq!(large.to_rgba8().get_pixel(39, 79).0, [255, 0, 0, 255]);
+
+        // The aspect ratio is preserved, the image covers the requested size.
+        let stretched = load_from_data(SQUARE, Some((40, 20))).unwrap();
+        assert_eq!(image::GenericImageView::dimensions(&stretched), (40, 80));
+    }
+
+    #[test]
+    fn aspect_ratio_follows_preserve_aspect_ratio() {
+        let svg = |preserve_aspect_ratio: &str| {
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="20" viewBox="0 0 10 20" {}>
+                    <rect x="0" y="0" width="10" height="20" fill="red"/>
+                </svg>"#,
+                preserve_aspect_ratio
+            )
+        };
+        let centered = AspectRatio { stretch: false, slice: false, alignment: (0.5, 0.5) };
+        assert_eq!(aspect_ratio_from_data(svg("").as_bytes()).unwrap(), centered);
+        assert_eq!(aspect_ratio_from_data(SQUARE).unwrap(), centered);
+        assert!(
+            aspect_ratio_from_data(svg(r#"preserveAspectRatio="none""#).as_bytes())
+                .unwrap()
+                .stretch
+        );
+        assert_eq!(
+            aspect_ratio_from_data(svg(r#"preserveAspectRatio="xMaxYMin slice""#).as_bytes())
+                .unwrap(),
+            AspectRatio { stretch: false, slice: true, alignment: (1., 0.) }
+        );
+    }
+
+    #[test]
+    fn rasterized_pixels_have_straight_alpha() {
+        let translucent = br#"<svg xmlns="http://www.w3.org/2000/svg" width="2" height="2">
+            <rect x="0" y="0" width="2" height="2" fill="red" fill-opacity="0.5"/>
+        </svg>"#;
+        let image = load_from_data(translucent, None).unwrap();
+        let [red, green, blue, alpha] = image.to_rgba8().get_pixel(0, 0).0;
+        assert!((alpha as i32 - 128).abs() <= 1, "{}", alpha);
+        assert_eq!((red, green, blue), (255, 0, 0));
+    }
 }
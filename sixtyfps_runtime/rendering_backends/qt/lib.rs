@@ -129,6 +129,42 @@ fn register_application_font_from_memory(
         Ok(())
     }
 
+    fn register_application_font_from_path(
+        &'static self,
+        _path: &std::path::Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        #[cfg(not(no_qt))]
+        {
+            use cpp::cpp;
+            let data = qttypes::QByteArray::from(std::fs::read(_path)?.as_slice());
+            let id = cpp! {unsafe [data as "QByteArray"] -> i32 as "int" {
+                ensure_initialized();
+                return QFontDatabase::addApplicationFontFromData(data);
+            } };
+            if id < 0 {
+                return Err(format!("{} does not contain a supported font", _path.display()).into());
+            }
+        };
+        Ok(())
+    }
+
+    fn available_font_families(&'static self) -> Vec<String> {
+        #[cfg(not(no_qt))]
+        {
+            use cpp::cpp;
+            let families: qttypes::QString = cpp! {unsafe [] -> qttypes::QString as "QString" {
+                ensure_initialized();
+                return QFontDatabase().families().join(QLatin1Char('\n'));
+            } };
+            let mut families = String::from(families).lines().map(String::from).collect::<Vec<_>>();
+            families.sort();
+            families.dedup();
+            return families;
+        }
+        #[cfg(no_qt)]
+        Vec::new()
+    }
+
     fn set_clipboard_text(&'static self, _text: String) {
         #[cfg(not(no_qt))]
         {
@@ -160,4 +196,78 @@ fn clipboard_text(&'static self) -> Option<String> {
         }
         None
     }
+
+    fn set_primary_selection_text(&'static self, _text: String) {
+        #[cfg(not(no_qt))]
+        {
+            use cpp::cpp;
+            let text: qttypes::QString = _text.into();
+            cpp! {unsafe [text as "QString"] {
+                ensure_initialized();
+                if (QGuiApplication::clipboard()->supportsSelection())
+                    QGuiApplication::clipboard()->setText(text, QClipboard::Selection);
+            } }
+        }
+    }
+
+    fn primary_selection_text(&'static self) -> Option<String> {
+        #[cfg(not(no_qt))]
+        {
+            use cpp::cpp;
+            let has_text = cpp! {unsafe [] -> bool as "bool" {
+                ensure_initialized();
+                if (!QGuiApplication::clipboard()->supportsSelection())
+                    return false;
+                return QGuiApplication::clipboard()->mimeData(QClipboard::Selection)->hasText();
+            } };
+            if has_text {
+                return Some(
+                    cpp! { unsafe [] -> qttypes::QString as "QString" {
+                        return QGuiApplication::clipboard()->text(QClipboard::Selection);
+                    }}
+                    .into(),
+                );
+            }
+        }
+        None
+    }
+
+    fn set_clipboard_image(&'static self, _rgba: &[u8], _width: u32, _height: u32) {
+        #[cfg(not(no_qt))]
+        if _rgba.len() == _width as usize * _height as usize * 4 {
+            use cpp::cpp;
+            let data = qttypes::QByteArray::from(_rgba);
+            let (width, height) = (_width, _height);
+            cpp! {unsafe [data as "QByteArray", width as "int", height as "int"] {
+                ensure_initialized();
+                QImage image(reinterpret_cast<const uchar *>(data.constData()), width, height, width * 4,
+                             QImage::Format_RGBA8888);
+                QGuiApplication::clipboard()->setImage(image.copy());
+            } }
+        }
+    }
+
+    fn clipboard_image(&'static self) -> Option<(Vec<u8>, u32, u32)> {
+        #[cfg(not(no_qt))]
+        {
+            use cpp::cpp;
+            let mut width: u32 = 0;
+            let mut height: u32 = 0;
+            let (width_ptr, height_ptr) = (&mut width, &mut height);
+            let data = cpp! {unsafe [width_ptr as "uint32_t*", height_ptr as "uint32_t*"] -> qttypes::QByteArray as "QByteArray" {
+                ensure_initialized();
+                QImage image = QGuiApplication::clipboard()->image().convertToFormat(QImage::Format_RGBA8888);
+                *width_ptr = image.width();
+                *height_ptr = image.height();
+                QByteArray data;
+                for (int y = 0; y < image.height(); ++y)
+                    data.append(reinterpret_cast<const char *>(image.constScanLine(y)), image.width() * 4);
+                return data;
+            } };
+            if width > 0 && height > 0 {
+                return Some((data.to_slice().to_vec(), width, height));
+            }
+        }
+        None
+    }
 }
@@ -9,10 +9,10 @@
 LICENSE END */
 
 use cpp::*;
-use items::{ImageFit, TextHorizontalAlignment, TextVerticalAlignment};
+use items::{ImageFit, ImageRendering, TextHorizontalAlignment, TextVerticalAlignment};
 use sixtyfps_corelib::graphics::{Color, FontRequest, Point, RenderingCache};
 use sixtyfps_corelib::input::{InternalKeyCode, KeyEvent, KeyEventType, MouseEventType};
-use sixtyfps_corelib::item_rendering::{CachedRenderingData, ItemRenderer};
+use sixtyfps_corelib::item_rendering::{CachedRenderingData, ImageScaleTracker, ItemRenderer};
 use sixtyfps_corelib::items::{self, ItemRef, TextOverflow, TextWrap};
 use sixtyfps_corelib::properties::PropertyTracker;
 use sixtyfps_corelib::slice::Slice;
@@ -221,6 +221,7 @@ enum QtRenderingCacheItem {
 struct QtItemRenderer<'a> {
     painter: &'a mut QPainter,
     cache: QtRenderingCache,
+    image_scales: &'a RefCell<ImageScaleTracker>,
 }
 
 impl ItemRenderer for QtItemRenderer<'_> {
@@ -235,12 +236,14 @@ fn draw_rectangle(&mut self, pos: Point, rect: Pin<&items::Rectangle>) {
     }
 
     fn draw_border_rectangle(&mut self, pos: Point, rect: std::pin::Pin<&items::BorderRectangle>) {
+        // FIXME: hairline borders are not supported yet, they are drawn with the border width.
         self.draw_rectangle_impl(
             get_geometry!(pos, items::BorderRectangle, rect),
             rect.color(),
             rect.border_color(),
             rect.border_width(),
             rect.border_radius(),
+            rect.stroke_alignment(),
         );
     }
 
@@ -252,6 +255,14 @@ fn draw_image(&mut self, pos: Point, image: Pin<&items::Image>) {
             dest_rect,
             None,
             image.image_fit(),
+            items::image_alignment_factors(
+                image.horizontal_alignment(),
+                image.vertical_alignment(),
+            ),
+            (image.rotation_angle(), image.flip_horizontal(), image.flip_vertical()),
+            image.colorize(),
+            image.image_rendering(),
+            image.opacity(),
         );
     }
 
@@ -269,6 +280,14 @@ fn draw_clipped_image(&mut self, pos: Point, image: Pin<&items::ClippedImage>) {
             dest_rect,
             Some(source_rect),
             image.image_fit(),
+            items::image_alignment_factors(
+                image.horizontal_alignment(),
+                image.vertical_alignment(),
+            ),
+            (image.rotation_angle(), image.flip_horizontal(), image.flip_vertical()),
+            image.colorize(),
+            image.image_rendering(),
+            image.opacity(),
         );
     }
 
@@ -281,8 +300,10 @@ fn draw_text(&mut self, pos: Point, text: std::pin::Pin<&items::Text>) {
             TextHorizontalAlignment::left => key_generated::Qt_AlignmentFlag_AlignLeft,
             TextHorizontalAlignment::center => key_generated::Qt_AlignmentFlag_AlignHCenter,
             TextHorizontalAlignment::right => key_generated::Qt_AlignmentFlag_AlignRight,
+            TextHorizontalAlignment::justify => key_generated::Qt_AlignmentFlag_AlignJustify,
         } | match text.vertical_alignment() {
             TextVerticalAlignment::top => key_generated::Qt_AlignmentFlag_AlignTop,
+            // FIXME: vertical_centering is not supported, Qt always centers the line boxes.
             TextVerticalAlignment::center => key_generated::Qt_AlignmentFlag_AlignVCenter,
             TextVerticalAlignment::bottom => key_generated::Qt_AlignmentFlag_AlignBottom,
         } | match text.wrap() {
@@ -290,17 +311,23 @@ fn draw_text(&mut self, pos: Point, text: std::pin::Pin<&items::Text>) {
             TextWrap::word_wrap => key_generated::Qt_TextFlag_TextWordWrap,
         };
         let elide = text.overflow() == TextOverflow::elide && text.wrap() == TextWrap::no_wrap;
+        // The shadow is drawn without blur.
+        let shadow_color: u32 = text.drop_shadow_color().as_argb_encoded();
+        let shadow_offset = qttypes::QPointF {
+            x: text.drop_shadow_offset_x() as _,
+            y: text.drop_shadow_offset_y() as _,
+        };
         let painter: &mut QPainter = &mut *self.painter;
-        cpp! { unsafe [painter as "QPainter*", rect as "QRectF", color as "QRgb", string as "QString", flags as "int", font as "QFont", elide as "bool"] {
+        cpp! { unsafe [painter as "QPainter*", rect as "QRectF", color as "QRgb", shadow_color as "QRgb", shadow_offset as "QPointF", string as "QString", flags as "int", font as "QFont", elide as "bool"] {
             painter->setFont(font);
-            painter->setPen(QColor{color});
             painter->setBrush(Qt::NoBrush);
-            if (!elide) {
-                painter->drawText(rect, flags, string);
-            } else {
-                auto elided = QFontMetrics(font).elidedText(string, Qt::ElideRight, rect.width());
-                painter->drawText(rect, flags, elided);
+            auto text = elide ? QFontMetrics(font).elidedText(string, Qt::ElideRight, rect.width()) : string;
+            if (qAlpha(shadow_color)) {
+                painter->setPen(QColor::fromRgba(shadow_color));
+                painter->drawText(rect.translated(shadow_offset), flags, text);
             }
+            painter->setPen(QColor{color});
+            painter->drawText(rect, flags, text);
         }}
     }
 
@@ -311,11 +338,16 @@ fn draw_text_input(&mut self, pos: Point, text_input: std::pin::Pin<&items::Text
             text_input.selection_foreground_color().as_argb_encoded();
         let selection_background_color: u32 =
             text_input.selection_background_color().as_argb_encoded();
+        let invert_selection: bool = text_input.invert_selection();
 
-        let string: qttypes::QString = text_input.text().as_str().into();
+        let text = text_input.displayed_text();
+        let string: qttypes::QString = text.text.as_str().into();
         let font: QFont = get_font(text_input.font_request());
         let flags = match text_input.horizontal_alignment() {
-            TextHorizontalAlignment::left => key_generated::Qt_AlignmentFlag_AlignLeft,
+            // The lines of a TextInput don't wrap, so there is nothing to justify.
+            TextHorizontalAlignment::left | TextHorizontalAlignment::justify => {
+                key_generated::Qt_AlignmentFlag_AlignLeft
+            }
             TextHorizontalAlignment::center => key_generated::Qt_AlignmentFlag_AlignHCenter,
             TextHorizontalAlignment::right => key_generated::Qt_AlignmentFlag_AlignRight,
         } | match text_input.vertical_alignment() {
@@ -323,10 +355,19 @@ fn draw_text_input(&mut self, pos: Point, text_input: std::pin::Pin<&items::Text
             TextVerticalAlignment::center => key_generated::Qt_AlignmentFlag_AlignVCenter,
             TextVerticalAlignment::bottom => key_generated::Qt_AlignmentFlag_AlignBottom,
         };
-        let cursor_position: i32 = text_input.cursor_position();
-        let anchor_position: i32 = text_input.anchor_position();
+        // QTextLayout takes positions in UTF-16 code units of the displayed text.
+        let utf16_position = |byte_offset: i32| -> i32 {
+            let offset = text.displayed_offset(byte_offset.max(0) as usize).min(text.text.len());
+            text.text
+                .as_str()
+                .get(..offset)
+                .map_or(0, |prefix| prefix.encode_utf16().count() as i32)
+        };
+        let cursor_position: i32 = utf16_position(text_input.cursor_position());
+        let anchor_position: i32 = utf16_position(text_input.anchor_position());
         let text_cursor_width: f32 =
             if text_input.cursor_visible() { text_input.text_cursor_width() } else { 0. };
+        let cursor_color: u32 = text_input.effective_cursor_color().as_argb_encoded();
 
         let painter: &mut QPainter = &mut *self.painter;
         cpp! { unsafe [
@@ -335,12 +376,14 @@ fn draw_text_input(&mut self, pos: Point, text_input: std::pin::Pin<&items::Text
                 color as "QRgb",
                 selection_foreground_color as "QRgb",
                 selection_background_color as "QRgb",
+                invert_selection as "bool",
                 string as "QString",
                 flags as "int",
                 font as "QFont",
                 cursor_position as "int",
                 anchor_position as "int",
-                text_cursor_width as "float"] {
+                text_cursor_width as "float",
+                cursor_color as "QRgb"] {
             Q_UNUSED(flags); // FIXME
             QTextLayout layout(string, font);
             layout.beginLayout();
@@ -348,7 +391,7 @@ fn draw_text_input(&mut self, pos: Point, text_input: std::pin::Pin<&items::Text
             layout.endLayout();
             painter->setPen(QColor{color});
             QVector<QTextLayout::FormatRange> selections;
-            if (anchor_position != cursor_position) {
+            if (anchor_position != cursor_position && !invert_selection) {
                 QTextCharFormat fmt;
                 fmt.setBackground(QColor(selection_background_color));
                 fmt.setForeground(QColor(selection_foreground_color));
@@ -359,7 +402,18 @@ fn draw_text_input(&mut self, pos: Point, text_input: std::pin::Pin<&items::Text
                 };
             }
             layout.draw(painter, rect.topLeft(), selections);
+            if (anchor_position != cursor_position && invert_selection) {
+                QTextLine line = layout.lineAt(0);
+                qreal start_x = line.cursorToX(std::min(anchor_position, cursor_position));
+                qreal end_x = line.cursorToX(std::max(anchor_position, cursor_position));
+                painter->save();
+                painter->setCompositionMode(QPainter::CompositionMode_Difference);
+                painter->fillRect(QRectF(rect.topLeft() + QPointF(start_x, line.y()),
+                                         QSizeF(end_x - start_x, line.height())), Qt::white);
+                painter->restore();
+            }
             if (text_cursor_width > 0) {
+                painter->setPen(QColor::fromRgba(cursor_color));
                 layout.drawCursor(painter, rect.topLeft(), cursor_position, text_cursor_width);
             }
         }}
@@ -373,6 +427,8 @@ fn draw_path(&mut self, pos: Point, path: Pin<&items::Path>) {
         // FIXME: handle width/height
         //let rect: qttypes::QRectF = get_geometry!(pos, items::Path, path);
         let pos = qttypes::QPoint { x: (pos.x + path.x()) as _, y: (pos.y + path.y()) as _ };
+        // FIXME: the stroke alignment is not supported yet, the stroke is always centered on the path.
+        // FIXME: hairline strokes are not supported yet, they are drawn with the stroke width.
         let fill_color: u32 = path.fill_color().as_argb_encoded();
         let stroke_color: u32 = path.stroke_color().as_argb_encoded();
         let stroke_width: f32 = path.stroke_width();
@@ -423,6 +479,40 @@ fn from(p: Point) -> Self {
         }}
     }
 
+    fn draw_line(&mut self, pos: Point, line: Pin<&items::Line>) {
+        let stroke_width: f32 = line.stroke_width();
+        if stroke_width <= 0. {
+            return;
+        }
+        let pos = qttypes::QPoint { x: (pos.x + line.x()) as _, y: (pos.y + line.y()) as _ };
+        let stroke_color: u32 = line.stroke_color().as_argb_encoded();
+        let line_cap: u32 = line.stroke_line_cap() as u32;
+        let mut painter_path = QPainterPath::default();
+        for (from, to) in line.segments() {
+            painter_path.move_to(qttypes::QPointF { x: from.x as _, y: from.y as _ });
+            painter_path.line_to(qttypes::QPointF { x: to.x as _, y: to.y as _ });
+        }
+
+        let painter: &mut QPainter = &mut *self.painter;
+        cpp! { unsafe [
+                painter as "QPainter*",
+                pos as "QPoint",
+                mut painter_path as "QPainterPath",
+                stroke_color as "QRgb",
+                stroke_width as "float",
+                line_cap as "uint"] {
+            painter->save();
+            auto cleanup = qScopeGuard([&] { painter->restore(); });
+            painter->translate(pos);
+            QPen pen(QColor::fromRgba(stroke_color), stroke_width);
+            const Qt::PenCapStyle caps[] = { Qt::FlatCap, Qt::RoundCap, Qt::SquareCap };
+            pen.setCapStyle(caps[line_cap]);
+            painter->setPen(pen);
+            painter->setBrush(Qt::NoBrush);
+            painter->drawPath(painter_path);
+        }}
+    }
+
     fn draw_box_shadow(&mut self, pos: Point, box_shadow: Pin<&items::BoxShadow>) {
         // This could be improved to use a guassian blur.
 
@@ -436,6 +526,7 @@ fn draw_box_shadow(&mut self, pos: Point, box_shadow: Pin<&items::BoxShadow>) {
             Color::default(),
             0.,
             box_shadow.border_radius(),
+            items::StrokeAlignment::inside,
         );
     }
 
@@ -446,12 +537,57 @@ fn combine_clip(&mut self, pos: Point, clip: Pin<&items::Clip>) {
             width: clip.width() as _,
             height: clip.height() as _,
         };
+        // FIXME: fade_vertical and fade_horizontal are not supported yet, the children are cut off at the edges.
+        let ellipse = clip.shape() == items::ClipShape::ellipse;
         let painter: &mut QPainter = &mut *self.painter;
-        cpp! { unsafe [painter as "QPainter*", clip_rect as "QRectF"] {
-            painter->setClipRect(clip_rect, Qt::IntersectClip);
+        cpp! { unsafe [painter as "QPainter*", clip_rect as "QRectF", ellipse as "bool"] {
+            if (ellipse) {
+                QPainterPath path;
+                path.addEllipse(clip_rect);
+                painter->setClipPath(path, Qt::IntersectClip);
+            } else {
+                painter->setClipRect(clip_rect, Qt::IntersectClip);
+            }
         }}
     }
 
+    fn combine_transform(&mut self, pos: Point, transform: Pin<&items::Transform>) {
+        let matrix = transform.children_transform(pos).to_array();
+        let (m11, m12, m21, m22, dx, dy) =
+            (matrix[0], matrix[1], matrix[2], matrix[3], matrix[4], matrix[5]);
+        let painter: &mut QPainter = &mut *self.painter;
+        cpp! { unsafe [painter as "QPainter*", m11 as "float", m12 as "float", m21 as "float",
+                       m22 as "float", dx as "float", dy as "float"] {
+            painter->setTransform(QTransform(m11, m12, m21, m22, dx, dy), true);
+        }}
+    }
+
+    fn combine_desaturate(&mut self, _pos: Point, _desaturate: Pin<&items::Desaturate>) {
+        // FIXME: not supported yet, the children are drawn with their colors unchanged.
+    }
+
+    fn draw_layer(
+        &mut self,
+        _pos: Point,
+        _layer: Pin<&items::Layer>,
+        render_children: &mut dyn FnMut(&mut dyn ItemRenderer),
+    ) {
+        // `cache_rendering_hint` is only a hint, and this backend ignores it: the children are drawn directly,
+        // in every frame, exactly as if they were not in a layer. Caching them would mean rendering them into a
+        // QPixmap with a painter of its own, which the QPainter wrapper doesn't support yet. The items that are
+        // costly to draw with QPainter, such as the images, already cache their pixmaps on their own.
+        render_children(self)
+    }
+
+    fn draw_custom_rendering(
+        &mut self,
+        _pos: Point,
+        _custom_rendering: Pin<&items::CustomRendering>,
+    ) {
+        // The draw handlers are registered with the GL backend and draw with femtovg, so there is nothing to
+        // call here: the element is left empty, and the elements behind it show through.
+    }
+
     fn save_state(&mut self) {
         self.painter.save_state()
     }
@@ -494,7 +630,19 @@ fn load_image_from_resource(resource: Resource) -> Option<qttypes::QPixmap> {
         Resource::None => return None,
         Resource::AbsoluteFilePath(path) => (true, qttypes::QByteArray::from(path.as_str())),
         Resource::EmbeddedData(data) => (false, qttypes::QByteArray::from(data.as_slice())),
-        Resource::EmbeddedRgbaImage { .. } => todo!(),
+        Resource::EmbeddedRgbaImage { width, height, data } => {
+            if data.len() != width as usize * height as usize {
+                return None;
+            }
+            // The pixels are ARGB in native byte order, which is what QImage::Format_ARGB32 expects.
+            let data = data.as_ptr();
+            return Some(
+                cpp! { unsafe [data as "const uint32_t*", width as "int", height as "int"] -> qttypes::QPixmap as "QPixmap" {
+                    return QPixmap::fromImage(QImage(reinterpret_cast<const uchar *>(data), width, height, width * 4,
+                                                     QImage::Format_ARGB32));
+                }},
+            );
+        }
     };
     Some(cpp! { unsafe [data as "QByteArray", is_path as "bool"] -> qttypes::QPixmap as "QPixmap" {
         QPixmap img;
@@ -503,6 +651,37 @@ fn load_image_from_resource(resource: Resource) -> Option<qttypes::QPixmap> {
     }})
 }
 
+/// Multiplies the pixels with the given color. Pixmaps where all visible pixels are black, such as icons that
+/// only carry an alpha channel, take the color itself and keep their alpha.
+fn colorize_pixmap(pixmap: &qttypes::QPixmap, color: Color) -> qttypes::QPixmap {
+    let color: u32 = color.as_argb_encoded();
+    cpp! { unsafe [pixmap as "const QPixmap*", color as "QRgb"] -> qttypes::QPixmap as "QPixmap" {
+        QImage image = pixmap->toImage().convertToFormat(QImage::Format_ARGB32);
+        bool is_alpha_mask = true;
+        for (int y = 0; y < image.height() && is_alpha_mask; ++y) {
+            const QRgb *line = reinterpret_cast<const QRgb *>(image.constScanLine(y));
+            for (int x = 0; x < image.width(); ++x) {
+                if (qAlpha(line[x]) != 0 && (line[x] & RGB_MASK) != 0) {
+                    is_alpha_mask = false;
+                    break;
+                }
+            }
+        }
+        auto multiply = [](int component, int factor) { return (component * factor + 127) / 255; };
+        for (int y = 0; y < image.height(); ++y) {
+            QRgb *line = reinterpret_cast<QRgb *>(image.scanLine(y));
+            for (int x = 0; x < image.width(); ++x) {
+                QRgb p = line[x];
+                line[x] = is_alpha_mask
+                    ? qRgba(qRed(color), qGreen(color), qBlue(color), multiply(qAlpha(p), qAlpha(color)))
+                    : qRgba(multiply(qRed(p), qRed(color)), multiply(qGreen(p), qGreen(color)),
+                            multiply(qBlue(p), qBlue(color)), multiply(qAlpha(p), qAlpha(color)));
+            }
+        }
+        return QPixmap::fromImage(image);
+    }}
+}
+
 impl QtItemRenderer<'_> {
     fn draw_image_impl(
         &mut self,
@@ -511,7 +690,15 @@ fn draw_image_impl(
         dest_rect: qttypes::QRectF,
         source_rect: Option<qttypes::QRectF>,
         image_fit: ImageFit,
+        (align_x, align_y): (f32, f32),
+        (rotation_angle, flip_horizontal, flip_vertical): (f32, bool, bool),
+        colorize: Color,
+        image_rendering: ImageRendering,
+        opacity: f32,
     ) {
+        if opacity <= 0. {
+            return;
+        }
         let cached = item_cache.ensure_up_to_date(&mut self.cache.borrow_mut(), || {
             load_image_from_resource(source_property.get())
                 .map_or(QtRenderingCacheItem::Invalid, |pixmap| {
@@ -522,6 +709,14 @@ fn draw_image_impl(
             QtRenderingCacheItem::Pixmap(pixmap) => pixmap,
             _ => return,
         };
+        // FIXME: cache the colorized pixmap instead of tinting it for every frame
+        let colorized_pixmap;
+        let pixmap = if colorize.alpha() > 0 {
+            colorized_pixmap = colorize_pixmap(pixmap, colorize);
+            &colorized_pixmap
+        } else {
+            pixmap
+        };
         let image_size = pixmap.size();
         let mut source_rect = source_rect.unwrap_or_else(|| qttypes::QRectF {
             x: 0.,
@@ -529,6 +724,18 @@ fn draw_image_impl(
             width: image_size.width as _,
             height: image_size.height as _,
         });
+        // The image is drawn rotated around the center of the element. For rotations by 90 or 270
+        // degrees, it needs to fit into the element with width and height swapped.
+        let center = qttypes::QPointF {
+            x: dest_rect.x + dest_rect.width / 2.,
+            y: dest_rect.y + dest_rect.height / 2.,
+        };
+        let mut dest_rect = dest_rect;
+        if (rotation_angle / 90.).round() as i32 % 2 != 0 {
+            std::mem::swap(&mut dest_rect.width, &mut dest_rect.height);
+        }
+        dest_rect.x = -dest_rect.width / 2.;
+        dest_rect.y = -dest_rect.height / 2.;
         match image_fit {
             sixtyfps_corelib::items::ImageFit::fill => (),
             sixtyfps_corelib::items::ImageFit::contain => {
@@ -537,17 +744,64 @@ fn draw_image_impl(
                     dest_rect.height / source_rect.height,
                 );
                 if source_rect.width > dest_rect.width / ratio {
-                    source_rect.x += (source_rect.width - dest_rect.width / ratio) / 2.;
+                    source_rect.x +=
+                        (source_rect.width - dest_rect.width / ratio) * align_x as qttypes::qreal;
                     source_rect.width = dest_rect.width / ratio;
                 }
                 if source_rect.height > dest_rect.height / ratio {
-                    source_rect.y += (source_rect.height - dest_rect.height / ratio) / 2.;
+                    source_rect.y +=
+                        (source_rect.height - dest_rect.height / ratio) * align_y as qttypes::qreal;
                     source_rect.height = dest_rect.height / ratio;
                 }
             }
+            sixtyfps_corelib::items::ImageFit::none => {
+                // One image pixel per logical pixel, aligned and cut off at the bounds of the element.
+                if source_rect.width > dest_rect.width {
+                    source_rect.x +=
+                        (source_rect.width - dest_rect.width) * align_x as qttypes::qreal;
+                    source_rect.width = dest_rect.width;
+                } else {
+                    dest_rect.x +=
+                        (dest_rect.width - source_rect.width) * align_x as qttypes::qreal;
+                    dest_rect.width = source_rect.width;
+                }
+                if source_rect.height > dest_rect.height {
+                    source_rect.y +=
+                        (source_rect.height - dest_rect.height) * align_y as qttypes::qreal;
+                    source_rect.height = dest_rect.height;
+                } else {
+                    dest_rect.y +=
+                        (dest_rect.height - source_rect.height) * align_y as qttypes::qreal;
+                    dest_rect.height = source_rect.height;
+                }
+            }
         };
+        // Pixelated images are drawn smooth while their size changes, for example during an animation, and
+        // switch to nearest pixel sampling once it settles.
+        let smooth = image_rendering == ImageRendering::smooth
+            || self.image_scales.borrow_mut().is_scaling(
+                item_cache,
+                (dest_rect.width.ceil() as u32, dest_rect.height.ceil() as u32),
+            );
         let painter: &mut QPainter = &mut *self.painter;
-        cpp! { unsafe [painter as "QPainter*", pixmap as "QPixmap*", source_rect as "QRectF", dest_rect as "QRectF"] {
+        cpp! { unsafe [
+                painter as "QPainter*",
+                pixmap as "QPixmap*",
+                source_rect as "QRectF",
+                dest_rect as "QRectF",
+                center as "QPointF",
+                rotation_angle as "float",
+                flip_horizontal as "bool",
+                flip_vertical as "bool",
+                smooth as "bool",
+                opacity as "float"] {
+            painter->save();
+            auto cleanup = qScopeGuard([&] { painter->restore(); });
+            painter->setRenderHint(QPainter::SmoothPixmapTransform, smooth);
+            painter->setOpacity(painter->opacity() * qMin(opacity, 1.f));
+            painter->translate(center);
+            painter->rotate(rotation_angle);
+            painter->scale(flip_horizontal ? -1 : 1, flip_vertical ? -1 : 1);
             painter->drawPixmap(dest_rect, *pixmap, source_rect);
         }};
     }
@@ -559,23 +813,40 @@ fn draw_rectangle_impl(
         border_color: Color,
         border_width: f32,
         border_radius: f32,
+        stroke_alignment: items::StrokeAlignment,
     ) {
         let color: u32 = color.as_argb_encoded();
         let border_color: u32 = border_color.as_argb_encoded();
         let border_width: f32 = border_width.min((rect.width as f32) / 2.);
-        // adjust the size so that the border is drawn within the geometry
-        rect.x += border_width as f64 / 2.;
-        rect.y += border_width as f64 / 2.;
-        rect.width -= border_width as f64;
-        rect.height -= border_width as f64;
+        // adjust the size so that the border is drawn along the outline of the given alignment
+        let offset = items::stroke_offset(stroke_alignment, border_width);
+        let mut stroke_rect = rect;
+        stroke_rect.x -= offset as f64;
+        stroke_rect.y -= offset as f64;
+        stroke_rect.width += 2. * offset as f64;
+        stroke_rect.height += 2. * offset as f64;
+        let stroke_radius: f32 =
+            if border_radius > 0. && offset > 0. { border_radius + offset } else { border_radius };
+        // The fill reaches the middle of a border inside of the rectangle, and the whole rectangle otherwise.
+        if stroke_alignment == items::StrokeAlignment::inside {
+            rect = stroke_rect;
+        }
         let painter: &mut QPainter = &mut *self.painter;
-        cpp! { unsafe [painter as "QPainter*", color as "QRgb",  border_color as "QRgb", border_width as "float", border_radius as "float", rect as "QRectF"] {
-            painter->setPen(border_width > 0 ? QPen(QColor::fromRgba(border_color), border_width) : Qt::NoPen);
+        cpp! { unsafe [painter as "QPainter*", color as "QRgb",  border_color as "QRgb", border_width as "float", border_radius as "float", rect as "QRectF", stroke_rect as "QRectF", stroke_radius as "float"] {
+            auto draw = [&](const QRectF &rect, float radius) {
+                if (radius > 0) {
+                    painter->drawRoundedRect(rect, radius, radius);
+                } else {
+                    painter->drawRect(rect);
+                }
+            };
+            painter->setPen(Qt::NoPen);
             painter->setBrush(QColor::fromRgba(color));
-            if (border_radius > 0) {
-                painter->drawRoundedRect(rect, border_radius, border_radius);
-            } else {
-                painter->drawRect(rect);
+            draw(rect, border_radius);
+            if (border_width > 0) {
+                painter->setPen(QPen(QColor::fromRgba(border_color), border_width));
+                painter->setBrush(Qt::NoBrush);
+                draw(stroke_rect, stroke_radius);
             }
         }}
     }
@@ -595,6 +866,7 @@ pub struct QtWindow {
     popup_window: RefCell<Option<(Rc<sixtyfps_corelib::window::Window>, ComponentRc)>>,
 
     cache: QtRenderingCache,
+    image_scales: RefCell<ImageScaleTracker>,
 
     scale_factor: Pin<Box<Property<f32>>>,
 }
@@ -612,6 +884,7 @@ pub fn new() -> Rc<Self> {
             redraw_listener: Rc::pin(Default::default()),
             popup_window: Default::default(),
             cache: Default::default(),
+            image_scales: Default::default(),
             scale_factor: Box::pin(Property::new(1.)),
         });
         let self_weak = Rc::downgrade(&rc);
@@ -633,7 +906,9 @@ fn widget_ptr(&self) -> NonNull<()> {
     fn paint_event(&self, painter: &mut QPainter) {
         sixtyfps_corelib::animations::update_animations();
 
-        let component_rc = self.self_weak.get().unwrap().upgrade().unwrap().component();
+        let window = self.self_weak.get().unwrap().upgrade().unwrap();
+        window.update_rendered_scale_factor(self.scale_factor());
+        let component_rc = window.component();
         let component = ComponentRc::borrow_pin(&component_rc);
 
         self.meta_property_listener.as_ref().evaluate_if_dirty(|| {
@@ -648,13 +923,14 @@ fn paint_event(&self, painter: &mut QPainter) {
 
         let cache = self.cache.clone();
         self.redraw_listener.as_ref().evaluate(|| {
-            let mut renderer = QtItemRenderer { painter, cache };
+            let mut renderer = QtItemRenderer { painter, cache, image_scales: &self.image_scales };
             sixtyfps_corelib::item_rendering::render_component_items(
                 &component_rc,
                 &mut renderer,
                 Point::default(),
             );
         });
+        self.image_scales.borrow_mut().finish_frame();
 
         sixtyfps_corelib::animations::CURRENT_ANIMATION_DRIVER.with(|driver| {
             if !driver.has_active_animations() {
@@ -847,7 +1123,9 @@ fn get_font(request: FontRequest) -> QFont {
     let family: qttypes::QString = request.family.as_str().into();
     let pixel_size: f32 = request.pixel_size.unwrap_or(0.);
     let weight: i32 = request.weight.unwrap_or(0);
-    cpp!(unsafe [family as "QString", pixel_size as "float", weight as "int"] -> QFont as "QFont" {
+    let italic = request.style == sixtyfps_corelib::items::FontStyle::italic;
+    let oblique = request.style == sixtyfps_corelib::items::FontStyle::oblique;
+    cpp!(unsafe [family as "QString", pixel_size as "float", weight as "int", italic as "bool", oblique as "bool"] -> QFont as "QFont" {
         QFont f;
         if (!family.isEmpty())
             f.setFamily(family);
@@ -855,6 +1133,10 @@ fn get_font(request: FontRequest) -> QFont {
             f.setPixelSize(pixel_size);
         if (weight > 0)
             f.setWeight(weight);
+        if (italic)
+            f.setStyle(QFont::StyleItalic);
+        else if (oblique)
+            f.setStyle(QFont::StyleOblique);
         return f;
     })
 }
@@ -871,6 +1153,26 @@ fn text_size(&self, text: &str) -> sixtyfps_corelib::graphics::Size {
         sixtyfps_corelib::graphics::Size::new(size.width as _, size.height as _)
     }
 
+    fn text_size_for_width(&self, text: &str, max_width: f32) -> sixtyfps_corelib::graphics::Size {
+        let string = qttypes::QString::from(text);
+        let size = cpp! { unsafe [self as "const QFont*",  string as "QString", max_width as "float"]
+                -> qttypes::QSizeF as "QSizeF"{
+            return QFontMetricsF(*self).boundingRect(QRectF(0, 0, max_width, 0), Qt::TextWordWrap, string).size();
+        }};
+        sixtyfps_corelib::graphics::Size::new(size.width as _, size.height as _)
+    }
+
+    fn text_ink_bounds(&self, text: &str) -> sixtyfps_corelib::graphics::Rect {
+        let string = qttypes::QString::from(text);
+        let rect = cpp! { unsafe [self as "const QFont*",  string as "QString"]
+                -> qttypes::QRectF as "QRectF" {
+            QFontMetricsF metrics(*self);
+            // The rectangle is relative to the baseline, which is at the ascent below the top of the text.
+            return metrics.tightBoundingRect(string).translated(0, metrics.ascent());
+        }};
+        euclid::rect(rect.x as _, rect.y as _, rect.width as _, rect.height as _)
+    }
+
     fn text_offset_for_x_position<'a>(&self, text: &'a str, x: f32) -> usize {
         let string = qttypes::QString::from(text);
         cpp! { unsafe [self as "const QFont*", string as "QString", x as "float"] -> usize as "long long" {
@@ -886,11 +1188,68 @@ fn text_offset_for_x_position<'a>(&self, text: &'a str, x: f32) -> usize {
         }}
     }
 
+    fn text_offset_for_position(
+        &self,
+        text: &str,
+        pos: sixtyfps_corelib::graphics::Point,
+        wrap_width: Option<f32>,
+    ) -> usize {
+        let string = qttypes::QString::from(text);
+        let (x, y) = (pos.x, pos.y);
+        let wrap = wrap_width.is_some();
+        let wrap_width = wrap_width.unwrap_or_default();
+        cpp! { unsafe [self as "const QFont*", string as "QString", x as "float", y as "float", wrap as "bool", wrap_width as "float"] -> usize as "long long" {
+            // QTextLayout only breaks lines at line separators. The replacement keeps the positions in the string.
+            QString laid_out = string;
+            laid_out.replace(QLatin1Char('\n'), QChar::LineSeparator);
+            QTextLayout layout(laid_out, *self);
+            QTextOption option;
+            option.setWrapMode(wrap ? QTextOption::WordWrap : QTextOption::NoWrap);
+            layout.setTextOption(option);
+            qreal height = 0;
+            layout.beginLayout();
+            while (true) {
+                auto line = layout.createLine();
+                if (!line.isValid())
+                    break;
+                if (wrap)
+                    line.setLineWidth(wrap_width);
+                line.setPosition(QPointF(0, height));
+                height += QFontMetricsF(*self).height();
+            }
+            layout.endLayout();
+            if (layout.lineCount() == 0)
+                return 0;
+            int line_index = qBound(0, int(y / QFontMetricsF(*self).height()), layout.lineCount() - 1);
+            auto cur = layout.lineAt(line_index).xToCursor(x);
+            // convert to an utf8 pos;
+            return QStringView(string).left(cur).toUtf8().size();
+        }}
+    }
+
     fn height(&self) -> f32 {
         cpp! { unsafe [self as "const QFont*"] -> f32 as "float"{
             return QFontMetricsF(*self).height();
         }}
     }
+
+    fn ascent(&self) -> f32 {
+        cpp! { unsafe [self as "const QFont*"] -> f32 as "float"{
+            return QFontMetricsF(*self).ascent();
+        }}
+    }
+
+    fn descent(&self) -> f32 {
+        cpp! { unsafe [self as "const QFont*"] -> f32 as "float"{
+            return QFontMetricsF(*self).descent();
+        }}
+    }
+
+    fn line_gap(&self) -> f32 {
+        cpp! { unsafe [self as "const QFont*"] -> f32 as "float"{
+            return qMax(QFontMetricsF(*self).leading(), qreal(0));
+        }}
+    }
 }
 
 thread_local! {
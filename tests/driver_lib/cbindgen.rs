@@ -58,6 +58,15 @@ fn gen_corelib(include_dir: &Path) -> anyhow::Result<()> {
         "Flickable",
         "Text",
         "Path",
+        "Line",
+        "LineCap",
+        "StrokeAlignment",
+        "Transform",
+        "Desaturate",
+        "Layer",
+        "CustomRendering",
+        "ClipShape",
+        "InputType",
         "ComponentVTable",
         "Slice",
         "ComponentWindowOpaque",
@@ -65,9 +74,15 @@ fn gen_corelib(include_dir: &Path) -> anyhow::Result<()> {
         "EasingCurve",
         "TextHorizontalAlignment",
         "TextVerticalAlignment",
+        "TextVerticalCentering",
         "TextOverflow",
         "TextWrap",
         "ImageFit",
+        "ImageRendering",
+        "ImageHorizontalAlignment",
+        "ImageVerticalAlignment",
+        "FontStyle",
+        "NumeralStyle",
         "Window",
         "TextInput",
         "Clip",
@@ -213,7 +228,7 @@ fn gen_corelib(include_dir: &Path) -> anyhow::Result<()> {
     );
     config.export.body.insert(
         "CachedRenderingData".to_owned(),
-        "    constexpr CachedRenderingData() : cache_index{}, cache_ok{} {}".to_owned(),
+        "    constexpr CachedRenderingData() : cache_index{}, cache_ok{}, cache_invalidated{} {}".to_owned(),
     );
     config.export.body.insert(
         "EasingCurve".to_owned(),